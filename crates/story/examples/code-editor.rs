@@ -3,8 +3,11 @@ use gpui_component::{
     checkbox::Checkbox,
     dropdown::{Dropdown, DropdownEvent, DropdownState},
     h_flex,
-    highlighter::{Language, LanguageConfig, LanguageRegistry},
-    input::{InputEvent, InputState, Marker, TabSize, TextInput},
+    highlighter::{Language, LanguageConfig, LanguageRegistry, Snippet},
+    input::{
+        CompletionItem, CompletionItemKind, CompletionProvider, Hover, HoverProvider, InputEvent,
+        InputState, KeymapMode, Marker, Rope, RopeExt as _, ShowWhitespace, TabSize, TextInput,
+    },
     v_flex,
 };
 use story::Assets;
@@ -21,13 +24,109 @@ fn init(cx: &mut App) {
             "",
         ),
     );
+    LanguageRegistry::global_mut(cx).register_snippets(
+        "rust",
+        vec![
+            Snippet::new("fn", "fn ${1:name}($2) ${3:-> ${4:()} }{\n\t$0\n}")
+                .description("Function"),
+            Snippet::new("for", "for ${1:item} in ${2:iter} {\n\t$0\n}").description("For loop"),
+            Snippet::new("derive", "#[derive(${1|Debug,Clone,Default|})]").description("Derive"),
+            Snippet::new(
+                "let",
+                "let ${1:name} = ${2:value};\nprintln!(\"{:?}\", $1);",
+            )
+            .description("Let and print"),
+        ],
+    );
+}
+
+const RUST_KEYWORDS: [&str; 37] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// A simple completion provider to complete the Rust keywords and some std macros.
+struct RustCompletionProvider;
+
+impl CompletionProvider for RustCompletionProvider {
+    fn completions(
+        &self,
+        _: &Rope,
+        _: usize,
+        _: &mut Window,
+        _: &mut Context<InputState>,
+    ) -> Task<anyhow::Result<Vec<CompletionItem>>> {
+        let mut items = RUST_KEYWORDS
+            .into_iter()
+            .map(|keyword| CompletionItem::new(keyword).kind(CompletionItemKind::Keyword))
+            .collect::<Vec<_>>();
+        items.extend([
+            CompletionItem::new("println!")
+                .kind(CompletionItemKind::Function)
+                .detail("macro")
+                .insert_text("println!(\"{}\", )")
+                .documentation("Prints to the standard output, with a newline."),
+            CompletionItem::new("format!")
+                .kind(CompletionItemKind::Function)
+                .detail("macro")
+                .insert_text("format!(\"{}\", )")
+                .documentation("Creates a `String` using interpolation of runtime expressions."),
+            CompletionItem::new("vec!")
+                .kind(CompletionItemKind::Function)
+                .detail("macro")
+                .insert_text("vec![]")
+                .documentation("Creates a `Vec` containing the arguments."),
+        ]);
+
+        Task::ready(Ok(items))
+    }
+}
+
+/// A simple hover provider to show a message for the Rust keywords.
+struct RustHoverProvider;
+
+impl HoverProvider for RustHoverProvider {
+    fn hover(
+        &self,
+        text: &Rope,
+        offset: usize,
+        _: &mut Window,
+        _: &mut Context<InputState>,
+    ) -> Task<anyhow::Result<Option<Hover>>> {
+        let row = text.offset_to_row(offset);
+        let line_start = text.line_start_offset(row);
+        let line = text.slice_line(row).to_string();
+        let column = offset - line_start;
+
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let start = line[..column]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_word(*c))
+            .map_or(0, |(ix, c)| ix + c.len_utf8());
+        let end = line[column..]
+            .find(|c| !is_word(c))
+            .map_or(line.len(), |ix| column + ix);
+        let word = &line[start..end];
+
+        let hover = RUST_KEYWORDS.contains(&word).then(|| {
+            Hover::new(format!("`{}` is a **Rust** keyword.", word))
+                .range(line_start + start..line_start + end)
+        });
+        Task::ready(Ok(hover))
+    }
 }
 
 pub struct Example {
     input_state: Entity<InputState>,
     language_state: Entity<DropdownState<Vec<SharedString>>>,
+    keymap_state: Entity<DropdownState<Vec<SharedString>>>,
     language: Lang,
     line_number: bool,
+    minimap: bool,
+    sticky_scroll: bool,
     need_update: bool,
     _subscribes: Vec<Subscription>,
 }
@@ -87,6 +186,12 @@ const LANGUAGES: [(Lang, &'static str); 10] = [
     (Lang::External("navi"), include_str!("./fixtures/test.nv")),
 ];
 
+const KEYMAPS: [(&str, KeymapMode); 3] = [
+    ("Default", KeymapMode::Default),
+    ("Vim", KeymapMode::Vim),
+    ("Emacs", KeymapMode::Emacs),
+];
+
 impl Example {
     pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let default_language = LANGUAGES[0].clone();
@@ -94,12 +199,18 @@ impl Example {
             InputState::new(window, cx)
                 .code_editor(default_language.0.name().to_string())
                 .line_number(true)
+                .minimap(true)
+                .sticky_scroll(true)
+                .indent_guides(true)
+                .show_whitespace(ShowWhitespace::Selection)
                 .tab_size(TabSize {
                     tab_size: 4,
                     hard_tabs: false,
                 })
                 .default_value(default_language.1)
                 .placeholder("Enter your code here...")
+                .completion_provider(RustCompletionProvider)
+                .hover_provider(RustHoverProvider)
         });
         let language_state = cx.new(|cx| {
             DropdownState::new(
@@ -110,6 +221,15 @@ impl Example {
             )
         });
 
+        let keymap_state = cx.new(|cx| {
+            DropdownState::new(
+                KEYMAPS.iter().map(|s| s.0.into()).collect(),
+                Some(0),
+                window,
+                cx,
+            )
+        });
+
         let _subscribes = vec![
             cx.subscribe(&input_state, |_, _, _: &InputEvent, cx| {
                 cx.notify();
@@ -129,13 +249,30 @@ impl Example {
                     }
                 },
             ),
+            cx.subscribe_in(
+                &keymap_state,
+                window,
+                |this, state, _: &DropdownEvent<Vec<SharedString>>, window, cx| {
+                    let Some(val) = state.read(cx).selected_value().cloned() else {
+                        return;
+                    };
+                    if let Some((_, keymap)) = KEYMAPS.iter().find(|s| s.0 == val.as_ref()) {
+                        this.input_state.update(cx, |state, cx| {
+                            state.set_keymap_mode(*keymap, window, cx);
+                        });
+                    }
+                },
+            ),
         ];
 
         Self {
             input_state,
             language_state,
+            keymap_state,
             language: default_language.0,
             line_number: true,
+            minimap: true,
+            sticky_scroll: true,
             need_update: false,
             _subscribes,
         }
@@ -195,18 +332,51 @@ impl Render for Example {
                     .flex_shrink_0()
                     .items_center()
                     .justify_between()
-                    .child(Dropdown::new(&self.language_state).title_prefix("Language: "))
                     .child(
-                        Checkbox::new("line-numbger")
-                            .checked(self.line_number)
-                            .on_click(cx.listener(|this, checked: &bool, window, cx| {
-                                this.line_number = *checked;
-                                this.input_state.update(cx, |state, cx| {
-                                    state.set_line_number(this.line_number, window, cx);
-                                });
-                                cx.notify();
-                            }))
-                            .label("Line Number"),
+                        h_flex()
+                            .gap_4()
+                            .child(Dropdown::new(&self.language_state).title_prefix("Language: "))
+                            .child(Dropdown::new(&self.keymap_state).title_prefix("Keymap: ")),
+                    )
+                    .child(
+                        h_flex()
+                            .gap_4()
+                            .child(
+                                Checkbox::new("line-numbger")
+                                    .checked(self.line_number)
+                                    .on_click(cx.listener(|this, checked: &bool, window, cx| {
+                                        this.line_number = *checked;
+                                        this.input_state.update(cx, |state, cx| {
+                                            state.set_line_number(this.line_number, window, cx);
+                                        });
+                                        cx.notify();
+                                    }))
+                                    .label("Line Number"),
+                            )
+                            .child(
+                                Checkbox::new("minimap")
+                                    .checked(self.minimap)
+                                    .on_click(cx.listener(|this, checked: &bool, window, cx| {
+                                        this.minimap = *checked;
+                                        this.input_state.update(cx, |state, cx| {
+                                            state.set_minimap(this.minimap, window, cx);
+                                        });
+                                        cx.notify();
+                                    }))
+                                    .label("Minimap"),
+                            )
+                            .child(
+                                Checkbox::new("sticky-scroll")
+                                    .checked(self.sticky_scroll)
+                                    .on_click(cx.listener(|this, checked: &bool, window, cx| {
+                                        this.sticky_scroll = *checked;
+                                        this.input_state.update(cx, |state, cx| {
+                                            state.set_sticky_scroll(this.sticky_scroll, window, cx);
+                                        });
+                                        cx.notify();
+                                    }))
+                                    .label("Sticky Scroll"),
+                            ),
                     ),
             )
             .child(
@@ -0,0 +1,158 @@
+use gpui::*;
+use gpui_component::{
+    button::{Button, ButtonVariants as _},
+    diff::{DiffView, DiffViewMode},
+    h_flex, v_flex, ActiveTheme as _, Selectable as _, Sizable as _,
+};
+use story::Assets;
+
+const OLD_TEXT: &str = r#"use std::collections::HashMap;
+
+/// A simple counter of the words.
+pub struct Counter {
+    words: HashMap<String, usize>,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self {
+            words: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, text: &str) {
+        for word in text.split(' ') {
+            *self.words.entry(word.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn count(&self, word: &str) -> usize {
+        self.words.get(word).copied().unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+}
+"#;
+
+const NEW_TEXT: &str = r#"use std::collections::BTreeMap;
+
+/// A simple counter of the words.
+#[derive(Default)]
+pub struct Counter {
+    words: BTreeMap<String, usize>,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, text: &str) {
+        for word in text.split_whitespace() {
+            *self.words.entry(word.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn count(&self, word: &str) -> usize {
+        self.words.get(word).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+"#;
+
+pub struct Example {
+    diff_view: Entity<DiffView>,
+}
+
+impl Example {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let diff_view = cx.new(|cx| {
+            let mut view = DiffView::new(window, cx).language("rust");
+            view.set_text(OLD_TEXT, NEW_TEXT, window, cx);
+            view
+        });
+
+        Self { diff_view }
+    }
+
+    fn view(window: &mut Window, cx: &mut App) -> Entity<Self> {
+        cx.new(|cx| Self::new(window, cx))
+    }
+
+    fn set_mode(&mut self, mode: DiffViewMode, window: &mut Window, cx: &mut Context<Self>) {
+        self.diff_view
+            .update(cx, |view, cx| view.set_mode(mode, window, cx));
+    }
+}
+
+impl Render for Example {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let mode = self.diff_view.read(cx).display_mode();
+
+        v_flex()
+            .size_full()
+            .child(
+                h_flex()
+                    .p_2()
+                    .gap_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        Button::new("side-by-side")
+                            .small()
+                            .label("Side by Side")
+                            .selected(mode == DiffViewMode::SideBySide)
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.set_mode(DiffViewMode::SideBySide, window, cx)
+                            })),
+                    )
+                    .child(
+                        Button::new("unified")
+                            .small()
+                            .label("Unified")
+                            .selected(mode == DiffViewMode::Unified)
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.set_mode(DiffViewMode::Unified, window, cx)
+                            })),
+                    )
+                    .child(div().flex_1())
+                    .child(
+                        Button::new("prev-hunk")
+                            .small()
+                            .ghost()
+                            .label("Previous Change")
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.diff_view
+                                    .update(cx, |view, cx| view.go_to_prev_hunk(window, cx))
+                            })),
+                    )
+                    .child(
+                        Button::new("next-hunk")
+                            .small()
+                            .ghost()
+                            .label("Next Change")
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.diff_view
+                                    .update(cx, |view, cx| view.go_to_next_hunk(window, cx))
+                            })),
+                    ),
+            )
+            .child(self.diff_view.clone())
+    }
+}
+
+fn main() {
+    let app = Application::new().with_assets(Assets);
+
+    app.run(move |cx| {
+        story::init(cx);
+        cx.activate(true);
+
+        story::create_new_window("Diff Example", Example::view, cx);
+    });
+}
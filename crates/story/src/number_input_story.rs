@@ -112,10 +112,11 @@ impl NumberInputStory {
         this: &Entity<InputState>,
         event: &InputEvent,
         _: &mut Window,
-        _: &mut Context<Self>,
+        cx: &mut Context<Self>,
     ) {
         match event {
-            InputEvent::Change(text) => {
+            InputEvent::Change => {
+                let text = this.read(cx).unmask_value();
                 if this == &self.number_input1 {
                     if let Ok(value) = text.parse::<i64>() {
                         self.number_input1_value = value;
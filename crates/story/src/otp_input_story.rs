@@ -59,16 +59,15 @@ impl OtpInputStory {
     fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         let otp_state = cx.new(|cx| OtpState::new(6, window, cx).masked(true));
 
-        let _subscriptions =
-            vec![
-                cx.subscribe(&otp_state, |this, _, ev: &InputEvent, cx| match ev {
-                    InputEvent::Change(text) => {
-                        this.otp_value = Some(text.clone());
-                        cx.notify();
-                    }
-                    _ => {}
-                }),
-            ];
+        let _subscriptions = vec![
+            cx.subscribe(&otp_state, |this, state, ev: &InputEvent, cx| match ev {
+                InputEvent::Change => {
+                    this.otp_value = Some(state.read(cx).value().clone());
+                    cx.notify();
+                }
+                _ => {}
+            }),
+        ];
 
         Self {
             otp_masked: true,
@@ -50,9 +50,9 @@ impl Render for WelcomeStory {
         _: &mut gpui::Window,
         _cx: &mut gpui::Context<Self>,
     ) -> impl gpui::IntoElement {
-        v_flex().p_4().gap_5().child(TextView::markdown(
-            "intro",
-            include_str!("../../../README.md"),
-        ))
+        v_flex()
+            .p_4()
+            .gap_5()
+            .child(TextView::markdown("intro", include_str!("../../../README.md")).selectable(true))
     }
 }
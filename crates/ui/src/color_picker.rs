@@ -74,9 +74,9 @@ impl ColorPickerState {
         let _subscriptions = vec![cx.subscribe_in(
             &state,
             window,
-            |this, _, ev: &InputEvent, window, cx| match ev {
-                InputEvent::Change(value) => {
-                    if let Ok(color) = Hsla::parse_hex(value) {
+            |this, state, ev: &InputEvent, window, cx| match ev {
+                InputEvent::Change => {
+                    if let Ok(color) = Hsla::parse_hex(state.read(cx).value()) {
                         this.value = Some(color);
                         this.hovered_color = Some(color);
                     }
@@ -0,0 +1,470 @@
+use std::{collections::HashSet, ops::Range};
+
+use gpui::{
+    actions, div, prelude::FluentBuilder as _, px, uniform_list, AnyElement, App, Context,
+    FocusHandle, Focusable, HighlightStyle, Hsla, InteractiveElement as _, IntoElement, KeyBinding,
+    ParentElement as _, Pixels, Render, ScrollStrategy, SharedString,
+    StatefulInteractiveElement as _, Styled, StyledText, UniformListScrollHandle, Window,
+};
+use ropey::Rope;
+use rust_i18n::t;
+
+use crate::{
+    h_flex,
+    highlighter::{HighlightTheme, LanguageRegistry, SyntaxHighlighter},
+    input::RopeExt as _,
+    scroll::{Scrollbar, ScrollbarState},
+    v_flex, ActiveTheme as _, Icon, IconName, Sizable as _,
+};
+
+use super::text_diff::{diff_rows, DiffHunk, DiffLine, DiffLineKind, DiffRow, LineDiff};
+
+actions!(diff_view, [NextHunk, PrevHunk]);
+
+const CONTEXT: &str = "DiffView";
+/// The height of each row.
+const ROW_HEIGHT: Pixels = px(20.);
+/// The default number of the unchanged lines to display around the hunks.
+const DEFAULT_CONTEXT_LINES: usize = 3;
+
+pub fn init(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("f7", NextHunk, Some(CONTEXT)),
+        KeyBinding::new("shift-f7", PrevHunk, Some(CONTEXT)),
+    ]);
+}
+
+/// The display mode of the [`DiffView`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffViewMode {
+    /// Display the old text and the new text side by side.
+    #[default]
+    SideBySide,
+    /// Display the deleted and inserted lines in one column.
+    Unified,
+}
+
+/// The old or new text of the diff, with the syntax highlighter.
+struct DiffText {
+    text: Rope,
+    highlighter: SyntaxHighlighter,
+}
+
+impl DiffText {
+    fn new(text: &str, language: &str, cx: &mut App) -> Self {
+        let text = Rope::from_str(text);
+        let mut highlighter = SyntaxHighlighter::new(language, cx);
+        highlighter.update(&(0..0), &text, "", cx);
+        Self { text, highlighter }
+    }
+
+    /// Return the text and the highlight styles (relative to the line start) of the `row`.
+    fn line(
+        &self,
+        row: usize,
+        theme: &HighlightTheme,
+    ) -> (SharedString, Vec<(Range<usize>, HighlightStyle)>) {
+        let start = self.text.line_start_offset(row);
+        let line = self.text.slice_line(row).to_string();
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            return (SharedString::default(), vec![]);
+        }
+
+        let styles = self
+            .highlighter
+            .styles(&(start..start + line.len()), theme)
+            .into_iter()
+            .map(|(range, style)| (range.start - start..range.end - start, style))
+            .collect();
+        (line.to_string().into(), styles)
+    }
+}
+
+/// The side of the text to display a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffSide {
+    Old,
+    New,
+}
+
+/// A view to compare two texts, with the syntax highlighting.
+///
+/// - Side by side or unified mode.
+/// - The word changes in the changed lines are highlighted.
+/// - The unchanged lines away from the changes are collapsed, click to expand.
+/// - Press `F7` / `Shift-F7` to go to the next / previous hunk.
+pub struct DiffView {
+    focus_handle: FocusHandle,
+    language: SharedString,
+    mode: DiffViewMode,
+    context_lines: usize,
+    old: DiffText,
+    new: DiffText,
+    diff: LineDiff,
+    rows: Vec<DiffRow>,
+    /// The first row of each hunk.
+    hunk_rows: Vec<usize>,
+    /// The start lines of the expanded collapsed regions.
+    expanded: HashSet<usize>,
+    /// The index of the current hunk, for the next / previous hunk navigation.
+    current_hunk: Option<usize>,
+    scroll_handle: UniformListScrollHandle,
+    scroll_state: ScrollbarState,
+}
+
+impl DiffView {
+    pub fn new(_: &mut Window, cx: &mut Context<Self>) -> Self {
+        let language = SharedString::from("text");
+        Self {
+            focus_handle: cx.focus_handle(),
+            old: DiffText::new("", &language, cx),
+            new: DiffText::new("", &language, cx),
+            language,
+            mode: DiffViewMode::default(),
+            context_lines: DEFAULT_CONTEXT_LINES,
+            diff: LineDiff::default(),
+            rows: vec![],
+            hunk_rows: vec![],
+            expanded: HashSet::new(),
+            current_hunk: None,
+            scroll_handle: UniformListScrollHandle::new(),
+            scroll_state: ScrollbarState::default(),
+        }
+    }
+
+    /// Set the language for the syntax highlighting, default is `text`.
+    ///
+    /// This must be called before [`DiffView::set_text`], or use [`DiffView::set_language`].
+    pub fn language(mut self, language: impl Into<SharedString>) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// Set the display mode, default is [`DiffViewMode::SideBySide`].
+    pub fn mode(mut self, mode: DiffViewMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the number of the unchanged lines to display around the hunks, default is 3.
+    pub fn context_lines(mut self, lines: usize) -> Self {
+        self.context_lines = lines;
+        self
+    }
+
+    /// Set the old and new texts to compare.
+    pub fn set_text(
+        &mut self,
+        old_text: &str,
+        new_text: &str,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.old = DiffText::new(old_text, &self.language, cx);
+        self.new = DiffText::new(new_text, &self.language, cx);
+        self.diff = LineDiff::new(old_text, new_text);
+        self.expanded.clear();
+        self.current_hunk = None;
+        self.update_rows();
+        cx.notify();
+    }
+
+    /// Set the language for the syntax highlighting.
+    pub fn set_language(
+        &mut self,
+        language: impl Into<SharedString>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.language = language.into();
+        let old_text = self.old.text.to_string();
+        let new_text = self.new.text.to_string();
+        self.old = DiffText::new(&old_text, &self.language, cx);
+        self.new = DiffText::new(&new_text, &self.language, cx);
+        cx.notify();
+    }
+
+    /// Set the display mode.
+    pub fn set_mode(&mut self, mode: DiffViewMode, _: &mut Window, cx: &mut Context<Self>) {
+        self.mode = mode;
+        self.update_rows();
+        cx.notify();
+    }
+
+    /// Return the display mode.
+    pub fn display_mode(&self) -> DiffViewMode {
+        self.mode
+    }
+
+    /// Return the diff of the texts.
+    pub fn diff(&self) -> &LineDiff {
+        &self.diff
+    }
+
+    /// Return the hunks of the diff.
+    pub fn hunks(&self) -> &[DiffHunk] {
+        &self.diff.hunks
+    }
+
+    /// Scroll to the hunk at the `ix`.
+    pub fn go_to_hunk(&mut self, ix: usize, _: &mut Window, cx: &mut Context<Self>) {
+        let Some(row) = self.hunk_rows.get(ix) else {
+            return;
+        };
+
+        self.current_hunk = Some(ix);
+        // Keep some unchanged lines above the hunk visible.
+        let row = row.saturating_sub(self.context_lines.min(1));
+        self.scroll_handle.scroll_to_item(row, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    /// Expand all the collapsed unchanged lines.
+    pub fn expand_all(&mut self, _: &mut Window, cx: &mut Context<Self>) {
+        let collapsed = self.rows.iter().filter_map(|row| match row {
+            DiffRow::Collapsed(lines) => Some(lines.start),
+            _ => None,
+        });
+        self.expanded.extend(collapsed);
+        self.update_rows();
+        cx.notify();
+    }
+
+    fn update_rows(&mut self) {
+        let side_by_side = self.mode == DiffViewMode::SideBySide;
+        (self.rows, self.hunk_rows) =
+            diff_rows(&self.diff, side_by_side, self.context_lines, &self.expanded);
+    }
+
+    fn expand(&mut self, start: usize, cx: &mut Context<Self>) {
+        self.expanded.insert(start);
+        self.update_rows();
+        cx.notify();
+    }
+
+    /// Scroll to the next hunk, or the first hunk if at the last one.
+    pub fn go_to_next_hunk(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.hunk_rows.is_empty() {
+            return;
+        }
+
+        let ix = match self.current_hunk {
+            Some(ix) => (ix + 1) % self.hunk_rows.len(),
+            None => 0,
+        };
+        self.go_to_hunk(ix, window, cx);
+    }
+
+    /// Scroll to the previous hunk, or the last hunk if at the first one.
+    pub fn go_to_prev_hunk(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.hunk_rows.is_empty() {
+            return;
+        }
+
+        let len = self.hunk_rows.len();
+        let ix = match self.current_hunk {
+            Some(ix) => (ix + len - 1) % len,
+            None => len - 1,
+        };
+        self.go_to_hunk(ix, window, cx);
+    }
+
+    fn on_action_next_hunk(&mut self, _: &NextHunk, window: &mut Window, cx: &mut Context<Self>) {
+        self.go_to_next_hunk(window, cx);
+    }
+
+    fn on_action_prev_hunk(&mut self, _: &PrevHunk, window: &mut Window, cx: &mut Context<Self>) {
+        self.go_to_prev_hunk(window, cx);
+    }
+
+    fn line_number_width(&self) -> Pixels {
+        let lines = self.old.text.len_lines().max(self.new.text.len_lines());
+        let digits = lines.to_string().len().max(2);
+        px(digits as f32 * 8. + 16.)
+    }
+
+    /// Return the (background, changed words background) of the line.
+    fn line_colors(line: &DiffLine, cx: &App) -> (Option<Hsla>, Hsla) {
+        match line.kind {
+            DiffLineKind::Equal => (None, gpui::transparent_black()),
+            DiffLineKind::Insert => (
+                Some(cx.theme().success.opacity(0.12)),
+                cx.theme().success.opacity(0.3),
+            ),
+            DiffLineKind::Delete => (
+                Some(cx.theme().danger.opacity(0.12)),
+                cx.theme().danger.opacity(0.3),
+            ),
+        }
+    }
+
+    fn render_line_number(&self, row: Option<usize>, cx: &App) -> impl IntoElement {
+        h_flex()
+            .flex_shrink_0()
+            .justify_end()
+            .w(self.line_number_width())
+            .pr_2()
+            .text_color(cx.theme().muted_foreground)
+            .when_some(row, |this, row| this.child((row + 1).to_string()))
+    }
+
+    fn render_text(
+        &self,
+        line: &DiffLine,
+        side: DiffSide,
+        theme: &HighlightTheme,
+        cx: &App,
+    ) -> impl IntoElement {
+        let (diff_text, row) = match side {
+            DiffSide::Old => (&self.old, line.old_row),
+            DiffSide::New => (&self.new, line.new_row),
+        };
+        let (text, styles) = row
+            .map(|row| diff_text.line(row, theme))
+            .unwrap_or_default();
+
+        let (_, change_color) = Self::line_colors(line, cx);
+        let changes = line
+            .changes
+            .iter()
+            .filter(|range| range.end <= text.len())
+            .map(|range| {
+                let style = HighlightStyle {
+                    background_color: Some(change_color),
+                    ..Default::default()
+                };
+                (range.clone(), style)
+            })
+            .collect::<Vec<_>>();
+        let styles = gpui::combine_highlights(styles, changes).collect::<Vec<_>>();
+
+        div()
+            .flex_1()
+            .overflow_hidden()
+            .whitespace_nowrap()
+            .child(StyledText::new(text).with_highlights(styles))
+    }
+
+    /// Render a half of the side by side row, the `line` is `None` for the filler.
+    fn render_side(
+        &self,
+        line_ix: Option<usize>,
+        side: DiffSide,
+        theme: &HighlightTheme,
+        cx: &App,
+    ) -> impl IntoElement {
+        let line = line_ix.map(|ix| &self.diff.lines[ix]);
+        let bg = match line {
+            Some(line) => Self::line_colors(line, cx).0,
+            None => Some(cx.theme().muted.opacity(0.5)),
+        };
+
+        h_flex()
+            .flex_1()
+            .h_full()
+            .overflow_hidden()
+            .when_some(bg, |this, bg| this.bg(bg))
+            .when_some(line, |this, line| {
+                let row = match side {
+                    DiffSide::Old => line.old_row,
+                    DiffSide::New => line.new_row,
+                };
+                this.child(self.render_line_number(row, cx))
+                    .child(self.render_text(line, side, theme, cx))
+            })
+    }
+
+    fn render_row(&self, ix: usize, theme: &HighlightTheme, cx: &mut Context<Self>) -> AnyElement {
+        let row = h_flex().id(("diff-row", ix)).w_full().h(ROW_HEIGHT);
+
+        match &self.rows[ix] {
+            DiffRow::Unified(line_ix) => {
+                let line = &self.diff.lines[*line_ix];
+                let (bg, _) = Self::line_colors(line, cx);
+                let (sign, side) = match line.kind {
+                    DiffLineKind::Equal => (" ", DiffSide::New),
+                    DiffLineKind::Insert => ("+", DiffSide::New),
+                    DiffLineKind::Delete => ("-", DiffSide::Old),
+                };
+
+                row.when_some(bg, |this, bg| this.bg(bg))
+                    .child(self.render_line_number(line.old_row, cx))
+                    .child(self.render_line_number(line.new_row, cx))
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .w_4()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(sign),
+                    )
+                    .child(self.render_text(line, side, theme, cx))
+                    .into_any_element()
+            }
+            DiffRow::Split(old_ix, new_ix) => row
+                .child(self.render_side(*old_ix, DiffSide::Old, theme, cx))
+                .child(div().h_full().w(px(1.)).bg(cx.theme().border))
+                .child(self.render_side(*new_ix, DiffSide::New, theme, cx))
+                .into_any_element(),
+            DiffRow::Collapsed(lines) => {
+                let start = lines.start;
+                row.gap_2()
+                    .px_2()
+                    .bg(cx.theme().muted)
+                    .text_color(cx.theme().muted_foreground)
+                    .cursor_pointer()
+                    .hover(|this| this.text_color(cx.theme().foreground))
+                    .child(Icon::new(IconName::ChevronsUpDown).xsmall())
+                    .child(t!("DiffView.Unchanged lines", count = lines.len()).to_string())
+                    .on_click(cx.listener(move |this, _, _, cx| this.expand(start, cx)))
+                    .into_any_element()
+            }
+        }
+    }
+}
+
+impl Focusable for DiffView {
+    fn focus_handle(&self, _: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for DiffView {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let rows_count = self.rows.len();
+
+        v_flex()
+            .id("diff-view")
+            .key_context(CONTEXT)
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::on_action_next_hunk))
+            .on_action(cx.listener(Self::on_action_prev_hunk))
+            .size_full()
+            .relative()
+            .overflow_hidden()
+            .font_family("Menlo, Monaco, Consolas, monospace")
+            .text_sm()
+            .line_height(ROW_HEIGHT)
+            .child(
+                uniform_list(
+                    "diff-view-rows",
+                    rows_count,
+                    cx.processor(move |this, visible_range: Range<usize>, _, cx| {
+                        let theme = LanguageRegistry::global(cx)
+                            .theme(cx.theme().is_dark())
+                            .clone();
+                        visible_range
+                            .map(|ix| this.render_row(ix, &theme, cx))
+                            .collect::<Vec<_>>()
+                    }),
+                )
+                .flex_grow()
+                .size_full()
+                .track_scroll(self.scroll_handle.clone()),
+            )
+            .child(Scrollbar::uniform_scroll(
+                &self.scroll_state,
+                &self.scroll_handle,
+            ))
+    }
+}
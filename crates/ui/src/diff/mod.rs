@@ -0,0 +1,5 @@
+mod diff_view;
+mod text_diff;
+
+pub use diff_view::*;
+pub use text_diff::{DiffHunk, DiffLine, DiffLineKind, LineDiff};
@@ -0,0 +1,288 @@
+use std::{collections::HashSet, ops::Range, time::Duration};
+
+use similar::{Algorithm, ChangeTag, DiffTag, TextDiff};
+
+/// The max time to compute the diff, the result may be not minimal when timeout.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The kind of a line in the diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A line in the diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// The row (zero based) in the old text, `None` for the inserted line.
+    pub old_row: Option<usize>,
+    /// The row (zero based) in the new text, `None` for the deleted line.
+    pub new_row: Option<usize>,
+    /// The byte ranges (relative to the line start) of the changed words in the line.
+    pub changes: Vec<Range<usize>>,
+}
+
+/// A group of continuous changed lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// The rows range in the old text.
+    pub old_rows: Range<usize>,
+    /// The rows range in the new text.
+    pub new_rows: Range<usize>,
+    /// The range of the lines in [`LineDiff::lines`].
+    pub lines: Range<usize>,
+}
+
+/// The line diff of two texts, with the word diff of the changed lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineDiff {
+    pub lines: Vec<DiffLine>,
+    pub hunks: Vec<DiffHunk>,
+}
+
+impl LineDiff {
+    /// Compute the diff of the `old` and `new` texts.
+    pub fn new(old: &str, new: &str) -> Self {
+        let diff = TextDiff::configure()
+            .algorithm(Algorithm::Myers)
+            .timeout(DIFF_TIMEOUT)
+            .diff_lines(old, new);
+
+        let mut lines: Vec<DiffLine> = vec![];
+        let mut hunks: Vec<DiffHunk> = vec![];
+        for op in diff.ops() {
+            let start = lines.len();
+            for change in diff.iter_inline_changes(op) {
+                let kind = match change.tag() {
+                    ChangeTag::Equal => DiffLineKind::Equal,
+                    ChangeTag::Insert => DiffLineKind::Insert,
+                    ChangeTag::Delete => DiffLineKind::Delete,
+                };
+
+                let mut changes: Vec<Range<usize>> = vec![];
+                let mut offset = 0;
+                for (emphasized, value) in change.iter_strings_lossy() {
+                    let len = value.trim_end_matches(['\r', '\n']).len();
+                    if emphasized && len > 0 {
+                        match changes.last_mut() {
+                            Some(last) if last.end == offset => last.end = offset + len,
+                            _ => changes.push(offset..offset + len),
+                        }
+                    }
+                    offset += value.len();
+                }
+
+                lines.push(DiffLine {
+                    kind,
+                    old_row: change.old_index(),
+                    new_row: change.new_index(),
+                    changes,
+                });
+            }
+
+            if op.tag() == DiffTag::Equal {
+                continue;
+            }
+
+            // Merge the continuous changes (e.g.: a delete followed by an insert) into one hunk.
+            match hunks.last_mut() {
+                Some(hunk) if hunk.lines.end == start => {
+                    hunk.old_rows.end = op.old_range().end;
+                    hunk.new_rows.end = op.new_range().end;
+                    hunk.lines.end = lines.len();
+                }
+                _ => hunks.push(DiffHunk {
+                    old_rows: op.old_range(),
+                    new_rows: op.new_range(),
+                    lines: start..lines.len(),
+                }),
+            }
+        }
+
+        Self { lines, hunks }
+    }
+
+    /// Return true if the two texts are the same.
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+}
+
+/// A row to display in the [`super::DiffView`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DiffRow {
+    /// The index of the line, in the unified mode.
+    Unified(usize),
+    /// The index of the old line and the new line, in the side by side mode.
+    Split(Option<usize>, Option<usize>),
+    /// The range of the unchanged lines are collapsed.
+    Collapsed(Range<usize>),
+}
+
+/// Return the rows to display the `diff`, and the first row of each hunk.
+///
+/// The unchanged lines more than `context` lines away from the hunks are collapsed,
+/// unless the start of the collapsed lines is in the `expanded`.
+pub(crate) fn diff_rows(
+    diff: &LineDiff,
+    side_by_side: bool,
+    context: usize,
+    expanded: &HashSet<usize>,
+) -> (Vec<DiffRow>, Vec<usize>) {
+    let mut rows = vec![];
+    let mut hunk_rows = vec![];
+
+    let push_lines = |rows: &mut Vec<DiffRow>, lines: Range<usize>| {
+        rows.extend(lines.map(|ix| {
+            if side_by_side {
+                DiffRow::Split(Some(ix), Some(ix))
+            } else {
+                DiffRow::Unified(ix)
+            }
+        }));
+    };
+
+    let mut start = 0;
+    for (hunk_ix, hunk) in diff.hunks.iter().enumerate() {
+        // The unchanged lines before the hunk.
+        let end = hunk.lines.start;
+        let leading = if hunk_ix == 0 { 0 } else { context };
+        let hidden_start = (start + leading).min(end);
+        let hidden = hidden_start..end.saturating_sub(context).max(hidden_start);
+        if hidden.len() > 1 && !expanded.contains(&hidden.start) {
+            push_lines(&mut rows, start..hidden.start);
+            rows.push(DiffRow::Collapsed(hidden.clone()));
+            push_lines(&mut rows, hidden.end..end);
+        } else {
+            push_lines(&mut rows, start..end);
+        }
+
+        hunk_rows.push(rows.len());
+        if side_by_side {
+            let deletes = hunk
+                .lines
+                .clone()
+                .filter(|&ix| diff.lines[ix].kind == DiffLineKind::Delete)
+                .collect::<Vec<_>>();
+            let inserts = hunk
+                .lines
+                .clone()
+                .filter(|&ix| diff.lines[ix].kind == DiffLineKind::Insert)
+                .collect::<Vec<_>>();
+            for i in 0..deletes.len().max(inserts.len()) {
+                rows.push(DiffRow::Split(
+                    deletes.get(i).copied(),
+                    inserts.get(i).copied(),
+                ));
+            }
+        } else {
+            rows.extend(hunk.lines.clone().map(DiffRow::Unified));
+        }
+        start = hunk.lines.end;
+    }
+
+    // The unchanged lines after the last hunk.
+    let end = diff.lines.len();
+    if diff.hunks.is_empty() {
+        push_lines(&mut rows, start..end);
+    } else {
+        let hidden = (start + context).min(end)..end;
+        if hidden.len() > 1 && !expanded.contains(&hidden.start) {
+            push_lines(&mut rows, start..hidden.start);
+            rows.push(DiffRow::Collapsed(hidden));
+        } else {
+            push_lines(&mut rows, start..end);
+        }
+    }
+
+    (rows, hunk_rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff() {
+        let old = "fn main() {\n    let a = 1;\n    foo(a);\n}\n";
+        let new = "fn main() {\n    let a = 2;\n    foo(a);\n    bar();\n}\n";
+        let diff = LineDiff::new(old, new);
+
+        let kinds = diff.lines.iter().map(|line| line.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                DiffLineKind::Equal,
+                DiffLineKind::Delete,
+                DiffLineKind::Insert,
+                DiffLineKind::Equal,
+                DiffLineKind::Insert,
+                DiffLineKind::Equal,
+            ]
+        );
+        assert_eq!(diff.lines[1].old_row, Some(1));
+        assert_eq!(diff.lines[1].new_row, None);
+        assert_eq!(diff.lines[4].new_row, Some(3));
+
+        // The word changes.
+        assert_eq!(diff.lines[1].changes, vec![12..14]);
+        assert_eq!(diff.lines[2].changes, vec![12..14]);
+        assert!(diff.lines[4].changes.is_empty());
+
+        assert_eq!(
+            diff.hunks,
+            vec![
+                DiffHunk {
+                    old_rows: 1..2,
+                    new_rows: 1..2,
+                    lines: 1..3,
+                },
+                DiffHunk {
+                    old_rows: 3..3,
+                    new_rows: 3..4,
+                    lines: 4..5,
+                },
+            ]
+        );
+
+        assert!(LineDiff::new(old, old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_rows() {
+        let old = (0..20).map(|i| format!("{}\n", i)).collect::<String>();
+        let new = old.replace("10\n", "ten\n");
+        let diff = LineDiff::new(&old, &new);
+        assert_eq!(diff.hunks.len(), 1);
+
+        let (rows, hunk_rows) = diff_rows(&diff, false, 3, &HashSet::new());
+        assert_eq!(rows[0], DiffRow::Collapsed(0..7));
+        assert_eq!(rows[1], DiffRow::Unified(7));
+        assert_eq!(hunk_rows, vec![4]);
+        assert_eq!(rows[4], DiffRow::Unified(10));
+        assert_eq!(rows[5], DiffRow::Unified(11));
+        assert_eq!(rows[8], DiffRow::Unified(14));
+        assert_eq!(rows[9], DiffRow::Collapsed(15..21));
+        assert_eq!(rows.len(), 10);
+
+        // Expand the collapsed lines.
+        let (rows, hunk_rows) = diff_rows(&diff, false, 3, &HashSet::from([0]));
+        assert_eq!(rows[0], DiffRow::Unified(0));
+        assert_eq!(hunk_rows, vec![10]);
+
+        let (rows, hunk_rows) = diff_rows(&diff, true, 3, &HashSet::new());
+        assert_eq!(hunk_rows, vec![4]);
+        assert_eq!(rows[1], DiffRow::Split(Some(7), Some(7)));
+        assert_eq!(rows[4], DiffRow::Split(Some(10), Some(11)));
+        assert_eq!(rows[5], DiffRow::Split(Some(12), Some(12)));
+
+        // The same text is not collapsed.
+        let diff = LineDiff::new(&old, &old);
+        let (rows, hunk_rows) = diff_rows(&diff, false, 3, &HashSet::new());
+        assert_eq!(rows.len(), 20);
+        assert!(hunk_rows.is_empty());
+    }
+}
@@ -0,0 +1,615 @@
+//! Detect the [`Language`] of a text by the file name, the shebang, the modeline and the content.
+//!
+//! ```ignore
+//! let language = Language::detect(Some("build.rs"), &text);
+//! let ranked = Language::detect_ranked(None, "#!/usr/bin/env python3\nprint(1)");
+//! assert_eq!(ranked[0].0, Language::Python);
+//! ```
+use std::{path::Path, sync::LazyLock};
+
+use regex::Regex;
+
+use super::Language;
+
+/// The confidence of the languages detected by the file name, modeline and shebang.
+const FILE_NAME_SCORE: f32 = 1.0;
+const MODELINE_SCORE: f32 = 0.95;
+const SHEBANG_SCORE: f32 = 0.9;
+/// The max confidence of the languages detected by the content heuristics.
+const CONTENT_MAX_SCORE: f32 = 0.8;
+/// The min weight of the matched patterns to detect a language by the content.
+const CONTENT_MIN_WEIGHT: u32 = 3;
+/// The number of lines at the start of the text to check the content heuristics.
+const CONTENT_MAX_LINES: usize = 100;
+/// The number of lines at the start and the end of the text to find the modeline.
+const MODELINE_LINES: usize = 5;
+
+static MODELINE_PATTERNS: LazyLock<[Regex; 2]> = LazyLock::new(|| {
+    [
+        // vim: set ft=rust: / vi: filetype=python
+        Regex::new(r"\b(?:vi|vim|ex):.*?\b(?:ft|filetype|syntax)=([\w+#-]+)").unwrap(),
+        // -*- mode: python; coding: utf-8 -*- / -*- rust -*-
+        Regex::new(r"-\*-\s*(?:.*?\bmode:\s*)?([\w+#-]+)\s*(?:;.*?)?-\*-").unwrap(),
+    ]
+});
+
+/// The patterns of the content heuristics, with the weight of each pattern.
+static CONTENT_PATTERNS: LazyLock<Vec<(Language, Vec<(Regex, u32)>)>> = LazyLock::new(|| {
+    let rules: Vec<(Language, Vec<(&str, u32)>)> = vec![
+        (
+            Language::Rust,
+            vec![
+                (r"\bfn\s+\w+\s*(<.*>)?\(", 2),
+                (r"\blet\s+mut\b", 3),
+                (r"\bimpl\b.*\{", 2),
+                (r"^\s*use\s+[\w:]+::", 2),
+                (r"\b(println|format|vec)!", 3),
+                (r"\bpub(\(crate\))?\s+(fn|struct|enum|mod)\b", 2),
+            ],
+        ),
+        (
+            Language::Python,
+            vec![
+                (r"^\s*def\s+\w+\(.*\)\s*(->.*)?:\s*$", 3),
+                (r"^\s*(import\s+\w+|from\s+[\w.]+\s+import\b)", 2),
+                (r"^\s*class\s+\w+(\(.*\))?:\s*$", 3),
+                (r"\bself\.\w+", 1),
+                (r"^\s*(if|elif|for|while)\b.*:\s*$", 1),
+                (r"\bprint\(", 1),
+            ],
+        ),
+        (
+            Language::JavaScript,
+            vec![
+                (r"\b(const|let|var)\s+\w+\s*=", 1),
+                (r"\bfunction\s*\w*\s*\(", 2),
+                (r"=>", 1),
+                (r"\bconsole\.\w+\(", 3),
+                (r"\brequire\(['\x22]", 3),
+                (r"^\s*(export|import)\b", 1),
+            ],
+        ),
+        (
+            Language::TypeScript,
+            vec![
+                (r"\b(const|let|var)\s+\w+\s*=", 1),
+                (r"^\s*(export|import)\b", 1),
+                (r":\s*(string|number|boolean|void|any|unknown)\b", 3),
+                (r"^\s*(export\s+)?(interface|type)\s+\w+", 3),
+                (r"\b(private|public|readonly)\s+\w+\s*:", 2),
+            ],
+        ),
+        (
+            Language::Go,
+            vec![
+                (r"^package\s+\w+\s*$", 3),
+                (r"^func\s+(\(.*\)\s*)?\w+\(", 3),
+                (r":=", 1),
+                (r"\bfmt\.\w+\(", 3),
+            ],
+        ),
+        (
+            Language::C,
+            vec![
+                (r"^\s*#include\s*<\w+\.h>", 2),
+                (r"^\s*#(define|ifdef|ifndef|endif)\b", 1),
+                (r"\bint\s+main\s*\(", 2),
+                (r"\b(printf|malloc|free)\(", 1),
+            ],
+        ),
+        (
+            Language::Cpp,
+            vec![
+                (r"^\s*#include\s*<\w+(\.h)?>", 1),
+                (r"\bstd::", 3),
+                (r"\btemplate\s*<", 3),
+                (r"^\s*(class|namespace)\s+\w+", 1),
+                (r"\b(cout|cin|endl)\b", 2),
+            ],
+        ),
+        (
+            Language::Java,
+            vec![
+                (r"\bpublic\s+(final\s+)?class\s+\w+", 3),
+                (r"\bSystem\.out\.\w+\(", 3),
+                (r"\bpublic\s+static\s+void\s+main\b", 3),
+                (r"^\s*import\s+java\.", 3),
+                (r"^\s*package\s+[\w.]+;", 2),
+            ],
+        ),
+        (
+            Language::CSharp,
+            vec![
+                (r"^\s*using\s+System[\w.]*;", 3),
+                (r"^\s*namespace\s+[\w.]+", 1),
+                (r"\bConsole\.Write(Line)?\(", 3),
+                (r"\{\s*get;\s*(set;)?\s*\}", 3),
+            ],
+        ),
+        (
+            Language::Ruby,
+            vec![
+                (r"^\s*def\s+\w+[?!]?(\(.*\))?\s*$", 2),
+                (r"^\s*end\s*$", 1),
+                (r"^\s*(require|require_relative)\s+['\x22]", 2),
+                (r"\bputs\b", 2),
+                (r"\bdo\s*\|\w+", 3),
+            ],
+        ),
+        (
+            Language::Elixir,
+            vec![
+                (r"^\s*defmodule\s+[\w.]+\s+do", 3),
+                (r"^\s*defp?\s+\w+.*\bdo\s*$", 2),
+                (r"\|>", 2),
+            ],
+        ),
+        (
+            Language::Swift,
+            vec![
+                (r"^\s*import\s+(Foundation|SwiftUI|UIKit)", 3),
+                (r"\bfunc\s+\w+\s*\(", 2),
+                (r"\bguard\s+let\b", 3),
+                (r"\bvar\s+\w+\s*:\s*\w+", 1),
+            ],
+        ),
+        (
+            Language::Scala,
+            vec![
+                (r"^\s*object\s+\w+", 2),
+                (r"\bcase\s+class\b", 3),
+                (r"\bdef\s+\w+(\[.*\])?\(.*\)\s*:\s*\w+.*=", 3),
+                (r"\bval\s+\w+\s*=", 1),
+            ],
+        ),
+        (
+            Language::Zig,
+            vec![
+                (r"@import\(\x22std\x22\)", 3),
+                (r"\bpub\s+fn\s+\w+\(.*\)\s*!?\w+", 1),
+                (r"\btry\s+\w+", 1),
+            ],
+        ),
+        (
+            Language::Html,
+            vec![
+                (r"(?i)<!DOCTYPE\s+html", 3),
+                (r"(?i)<html\b", 3),
+                (r"(?i)</(div|span|p|body|head|a|ul|li)>", 2),
+            ],
+        ),
+        (
+            Language::Css,
+            vec![
+                (r"^\s*[.#]?[\w-]+(\s*[,>+~]?\s*[.#:]?[\w-]+)*\s*\{\s*$", 1),
+                (r"^\s*[\w-]+\s*:\s*[^;]+;\s*$", 1),
+                (r"^\s*@(media|import|keyframes)\b", 3),
+            ],
+        ),
+        (
+            Language::Json,
+            vec![(r"^\s*[\[{]\s*$", 1), (r"^\s*\x22[^\x22]+\x22\s*:\s*", 2)],
+        ),
+        (
+            Language::Yaml,
+            vec![
+                (r"^---\s*$", 2),
+                (r"^[\w-]+:(\s+[^{;]*)?$", 1),
+                (r"^\s*-\s+[\w-]+:\s", 2),
+            ],
+        ),
+        (
+            Language::Toml,
+            vec![
+                (r"^\[\[?[\w.-]+\]\]?\s*$", 2),
+                (r"^[\w-]+\s*=\s*(\x22|\d|true|false|\[|\{)", 2),
+            ],
+        ),
+        (
+            Language::Sql,
+            vec![
+                (r"(?i)\bselect\b.+\bfrom\b", 3),
+                (r"(?i)\b(create|alter|drop)\s+table\b", 3),
+                (r"(?i)\binsert\s+into\b", 3),
+                (r"(?i)\bwhere\b", 1),
+            ],
+        ),
+        (
+            Language::Bash,
+            vec![
+                (r"^\s*echo\s", 2),
+                (r"^\s*(if|while)\s+\[", 3),
+                (r"^\s*(fi|done|esac)\s*$", 3),
+                (r"^\s*export\s+\w+=", 2),
+                (r"\$\{?\w+\}?", 1),
+            ],
+        ),
+        (
+            Language::Diff,
+            vec![
+                (r"^diff\s+--git\b", 3),
+                (r"^@@\s+-\d+(,\d+)?\s+\+\d+(,\d+)?\s+@@", 3),
+                (r"^(\+\+\+|---)\s+\S", 1),
+            ],
+        ),
+        (
+            Language::Markdown,
+            vec![
+                (r"^#{1,6}\s+\S", 2),
+                (r"^\s*[-*+]\s+\S", 1),
+                (r"\[[^\]]+\]\([^)]+\)", 2),
+                (r"^```", 2),
+            ],
+        ),
+        (
+            Language::Proto,
+            vec![
+                (r"^syntax\s*=\s*\x22proto[23]\x22", 3),
+                (r"^\s*message\s+\w+\s*\{", 2),
+                (r"^\s*service\s+\w+\s*\{", 2),
+            ],
+        ),
+        (
+            Language::GraphQL,
+            vec![
+                (r"^\s*(query|mutation|subscription|fragment)\b.*\{", 3),
+                (r"^\s*type\s+\w+(\s+implements\s+\w+)?\s*\{", 2),
+            ],
+        ),
+        (
+            Language::Make,
+            vec![
+                (r"^[\w./-]+\s*:([^=]|$)", 1),
+                (r"^\t\S", 1),
+                (r"^\.PHONY\s*:", 3),
+                (r"\$\([\w@<^]+\)", 1),
+            ],
+        ),
+        (
+            Language::CMake,
+            vec![
+                (r"(?i)^\s*cmake_minimum_required\s*\(", 3),
+                (
+                    r"(?i)^\s*(add_executable|add_library|target_link_libraries)\s*\(",
+                    3,
+                ),
+                (r"(?i)^\s*project\s*\(", 1),
+            ],
+        ),
+    ];
+
+    rules
+        .into_iter()
+        .map(|(language, patterns)| {
+            let patterns = patterns
+                .into_iter()
+                .map(|(pattern, weight)| {
+                    let pattern = format!("(?m){}", pattern);
+                    (Regex::new(&pattern).unwrap(), weight)
+                })
+                .collect();
+            (language, patterns)
+        })
+        .collect()
+});
+
+/// The language of the file name, the file name without extension is detected first,
+/// e.g.: `Makefile`, `Dockerfile`.
+fn from_file_name(name: &str) -> Option<Language> {
+    let language = match name {
+        "Makefile" | "makefile" | "GNUmakefile" => Language::Make,
+        "CMakeLists.txt" => Language::CMake,
+        // No Dockerfile grammar, the instructions are highlighted as shell commands.
+        "Dockerfile" | "Containerfile" => Language::Bash,
+        ".bashrc" | ".bash_profile" | ".zshrc" | ".profile" | ".envrc" => Language::Bash,
+        "Gemfile" | "Rakefile" | "Podfile" => Language::Ruby,
+        "Cargo.lock" | "Pipfile" => Language::Toml,
+        _ => {
+            if name.starts_with("Dockerfile.") || name.ends_with(".Dockerfile") {
+                return Some(Language::Bash);
+            }
+
+            let (_, extension) = name.rsplit_once('.')?;
+            match extension.to_ascii_lowercase().as_str() {
+                "sh" | "bash" | "zsh" | "ksh" => Language::Bash,
+                "c" | "h" => Language::C,
+                "cmake" => Language::CMake,
+                "cs" | "csx" => Language::CSharp,
+                "cpp" | "cc" | "cxx" | "c++" | "hpp" | "hh" | "hxx" | "h++" | "ipp" => {
+                    Language::Cpp
+                }
+                "css" | "scss" => Language::Css,
+                "diff" | "patch" => Language::Diff,
+                "ejs" => Language::Ejs,
+                "ex" | "exs" => Language::Elixir,
+                "erb" => Language::Erb,
+                "go" => Language::Go,
+                "graphql" | "gql" => Language::GraphQL,
+                "html" | "htm" | "xhtml" => Language::Html,
+                "java" => Language::Java,
+                "js" | "mjs" | "cjs" | "jsx" => Language::JavaScript,
+                "json" | "jsonc" | "json5" => Language::Json,
+                "mk" | "mak" => Language::Make,
+                "md" | "markdown" | "mdx" => Language::Markdown,
+                "proto" => Language::Proto,
+                "py" | "pyi" | "pyw" => Language::Python,
+                "rb" | "rake" | "gemspec" => Language::Ruby,
+                "rs" => Language::Rust,
+                "scala" | "sc" => Language::Scala,
+                "sql" => Language::Sql,
+                "swift" => Language::Swift,
+                "toml" => Language::Toml,
+                "tsx" => Language::Tsx,
+                "ts" | "mts" | "cts" => Language::TypeScript,
+                "yaml" | "yml" => Language::Yaml,
+                "zig" => Language::Zig,
+                _ => return None,
+            }
+        }
+    };
+
+    Some(language)
+}
+
+/// The language of the interpreter in the shebang line, e.g.: `#!/usr/bin/env python3`.
+fn from_shebang(text: &str) -> Option<Language> {
+    let line = text.lines().next()?.strip_prefix("#!")?;
+    let mut args = line.split_whitespace();
+    let mut interpreter = args.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        // Skip the options of `env`, e.g.: `#!/usr/bin/env -S deno run`.
+        interpreter = args.find(|arg| !arg.starts_with('-') && !arg.contains('='))?;
+    }
+    // Strip the version, e.g.: `python3.12`.
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    let language = match name {
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" => Language::Bash,
+        "python" | "pypy" => Language::Python,
+        "node" | "nodejs" | "bun" => Language::JavaScript,
+        "deno" | "ts-node" | "tsx" => Language::TypeScript,
+        "ruby" => Language::Ruby,
+        "elixir" => Language::Elixir,
+        "make" => Language::Make,
+        "swift" => Language::Swift,
+        "scala" => Language::Scala,
+        _ => return None,
+    };
+
+    Some(language)
+}
+
+/// The language in the vim or emacs modeline, in the first or last lines of the text.
+fn from_modeline(text: &str) -> Option<Language> {
+    let lines: Vec<&str> = text.lines().collect();
+    let tail = lines
+        .len()
+        .saturating_sub(MODELINE_LINES)
+        .max(MODELINE_LINES);
+    let candidates = lines
+        .iter()
+        .take(MODELINE_LINES)
+        .chain(lines.iter().skip(tail));
+
+    for line in candidates {
+        for pattern in MODELINE_PATTERNS.iter() {
+            let Some(captures) = pattern.captures(line) else {
+                continue;
+            };
+            let name = captures[1].to_ascii_lowercase();
+            let language = match name.as_str() {
+                "sh" | "shell-script" | "zsh" => Language::Bash,
+                "c++" => Language::Cpp,
+                "js" | "js2" => Language::JavaScript,
+                "makefile" | "makefile-gmake" => Language::Make,
+                name => Language::from_str(name),
+            };
+            if language != Language::Plain {
+                return Some(language);
+            }
+        }
+    }
+
+    None
+}
+
+/// The languages matched the content heuristics, with the confidence.
+fn from_content(text: &str) -> Vec<(Language, f32)> {
+    let end = text
+        .match_indices('\n')
+        .nth(CONTENT_MAX_LINES)
+        .map_or(text.len(), |(ix, _)| ix);
+    let text = &text[..end];
+
+    CONTENT_PATTERNS
+        .iter()
+        .filter_map(|(language, patterns)| {
+            let weight: u32 = patterns
+                .iter()
+                .filter(|(pattern, _)| pattern.is_match(text))
+                .map(|(_, weight)| weight)
+                .sum();
+            if weight < CONTENT_MIN_WEIGHT {
+                return None;
+            }
+
+            let score = CONTENT_MAX_SCORE * weight as f32 / (weight + CONTENT_MIN_WEIGHT) as f32;
+            Some((*language, score))
+        })
+        .collect()
+}
+
+impl Language {
+    /// Return the language of the file name or path, by the file name and the extension,
+    /// e.g.: `src/main.rs`, `Makefile`, `Dockerfile`.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?;
+        from_file_name(name)
+    }
+
+    /// Detect the languages of the `text`, ranked by the confidence from 0.0 to 1.0.
+    ///
+    /// In order of the confidence:
+    ///
+    /// - The `file_name` (or path) with the name and the extension.
+    /// - The vim or emacs modeline, e.g.: `# vim: ft=python`, `-*- mode: ruby -*-`.
+    /// - The shebang line, e.g.: `#!/usr/bin/env node`.
+    /// - The lightweight heuristics of the content, only the first 100 lines are checked.
+    ///
+    /// Returns empty if nothing is detected.
+    pub fn detect_ranked(file_name: Option<&str>, text: &str) -> Vec<(Self, f32)> {
+        let mut ranked: Vec<(Self, f32)> = vec![];
+        let mut add =
+            |language: Self, score: f32| match ranked.iter_mut().find(|(l, _)| *l == language) {
+                Some((_, s)) => *s = s.max(score),
+                None => ranked.push((language, score)),
+            };
+
+        if let Some(language) = file_name.and_then(Self::from_path) {
+            add(language, FILE_NAME_SCORE);
+        }
+        if let Some(language) = from_modeline(text) {
+            add(language, MODELINE_SCORE);
+        }
+        if let Some(language) = from_shebang(text) {
+            add(language, SHEBANG_SCORE);
+        }
+        for (language, score) in from_content(text) {
+            add(language, score);
+        }
+
+        // The sort is stable, the order of `CONTENT_PATTERNS` is kept for the same score.
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+
+    /// Detect the most likely language of the `text`, see [`Language::detect_ranked`].
+    ///
+    /// Returns [`Language::Plain`] if nothing is detected.
+    pub fn detect(file_name: Option<&str>, text: &str) -> Self {
+        Self::detect_ranked(file_name, text)
+            .first()
+            .map_or(Self::Plain, |(language, _)| *language)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Language::from_path("src/main.rs"), Some(Language::Rust));
+        assert_eq!(Language::from_path("/tmp/App.TSX"), Some(Language::Tsx));
+        assert_eq!(Language::from_path("Makefile"), Some(Language::Make));
+        assert_eq!(
+            Language::from_path("docker/Dockerfile"),
+            Some(Language::Bash)
+        );
+        assert_eq!(Language::from_path("CMakeLists.txt"), Some(Language::CMake));
+        assert_eq!(Language::from_path("include/foo.hpp"), Some(Language::Cpp));
+        assert_eq!(Language::from_path("README"), None);
+        assert_eq!(Language::from_path("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_from_shebang_and_modeline() {
+        assert_eq!(from_shebang("#!/bin/bash\necho 1"), Some(Language::Bash));
+        assert_eq!(
+            from_shebang("#!/usr/bin/env python3.12"),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            from_shebang("#!/usr/bin/env -S deno run --allow-net"),
+            Some(Language::TypeScript)
+        );
+        assert_eq!(from_shebang("#!/usr/bin/env perl"), None);
+        assert_eq!(from_shebang("echo 1"), None);
+
+        assert_eq!(
+            from_modeline("# vim: set ft=python:\nx = 1"),
+            Some(Language::Python)
+        );
+        assert_eq!(
+            from_modeline("// -*- mode: c++; indent-tabs-mode: nil -*-\nint a;"),
+            Some(Language::Cpp)
+        );
+        assert_eq!(from_modeline("# -*- ruby -*-"), Some(Language::Ruby));
+        let text = format!("{}\n// vim: filetype=rust", "a\n".repeat(20));
+        assert_eq!(from_modeline(&text), Some(Language::Rust));
+        assert_eq!(from_modeline("-*- coding: utf-8 -*-"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let cases = [
+            (
+                "fn main() {\n    let mut a = 1;\n    println!(\"{}\", a);\n}",
+                Language::Rust,
+            ),
+            (
+                "import os\n\ndef main():\n    print(os.getcwd())\n",
+                Language::Python,
+            ),
+            (
+                "const a = require('fs');\nconsole.log(a);",
+                Language::JavaScript,
+            ),
+            (
+                "export interface Props {\n  name: string;\n}\nconst a = 1;",
+                Language::TypeScript,
+            ),
+            (
+                "package main\n\nimport \"fmt\"\n\nfunc main() {\n\tfmt.Println(1)\n}",
+                Language::Go,
+            ),
+            (
+                "#include <iostream>\nint main() {\n  std::cout << 1 << std::endl;\n}",
+                Language::Cpp,
+            ),
+            (
+                "#include <stdio.h>\nint main() {\n  printf(\"1\");\n}",
+                Language::C,
+            ),
+            ("SELECT id, name FROM users WHERE id = 1;", Language::Sql),
+            ("{\n  \"name\": \"a\",\n  \"version\": 1\n}", Language::Json),
+            (
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"",
+                Language::Toml,
+            ),
+            (
+                "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1,2 +1,2 @@",
+                Language::Diff,
+            ),
+            (
+                "<!DOCTYPE html>\n<html>\n<div>a</div>\n</html>",
+                Language::Html,
+            ),
+            ("if [ -f a ]; then\n  echo a\nfi", Language::Bash),
+            ("# Title\n\nSee [link](https://a.com)", Language::Markdown),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(Language::detect(None, text), expected, "{}", text);
+        }
+
+        assert_eq!(Language::detect(None, "hello world"), Language::Plain);
+        assert!(Language::detect_ranked(None, "").is_empty());
+    }
+
+    #[test]
+    fn test_detect_ranked() {
+        // The file name wins the content.
+        let ranked = Language::detect_ranked(Some("a.py"), "fn main() {\n    println!(\"a\");\n}");
+        assert_eq!(ranked[0], (Language::Python, FILE_NAME_SCORE));
+        assert_eq!(ranked[1].0, Language::Rust);
+        assert!(ranked[1].1 < CONTENT_MAX_SCORE);
+
+        // The shebang and the content of the same language are merged.
+        let ranked = Language::detect_ranked(None, "#!/usr/bin/env ruby\nputs 'a'\n");
+        assert_eq!(ranked[0], (Language::Ruby, SHEBANG_SCORE));
+        assert_eq!(
+            ranked.iter().filter(|(l, _)| *l == Language::Ruby).count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Export the highlighted text as HTML, ANSI and RTF, e.g.: to copy the code with the colors
+//! into emails and terminals.
+use std::{fmt::Write as _, ops::Range};
+
+use gpui::{FontStyle, HighlightStyle, Hsla};
+
+use super::HighlightTheme;
+use crate::Colorize as _;
+
+/// The format to export the highlighted text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A standalone HTML `<pre>` element with the inline styles.
+    Html,
+    /// The text with the 24-bit ANSI escape codes for the terminals.
+    Ansi,
+    /// The Rich Text Format document.
+    Rtf,
+}
+
+impl ExportFormat {
+    /// Export the `text` with the `styles`, the ranges of the `styles` are the byte offsets in the `text`.
+    ///
+    /// The `theme` provides the foreground and background of the HTML.
+    pub fn export(
+        &self,
+        text: &str,
+        styles: &[(Range<usize>, HighlightStyle)],
+        theme: &HighlightTheme,
+    ) -> String {
+        let spans = spans(text, styles);
+        match self {
+            Self::Html => to_html(&spans, theme),
+            Self::Ansi => to_ansi(&spans),
+            Self::Rtf => to_rtf(&spans),
+        }
+    }
+}
+
+/// Split the `text` into the spans of the `styles`, the text not covered by the styles has the default style.
+fn spans<'a>(
+    text: &'a str,
+    styles: &[(Range<usize>, HighlightStyle)],
+) -> Vec<(&'a str, HighlightStyle)> {
+    let mut spans = vec![];
+    let mut offset = 0;
+    for (range, style) in styles {
+        let start = range.start.max(offset);
+        let end = range.end.min(text.len());
+        if start >= end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            continue;
+        }
+
+        if offset < start {
+            spans.push((&text[offset..start], HighlightStyle::default()));
+        }
+        spans.push((&text[start..end], *style));
+        offset = end;
+    }
+    if offset < text.len() {
+        spans.push((&text[offset..], HighlightStyle::default()));
+    }
+
+    spans
+}
+
+fn is_bold(style: &HighlightStyle) -> bool {
+    style.font_weight.is_some_and(|weight| weight.0 >= 600.)
+}
+
+fn is_italic(style: &HighlightStyle) -> bool {
+    style.font_style == Some(FontStyle::Italic)
+}
+
+fn rgb(color: Hsla) -> (u8, u8, u8) {
+    let rgba = color.to_rgb();
+    let channel = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+    (channel(rgba.r), channel(rgba.g), channel(rgba.b))
+}
+
+fn escape_html(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#39;"),
+            _ => output.push(c),
+        }
+    }
+}
+
+fn to_html(spans: &[(&str, HighlightStyle)], theme: &HighlightTheme) -> String {
+    let mut pre_style = String::new();
+    if let Some(background) = theme.style.background {
+        _ = write!(pre_style, "background-color:{};", background.to_hex());
+    }
+    if let Some(foreground) = theme.style.foreground {
+        _ = write!(pre_style, "color:{};", foreground.to_hex());
+    }
+
+    let mut output = format!("<pre style=\"{}\"><code>", pre_style);
+    for (text, style) in spans {
+        let mut css = String::new();
+        if let Some(color) = style.color {
+            _ = write!(css, "color:{};", color.to_hex());
+        }
+        if let Some(weight) = style.font_weight {
+            _ = write!(css, "font-weight:{};", weight.0);
+        }
+        if is_italic(style) {
+            css.push_str("font-style:italic;");
+        }
+        match (style.underline.is_some(), style.strikethrough.is_some()) {
+            (true, true) => css.push_str("text-decoration:underline line-through;"),
+            (true, false) => css.push_str("text-decoration:underline;"),
+            (false, true) => css.push_str("text-decoration:line-through;"),
+            (false, false) => {}
+        }
+
+        if css.is_empty() {
+            escape_html(text, &mut output);
+        } else {
+            _ = write!(output, "<span style=\"{}\">", css);
+            escape_html(text, &mut output);
+            output.push_str("</span>");
+        }
+    }
+    output.push_str("</code></pre>");
+    output
+}
+
+fn to_ansi(spans: &[(&str, HighlightStyle)]) -> String {
+    let mut output = String::new();
+    for (text, style) in spans {
+        let mut codes = vec![];
+        if let Some(color) = style.color {
+            let (r, g, b) = rgb(color);
+            codes.push(format!("38;2;{};{};{}", r, g, b));
+        }
+        if is_bold(style) {
+            codes.push("1".into());
+        }
+        if is_italic(style) {
+            codes.push("3".into());
+        }
+        if style.underline.is_some() {
+            codes.push("4".into());
+        }
+        if style.strikethrough.is_some() {
+            codes.push("9".into());
+        }
+
+        // Reset the style at the end of each line, to keep every line self-contained.
+        for (ix, line) in text.split('\n').enumerate() {
+            if ix > 0 {
+                output.push('\n');
+            }
+            if codes.is_empty() || line.is_empty() {
+                output.push_str(line);
+            } else {
+                _ = write!(output, "\x1b[{}m{}\x1b[0m", codes.join(";"), line);
+            }
+        }
+    }
+    output
+}
+
+fn escape_rtf(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '{' => output.push_str("\\{"),
+            '}' => output.push_str("\\}"),
+            '\n' => output.push_str("\\line\n"),
+            '\t' => output.push_str("\\tab "),
+            c if c.is_ascii() => output.push(c),
+            c => {
+                // The `\uN` takes a signed 16-bit value of the UTF-16 code unit, with `?` as the fallback.
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    _ = write!(output, "\\u{}?", *unit as i16);
+                }
+            }
+        }
+    }
+}
+
+fn to_rtf(spans: &[(&str, HighlightStyle)]) -> String {
+    let mut colors: Vec<(u8, u8, u8)> = vec![];
+    for (_, style) in spans {
+        if let Some(color) = style.color.map(rgb) {
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+    }
+
+    // The index 0 of the color table is the default color.
+    let mut output =
+        String::from("{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}{\\colortbl ;");
+    for (r, g, b) in &colors {
+        _ = write!(output, "\\red{}\\green{}\\blue{};", r, g, b);
+    }
+    output.push_str("}\n\\f0 ");
+
+    for (text, style) in spans {
+        let mut control = String::new();
+        if let Some(color) = style.color.map(rgb) {
+            let ix = colors.iter().position(|c| *c == color).unwrap_or_default() + 1;
+            _ = write!(control, "\\cf{}", ix);
+        }
+        if is_bold(style) {
+            control.push_str("\\b");
+        }
+        if is_italic(style) {
+            control.push_str("\\i");
+        }
+        if style.underline.is_some() {
+            control.push_str("\\ul");
+        }
+        if style.strikethrough.is_some() {
+            control.push_str("\\strike");
+        }
+
+        if control.is_empty() {
+            escape_rtf(text, &mut output);
+        } else {
+            _ = write!(output, "{{{} ", control);
+            escape_rtf(text, &mut output);
+            output.push('}');
+        }
+    }
+    output.push('}');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::{rgb, FontWeight};
+
+    use super::*;
+
+    fn styles() -> Vec<(Range<usize>, HighlightStyle)> {
+        vec![
+            (
+                0..2,
+                HighlightStyle {
+                    color: Some(rgb(0xff0000).into()),
+                    font_weight: Some(FontWeight::BOLD),
+                    ..Default::default()
+                },
+            ),
+            (
+                8..11,
+                HighlightStyle {
+                    color: Some(rgb(0x00ff00).into()),
+                    font_style: Some(FontStyle::Italic),
+                    ..Default::default()
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_spans() {
+        let spans = spans("fn a() {\"<\"}", &styles());
+        let texts: Vec<&str> = spans.iter().map(|(text, _)| *text).collect();
+        assert_eq!(texts, vec!["fn", " a() {", "\"<\"", "}"]);
+        assert_eq!(spans[1].1, HighlightStyle::default());
+
+        // Out of the text or not on the char boundary.
+        let spans = super::spans(
+            "中文",
+            &[
+                (1..3, HighlightStyle::default()),
+                (3..9, HighlightStyle::default()),
+            ],
+        );
+        let texts: Vec<&str> = spans.iter().map(|(text, _)| *text).collect();
+        assert_eq!(texts, vec!["中", "文"]);
+    }
+
+    #[test]
+    fn test_export() {
+        let mut theme = HighlightTheme::default_light();
+        theme.style.background = Some(rgb(0xffffff).into());
+        theme.style.foreground = Some(rgb(0x000000).into());
+        let text = "fn a() {\"<\"}";
+
+        assert_eq!(
+            ExportFormat::Html.export(text, &styles(), &theme),
+            "<pre style=\"background-color:#FFFFFF;color:#000000;\"><code>\
+            <span style=\"color:#FF0000;font-weight:700;\">fn</span> a() {\
+            <span style=\"color:#00FF00;font-style:italic;\">&quot;&lt;&quot;</span>}</code></pre>"
+        );
+        assert_eq!(
+            ExportFormat::Ansi.export(text, &styles(), &theme),
+            "\x1b[38;2;255;0;0;1mfn\x1b[0m a() {\x1b[38;2;0;255;0;3m\"<\"\x1b[0m}"
+        );
+        assert_eq!(
+            ExportFormat::Rtf.export(text, &styles(), &theme),
+            "{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}\
+            {\\colortbl ;\\red255\\green0\\blue0;\\red0\\green255\\blue0;}\n\
+            \\f0 {\\cf1\\b fn} a() \\{{\\cf2\\i \"<\"}\\}}"
+        );
+    }
+
+    #[test]
+    fn test_export_multi_lines() {
+        let theme = HighlightTheme::default_light();
+        let style = HighlightStyle {
+            color: Some(rgb(0x0000ff).into()),
+            ..Default::default()
+        };
+        let text = "/* a\nb */\n中";
+
+        assert_eq!(
+            ExportFormat::Ansi.export(text, &[(0..9, style)], &theme),
+            "\x1b[38;2;0;0;255m/* a\x1b[0m\n\x1b[38;2;0;0;255mb */\x1b[0m\n中"
+        );
+        assert_eq!(
+            ExportFormat::Rtf.export(text, &[(0..9, style)], &theme),
+            "{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}\
+            {\\colortbl ;\\red0\\green0\\blue255;}\n\
+            \\f0 {\\cf1 /* a\\line\nb */}\\line\n\\u20013?}"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Load the tree-sitter grammars and the query files at runtime, to register the languages
+//! that are not built in by [`LanguageRegistry::register`](super::LanguageRegistry::register).
+//!
+//! ```ignore
+//! // SAFETY: The library is a tree-sitter grammar built by `tree-sitter build`.
+//! let language = unsafe { load_grammar_library("grammars/libtree-sitter-kotlin.so", "kotlin")? };
+//! let config = LanguageConfig::from_query_dir("kotlin", language, "grammars/kotlin/queries")?;
+//! LanguageRegistry::global_mut(cx).register("kotlin", &config);
+//! ```
+use std::path::Path;
+
+use anyhow::{anyhow, Context as _, Result};
+use gpui::SharedString;
+use tree_sitter::{Parser, Query};
+
+use super::LanguageConfig;
+
+/// Load a tree-sitter grammar from a shared library (`.so`, `.dylib` or `.dll`),
+/// e.g.: built by `tree-sitter build`.
+///
+/// The library must export the `tree_sitter_{name}` function.
+///
+/// The library is leaked on purpose, it is never unloaded: the [`tree_sitter::Language`]
+/// points to the static data in the library, and it is cloned to the parsers and trees
+/// that may live until the app exits.
+///
+/// # Safety
+///
+/// Loading a library runs its initialization code, and the `tree_sitter_{name}` symbol is
+/// called as `extern "C" fn() -> *const TSLanguage`. The caller must make sure that the
+/// `path` is a trusted tree-sitter grammar library, built for the ABI of the `tree-sitter`
+/// used by this crate.
+pub unsafe fn load_grammar_library(
+    path: impl AsRef<Path>,
+    name: &str,
+) -> Result<tree_sitter::Language> {
+    let path = path.as_ref();
+    let symbol = format!("tree_sitter_{}", name.replace('-', "_"));
+
+    // SAFETY: The library is trusted by the caller, see `# Safety`.
+    let library = unsafe { libloading::Library::new(path) }
+        .with_context(|| format!("failed to load grammar library {:?}", path))?;
+    // SAFETY: The tree-sitter grammar exports the symbol with this signature.
+    let language_fn = unsafe {
+        *library
+            .get::<unsafe extern "C" fn() -> *const ()>(symbol.as_bytes())
+            .with_context(|| format!("symbol `{}` not found in {:?}", symbol, path))?
+    };
+    // Keep the library loaded, the language refers to its data.
+    std::mem::forget(library);
+
+    // SAFETY: The function returns a `TSLanguage` pointer that lives as long as the library.
+    Ok(tree_sitter::Language::new(unsafe {
+        tree_sitter_language::LanguageFn::from_raw(language_fn)
+    }))
+}
+
+#[cfg(feature = "wasm-grammars")]
+static WASM_ENGINE: std::sync::LazyLock<tree_sitter::wasmtime::Engine> =
+    std::sync::LazyLock::new(tree_sitter::wasmtime::Engine::default);
+
+#[cfg(feature = "wasm-grammars")]
+thread_local! {
+    /// The WASM store of the thread, it is reused by the parsers instead of creating
+    /// a wasmtime store for every parse.
+    static WASM_STORE: std::cell::RefCell<Option<tree_sitter::WasmStore>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Take the WASM store of the thread, or create a new one if it is in use.
+#[cfg(feature = "wasm-grammars")]
+fn take_wasm_store() -> Result<tree_sitter::WasmStore> {
+    match WASM_STORE.with_borrow_mut(Option::take) {
+        Some(store) => Ok(store),
+        None => Ok(tree_sitter::WasmStore::new(&WASM_ENGINE)?),
+    }
+}
+
+/// Return the WASM store to the thread, to be reused by the next parser.
+#[cfg(feature = "wasm-grammars")]
+fn release_wasm_store(store: tree_sitter::WasmStore) {
+    WASM_STORE.with_borrow_mut(|cached| {
+        cached.get_or_insert(store);
+    });
+}
+
+/// Load a tree-sitter grammar from a WASM file, e.g.: built by `tree-sitter build --wasm`.
+///
+/// The `name` is the name of the grammar, e.g.: `kotlin` for `tree-sitter-kotlin.wasm`.
+#[cfg(feature = "wasm-grammars")]
+pub fn load_wasm_grammar(path: impl AsRef<Path>, name: &str) -> Result<tree_sitter::Language> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read {:?}", path))?;
+    let mut store = take_wasm_store()?;
+    let language = store
+        .load_language(name, &bytes)
+        .with_context(|| format!("failed to load WASM grammar {:?}", path));
+    release_wasm_store(store);
+
+    language
+}
+
+/// A tree-sitter [`Parser`], the WASM store is returned to the thread when it is dropped.
+pub(crate) struct GrammarParser(Parser);
+
+impl std::ops::Deref for GrammarParser {
+    type Target = Parser;
+
+    fn deref(&self) -> &Parser {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for GrammarParser {
+    fn deref_mut(&mut self) -> &mut Parser {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "wasm-grammars")]
+impl Drop for GrammarParser {
+    fn drop(&mut self) {
+        if let Some(store) = self.0.take_wasm_store() {
+            release_wasm_store(store);
+        }
+    }
+}
+
+/// Create a parser for the `language`, the WASM grammar is supported by the `wasm-grammars` feature.
+pub(crate) fn new_parser(language: &tree_sitter::Language) -> Result<GrammarParser> {
+    let mut parser = GrammarParser(Parser::new());
+    #[cfg(feature = "wasm-grammars")]
+    if language.is_wasm() {
+        parser.set_wasm_store(take_wasm_store()?)?;
+    }
+    parser
+        .set_language(language)
+        .context("parse set_language")?;
+
+    Ok(parser)
+}
+
+/// Read the query file in the `dir`, returns empty if the file not exists.
+fn read_query(language: &tree_sitter::Language, dir: &Path, name: &str) -> Result<String> {
+    let path = dir.join(format!("{}.scm", name));
+    if !path.exists() {
+        return Ok(String::new());
+    }
+
+    let source =
+        std::fs::read_to_string(&path).with_context(|| format!("failed to read {:?}", path))?;
+    // Report the errors of the query on loading, instead of on highlighting.
+    Query::new(language, &source).with_context(|| format!("invalid query {:?}", path))?;
+
+    Ok(source)
+}
+
+impl LanguageConfig {
+    /// Create a language config with the query files in the `dir`.
+    ///
+    /// - `highlights.scm` is required.
+    /// - `injections.scm`, `locals.scm`, `folds.scm` and `indents.scm` are optional.
+    /// - `outline.scm` is optional, or `tags.scm` if `outline.scm` not exists.
+    ///
+    /// The languages used in `injections.scm` can be set by [`LanguageConfig::injection_languages`].
+    pub fn from_query_dir(
+        name: impl Into<SharedString>,
+        language: tree_sitter::Language,
+        dir: impl AsRef<Path>,
+    ) -> Result<Self> {
+        let dir = dir.as_ref();
+        let highlights = read_query(&language, dir, "highlights")?;
+        if highlights.is_empty() {
+            return Err(anyhow!("`highlights.scm` not found in {:?}", dir));
+        }
+        let injections = read_query(&language, dir, "injections")?;
+        let locals = read_query(&language, dir, "locals")?;
+        let folds = read_query(&language, dir, "folds")?;
+        let indents = read_query(&language, dir, "indents")?;
+        let mut outline = read_query(&language, dir, "outline")?;
+        if outline.is_empty() {
+            outline = read_query(&language, dir, "tags")?;
+        }
+
+        Ok(
+            Self::new(name, language, vec![], &highlights, &injections, &locals)
+                .folds(&folds)
+                .indents(&indents)
+                .outline(&outline),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_query_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("gpui-component-queries-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let language: tree_sitter::Language = tree_sitter_rust::LANGUAGE.into();
+
+        let err = LanguageConfig::from_query_dir("rust", language.clone(), &dir).unwrap_err();
+        assert!(err.to_string().contains("`highlights.scm` not found"));
+
+        std::fs::write(dir.join("highlights.scm"), "(identifier) @variable").unwrap();
+        std::fs::write(dir.join("folds.scm"), "(block) @fold").unwrap();
+        let config = LanguageConfig::from_query_dir("rust", language.clone(), &dir).unwrap();
+        assert_eq!(config.name, "rust");
+        assert_eq!(config.highlights, "(identifier) @variable");
+        assert_eq!(config.folds, "(block) @fold");
+        assert_eq!(config.injections, "");
+        assert_eq!(config.indents, "");
+        assert_eq!(config.outline, "");
+
+        std::fs::write(
+            dir.join("tags.scm"),
+            "(function_item name: (identifier) @name) @definition.function",
+        )
+        .unwrap();
+        let config = LanguageConfig::from_query_dir("rust", language.clone(), &dir).unwrap();
+        assert_eq!(
+            config.outline,
+            "(function_item name: (identifier) @name) @definition.function"
+        );
+
+        std::fs::write(dir.join("locals.scm"), "(unknown_node) @local.scope").unwrap();
+        let err = LanguageConfig::from_query_dir("rust", language, &dir).unwrap_err();
+        assert!(err.to_string().contains("invalid query"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
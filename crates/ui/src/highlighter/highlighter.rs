@@ -1,37 +1,75 @@
-use super::HighlightTheme;
-use crate::highlighter::LanguageRegistry;
+use super::{
+    grammar::new_parser,
+    outline::{build_outline, outline_name},
+    ExportFormat, HighlightTheme, OutlineItem,
+};
+use crate::highlighter::{Language, LanguageRegistry};
 use anyhow::{anyhow, Context, Result};
 use gpui::{App, HighlightStyle, SharedString};
 use indexset::BTreeMap;
+use ropey::Rope;
 use std::{
     collections::HashMap,
     ops::{Bound, Range},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 use tree_sitter::{
-    InputEdit, Node, Parser, Point, Query, QueryCursor, QueryMatch, StreamingIterator, Tree,
+    InputEdit, Node, ParseOptions, ParseState, Parser, Point, Query, QueryCursor, QueryMatch,
+    StreamingIterator, Tree,
 };
 
+/// The rows to highlight before and after the visible range.
+const HIGHLIGHT_MARGIN_ROWS: usize = 50;
+
+/// The grammar and the highlights queries of a language, shared with the background parsing.
+struct HighlightConfig {
+    language: SharedString,
+    ts_language: tree_sitter::Language,
+    query: Query,
+    /// The grammar and the highlights query of the injection languages.
+    injections: HashMap<SharedString, (tree_sitter::Language, Query)>,
+    injection_content_capture_index: Option<u32>,
+    injection_language_capture_index: Option<u32>,
+    /// The query to find the foldable nodes for code folding.
+    fold_query: Option<Query>,
+}
+
 /// A syntax highlighter that supports incremental parsing, multiline text,
 /// and caching of highlight results.
-#[allow(unused)]
+///
+/// The [`InputState`](crate::input::InputState) parses the text in the background by [`ParseJob`],
+/// and only the visible range (with a margin) is highlighted.
 pub struct SyntaxHighlighter {
-    language: SharedString,
-    query: Option<Query>,
-    injection_queries: HashMap<SharedString, Query>,
-    parser: Parser,
+    /// The grammar and the queries, `None` for the plain text.
+    config: Option<Arc<HighlightConfig>>,
+    /// The foldable row ranges of the last parsed syntax tree.
+    fold_ranges: Vec<Range<usize>>,
+    /// The query to find the symbols of the outline.
+    outline_query: Option<Query>,
+    /// The query to find the indented nodes for auto indent.
+    indent_query: Option<Query>,
     old_tree: Option<Tree>,
-    text: SharedString,
-
-    locals_pattern_index: usize,
-    highlights_pattern_index: usize,
-    // highlight_indices: Vec<Option<Highlight>>,
-    non_local_variable_patterns: Vec<bool>,
-    injection_content_capture_index: Option<u32>,
-    injection_language_capture_index: Option<u32>,
-    local_scope_capture_index: Option<u32>,
-    local_def_capture_index: Option<u32>,
-    local_def_value_capture_index: Option<u32>,
-    local_ref_capture_index: Option<u32>,
+    text: Rope,
+    /// Increased on every change of the text.
+    version: usize,
+    /// The version of the text that the `old_tree` is parsed from.
+    parsed_version: Option<usize>,
+    /// The cancel flag of the [`ParseJob`] in progress.
+    parsing: Option<Arc<AtomicBool>>,
+    /// The version of the text that is failed to parse, it is not parsed again until the next edit.
+    failed_version: Option<usize>,
+    /// The edits since the [`ParseJob`] in progress is created,
+    /// they are applied to its result if the text has been changed.
+    pending_edits: Vec<InputEdit>,
+    /// The visible range of the text, it is highlighted first after parsing.
+    visible_range: Range<usize>,
+    /// The ranges that have been highlighted into the `cache`, sorted and not overlapped.
+    highlighted_ranges: Vec<Range<usize>>,
+    /// The ranges requested to highlight by the next [`ParseJob`], sorted and not overlapped.
+    requested_ranges: Vec<Range<usize>>,
 
     /// Cache of highlight, the range is offset of the token in the tree.
     ///
@@ -42,299 +80,271 @@ pub struct SyntaxHighlighter {
     cache: BTreeMap<usize, (Range<usize>, SharedString)>,
 }
 
-impl SyntaxHighlighter {
-    /// Create a new SyntaxHighlighter for HTML.
-    pub fn new(lang: &str, cx: &App) -> Self {
-        match Self::build_combined_injections_query(&lang, cx) {
-            Ok(result) => result,
-            Err(err) => {
-                tracing::warn!(
-                    "SyntaxHighlighter init failed, fallback to use `text`, {}",
-                    err
-                );
-                Self::build_combined_injections_query("text", cx).unwrap()
-            }
-        }
-    }
+/// A job to parse the text of a [`SyntaxHighlighter`] in the background,
+/// or only to highlight the requested ranges if the syntax tree is up to date.
+///
+/// Created by [`SyntaxHighlighter::parse_job`], the result is applied even if the text
+/// has been changed before it is finished.
+pub struct ParseJob {
+    config: Arc<HighlightConfig>,
+    text: Rope,
+    old_tree: Option<Tree>,
+    reparse: bool,
+    version: usize,
+    highlight_ranges: Vec<Range<usize>>,
+    cancel: Arc<AtomicBool>,
+}
 
-    /// Build the combined injections query for the given language.
+/// The result of a [`ParseJob`], apply it by [`SyntaxHighlighter::apply_parsed`].
+pub struct ParsedSyntax {
+    /// The cancel flag of the job, to know which job is finished.
+    cancel: Arc<AtomicBool>,
+    /// The new syntax tree, `None` if the job is not to reparse, or the parsing is failed.
+    tree: Option<Tree>,
+    reparse: bool,
+    version: usize,
+    highlight_ranges: Vec<Range<usize>>,
+    styles: Vec<(Range<usize>, SharedString)>,
+    fold_ranges: Vec<Range<usize>>,
+}
+
+impl ParseJob {
+    /// Parse the text and highlight the requested ranges, this is slow for the large text,
+    /// should be run in the background.
     ///
-    /// https://github.com/tree-sitter/tree-sitter/blob/v0.25.5/highlight/src/lib.rs#L336
-    fn build_combined_injections_query(lang: &str, cx: &App) -> Result<Self> {
-        let registry = LanguageRegistry::global(cx);
-        let Some(config) = registry.language(&lang) else {
-            return Err(anyhow!(
-                "language {:?} is not registered in `LanguageRegistry`",
-                lang
-            ));
+    /// The result has no tree and styles if the job is canceled or the parsing is failed.
+    pub fn parse(self) -> ParsedSyntax {
+        let mut parsed = ParsedSyntax {
+            cancel: self.cancel.clone(),
+            tree: None,
+            reparse: self.reparse,
+            version: self.version,
+            highlight_ranges: vec![],
+            styles: vec![],
+            fold_ranges: vec![],
         };
 
-        let mut parser = Parser::new();
-        parser
-            .set_language(&config.language)
-            .context("parse set_language")?;
-
-        // Concatenate the query strings, keeping track of the start offset of each section.
-        let mut query_source = String::new();
-        query_source.push_str(&config.injections);
-        let locals_query_offset = query_source.len();
-        query_source.push_str(&config.locals);
-        let highlights_query_offset = query_source.len();
-        query_source.push_str(&config.highlights);
-
-        // Construct a single query by concatenating the three query strings, but record the
-        // range of pattern indices that belong to each individual string.
-        let query = Query::new(&config.language, &query_source).context("new query")?;
-
-        let mut locals_pattern_index = 0;
-        let mut highlights_pattern_index = 0;
-        for i in 0..(query.pattern_count()) {
-            let pattern_offset = query.start_byte_for_pattern(i);
-            if pattern_offset < highlights_query_offset {
-                if pattern_offset < highlights_query_offset {
-                    highlights_pattern_index += 1;
-                }
-                if pattern_offset < locals_query_offset {
-                    locals_pattern_index += 1;
-                }
-            }
+        let tree = if self.reparse {
+            let Ok(mut parser) = new_parser(&self.config.ts_language) else {
+                return parsed;
+            };
+            let Some(tree) = parse_text(
+                &mut parser,
+                &self.text,
+                self.old_tree.as_ref(),
+                Some(&*self.cancel),
+            ) else {
+                return parsed;
+            };
+            tree
+        } else {
+            let Some(tree) = self.old_tree else {
+                return parsed;
+            };
+            tree
+        };
+        if self.cancel.load(Ordering::Relaxed) {
+            return parsed;
         }
 
-        // let Some(mut combined_injections_query) =
-        //     Query::new(&config.language, &config.injections).ok()
-        // else {
-        //     return None;
-        // };
-
-        // let mut has_combined_queries = false;
-        // for pattern_index in 0..locals_pattern_index {
-        //     let settings = query.property_settings(pattern_index);
-        //     if settings.iter().any(|s| &*s.key == "injection.combined") {
-        //         has_combined_queries = true;
-        //         query.disable_pattern(pattern_index);
-        //     } else {
-        //         combined_injections_query.disable_pattern(pattern_index);
-        //     }
-        // }
-        // let combined_injections_query = if has_combined_queries {
-        //     Some(combined_injections_query)
-        // } else {
-        //     None
-        // };
-
-        // Find all of the highlighting patterns that are disabled for nodes that
-        // have been identified as local variables.
-        let non_local_variable_patterns = (0..query.pattern_count())
-            .map(|i| {
-                query
-                    .property_predicates(i)
-                    .iter()
-                    .any(|(prop, positive)| !*positive && prop.key.as_ref() == "local")
-            })
-            .collect();
-
-        // Store the numeric ids for all of the special captures.
-        let mut injection_content_capture_index = None;
-        let mut injection_language_capture_index = None;
-        let mut local_def_capture_index = None;
-        let mut local_def_value_capture_index = None;
-        let mut local_ref_capture_index = None;
-        let mut local_scope_capture_index = None;
-        for (i, name) in query.capture_names().iter().enumerate() {
-            let i = Some(i as u32);
-            match *name {
-                "injection.content" => injection_content_capture_index = i,
-                "injection.language" => injection_language_capture_index = i,
-                "local.definition" => local_def_capture_index = i,
-                "local.definition-value" => local_def_value_capture_index = i,
-                "local.reference" => local_ref_capture_index = i,
-                "local.scope" => local_scope_capture_index = i,
-                _ => {}
-            }
+        for range in self.highlight_ranges.iter() {
+            let styles = self.config.highlight(&tree, &self.text, range.clone());
+            parsed.styles.extend(styles);
         }
+        if self.reparse {
+            parsed.fold_ranges = self.config.fold_ranges(&tree, &self.text);
+            parsed.tree = Some(tree);
+        }
+        parsed.highlight_ranges = self.highlight_ranges;
+        parsed
+    }
+}
 
-        let mut injection_queries = HashMap::new();
-        for inj_language in config.injection_languages.iter() {
-            if let Some(inj_config) = registry.language(&inj_language) {
-                match Query::new(&inj_config.language, &inj_config.highlights) {
-                    Ok(q) => {
-                        injection_queries.insert(inj_config.name.clone(), q);
-                    }
-                    Err(e) => {
-                        tracing::error!(
-                            "failed to build injection query for {:?}: {:?}",
-                            inj_config.name,
-                            e
-                        );
-                    }
-                }
-            }
+/// Parse the `text` by the `parser`, feed the parser with the chunks of the rope to avoid
+/// copying the entire text.
+///
+/// The parsing is halted (returns `None`) once the `cancel` flag is set.
+fn parse_text(
+    parser: &mut Parser,
+    text: &Rope,
+    old_tree: Option<&Tree>,
+    cancel: Option<&AtomicBool>,
+) -> Option<Tree> {
+    let mut callback = move |offset: usize, _: Point| -> &[u8] {
+        if offset >= text.len_bytes() {
+            return &[];
         }
 
-        // let highlight_indices = vec![None; query.capture_names().len()];
+        let (chunk, chunk_byte_ix, _, _) = text.chunk_at_byte(offset);
+        &chunk.as_bytes()[offset - chunk_byte_ix..]
+    };
+    let mut progress = |_: &ParseState| cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed));
 
-        Ok(Self {
-            language: config.name.clone(),
-            query: Some(query),
-            injection_queries,
-            parser,
-            old_tree: None,
-            text: SharedString::new(""),
-            cache: BTreeMap::new(),
-            locals_pattern_index,
-            highlights_pattern_index,
-            non_local_variable_patterns,
-            injection_content_capture_index,
-            injection_language_capture_index,
-            local_scope_capture_index,
-            local_def_capture_index,
-            local_def_value_capture_index,
-            local_ref_capture_index,
-        })
-    }
+    parser.parse_with_options(
+        &mut callback,
+        old_tree,
+        Some(ParseOptions::new().progress_callback(&mut progress)),
+    )
+}
 
-    pub fn is_empty(&self) -> bool {
-        self.text.is_empty()
+/// Return the tree-sitter point (row, column in bytes) of the `offset`.
+fn point_for_offset(text: &Rope, offset: usize) -> Point {
+    let offset = offset.min(text.len_bytes());
+    let row = text.byte_to_line(offset);
+    Point::new(row, offset - text.line_to_byte(row))
+}
+
+/// Return the [`InputEdit`] of replacing the `range` in the `old_text` with `new_len` bytes,
+/// the `new_text` is the text after the edit.
+fn input_edit(old_text: &Rope, new_text: &Rope, range: &Range<usize>, new_len: usize) -> InputEdit {
+    let new_end = range.start + new_len;
+    InputEdit {
+        start_byte: range.start,
+        old_end_byte: range.end,
+        new_end_byte: new_end,
+        start_position: point_for_offset(old_text, range.start),
+        old_end_position: point_for_offset(old_text, range.end),
+        new_end_position: point_for_offset(new_text, new_end),
     }
+}
 
-    /// Highlight the given text, returning a map from byte ranges to highlight captures.
-    /// Uses incremental parsing, detects changed ranges, and caches unchanged results.
-    pub fn update(
-        &mut self,
-        selected_range: &Range<usize>,
-        full_text: SharedString,
-        new_text: &str,
-        cx: &mut App,
-    ) {
-        if self.text == full_text {
-            return;
-        }
+/// Return the `range` after replacing the `edit` range with `new_len` bytes.
+///
+/// The offsets inside the `edit` range are moved to the boundaries of the new text,
+/// so the range that overlaps the edit is extended to cover the new text.
+fn range_after_edit(range: &Range<usize>, edit: &Range<usize>, new_len: usize) -> Range<usize> {
+    let shift = |offset: usize| offset + new_len - edit.len();
+    let start = if range.start >= edit.end && range.start > edit.start {
+        shift(range.start)
+    } else {
+        range.start.min(edit.start)
+    };
+    let end = if range.end >= edit.end && range.end > edit.start {
+        shift(range.end)
+    } else if range.end > edit.start {
+        edit.start + new_len
+    } else {
+        range.end
+    };
+
+    start..end.max(start)
+}
 
-        // If insert a chart, this is 1.
-        // If backspace or delete, this is -1.
-        // If selected to delete, this is the length of the selected text.
-        let changed_len = new_text.len() as isize - selected_range.len() as isize;
-
-        let new_tree = match &self.old_tree {
-            None => self.parser.parse(full_text.as_ref(), None),
-            Some(old) => {
-                let edit = InputEdit {
-                    start_byte: selected_range.start,
-                    old_end_byte: selected_range.end,
-                    new_end_byte: (selected_range.end as isize + changed_len) as usize,
-                    start_position: Point::new(0, 0),
-                    old_end_position: Point::new(0, 0),
-                    new_end_position: Point::new(0, 0),
-                };
-                let mut old_cloned = old.clone();
-                old_cloned.edit(&edit);
-                // NOTE: 10K lines, about 4.5ms
-                self.parser.parse(full_text.as_ref(), Some(&old_cloned))
-            }
-        };
+/// Return the range of a style after the `edit`, `None` if the style is in the edited range.
+fn style_after_edit(range: &Range<usize>, edit: &InputEdit) -> Option<Range<usize>> {
+    if range.end <= edit.start_byte {
+        Some(range.clone())
+    } else if range.start >= edit.old_end_byte {
+        let start = range.start + edit.new_end_byte - edit.old_end_byte;
+        Some(start..start + range.len())
+    } else {
+        None
+    }
+}
 
-        let Some(new_tree) = new_tree else {
-            return;
-        };
+/// Return the row range of a fold after the `edit`, `None` if the rows are edited.
+fn rows_after_edit(rows: &Range<usize>, edit: &InputEdit) -> Option<Range<usize>> {
+    let start_row = edit.start_position.row;
+    let old_end_row = edit.old_end_position.row;
+    let new_end_row = edit.new_end_position.row;
+    if rows.end < start_row {
+        Some(rows.clone())
+    } else if rows.start > old_end_row {
+        Some(rows.start + new_end_row - old_end_row..rows.end + new_end_row - old_end_row)
+    } else {
+        None
+    }
+}
 
-        let mut changed_ranges = None;
-        if let Some(old_tree) = &self.old_tree {
-            changed_ranges = Some(new_tree.changed_ranges(old_tree));
+/// Return the parts of the `range` that are not covered by the `ranges` (sorted and not overlapped).
+fn uncovered_ranges(ranges: &[Range<usize>], range: &Range<usize>) -> Vec<Range<usize>> {
+    let mut uncovered = vec![];
+    let mut start = range.start;
+    for covered in ranges {
+        if covered.end <= start {
+            continue;
+        }
+        if covered.start >= range.end {
+            break;
         }
+        if covered.start > start {
+            uncovered.push(start..covered.start);
+        }
+        start = covered.end;
+    }
+    if start < range.end {
+        uncovered.push(start..range.end);
+    }
 
-        // Update state
-        self.old_tree = Some(new_tree);
-        self.text = full_text;
+    uncovered
+}
 
-        // let measure = Measure::new("build_styles");
-        self.build_styles(changed_ranges, changed_len, cx);
-        // measure.end();
+/// Insert the `range` into the `ranges` (sorted and not overlapped), merge the overlapped ones.
+fn insert_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    let ix = ranges.partition_point(|r| r.end < range.start);
+    let mut merged = range;
+    while ix < ranges.len() && ranges[ix].start <= merged.end {
+        let r = ranges.remove(ix);
+        merged = merged.start.min(r.start)..merged.end.max(r.end);
     }
+    ranges.insert(ix, merged);
+}
 
-    /// NOTE: 10K lines, about 180ms
-    fn build_styles(
-        &mut self,
-        changed_ranges: Option<impl ExactSizeIterator<Item = tree_sitter::Range>>,
-        changed_len: isize,
-        cx: &mut App,
-    ) {
-        let Some(tree) = &self.old_tree else {
-            return;
-        };
-
-        let Some(query) = &self.query else {
-            return;
+impl HighlightConfig {
+    /// Returns the foldable row ranges (zero based) by the folds query of the language.
+    ///
+    /// The `start` is the first row of the foldable node, and the `end` is the last row.
+    fn fold_ranges(&self, tree: &Tree, source: &Rope) -> Vec<Range<usize>> {
+        let Some(query) = &self.fold_query else {
+            return vec![];
         };
 
-        let source = self.text.as_bytes();
         let mut query_cursor = QueryCursor::new();
-        let mut root_node = tree.root_node();
-
-        // Incremental parsing to only update changed ranges.
-        if let Some(changed_ranges) = changed_ranges {
-            let mut total_range = 0..0;
-            for change_range in changed_ranges {
-                if total_range.start == 0 {
-                    total_range.start = change_range.start_byte;
-                }
-                if total_range.end == 0 {
-                    total_range.end = change_range.end_byte;
-                }
-            }
-
-            if total_range.len() == 0 {
-                return;
-            }
-
-            if let Some(node) =
-                root_node.descendant_for_byte_range(total_range.start, total_range.end)
-            {
-                root_node = node;
-            }
-
-            let byte_range = root_node.byte_range();
-
-            // let measure = Measure::new("update cache to change range offset");
-
-            // FIXME: If we delete 1 char in a node, that node will not highlighted.
-
-            // Remove the cache entries that are range is intersecting with the byte_range.
-            self.cache.retain(|_, (range, _)| {
-                if range.start < byte_range.end && range.end > byte_range.start {
-                    // Remove the item if it is intersecting with the byte_range.
-                    false
+        let mut matches = query_cursor.matches(query, tree.root_node(), move |node: Node| {
+            source
+                .byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        });
+
+        let mut ranges = vec![];
+        while let Some(m) = matches.next() {
+            for cap in m.captures {
+                let start = cap.node.start_position();
+                let end = cap.node.end_position();
+                // The node is end at the start of next line, e.g.: the section of Markdown.
+                let end_row = if end.column == 0 {
+                    end.row.saturating_sub(1)
                 } else {
-                    // Keep the item if it is not intersecting with the byte_range.
-                    true
-                }
-            });
+                    end.row
+                };
 
-            // Apply changed_len to reorder the cache to move the range offset
-            let mut old_cache: BTreeMap<usize, (Range<usize>, SharedString)> = BTreeMap::new();
-            std::mem::swap(&mut self.cache, &mut old_cache);
-
-            // NOTE: 10K lines, about 35ms
-            for (start, (old_range, highlight_name)) in old_cache.into_iter() {
-                if old_range.end >= byte_range.start {
-                    let new_range = Range {
-                        start: (old_range.start as isize + changed_len).max(0) as usize,
-                        end: (old_range.end as isize + changed_len).max(0) as usize,
-                    };
-
-                    if new_range.len() > 0 {
-                        self.cache
-                            .insert(new_range.start, (new_range, highlight_name));
-                    }
-                } else {
-                    self.cache.insert(start, (old_range, highlight_name));
+                if end_row > start.row {
+                    ranges.push(start.row..end_row);
                 }
             }
-            // measure.end();
-        } else {
-            self.cache.clear();
         }
 
-        let mut matches = query_cursor.matches(&query, root_node, source);
+        ranges
+    }
+
+    /// Highlight the `range` of the text by the query, return the styles ordered by the range.
+    fn highlight(
+        &self,
+        tree: &Tree,
+        source: &Rope,
+        range: Range<usize>,
+    ) -> Vec<(Range<usize>, SharedString)> {
+        let mut styles: BTreeMap<usize, (Range<usize>, SharedString)> = BTreeMap::new();
+        let query = &self.query;
+        let mut query_cursor = QueryCursor::new();
+        query_cursor.set_byte_range(range);
+        let mut matches = query_cursor.matches(query, tree.root_node(), move |node: Node| {
+            source
+                .byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        });
 
         while let Some(m) = matches.next() {
             // Ref:
@@ -342,12 +352,10 @@ impl SyntaxHighlighter {
             let (language_name, content_node, _) = self.injection_for_match(None, query, m, source);
             if let Some(language_name) = language_name {
                 if let Some(content_node) = content_node {
-                    let styles = self.handle_injection(&language_name, content_node, source, cx);
-                    for (node_range, highlight_name) in styles {
-                        self.cache.insert(
-                            node_range.start,
-                            (node_range, highlight_name.to_string().into()),
-                        );
+                    let injection_styles =
+                        self.handle_injection(&language_name, content_node, source);
+                    for (node_range, highlight_name) in injection_styles {
+                        styles.insert(node_range.start, (node_range, highlight_name.into()));
                     }
                 }
 
@@ -365,14 +373,14 @@ impl SyntaxHighlighter {
                 let highlight_name = SharedString::from(highlight_name.to_string());
 
                 // Merge near range and same highlight name
-                let last_item = self.cache.last_key_value().map(|kv| kv.1);
+                let last_item = styles.last_key_value().map(|kv| kv.1);
                 let last_range = last_item.map(|(range, _)| range).unwrap_or(&(0..0));
                 let last_highlight_name = last_item.map(|(_, name)| name.clone());
 
                 if last_range.end <= node_range.start
                     && last_highlight_name.as_ref() == Some(&highlight_name)
                 {
-                    self.cache.insert(
+                    styles.insert(
                         last_range.start,
                         (last_range.start..node_range.end, highlight_name.clone()),
                     );
@@ -380,21 +388,17 @@ impl SyntaxHighlighter {
                     // case:
                     // last_range: 213..220, last_highlight_name: Some("property")
                     // last_range: 213..220, last_highlight_name: Some("string")
-                    self.cache.insert(
+                    styles.insert(
                         node_range.start,
                         (node_range, last_highlight_name.unwrap_or(highlight_name)),
                     );
                 } else {
-                    self.cache
-                        .insert(node_range.start, (node_range, highlight_name.clone()));
+                    styles.insert(node_range.start, (node_range, highlight_name.clone()));
                 }
             }
         }
 
-        // DO NOT REMOVE THIS PRINT, it's useful for debugging
-        // for item in self.cache.iter() {
-        //     println!("item: {:?}", item);
-        // }
+        styles.into_iter().map(|(_, style)| style).collect()
     }
 
     /// TODO: Use incremental parsing to handle the injection.
@@ -402,34 +406,30 @@ impl SyntaxHighlighter {
         &self,
         injection_language: &str,
         node: Node,
-        source: &[u8],
-        cx: &App,
+        source: &Rope,
     ) -> Vec<(Range<usize>, String)> {
         let start_offset = node.start_byte();
         let end_offset = node.end_byte();
         let mut cache = vec![];
-        let Some(query) = &self.injection_queries.get(injection_language) else {
+        let Some((language, query)) = self.injections.get(injection_language) else {
             return cache;
         };
-        let Some(content) = source.get(node.start_byte()..node.end_byte()) else {
+        if end_offset > source.len_bytes() {
             return cache;
-        };
+        }
+        let content = source.byte_slice(start_offset..end_offset).to_string();
         if content.is_empty() {
             return cache;
         };
-        let Some(config) = LanguageRegistry::global(cx).language(injection_language) else {
+        let Ok(mut parser) = new_parser(language) else {
             return cache;
         };
-        let mut parser = Parser::new();
-        if parser.set_language(&config.language).is_err() {
-            return cache;
-        }
-        let Some(tree) = parser.parse(content, None) else {
+        let Some(tree) = parser.parse(&content, None) else {
             return cache;
         };
 
         let mut query_cursor = QueryCursor::new();
-        let mut matches = query_cursor.matches(query, tree.root_node(), content);
+        let mut matches = query_cursor.matches(query, tree.root_node(), content.as_bytes());
 
         let mut last_end = start_offset;
         while let Some(m) = matches.next() {
@@ -468,7 +468,7 @@ impl SyntaxHighlighter {
         parent_name: Option<SharedString>,
         query: &'a Query,
         query_match: &QueryMatch<'a, 'a>,
-        source: &'a [u8],
+        source: &Rope,
     ) -> (Option<SharedString>, Option<Node<'a>>, bool) {
         let content_capture_index = self.injection_content_capture_index;
         let language_capture_index = self.injection_language_capture_index;
@@ -479,12 +479,9 @@ impl SyntaxHighlighter {
         for capture in query_match.captures {
             let index = Some(capture.index);
             if index == language_capture_index {
-                language_name = capture
-                    .node
-                    .utf8_text(source)
-                    .ok()
-                    .map(ToString::to_string)
-                    .map(SharedString::from);
+                let range = capture.node.byte_range();
+                language_name = (range.end <= source.len_bytes())
+                    .then(|| SharedString::from(source.byte_slice(range).to_string()));
             } else if index == content_capture_index {
                 content_node = Some(capture.node);
             }
@@ -536,6 +533,587 @@ impl SyntaxHighlighter {
 
         (language_name, content_node, include_children)
     }
+}
+
+impl SyntaxHighlighter {
+    /// Create a new SyntaxHighlighter for the language.
+    ///
+    /// Fallback to the plain text if the language is not registered, e.g.: the grammar is
+    /// disabled by the cargo feature.
+    pub fn new(lang: &str, cx: &App) -> Self {
+        match Self::build_combined_injections_query(&lang, cx) {
+            Ok(result) => result,
+            Err(err) => {
+                if lang != Language::Plain.name() {
+                    tracing::warn!(
+                        "SyntaxHighlighter init failed, fallback to use `text`, {}",
+                        err
+                    );
+                }
+                Self::plain()
+            }
+        }
+    }
+
+    /// Create a SyntaxHighlighter for the plain text, it has no grammar to parse.
+    fn plain() -> Self {
+        Self {
+            config: None,
+            fold_ranges: vec![],
+            outline_query: None,
+            indent_query: None,
+            old_tree: None,
+            text: Rope::new(),
+            version: 0,
+            parsed_version: None,
+            parsing: None,
+            failed_version: None,
+            pending_edits: vec![],
+            visible_range: 0..0,
+            highlighted_ranges: vec![],
+            requested_ranges: vec![],
+            cache: BTreeMap::new(),
+        }
+    }
+
+    /// Build the combined injections query for the given language.
+    ///
+    /// https://github.com/tree-sitter/tree-sitter/blob/v0.25.5/highlight/src/lib.rs#L336
+    fn build_combined_injections_query(lang: &str, cx: &App) -> Result<Self> {
+        let registry = LanguageRegistry::global(cx);
+        let Some(config) = registry.language(&lang) else {
+            return Err(anyhow!(
+                "language {:?} is not registered in `LanguageRegistry`",
+                lang
+            ));
+        };
+
+        // Check the grammar is compatible with the tree-sitter.
+        new_parser(&config.language)?;
+
+        // Construct a single query by concatenating the three query strings.
+        let mut query_source = String::new();
+        query_source.push_str(&config.injections);
+        query_source.push_str(&config.locals);
+        query_source.push_str(&config.highlights);
+        let query = Query::new(&config.language, &query_source).context("new query")?;
+
+        // Store the numeric ids for the injection captures.
+        let mut injection_content_capture_index = None;
+        let mut injection_language_capture_index = None;
+        for (i, name) in query.capture_names().iter().enumerate() {
+            let i = Some(i as u32);
+            match *name {
+                "injection.content" => injection_content_capture_index = i,
+                "injection.language" => injection_language_capture_index = i,
+                _ => {}
+            }
+        }
+
+        let mut injections = HashMap::new();
+        for inj_language in config.injection_languages.iter() {
+            if let Some(inj_config) = registry.language(&inj_language) {
+                match Query::new(&inj_config.language, &inj_config.highlights) {
+                    Ok(q) => {
+                        injections
+                            .insert(inj_config.name.clone(), (inj_config.language.clone(), q));
+                    }
+                    Err(e) => {
+                        tracing::error!(
+                            "failed to build injection query for {:?}: {:?}",
+                            inj_config.name,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+
+        let fold_query = if config.folds.is_empty() {
+            None
+        } else {
+            match Query::new(&config.language, &config.folds) {
+                Ok(q) => Some(q),
+                Err(e) => {
+                    tracing::error!("failed to build folds query for {:?}: {:?}", config.name, e);
+                    None
+                }
+            }
+        };
+
+        let outline_query = if config.outline.is_empty() {
+            None
+        } else {
+            match Query::new(&config.language, &config.outline) {
+                Ok(q) => Some(q),
+                Err(e) => {
+                    tracing::error!(
+                        "failed to build outline query for {:?}: {:?}",
+                        config.name,
+                        e
+                    );
+                    None
+                }
+            }
+        };
+
+        let indent_query = if config.indents.is_empty() {
+            None
+        } else {
+            match Query::new(&config.language, &config.indents) {
+                Ok(q) => Some(q),
+                Err(e) => {
+                    tracing::error!(
+                        "failed to build indents query for {:?}: {:?}",
+                        config.name,
+                        e
+                    );
+                    None
+                }
+            }
+        };
+
+        Ok(Self {
+            config: Some(Arc::new(HighlightConfig {
+                language: config.name.clone(),
+                ts_language: config.language.clone(),
+                query,
+                injections,
+                injection_content_capture_index,
+                injection_language_capture_index,
+                fold_query,
+            })),
+            outline_query,
+            indent_query,
+            ..Self::plain()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.len_bytes() == 0
+    }
+
+    /// Highlight the given text, returning a map from byte ranges to highlight captures.
+    /// Uses incremental parsing, the entire text is parsed and highlighted synchronously.
+    ///
+    /// The [`InputState`](crate::input::InputState) uses [`SyntaxHighlighter::edit`] and
+    /// [`SyntaxHighlighter::parse_job`] to parse in the background instead.
+    ///
+    /// Returns true if the text has been parsed.
+    pub fn update(
+        &mut self,
+        selected_range: &Range<usize>,
+        full_text: &Rope,
+        new_text: &str,
+        _: &mut App,
+    ) -> bool {
+        // Nothing changed since the last parse.
+        if self.old_tree.is_some() && selected_range.is_empty() && new_text.is_empty() {
+            return false;
+        }
+
+        if self.old_tree.is_some() {
+            self.edit(selected_range, full_text, new_text);
+        } else {
+            self.set_text(full_text);
+        }
+
+        let Some(config) = &self.config else {
+            return false;
+        };
+        let Ok(mut parser) = new_parser(&config.ts_language) else {
+            return false;
+        };
+        // NOTE: 10K lines, about 4.5ms
+        let Some(tree) = parse_text(&mut parser, &self.text, self.old_tree.as_ref(), None) else {
+            return false;
+        };
+
+        self.old_tree = Some(tree);
+        self.parsed_version = Some(self.version);
+        self.cache.clear();
+        self.highlighted_ranges.clear();
+        self.highlight_range(0..self.text.len_bytes());
+        true
+    }
+
+    /// Reset the text, the syntax tree will be parsed from scratch.
+    pub(crate) fn set_text(&mut self, text: &Rope) {
+        self.cancel_parsing();
+        self.version += 1;
+        self.text = text.clone();
+        self.old_tree = None;
+        self.cache.clear();
+        self.highlighted_ranges.clear();
+        self.requested_ranges.clear();
+    }
+
+    /// Apply an edit to the syntax tree and the highlight cache, without parsing.
+    ///
+    /// - The `range` is the replaced range in the old text.
+    /// - The `full_text` is the entire text after the edit.
+    /// - The `new_text` is the text that replaced the `range`.
+    pub(crate) fn edit(&mut self, range: &Range<usize>, full_text: &Rope, new_text: &str) {
+        self.version += 1;
+
+        let edit = input_edit(&self.text, full_text, range, new_text.len());
+        if let Some(tree) = self.old_tree.as_mut() {
+            tree.edit(&edit);
+        }
+        // The job in progress is parsed from the old text, the edit is applied to its result.
+        if self.parsing.is_some() {
+            self.pending_edits.push(edit);
+        }
+        self.text = full_text.clone();
+
+        // The styles in the edited range are dropped until the next parse, the styles after
+        // the edit are moved, and the styles before the edit are not changed.
+        let mut keys = vec![];
+        let mut cursor = self.cache.lower_bound(Bound::Included(&range.start));
+        while let Some(key) = cursor.key() {
+            keys.push(*key);
+            cursor.move_next();
+        }
+        // The style before the edit may end in the edited range, e.g.: a block comment.
+        let mut cursor = self.cache.lower_bound(Bound::Included(&range.start));
+        cursor.move_prev();
+        if let Some((key, (style_range, _))) = cursor.key().zip(cursor.value()) {
+            if style_range.end > range.start {
+                keys.push(*key);
+            }
+        }
+
+        let mut moved = vec![];
+        for key in keys {
+            if let Some((style_range, name)) = self.cache.remove(&key) {
+                if let Some(style_range) = style_after_edit(&style_range, &edit) {
+                    moved.push((style_range, name));
+                }
+            }
+        }
+        for (style_range, name) in moved {
+            self.cache.insert(style_range.start, (style_range, name));
+        }
+        let new_len = new_text.len();
+        self.highlighted_ranges = self
+            .highlighted_ranges
+            .iter()
+            .map(|highlighted| range_after_edit(highlighted, range, new_len))
+            .filter(|highlighted| !highlighted.is_empty())
+            .collect();
+        self.requested_ranges.clear();
+    }
+
+    fn cancel_parsing(&mut self) {
+        if let Some(cancel) = self.parsing.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        self.pending_edits.clear();
+    }
+
+    /// Create a [`ParseJob`] to parse the text in the background,
+    /// or to highlight the requested ranges by the current syntax tree.
+    ///
+    /// Returns `None` for the plain text, or there is nothing to do, or a job is in progress.
+    pub(crate) fn parse_job(&mut self) -> Option<ParseJob> {
+        let config = self.config.clone()?;
+        if self.parsing.is_some() {
+            return None;
+        }
+
+        let reparse = self.parsed_version != Some(self.version);
+        if reparse && self.failed_version == Some(self.version) {
+            return None;
+        }
+        let mut highlight_ranges = std::mem::take(&mut self.requested_ranges);
+        if reparse {
+            insert_range(&mut highlight_ranges, self.visible_range_with_margin());
+        } else if highlight_ranges.is_empty() {
+            return None;
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.parsing = Some(cancel.clone());
+        self.pending_edits.clear();
+        Some(ParseJob {
+            config,
+            text: self.text.clone(),
+            old_tree: self.old_tree.clone(),
+            reparse,
+            version: self.version,
+            highlight_ranges,
+            cancel,
+        })
+    }
+
+    /// Apply the result of a [`ParseJob`].
+    ///
+    /// If the text has been changed since the job was created, the edits are applied to
+    /// the result, and the text is parsed again by the next job.
+    ///
+    /// Returns false if nothing is applied, then the next job should be started to parse the current text.
+    pub(crate) fn apply_parsed(&mut self, parsed: ParsedSyntax) -> bool {
+        if !self
+            .parsing
+            .as_ref()
+            .is_some_and(|cancel| Arc::ptr_eq(cancel, &parsed.cancel))
+        {
+            return false;
+        }
+        // The job is finished whatever the result, to allow the next job.
+        self.parsing = None;
+        let edits = std::mem::take(&mut self.pending_edits);
+
+        if parsed.reparse {
+            let Some(mut tree) = parsed.tree else {
+                if !parsed.cancel.load(Ordering::Relaxed) {
+                    self.failed_version = Some(parsed.version);
+                }
+                return false;
+            };
+            // The text may have been parsed by `update` in the meantime.
+            if self
+                .parsed_version
+                .is_some_and(|version| version >= parsed.version)
+            {
+                return false;
+            }
+
+            for edit in edits.iter() {
+                tree.edit(edit);
+            }
+            self.old_tree = Some(tree);
+            self.parsed_version = Some(parsed.version);
+            self.fold_ranges = parsed
+                .fold_ranges
+                .into_iter()
+                .filter_map(|rows| {
+                    edits
+                        .iter()
+                        .try_fold(rows, |rows, edit| rows_after_edit(&rows, edit))
+                })
+                .collect();
+            self.cache.clear();
+            self.highlighted_ranges.clear();
+        } else if self.parsed_version != Some(parsed.version) {
+            // The styles are highlighted by a syntax tree that has been replaced.
+            return false;
+        }
+
+        for (range, name) in parsed.styles {
+            if let Some(range) = edits
+                .iter()
+                .try_fold(range, |range, edit| style_after_edit(&range, edit))
+            {
+                self.cache.insert(range.start, (range, name));
+            }
+        }
+        for range in parsed.highlight_ranges {
+            let range = edits.iter().fold(range, |range, edit| {
+                range_after_edit(
+                    &range,
+                    &(edit.start_byte..edit.old_end_byte),
+                    edit.new_end_byte - edit.start_byte,
+                )
+            });
+            if !range.is_empty() {
+                insert_range(&mut self.highlighted_ranges, range);
+            }
+        }
+        true
+    }
+
+    /// Set the visible range of the text, and request to highlight it (with a margin)
+    /// if not highlighted yet.
+    pub(crate) fn set_visible_range(&mut self, range: Range<usize>) {
+        self.visible_range = range;
+        self.request_highlight(self.visible_range_with_margin());
+    }
+
+    /// Request to highlight the `range` by the next [`ParseJob`],
+    /// only the parts that are not highlighted yet are requested.
+    pub(crate) fn request_highlight(&mut self, range: Range<usize>) {
+        for uncovered in uncovered_ranges(&self.highlighted_ranges, &range) {
+            insert_range(&mut self.requested_ranges, uncovered);
+        }
+    }
+
+    fn visible_range_with_margin(&self) -> Range<usize> {
+        let start_row = self
+            .text
+            .byte_to_line(self.visible_range.start.min(self.text.len_bytes()))
+            .saturating_sub(HIGHLIGHT_MARGIN_ROWS);
+        let end_row = self
+            .text
+            .byte_to_line(self.visible_range.end.min(self.text.len_bytes()))
+            + HIGHLIGHT_MARGIN_ROWS;
+
+        let start = self.text.line_to_byte(start_row);
+        let end = if end_row >= self.text.len_lines() {
+            self.text.len_bytes()
+        } else {
+            self.text.line_to_byte(end_row)
+        };
+        start..end
+    }
+
+    /// Highlight the `range` of the text by the current syntax tree synchronously,
+    /// only the parts that are not highlighted yet are queried.
+    fn highlight_range(&mut self, range: Range<usize>) {
+        let (Some(tree), Some(config)) = (&self.old_tree, &self.config) else {
+            return;
+        };
+
+        for uncovered in uncovered_ranges(&self.highlighted_ranges, &range) {
+            let styles = config.highlight(tree, &self.text, uncovered.clone());
+            for (style_range, name) in styles {
+                self.cache.insert(style_range.start, (style_range, name));
+            }
+            insert_range(&mut self.highlighted_ranges, uncovered);
+        }
+    }
+
+    /// The version of the text that the syntax tree is parsed from, `None` if not parsed yet.
+    pub(crate) fn parsed_version(&self) -> Option<usize> {
+        self.parsed_version
+    }
+
+    /// Returns the syntax tree if it is parsed from the current text,
+    /// `None` for the plain text or the text has been changed since the last parse.
+    pub(crate) fn tree(&self) -> Option<&Tree> {
+        if self.parsed_version != Some(self.version) {
+            return None;
+        }
+
+        self.old_tree.as_ref()
+    }
+
+    /// Returns the symbols of the outline by the outline query of the [`LanguageConfig`](super::LanguageConfig).
+    ///
+    /// The symbols are nested by their ranges, e.g.: the methods are the children of the impl.
+    pub fn outline(&self) -> Vec<OutlineItem> {
+        let (Some(tree), Some(query)) = (&self.old_tree, &self.outline_query) else {
+            return vec![];
+        };
+        let Some(name_ix) = query.capture_index_for_name("name") else {
+            return vec![];
+        };
+
+        let source = &self.text;
+        let mut query_cursor = QueryCursor::new();
+        let mut matches = query_cursor.matches(query, tree.root_node(), move |node: Node| {
+            source
+                .byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        });
+
+        let mut items = vec![];
+        while let Some(m) = matches.next() {
+            let mut name_node = None;
+            let mut definition = None;
+            for cap in m.captures {
+                if cap.index == name_ix {
+                    name_node = Some(cap.node);
+                } else if let Some(kind) =
+                    query.capture_names()[cap.index as usize].strip_prefix("definition.")
+                {
+                    definition = Some((kind, cap.node));
+                }
+            }
+            let (Some(name_node), Some((kind, node))) = (name_node, definition) else {
+                continue;
+            };
+
+            let name_range = name_node.byte_range();
+            let name = outline_name(&self.text.byte_slice(name_range.clone()).to_string());
+            if name.is_empty() {
+                continue;
+            }
+            items.push(OutlineItem {
+                name: name.into(),
+                kind: SharedString::from(kind.to_string()),
+                range: node.byte_range(),
+                name_range,
+                children: vec![],
+            });
+        }
+
+        build_outline(items)
+    }
+
+    /// Returns the foldable row ranges (zero based) of the last parsed syntax tree.
+    ///
+    /// The ranges are found by the folds query in [`ParseJob`], so it is cheap to call.
+    pub(crate) fn fold_ranges(&self) -> &[Range<usize>] {
+        &self.fold_ranges
+    }
+
+    /// Returns true if the new line should be indented when inserting a newline at the `offset`,
+    /// by the indents query of the language.
+    ///
+    /// The line is indented if there is an `@indent` node starts at the line of the `offset`,
+    /// and ends after the `offset` (or the `@end` token is after the `offset`).
+    ///
+    /// Returns `None` if there is no indents query.
+    pub(crate) fn indent_after(&self, offset: usize) -> Option<bool> {
+        let (Some(tree), Some(query)) = (&self.old_tree, &self.indent_query) else {
+            return None;
+        };
+        let indent_ix = query.capture_index_for_name("indent")?;
+        let end_ix = query.capture_index_for_name("end");
+
+        let offset = offset.min(self.text.len_bytes());
+        let row = self.text.byte_to_line(offset);
+        let line_start = self.text.line_to_byte(row);
+
+        let source = &self.text;
+        let mut query_cursor = QueryCursor::new();
+        query_cursor.set_byte_range(line_start..offset + 1);
+        let mut matches = query_cursor.matches(query, tree.root_node(), move |node: Node| {
+            source
+                .byte_slice(node.byte_range())
+                .chunks()
+                .map(str::as_bytes)
+        });
+
+        while let Some(m) = matches.next() {
+            let Some(node) = m
+                .captures
+                .iter()
+                .find(|cap| cap.index == indent_ix)
+                .map(|cap| cap.node)
+            else {
+                continue;
+            };
+            if node.start_position().row != row || node.start_byte() >= offset {
+                continue;
+            }
+
+            let end_node = m
+                .captures
+                .iter()
+                .find(|cap| Some(cap.index) == end_ix)
+                .map(|cap| cap.node);
+            let is_inside = match end_node {
+                Some(end_node) => offset <= end_node.start_byte(),
+                None => offset < node.end_byte(),
+            };
+            if is_inside {
+                return Some(true);
+            }
+        }
+
+        Some(false)
+    }
+
+    /// Returns the range of the smallest syntax node that contains the `range` and is larger than it.
+    ///
+    /// Returns `None` if the language has no grammar (the plain text), or no node is larger.
+    pub(crate) fn larger_node_range(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        let tree = self.old_tree.as_ref()?;
+        larger_node_range(tree.root_node(), range)
+    }
 
     /// The argument `range` is the range of the line in the text.
     ///
@@ -549,11 +1127,6 @@ impl SyntaxHighlighter {
         let start_offset = range.start;
         let mut last_range = start_offset..start_offset;
 
-        // NOTE: Iterate over the cache and print the range and style for each item.
-        // for (_, (range, style)) in self.cache.iter() {
-        //     println!("-- range: {:?}, style: {:?}", range, style);
-        // }
-
         let mut cursor = self.cache.lower_bound(Bound::Included(&range.start));
         // Move to the previous item if the current item is not the start of the range.
         // This is for case like JsDoc, where token may contains multiple lines.
@@ -597,15 +1170,31 @@ impl SyntaxHighlighter {
             styles.push((last_range.end..range.end, HighlightStyle::default()));
         }
 
-        let styles = unique_styles(styles);
+        unique_styles(styles)
+    }
 
-        // NOTE: DO NOT remove this comment, it is used for debugging.
-        // for style in &styles {
-        //     println!("---- style: {:?} - {:?}", style.0, style.1.color);
-        // }
-        // println!("--------------------------------");
+    /// Export the `range` of the text with the highlight styles of the `theme`, see [`ExportFormat`].
+    pub fn export(
+        &mut self,
+        range: Range<usize>,
+        format: ExportFormat,
+        theme: &HighlightTheme,
+    ) -> String {
+        let range = range.start.min(self.text.len_bytes())..range.end.min(self.text.len_bytes());
+        self.highlight_range(range.clone());
+        let text = self.text.byte_slice(range.clone()).to_string();
+        let styles = self
+            .styles(&range, theme)
+            .into_iter()
+            .map(|(node_range, style)| {
+                (
+                    node_range.start - range.start..node_range.end - range.start,
+                    style,
+                )
+            })
+            .collect::<Vec<_>>();
 
-        styles
+        format.export(&text, &styles, theme)
     }
 }
 
@@ -686,9 +1275,24 @@ pub(crate) fn unique_styles(
     result
 }
 
+/// Returns the range of the smallest node under the `root` that contains the `range` and is larger than it.
+fn larger_node_range(root: Node, range: &Range<usize>) -> Option<Range<usize>> {
+    let mut node = root.descendant_for_byte_range(range.start, range.end)?;
+    loop {
+        let node_range = node.byte_range();
+        if node_range.start <= range.start
+            && node_range.end >= range.end
+            && node_range.len() > range.len()
+        {
+            return Some(node_range);
+        }
+        node = node.parent()?;
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use gpui::Hsla;
+    use gpui::{Hsla, TestAppContext};
 
     use super::*;
     use crate::Colorize as _;
@@ -772,4 +1376,199 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_larger_node_range() {
+        let source = "fn foo() {\n    let a = bar(1, 2);\n}\n";
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_rust::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+        let root = tree.root_node();
+
+        let mut range = 24..24;
+        let mut ranges = vec![];
+        while let Some(larger) = larger_node_range(root, &range) {
+            ranges.push(&source[larger.clone()]);
+            range = larger;
+        }
+
+        assert_eq!(
+            ranges,
+            vec![
+                "bar",
+                "bar(1, 2)",
+                "let a = bar(1, 2);",
+                "{\n    let a = bar(1, 2);\n}",
+                "fn foo() {\n    let a = bar(1, 2);\n}",
+                "fn foo() {\n    let a = bar(1, 2);\n}\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_input_edit() {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_rust::LANGUAGE.into())
+            .unwrap();
+
+        let old_text = Rope::from_str("fn foo() {\n    bar\n}\n");
+        let new_text = Rope::from_str("fn foo() {\n    baz(\n1)\n}\n");
+        let edit = input_edit(&old_text, &new_text, &(17..18), 5);
+        assert_eq!(edit.start_byte, 17);
+        assert_eq!(edit.old_end_byte, 18);
+        assert_eq!(edit.new_end_byte, 22);
+        assert_eq!(edit.start_position, Point::new(1, 6));
+        assert_eq!(edit.old_end_position, Point::new(1, 7));
+        assert_eq!(edit.new_end_position, Point::new(2, 2));
+
+        // The incremental parsing should be the same as parsing from scratch.
+        let mut tree = parse_text(&mut parser, &old_text, None, None).unwrap();
+        tree.edit(&edit);
+        let tree = parse_text(&mut parser, &new_text, Some(&tree), None).unwrap();
+        let fresh_tree = parse_text(&mut parser, &new_text, None, None).unwrap();
+        assert_eq!(tree.root_node().to_sexp(), fresh_tree.root_node().to_sexp());
+        assert_eq!(
+            tree.root_node()
+                .descendant_for_byte_range(20, 21)
+                .unwrap()
+                .start_position(),
+            Point::new(2, 0)
+        );
+    }
+
+    #[test]
+    fn test_range_after_edit() {
+        // Replace 5..8 with 1 byte.
+        let edit = 5..8;
+        assert_eq!(range_after_edit(&(0..3), &edit, 1), 0..3);
+        assert_eq!(range_after_edit(&(0..5), &edit, 1), 0..5);
+        assert_eq!(range_after_edit(&(3..6), &edit, 1), 3..6);
+        assert_eq!(range_after_edit(&(6..10), &edit, 1), 5..8);
+        assert_eq!(range_after_edit(&(8..10), &edit, 1), 6..8);
+        assert_eq!(range_after_edit(&(10..12), &edit, 1), 8..10);
+        assert_eq!(range_after_edit(&(6..7), &edit, 1), 5..6);
+
+        // Insert 2 bytes at 5.
+        let edit = 5..5;
+        assert_eq!(range_after_edit(&(0..5), &edit, 2), 0..5);
+        assert_eq!(range_after_edit(&(5..8), &edit, 2), 5..10);
+        assert_eq!(range_after_edit(&(3..8), &edit, 2), 3..10);
+    }
+
+    #[test]
+    fn test_highlighted_ranges() {
+        let ranges = vec![2..4, 6..8];
+        assert_eq!(uncovered_ranges(&ranges, &(0..10)), vec![0..2, 4..6, 8..10]);
+        assert_eq!(uncovered_ranges(&ranges, &(3..7)), vec![4..6]);
+        assert!(uncovered_ranges(&ranges, &(2..4)).is_empty());
+        assert_eq!(uncovered_ranges(&[], &(2..4)), vec![2..4]);
+
+        let mut ranges = ranges;
+        insert_range(&mut ranges, 4..6);
+        assert_eq!(ranges, vec![2..8]);
+        insert_range(&mut ranges, 10..12);
+        assert_eq!(ranges, vec![2..8, 10..12]);
+        insert_range(&mut ranges, 0..1);
+        assert_eq!(ranges, vec![0..1, 2..8, 10..12]);
+        insert_range(&mut ranges, 1..11);
+        assert_eq!(ranges, vec![0..12]);
+    }
+
+    #[cfg(feature = "rust")]
+    #[gpui::test]
+    fn test_apply_stale_parsed(cx: &mut TestAppContext) {
+        cx.update(crate::init);
+        let mut highlighter = cx.update(|cx| SyntaxHighlighter::new("rust", cx));
+        let keyword_ranges = |highlighter: &SyntaxHighlighter| {
+            highlighter
+                .cache
+                .iter()
+                .filter(|(_, (_, name))| name.as_ref() == "keyword")
+                .map(|(_, (range, _))| range.clone())
+                .collect::<Vec<_>>()
+        };
+
+        highlighter.set_text(&Rope::from_str("fn foo() {}\n"));
+        let job = highlighter.parse_job().unwrap();
+
+        // Edit while parsing, the result is applied with the edit.
+        highlighter.edit(&(0..0), &Rope::from_str("// a\nfn foo() {}\n"), "// a\n");
+        assert!(highlighter.parse_job().is_none());
+        assert!(highlighter.apply_parsed(job.parse()));
+        assert_eq!(keyword_ranges(&highlighter), vec![5..7]);
+        assert!(highlighter.tree().is_none());
+
+        // Parse again for the edit.
+        let job = highlighter.parse_job().unwrap();
+        assert!(highlighter.apply_parsed(job.parse()));
+        assert_eq!(keyword_ranges(&highlighter), vec![5..7]);
+        assert!(highlighter.tree().is_some());
+        assert!(highlighter.parse_job().is_none());
+
+        // The styles before the edit are kept, the styles after the edit are moved.
+        highlighter.edit(
+            &(17..17),
+            &Rope::from_str("// a\nfn foo() {}\nfn\n"),
+            "fn\n",
+        );
+        assert_eq!(keyword_ranges(&highlighter), vec![5..7]);
+        highlighter.edit(&(17..20), &Rope::from_str("// a\nfn foo() {}\n"), "");
+        highlighter.edit(&(0..5), &Rope::from_str("fn foo() {}\n"), "");
+        assert_eq!(keyword_ranges(&highlighter), vec![0..2]);
+        highlighter.edit(&(0..0), &Rope::from_str("// a\nfn foo() {}\n"), "// a\n");
+        assert_eq!(keyword_ranges(&highlighter), vec![5..7]);
+        let job = highlighter.parse_job().unwrap();
+        assert!(highlighter.apply_parsed(job.parse()));
+
+        // The job is finished even if the parsing is failed.
+        highlighter.edit(&(5..7), &Rope::from_str("// a\nfoo() {}\n"), "");
+        let job = highlighter.parse_job().unwrap();
+        job.cancel.store(true, Ordering::Relaxed);
+        assert!(!highlighter.apply_parsed(job.parse()));
+        assert!(highlighter.parse_job().is_some());
+
+        // The requested range is highlighted by the next job.
+        highlighter.set_text(&Rope::from_str("fn a() {}\n"));
+        let job = highlighter.parse_job().unwrap();
+        assert!(highlighter.apply_parsed(job.parse()));
+        highlighter.cache.clear();
+        highlighter.highlighted_ranges.clear();
+        highlighter.request_highlight(0..2);
+        assert!(keyword_ranges(&highlighter).is_empty());
+        let job = highlighter.parse_job().unwrap();
+        assert!(highlighter.apply_parsed(job.parse()));
+        assert_eq!(keyword_ranges(&highlighter), vec![0..2]);
+    }
+
+    #[gpui::test]
+    fn test_plain_text(cx: &mut TestAppContext) {
+        cx.update(crate::init);
+        cx.update(|cx| {
+            assert!(LanguageRegistry::global(cx)
+                .language(Language::Plain.name())
+                .is_none());
+
+            let mut highlighter = SyntaxHighlighter::new(Language::Plain.name(), cx);
+            assert!(highlighter.config.is_none());
+            assert!(!highlighter.update(&(0..0), &Rope::from_str("fn foo() {}"), "", cx));
+            assert!(!highlighter.is_empty());
+            assert!(highlighter.parse_job().is_none());
+            assert!(highlighter.tree().is_none());
+        });
+    }
+
+    #[cfg(not(feature = "rust"))]
+    #[gpui::test]
+    fn test_disabled_language_fallback_to_plain(cx: &mut TestAppContext) {
+        cx.update(crate::init);
+        let mut highlighter = cx.update(|cx| SyntaxHighlighter::new("rust", cx));
+        assert!(highlighter.config.is_none());
+
+        highlighter.set_text(&Rope::from_str("fn foo() {}"));
+        assert!(highlighter.parse_job().is_none());
+        assert!(highlighter.tree().is_none());
+    }
 }
@@ -1,5 +1,9 @@
 use gpui::SharedString;
 
+/// The built-in languages of the highlighter.
+///
+/// The grammar of each language is enabled by the cargo feature of the same name as [`Language::name`],
+/// except `markdown_inline` is included by `markdown`, and `tsx` is included by `typescript`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, enum_iterator::Sequence)]
 pub enum Language {
     Plain,
@@ -37,6 +41,74 @@ pub enum Language {
     Zig,
 }
 
+/// A pair of brackets (or quotes) of a language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketPair {
+    pub start: SharedString,
+    pub end: SharedString,
+    /// Auto insert the `end` when typing the `start`.
+    pub close: bool,
+}
+
+impl BracketPair {
+    pub fn new(start: impl Into<SharedString>, end: impl Into<SharedString>) -> Self {
+        Self {
+            start: start.into(),
+            end: end.into(),
+            close: true,
+        }
+    }
+
+    /// Set whether to auto insert the `end` when typing the `start`, default is true.
+    pub fn close(mut self, close: bool) -> Self {
+        self.close = close;
+        self
+    }
+
+    /// Return true if the `start` and `end` are the same, e.g.: quotes.
+    pub fn is_quote(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The default bracket pairs: `()`, `[]`, `{}`, `""`, `''`.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("(", ")"),
+            Self::new("[", "]"),
+            Self::new("{", "}"),
+            Self::new("\"", "\""),
+            Self::new("'", "'"),
+        ]
+    }
+}
+
+/// A snippet of a language, offered as a completion item in the code editor.
+///
+/// The `body` is in the VS Code snippet syntax, e.g.: `fn ${1:name}($2) {\n\t$0\n}`,
+/// see [`crate::input::InputState::insert_snippet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// The word to trigger the snippet in the completion menu.
+    pub prefix: SharedString,
+    pub body: SharedString,
+    pub description: Option<SharedString>,
+}
+
+impl Snippet {
+    pub fn new(prefix: impl Into<SharedString>, body: impl Into<SharedString>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            body: body.into(),
+            description: None,
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<SharedString>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LanguageConfig {
     pub name: SharedString,
@@ -45,6 +117,20 @@ pub struct LanguageConfig {
     pub highlights: SharedString,
     pub injections: SharedString,
     pub locals: SharedString,
+    /// The tree-sitter query to find the foldable nodes, captured by `@fold`.
+    pub folds: SharedString,
+    /// The bracket pairs for bracket matching and auto closing, default is [`BracketPair::defaults`].
+    pub brackets: Vec<BracketPair>,
+    /// The tree-sitter query to find the nodes to indent their inner lines, captured by `@indent`,
+    /// and the optional `@end` for the closing token of the node.
+    pub indents: SharedString,
+    /// The tree-sitter query to find the symbols of the outline, the symbol nodes are captured by
+    /// `@definition.{kind}` (e.g.: `@definition.function`) and the names by `@name`, same as the tags query.
+    pub outline: SharedString,
+    /// The line comment token, e.g.: `//`.
+    pub line_comment: Option<SharedString>,
+    /// The block comment tokens, e.g.: `/*` and `*/`.
+    pub block_comment: Option<(SharedString, SharedString)>,
 }
 
 impl LanguageConfig {
@@ -63,8 +149,60 @@ impl LanguageConfig {
             highlights: SharedString::from(highlights.to_string()),
             injections: SharedString::from(injections.to_string()),
             locals: SharedString::from(locals.to_string()),
+            folds: SharedString::default(),
+            brackets: BracketPair::defaults(),
+            indents: SharedString::default(),
+            outline: SharedString::default(),
+            line_comment: None,
+            block_comment: None,
         }
     }
+
+    /// Set the languages used in the injections query, they must be registered to be highlighted.
+    pub fn injection_languages(mut self, languages: Vec<SharedString>) -> Self {
+        self.injection_languages = languages;
+        self
+    }
+
+    /// Set the folds query for code folding, the foldable nodes are captured by `@fold`.
+    pub fn folds(mut self, folds: &str) -> Self {
+        self.folds = SharedString::from(folds.to_string());
+        self
+    }
+
+    /// Set the bracket pairs for bracket matching and auto closing.
+    pub fn brackets(mut self, brackets: Vec<BracketPair>) -> Self {
+        self.brackets = brackets;
+        self
+    }
+
+    /// Set the indents query for auto indent, the indented nodes are captured by `@indent`.
+    pub fn indents(mut self, indents: &str) -> Self {
+        self.indents = SharedString::from(indents.to_string());
+        self
+    }
+
+    /// Set the outline query for the symbols, captured by `@definition.{kind}` and `@name`.
+    pub fn outline(mut self, outline: &str) -> Self {
+        self.outline = SharedString::from(outline.to_string());
+        self
+    }
+
+    /// Set the line comment token, e.g.: `//`.
+    pub fn line_comment(mut self, token: impl Into<SharedString>) -> Self {
+        self.line_comment = Some(token.into());
+        self
+    }
+
+    /// Set the block comment tokens, e.g.: `/*` and `*/`.
+    pub fn block_comment(
+        mut self,
+        start: impl Into<SharedString>,
+        end: impl Into<SharedString>,
+    ) -> Self {
+        self.block_comment = Some((start.into(), end.into()));
+        self
+    }
 }
 impl From<Language> for SharedString {
     fn from(language: Language) -> Self {
@@ -179,184 +317,342 @@ impl Language {
         .collect()
     }
 
+    /// Return the folds query of the language.
+    fn folds(&self) -> &'static str {
+        match self {
+            Self::Go => include_str!("languages/go/folds.scm"),
+            Self::Html => include_str!("languages/html/folds.scm"),
+            Self::JavaScript => include_str!("languages/javascript/folds.scm"),
+            Self::Json => include_str!("languages/json/folds.scm"),
+            Self::Markdown => include_str!("languages/markdown/folds.scm"),
+            Self::Rust => include_str!("languages/rust/folds.scm"),
+            Self::TypeScript | Self::Tsx => include_str!("languages/typescript/folds.scm"),
+            Self::Zig => include_str!("languages/zig/folds.scm"),
+            _ => "",
+        }
+    }
+
+    /// Return the outline query of the language.
+    fn outline(&self) -> &'static str {
+        match self {
+            Self::Go => include_str!("languages/go/outline.scm"),
+            Self::JavaScript => include_str!("languages/javascript/outline.scm"),
+            Self::Json => include_str!("languages/json/outline.scm"),
+            Self::Markdown => include_str!("languages/markdown/outline.scm"),
+            Self::Rust => include_str!("languages/rust/outline.scm"),
+            Self::TypeScript | Self::Tsx => include_str!("languages/typescript/outline.scm"),
+            Self::Zig => include_str!("languages/zig/outline.scm"),
+            _ => "",
+        }
+    }
+
+    /// Return the bracket pairs of the language.
+    fn brackets(&self) -> Vec<BracketPair> {
+        let pair = BracketPair::new;
+        match self {
+            Self::Plain => vec![],
+            // The `'` is used for lifetimes and chars.
+            Self::Rust => vec![
+                pair("(", ")"),
+                pair("[", "]"),
+                pair("{", "}"),
+                pair("\"", "\""),
+            ],
+            Self::JavaScript | Self::TypeScript | Self::Tsx => vec![
+                pair("(", ")"),
+                pair("[", "]"),
+                pair("{", "}"),
+                pair("\"", "\""),
+                pair("'", "'"),
+                pair("`", "`"),
+            ],
+            Self::Go => vec![
+                pair("(", ")"),
+                pair("[", "]"),
+                pair("{", "}"),
+                pair("\"", "\""),
+                pair("`", "`"),
+            ],
+            Self::Html | Self::Erb | Self::Ejs => vec![
+                pair("<", ">").close(false),
+                pair("(", ")"),
+                pair("[", "]"),
+                pair("{", "}"),
+                pair("\"", "\""),
+                pair("'", "'"),
+            ],
+            Self::Markdown | Self::MarkdownInline => vec![
+                pair("(", ")"),
+                pair("[", "]"),
+                pair("{", "}"),
+                pair("`", "`"),
+            ],
+            _ => BracketPair::defaults(),
+        }
+    }
+
+    /// Return the indents query of the language.
+    fn indents(&self) -> &'static str {
+        match self {
+            Self::Go => include_str!("languages/go/indents.scm"),
+            Self::JavaScript => include_str!("languages/javascript/indents.scm"),
+            Self::Json => include_str!("languages/json/indents.scm"),
+            Self::Rust => include_str!("languages/rust/indents.scm"),
+            Self::TypeScript | Self::Tsx => include_str!("languages/typescript/indents.scm"),
+            Self::Zig => include_str!("languages/zig/indents.scm"),
+            _ => "",
+        }
+    }
+
+    /// Return the line comment token and the block comment tokens of the language.
+    fn comments(&self) -> (Option<&'static str>, Option<(&'static str, &'static str)>) {
+        match self {
+            Self::C
+            | Self::Cpp
+            | Self::CSharp
+            | Self::Go
+            | Self::Java
+            | Self::JavaScript
+            | Self::Proto
+            | Self::Rust
+            | Self::Scala
+            | Self::Swift
+            | Self::Tsx
+            | Self::TypeScript => (Some("//"), Some(("/*", "*/"))),
+            Self::Zig => (Some("//"), None),
+            Self::Bash
+            | Self::CMake
+            | Self::Elixir
+            | Self::GraphQL
+            | Self::Make
+            | Self::Python
+            | Self::Ruby
+            | Self::Toml
+            | Self::Yaml => (Some("#"), None),
+            Self::Sql => (Some("--"), Some(("/*", "*/"))),
+            Self::Css => (None, Some(("/*", "*/"))),
+            Self::Html | Self::Markdown => (None, Some(("<!--", "-->"))),
+            Self::Erb | Self::Ejs => (None, Some(("<%#", "%>"))),
+            Self::Plain | Self::Diff | Self::JsDoc | Self::Json | Self::MarkdownInline => {
+                (None, None)
+            }
+        }
+    }
+
     /// Return the language info for the language.
     ///
     /// (language, query, injection, locals)
-    pub(super) fn config(&self) -> LanguageConfig {
+    ///
+    /// Returns `None` for [`Language::Plain`], or the grammar of the language is disabled
+    /// by the cargo feature.
+    pub(super) fn config(&self) -> Option<LanguageConfig> {
         let (language, query, injection, locals) = match self {
-            Self::Plain => (tree_sitter_json::LANGUAGE, "", "", ""),
+            #[cfg(feature = "json")]
             Self::Json => (
                 tree_sitter_json::LANGUAGE,
                 include_str!("languages/json/highlights.scm"),
                 "",
                 "",
             ),
+            #[cfg(feature = "markdown")]
             Self::Markdown => (
                 tree_sitter_md::LANGUAGE,
                 include_str!("languages/markdown/highlights.scm"),
                 include_str!("languages/markdown/injections.scm"),
                 "",
             ),
+            #[cfg(feature = "markdown")]
             Self::MarkdownInline => (
                 tree_sitter_md::INLINE_LANGUAGE,
                 include_str!("languages/markdown_inline/highlights.scm"),
                 "",
                 "",
             ),
+            #[cfg(feature = "toml")]
             Self::Toml => (
                 tree_sitter_toml_ng::LANGUAGE,
                 tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "yaml")]
             Self::Yaml => (
                 tree_sitter_yaml::LANGUAGE,
                 tree_sitter_yaml::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "rust")]
             Self::Rust => (
                 tree_sitter_rust::LANGUAGE,
                 include_str!("languages/rust/highlights.scm"),
                 include_str!("languages/rust/injections.scm"),
                 "",
             ),
+            #[cfg(feature = "go")]
             Self::Go => (
                 tree_sitter_go::LANGUAGE,
                 include_str!("languages/go/highlights.scm"),
                 "",
                 "",
             ),
+            #[cfg(feature = "c")]
             Self::C => (
                 tree_sitter_c::LANGUAGE,
                 tree_sitter_c::HIGHLIGHT_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "cpp")]
             Self::Cpp => (
                 tree_sitter_cpp::LANGUAGE,
                 tree_sitter_cpp::HIGHLIGHT_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "javascript")]
             Self::JavaScript => (
                 tree_sitter_javascript::LANGUAGE,
                 include_str!("languages/javascript/highlights.scm"),
                 include_str!("languages/javascript/injections.scm"),
                 tree_sitter_javascript::LOCALS_QUERY,
             ),
+            #[cfg(feature = "jsdoc")]
             Self::JsDoc => (
                 tree_sitter_jsdoc::LANGUAGE,
                 tree_sitter_jsdoc::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "zig")]
             Self::Zig => (
                 tree_sitter_zig::LANGUAGE,
                 include_str!("languages/zig/highlights.scm"),
                 include_str!("languages/zig/injections.scm"),
                 "",
             ),
+            #[cfg(feature = "java")]
             Self::Java => (
                 tree_sitter_java::LANGUAGE,
                 tree_sitter_java::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "python")]
             Self::Python => (
                 tree_sitter_python::LANGUAGE,
                 tree_sitter_python::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "ruby")]
             Self::Ruby => (
                 tree_sitter_ruby::LANGUAGE,
                 tree_sitter_ruby::HIGHLIGHTS_QUERY,
                 "",
                 tree_sitter_ruby::LOCALS_QUERY,
             ),
+            #[cfg(feature = "bash")]
             Self::Bash => (
                 tree_sitter_bash::LANGUAGE,
                 tree_sitter_bash::HIGHLIGHT_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "html")]
             Self::Html => (
                 tree_sitter_html::LANGUAGE,
                 include_str!("languages/html/highlights.scm"),
                 include_str!("languages/html/injections.scm"),
                 "",
             ),
+            #[cfg(feature = "css")]
             Self::Css => (
                 tree_sitter_css::LANGUAGE,
                 tree_sitter_css::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "swift")]
             Self::Swift => (tree_sitter_swift::LANGUAGE, "", "", ""),
+            #[cfg(feature = "scala")]
             Self::Scala => (
                 tree_sitter_scala::LANGUAGE,
                 tree_sitter_scala::HIGHLIGHTS_QUERY,
                 "",
                 tree_sitter_scala::LOCALS_QUERY,
             ),
+            #[cfg(feature = "sql")]
             Self::Sql => (
                 tree_sitter_sequel::LANGUAGE,
                 tree_sitter_sequel::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "csharp")]
             Self::CSharp => (tree_sitter_c_sharp::LANGUAGE, "", "", ""),
+            #[cfg(feature = "graphql")]
             Self::GraphQL => (tree_sitter_graphql::LANGUAGE, "", "", ""),
+            #[cfg(feature = "proto")]
             Self::Proto => (tree_sitter_proto::LANGUAGE, "", "", ""),
+            #[cfg(feature = "make")]
             Self::Make => (
                 tree_sitter_make::LANGUAGE,
                 tree_sitter_make::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "cmake")]
             Self::CMake => (tree_sitter_cmake::LANGUAGE, "", "", ""),
+            #[cfg(feature = "typescript")]
             Self::TypeScript => (
                 tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
                 include_str!("languages/typescript/highlights.scm"),
                 include_str!("languages/javascript/injections.scm"),
                 tree_sitter_typescript::LOCALS_QUERY,
             ),
+            #[cfg(feature = "typescript")]
             Self::Tsx => (
                 tree_sitter_typescript::LANGUAGE_TSX,
                 tree_sitter_typescript::HIGHLIGHTS_QUERY,
                 "",
                 tree_sitter_typescript::LOCALS_QUERY,
             ),
+            #[cfg(feature = "diff")]
             Self::Diff => (
                 tree_sitter_diff::LANGUAGE,
                 tree_sitter_diff::HIGHLIGHTS_QUERY,
                 "",
                 "",
             ),
+            #[cfg(feature = "elixir")]
             Self::Elixir => (
                 tree_sitter_elixir::LANGUAGE,
                 tree_sitter_elixir::HIGHLIGHTS_QUERY,
                 tree_sitter_elixir::INJECTIONS_QUERY,
                 "",
             ),
+            #[cfg(feature = "erb")]
             Self::Erb => (
                 tree_sitter_embedded_template::LANGUAGE,
                 tree_sitter_embedded_template::HIGHLIGHTS_QUERY,
                 tree_sitter_embedded_template::INJECTIONS_EJS_QUERY,
                 "",
             ),
+            #[cfg(feature = "ejs")]
             Self::Ejs => (
                 tree_sitter_embedded_template::LANGUAGE,
                 tree_sitter_embedded_template::HIGHLIGHTS_QUERY,
                 tree_sitter_embedded_template::INJECTIONS_EJS_QUERY,
                 "",
             ),
+            // The plain text has no grammar, and the others are disabled by the cargo features.
+            _ => return None,
         };
 
         let language = tree_sitter::Language::new(language);
 
-        LanguageConfig::new(
+        let mut config = LanguageConfig::new(
             self.name(),
             language,
             self.injection_languages(),
@@ -364,6 +660,19 @@ impl Language {
             injection,
             locals,
         )
+        .folds(self.folds())
+        .brackets(self.brackets())
+        .indents(self.indents())
+        .outline(self.outline());
+
+        let (line_comment, block_comment) = self.comments();
+        if let Some(token) = line_comment {
+            config = config.line_comment(token);
+        }
+        if let Some((start, end)) = block_comment {
+            config = config.block_comment(start, end);
+        }
+        Some(config)
     }
 }
 
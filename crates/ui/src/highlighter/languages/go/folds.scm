@@ -0,0 +1,11 @@
+[
+  (function_declaration)
+  (method_declaration)
+  (type_declaration)
+  (import_declaration)
+  (const_declaration)
+  (var_declaration)
+  (block)
+  (literal_value)
+  (field_declaration_list)
+] @fold
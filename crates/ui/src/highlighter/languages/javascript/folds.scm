@@ -0,0 +1,13 @@
+[
+  (class_body)
+  (statement_block)
+  (switch_body)
+  (object)
+  (array)
+  (object_pattern)
+  (array_pattern)
+  (named_imports)
+  (template_string)
+  (jsx_element)
+  (comment)
+] @fold
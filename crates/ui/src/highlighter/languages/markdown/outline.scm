@@ -0,0 +1,8 @@
+; The section contains the heading and the sub sections.
+(section
+  (atx_heading
+    (inline) @name)) @definition.heading
+
+(section
+  (setext_heading
+    (paragraph) @name)) @definition.heading
@@ -0,0 +1,17 @@
+[
+  (mod_item)
+  (foreign_mod_item)
+  (trait_item)
+  (impl_item)
+  (function_item)
+  (struct_item)
+  (enum_item)
+  (union_item)
+  (macro_definition)
+  (use_declaration)
+  (block)
+  (match_block)
+  (field_initializer_list)
+  (token_tree)
+  (block_comment)
+] @fold
@@ -0,0 +1,9 @@
+(_ "{" "}" @end) @indent
+(_ "(" ")" @end) @indent
+(_ "[" "]" @end) @indent
+
+[
+  (where_clause)
+  (field_expression)
+  (let_declaration)
+] @indent
@@ -0,0 +1,41 @@
+(mod_item
+  name: (identifier) @name) @definition.module
+
+(struct_item
+  name: (type_identifier) @name) @definition.struct
+
+(enum_item
+  name: (type_identifier) @name) @definition.enum
+
+(union_item
+  name: (type_identifier) @name) @definition.union
+
+(trait_item
+  name: (type_identifier) @name) @definition.interface
+
+(impl_item
+  type: (_) @name) @definition.implementation
+
+(function_item
+  name: (identifier) @name) @definition.function
+
+(function_signature_item
+  name: (identifier) @name) @definition.function
+
+(macro_definition
+  name: (identifier) @name) @definition.macro
+
+(const_item
+  name: (identifier) @name) @definition.constant
+
+(static_item
+  name: (identifier) @name) @definition.constant
+
+(type_item
+  name: (type_identifier) @name) @definition.type
+
+(enum_variant
+  name: (identifier) @name) @definition.variant
+
+(field_declaration
+  name: (field_identifier) @name) @definition.field
@@ -0,0 +1,14 @@
+[
+  (class_body)
+  (statement_block)
+  (switch_body)
+  (enum_body)
+  (object_type)
+  (object)
+  (array)
+  (object_pattern)
+  (array_pattern)
+  (named_imports)
+  (template_string)
+  (comment)
+] @fold
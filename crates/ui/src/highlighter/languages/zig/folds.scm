@@ -0,0 +1,10 @@
+[
+  (function_declaration)
+  (struct_declaration)
+  (enum_declaration)
+  (union_declaration)
+  (opaque_declaration)
+  (block)
+  (initializer_list)
+  (switch_expression)
+] @fold
@@ -0,0 +1,17 @@
+(function_declaration
+  name: (identifier) @name) @definition.function
+
+(variable_declaration
+  (identifier) @name
+  (struct_declaration)) @definition.struct
+
+(variable_declaration
+  (identifier) @name
+  (enum_declaration)) @definition.enum
+
+(variable_declaration
+  (identifier) @name
+  (union_declaration)) @definition.union
+
+(container_field
+  name: (identifier) @name) @definition.field
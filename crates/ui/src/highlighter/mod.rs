@@ -1,9 +1,17 @@
+mod detect;
+mod export;
+mod grammar;
 mod highlighter;
 mod languages;
+mod outline;
 mod registry;
+mod theme_loader;
 
+pub use export::*;
+pub use grammar::*;
 pub use highlighter::*;
 pub use languages::*;
+pub use outline::OutlineItem;
 pub use registry::*;
 
 use gpui::App;
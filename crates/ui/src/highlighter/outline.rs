@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use gpui::SharedString;
+
+/// A symbol of the outline, e.g.: a function, a struct, a Markdown heading or a JSON key.
+///
+/// See [`SyntaxHighlighter::outline`](super::SyntaxHighlighter::outline).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub name: SharedString,
+    /// The kind of the symbol by the `@definition.{kind}` capture, e.g.: `function`, `struct`, `heading`.
+    pub kind: SharedString,
+    /// The byte range of the symbol.
+    pub range: Range<usize>,
+    /// The byte range of the name of the symbol.
+    pub name_range: Range<usize>,
+    /// The symbols inside the range of this symbol.
+    pub children: Vec<OutlineItem>,
+}
+
+impl OutlineItem {
+    /// Return the symbols contain the `offset`, from the outermost to the innermost.
+    ///
+    /// If the `offset` is at the end of a symbol and the start of the next one, the next one is used.
+    pub fn path_at(items: &[OutlineItem], offset: usize) -> Vec<&OutlineItem> {
+        let mut path = vec![];
+        let mut items = items;
+        while let Some(item) = items
+            .iter()
+            .rev()
+            .find(|item| item.range.start <= offset && offset <= item.range.end)
+        {
+            path.push(item);
+            items = &item.children;
+        }
+
+        path
+    }
+}
+
+/// Clean up the name of the symbol, e.g.: the quotes of the JSON key, the line breaks of the heading.
+pub(super) fn outline_name(text: &str) -> String {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text);
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Build the tree of the symbols, a symbol inside the range of another symbol is its child.
+pub(super) fn build_outline(mut items: Vec<OutlineItem>) -> Vec<OutlineItem> {
+    // The outer symbol is before the inner symbols with the same start.
+    items.sort_by(|a, b| {
+        a.range
+            .start
+            .cmp(&b.range.start)
+            .then(b.range.end.cmp(&a.range.end))
+    });
+    // The same node may be matched by multiple patterns.
+    items.dedup_by(|a, b| a.range == b.range && a.name_range == b.name_range);
+
+    fn pop(stack: &mut Vec<OutlineItem>, roots: &mut Vec<OutlineItem>) {
+        if let Some(item) = stack.pop() {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(item),
+                None => roots.push(item),
+            }
+        }
+    }
+
+    let mut roots = vec![];
+    let mut stack: Vec<OutlineItem> = vec![];
+    for item in items {
+        while stack
+            .last()
+            .map_or(false, |parent| item.range.end > parent.range.end)
+        {
+            pop(&mut stack, &mut roots);
+        }
+        stack.push(item);
+    }
+    while !stack.is_empty() {
+        pop(&mut stack, &mut roots);
+    }
+
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, range: Range<usize>) -> OutlineItem {
+        OutlineItem {
+            name: name.to_string().into(),
+            kind: "function".into(),
+            name_range: range.start..range.start + 1,
+            range,
+            children: vec![],
+        }
+    }
+
+    fn names(items: &[OutlineItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| {
+                if item.children.is_empty() {
+                    item.name.to_string()
+                } else {
+                    format!("{}({})", item.name, names(&item.children).join(","))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_build_outline() {
+        let outline = build_outline(vec![
+            item("b", 20..30),
+            item("a", 0..50),
+            item("c", 32..40),
+            item("b1", 22..25),
+            item("b1", 22..25),
+            item("d", 50..60),
+            item("d1", 50..55),
+        ]);
+        assert_eq!(names(&outline), vec!["a(b(b1),c)", "d(d1)"]);
+        assert!(build_outline(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_path_at() {
+        let outline = build_outline(vec![
+            item("a", 0..50),
+            item("b", 20..30),
+            item("c", 30..40),
+            item("d", 60..70),
+        ]);
+
+        let path = |offset| {
+            OutlineItem::path_at(&outline, offset)
+                .iter()
+                .map(|item| item.name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(path(0), vec!["a"]);
+        assert_eq!(path(25), vec!["a", "b"]);
+        assert_eq!(path(30), vec!["a", "c"]);
+        assert_eq!(path(55), Vec::<String>::new());
+        assert_eq!(path(70), vec!["d"]);
+    }
+
+    #[test]
+    fn test_outline_name() {
+        assert_eq!(outline_name("\"name\""), "name");
+        assert_eq!(outline_name(" Hello\n  World "), "Hello World");
+        assert_eq!(outline_name("Foo<T>"), "Foo<T>");
+    }
+}
@@ -7,13 +7,17 @@ use std::{
     sync::{Arc, LazyLock},
 };
 
-use super::LanguageConfig;
+use super::{LanguageConfig, Snippet};
 use crate::{highlighter::languages, ThemeMode};
 
 pub(super) fn init(cx: &mut App) {
     let mut register = LanguageRegistry::new();
+    // The languages disabled by the cargo features are not registered,
+    // `SyntaxHighlighter` falls back to the plain text for them.
     for language in languages::Language::all() {
-        register.register(language.name(), &language.config());
+        if let Some(config) = language.config() {
+            register.register(language.name(), &config);
+        }
     }
 
     cx.set_global(register);
@@ -407,6 +411,7 @@ impl HighlightTheme {
 #[derive(Clone)]
 pub struct LanguageRegistry {
     languages: HashMap<String, LanguageConfig>,
+    snippets: HashMap<String, Vec<Snippet>>,
     pub(crate) light_theme: Arc<HighlightTheme>,
     pub(crate) dark_theme: Arc<HighlightTheme>,
 }
@@ -425,16 +430,24 @@ impl LanguageRegistry {
     pub fn new() -> Self {
         Self {
             languages: HashMap::new(),
+            snippets: HashMap::new(),
             light_theme: Arc::new(HighlightTheme::default_light()),
             dark_theme: Arc::new(HighlightTheme::default_dark()),
         }
     }
 
+    /// Register a language, or replace the registered one with the same name.
+    ///
+    /// The grammar can also be loaded at runtime, see [`load_grammar_library`](super::load_grammar_library)
+    /// and [`LanguageConfig::from_query_dir`].
     pub fn register(&mut self, lang: &str, config: &LanguageConfig) {
         self.languages.insert(lang.to_string(), config.clone());
     }
 
     /// Set highlighter theme.
+    ///
+    /// The open editors are refreshed when it is set by [`LanguageRegistry::global_mut`],
+    /// the themes can be loaded by [`HighlightTheme::from_vscode_theme`] and [`HighlightTheme::from_tm_theme`].
     pub fn set_theme(&mut self, light: &HighlightTheme, dark: &HighlightTheme) {
         self.light_theme = Arc::new(light.clone());
         self.dark_theme = Arc::new(dark.clone());
@@ -457,6 +470,21 @@ impl LanguageRegistry {
     pub fn language(&self, name: &str) -> Option<&LanguageConfig> {
         self.languages.get(name)
     }
+
+    /// Register the `snippets` for the language, appended to the registered snippets.
+    pub fn register_snippets(&mut self, lang: &str, snippets: Vec<Snippet>) {
+        self.snippets
+            .entry(lang.to_string())
+            .or_default()
+            .extend(snippets);
+    }
+
+    /// Returns the snippets registered for the given language name.
+    pub fn snippets(&self, name: &str) -> &[Snippet] {
+        self.snippets
+            .get(name)
+            .map_or(&[], |snippets| snippets.as_slice())
+    }
 }
 
 #[cfg(test)]
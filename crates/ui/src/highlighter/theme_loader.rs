@@ -0,0 +1,576 @@
+//! Convert the VS Code color themes and the TextMate themes into [`HighlightTheme`].
+//!
+//! The TextMate scopes of the token colors are mapped to the capture names of [`SyntaxColors`](super::SyntaxColors),
+//! e.g.: `keyword.control` to `keyword`, `entity.name.function` to `function`.
+use anyhow::{anyhow, Context as _, Result};
+use serde_json::{json, Map, Value};
+
+use super::HighlightTheme;
+
+/// The TextMate scopes of the capture names, in order of priority.
+///
+/// The capture names with a prefix (e.g.: `comment.doc`) fall back to the style of the prefix,
+/// see [`SyntaxColors::style`](super::SyntaxColors::style).
+const CAPTURE_SCOPES: &[(&str, &[&str])] = &[
+    ("attribute", &["entity.other.attribute-name"]),
+    (
+        "boolean",
+        &["constant.language.boolean", "constant.language"],
+    ),
+    ("comment", &["comment"]),
+    ("comment.doc", &["comment.block.documentation"]),
+    (
+        "constant",
+        &["constant.other", "constant.language", "constant"],
+    ),
+    (
+        "constructor",
+        &["entity.name.function.constructor", "entity.name.type.class"],
+    ),
+    ("embedded", &["meta.embedded"]),
+    ("emphasis", &["markup.italic"]),
+    ("emphasis.strong", &["markup.bold"]),
+    ("enum", &["entity.name.type.enum"]),
+    ("function", &["entity.name.function", "support.function"]),
+    ("keyword", &["keyword.control", "keyword", "storage"]),
+    ("label", &["entity.name.label"]),
+    ("link_text", &["string.other.link", "markup.underline.link"]),
+    ("link_uri", &["markup.underline.link"]),
+    ("number", &["constant.numeric"]),
+    ("operator", &["keyword.operator"]),
+    (
+        "preproc",
+        &["meta.preprocessor", "keyword.control.directive"],
+    ),
+    (
+        "property",
+        &[
+            "variable.other.property",
+            "support.type.property-name",
+            "variable.other.member",
+        ],
+    ),
+    ("punctuation", &["punctuation"]),
+    (
+        "punctuation.bracket",
+        &["punctuation.bracket", "punctuation.section", "meta.brace"],
+    ),
+    (
+        "punctuation.delimiter",
+        &["punctuation.separator", "punctuation.terminator"],
+    ),
+    (
+        "punctuation.list_marker",
+        &["punctuation.definition.list", "markup.list"],
+    ),
+    (
+        "punctuation.special",
+        &[
+            "punctuation.definition.template-expression",
+            "punctuation.section.embedded",
+        ],
+    ),
+    ("string", &["string.quoted", "string"]),
+    ("string.escape", &["constant.character.escape"]),
+    ("string.regex", &["string.regexp"]),
+    ("string.special", &["string.other"]),
+    ("string.special.symbol", &["constant.other.symbol"]),
+    ("tag", &["entity.name.tag"]),
+    ("tag.doctype", &["meta.tag.sgml.doctype"]),
+    ("text.literal", &["markup.inline.raw", "markup.raw"]),
+    ("title", &["markup.heading", "entity.name.section"]),
+    (
+        "type",
+        &["entity.name.type", "support.type", "storage.type"],
+    ),
+    ("variable", &["variable.other", "variable"]),
+    ("variable.special", &["variable.language"]),
+    (
+        "variant",
+        &["variable.other.enummember", "constant.other.enum"],
+    ),
+];
+
+/// A token color rule of the theme.
+#[derive(Debug, Default)]
+struct TokenRule {
+    /// The scope selectors, only the simple selectors (e.g.: `keyword.control`) are supported.
+    selectors: Vec<String>,
+    foreground: Option<String>,
+    font_style: Option<String>,
+}
+
+impl TokenRule {
+    /// Parse the rule from the `{ "scope": ..., "settings": { ... } }` object.
+    fn parse(value: &Value) -> Self {
+        let scopes = match value.get("scope") {
+            Some(Value::String(scope)) => vec![scope.as_str()],
+            Some(Value::Array(scopes)) => scopes.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        let selectors = scopes
+            .iter()
+            .flat_map(|scope| scope.split(','))
+            .map(str::trim)
+            .filter(|selector| {
+                !selector.is_empty() && !selector.contains(' ') && !selector.starts_with('-')
+            })
+            .map(ToString::to_string)
+            .collect();
+
+        let settings = value.get("settings");
+        let setting = |key: &str| {
+            settings
+                .and_then(|settings| settings.get(key))
+                .and_then(Value::as_str)
+                .map(ToString::to_string)
+        };
+
+        Self {
+            selectors,
+            foreground: setting("foreground").filter(|color| is_color(color)),
+            font_style: setting("fontStyle"),
+        }
+    }
+}
+
+/// Return true if the selector matches the scope, e.g.: `keyword` matches `keyword.control`.
+fn selector_matches(selector: &str, scope: &str) -> bool {
+    scope
+        .strip_prefix(selector)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Return the setting of the most specific rule matching the `scope`, the later rule wins the tie.
+fn resolve<'a>(
+    rules: &'a [TokenRule],
+    scope: &str,
+    setting: impl Fn(&'a TokenRule) -> Option<&'a str>,
+) -> Option<&'a str> {
+    let mut best: Option<(usize, &str)> = None;
+    for rule in rules {
+        let Some(value) = setting(rule) else {
+            continue;
+        };
+        for selector in &rule.selectors {
+            if selector_matches(selector, scope)
+                && best.map_or(true, |(len, _)| selector.len() >= len)
+            {
+                best = Some((selector.len(), value));
+            }
+        }
+    }
+
+    best.map(|(_, value)| value)
+}
+
+/// Return the syntax styles of the capture names in the format of [`SyntaxColors`](super::SyntaxColors).
+fn syntax_styles(rules: &[TokenRule]) -> Map<String, Value> {
+    let mut styles = Map::new();
+    for (name, scopes) in CAPTURE_SCOPES {
+        let color = scopes
+            .iter()
+            .find_map(|scope| resolve(rules, scope, |rule| rule.foreground.as_deref()));
+        let font_style = scopes
+            .iter()
+            .find_map(|scope| resolve(rules, scope, |rule| rule.font_style.as_deref()))
+            .unwrap_or_default();
+        if color.is_none() && font_style.is_empty() {
+            continue;
+        }
+
+        styles.insert(
+            name.to_string(),
+            json!({
+                "color": color,
+                "font_style": font_style.contains("italic").then_some("italic"),
+                "font_weight": font_style.contains("bold").then_some(700),
+            }),
+        );
+    }
+
+    styles
+}
+
+fn is_color(color: &str) -> bool {
+    color.starts_with('#')
+        && matches!(color.len(), 4 | 5 | 7 | 9)
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Return true if the color (e.g.: `#1e1e1e`) is dark, by the relative luminance.
+fn is_dark_color(color: &str) -> Option<bool> {
+    if !is_color(color) {
+        return None;
+    }
+
+    let hex = &color[1..];
+    let channel = |ix: usize| -> Option<f32> {
+        let value = if hex.len() <= 4 {
+            u8::from_str_radix(&hex[ix..ix + 1], 16).ok()? * 17
+        } else {
+            u8::from_str_radix(&hex[ix * 2..ix * 2 + 2], 16).ok()?
+        };
+        Some(value as f32 / 255.)
+    };
+    let luminance = 0.2126 * channel(0)? + 0.7152 * channel(1)? + 0.0722 * channel(2)?;
+    Some(luminance < 0.5)
+}
+
+/// Remove the comments and the trailing commas of the JSON with comments (e.g.: the VS Code themes).
+fn strip_json_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => output.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                output.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|c| *c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            (']' | '}', _) => {
+                // Remove the trailing comma before the closing bracket.
+                let content_len = output.trim_end().len();
+                if output[..content_len].ends_with(',') {
+                    output.truncate(content_len - 1);
+                }
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+/// Convert the property list XML (e.g.: the `.tmTheme` files) into JSON value.
+fn plist_to_json(xml: &str) -> Result<Value> {
+    fn convert(node: roxmltree::Node) -> Result<Value> {
+        let text = || node.text().unwrap_or_default();
+        Ok(match node.tag_name().name() {
+            "dict" => {
+                let mut map = Map::new();
+                let mut children = node.children().filter(|child| child.is_element());
+                while let Some(key) = children.next() {
+                    let value = children
+                        .next()
+                        .ok_or_else(|| anyhow!("missing value of key {:?}", key.text()))?;
+                    map.insert(key.text().unwrap_or_default().to_string(), convert(value)?);
+                }
+                Value::Object(map)
+            }
+            "array" => Value::Array(
+                node.children()
+                    .filter(|child| child.is_element())
+                    .map(convert)
+                    .collect::<Result<_>>()?,
+            ),
+            "integer" => json!(text().trim().parse::<i64>()?),
+            "real" => json!(text().trim().parse::<f64>()?),
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::String(text().to_string()),
+        })
+    }
+
+    let document = roxmltree::Document::parse_with_options(
+        xml,
+        roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )?;
+    let root = document
+        .root_element()
+        .children()
+        .find(|child| child.is_element())
+        .ok_or_else(|| anyhow!("empty plist"))?;
+    convert(root)
+}
+
+/// Build the theme in the format of [`HighlightTheme`].
+///
+/// - `editor_colors` is the map of the [`HighlightThemeStyle`](super::HighlightThemeStyle) keys to the colors.
+fn build_theme(
+    name: &str,
+    is_dark: Option<bool>,
+    mut editor_colors: Map<String, Value>,
+    rules: &[TokenRule],
+) -> Result<HighlightTheme> {
+    editor_colors.retain(|_, color| color.as_str().is_some_and(is_color));
+    let is_dark = is_dark
+        .or_else(|| {
+            editor_colors
+                .get("editor.background")
+                .and_then(Value::as_str)
+                .and_then(is_dark_color)
+        })
+        .unwrap_or(true);
+    editor_colors.insert("syntax".into(), Value::Object(syntax_styles(rules)));
+
+    let theme = json!({
+        "name": name,
+        "appearance": if is_dark { "dark" } else { "light" },
+        "style": editor_colors,
+    });
+    serde_json::from_value(theme).context("failed to build highlight theme")
+}
+
+impl HighlightTheme {
+    /// Load a VS Code color theme from the `.json` file content, the comments are allowed.
+    ///
+    /// The `editor.*` colors and the `tokenColors` are used.
+    pub fn from_vscode_theme(json: &str) -> Result<Self> {
+        let theme: Value = serde_json::from_str(&strip_json_comments(json))
+            .context("failed to parse VS Code theme")?;
+
+        let rules = match theme.get("tokenColors") {
+            Some(Value::Array(rules)) => rules.iter().map(TokenRule::parse).collect(),
+            Some(Value::String(path)) => {
+                return Err(anyhow!(
+                    "`tokenColors` from the file {:?} is not supported",
+                    path
+                ))
+            }
+            _ => vec![],
+        };
+        let is_dark = theme
+            .get("type")
+            .and_then(Value::as_str)
+            .map(|kind| !kind.to_lowercase().contains("light"));
+
+        const KEYS: [(&str, &str); 9] = [
+            ("editor.background", "editor.background"),
+            ("editor.foreground", "editor.foreground"),
+            (
+                "editor.lineHighlightBackground",
+                "editor.active_line.background",
+            ),
+            ("editorLineNumber.foreground", "editor.line_number"),
+            (
+                "editorLineNumber.activeForeground",
+                "editor.active_line_number",
+            ),
+            ("editorError.foreground", "error"),
+            ("editorWarning.foreground", "warning"),
+            ("editorInfo.foreground", "info"),
+            ("editorHint.foreground", "hint"),
+        ];
+        let colors = theme.get("colors");
+        let editor_colors = KEYS
+            .iter()
+            .filter_map(|(vscode_key, key)| {
+                let color = colors?.get(vscode_key)?;
+                Some((key.to_string(), color.clone()))
+            })
+            .collect();
+
+        let name = theme
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or("VS Code Theme");
+        build_theme(name, is_dark, editor_colors, &rules)
+    }
+
+    /// Load a TextMate theme from the `.tmTheme` file content.
+    ///
+    /// The global settings (the first rule without scope) are used for the editor colors.
+    pub fn from_tm_theme(xml: &str) -> Result<Self> {
+        let theme = plist_to_json(xml).context("failed to parse TextMate theme")?;
+        let settings = theme
+            .get("settings")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("missing `settings` in TextMate theme"))?;
+
+        let rules: Vec<TokenRule> = settings.iter().map(TokenRule::parse).collect();
+        const KEYS: [(&str, &str); 5] = [
+            ("background", "editor.background"),
+            ("foreground", "editor.foreground"),
+            ("lineHighlight", "editor.active_line.background"),
+            ("gutterForeground", "editor.line_number"),
+            ("gutterForegroundHighlight", "editor.active_line_number"),
+        ];
+        let global = settings
+            .iter()
+            .find(|rule| rule.get("scope").is_none())
+            .and_then(|rule| rule.get("settings"));
+        let editor_colors = KEYS
+            .iter()
+            .filter_map(|(tm_key, key)| {
+                let color = global?.get(tm_key)?;
+                Some((key.to_string(), color.clone()))
+            })
+            .collect();
+
+        let name = theme
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or("TextMate Theme");
+        build_theme(name, None, editor_colors, &rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::{rgb, FontWeight};
+
+    use super::*;
+
+    #[test]
+    fn test_strip_json_comments() {
+        let json = r#"{
+            // comment
+            "a": "http://b", /* block
+            comment */
+            "c": [1, 2,],
+            "d": "\"//\"",
+        }"#;
+        let value: Value = serde_json::from_str(&strip_json_comments(json)).unwrap();
+        assert_eq!(
+            value,
+            json!({ "a": "http://b", "c": [1, 2], "d": "\"//\"" })
+        );
+    }
+
+    #[test]
+    fn test_resolve_scope() {
+        let rules: Vec<TokenRule> = [
+            json!({ "scope": "keyword", "settings": { "foreground": "#111111" } }),
+            json!({ "scope": ["keyword.control", "storage"], "settings": { "foreground": "#222222" } }),
+            json!({ "scope": "keyword.operator, source.js keyword", "settings": { "foreground": "#333333" } }),
+            json!({ "scope": "keyword", "settings": { "fontStyle": "bold" } }),
+        ]
+        .iter()
+        .map(TokenRule::parse)
+        .collect();
+
+        let foreground = |scope| resolve(&rules, scope, |rule| rule.foreground.as_deref());
+        assert_eq!(foreground("keyword"), Some("#111111"));
+        assert_eq!(foreground("keyword.control.flow"), Some("#222222"));
+        assert_eq!(foreground("keyword.operator"), Some("#333333"));
+        assert_eq!(foreground("keywords"), None);
+        assert_eq!(foreground("storage.type"), Some("#222222"));
+        assert_eq!(
+            resolve(&rules, "keyword.control", |rule| rule.font_style.as_deref()),
+            Some("bold")
+        );
+    }
+
+    #[test]
+    fn test_from_vscode_theme() {
+        let theme = HighlightTheme::from_vscode_theme(
+            r##"{
+                "name": "Test Light",
+                "type": "light",
+                "colors": {
+                    "editor.background": "#ffffff",
+                    "editorLineNumber.foreground": "#999999",
+                },
+                // Token colors
+                "tokenColors": [
+                    { "settings": { "foreground": "#000000" } },
+                    { "scope": "comment", "settings": { "foreground": "#008000", "fontStyle": "italic" } },
+                    { "scope": ["keyword", "storage.type"], "settings": { "foreground": "#0000ff", "fontStyle": "bold" } },
+                    { "scope": "entity.name.function", "settings": { "foreground": "#795e26" } },
+                ],
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.name, "Test Light");
+        assert_eq!(theme.appearance, crate::ThemeMode::Light);
+        assert_eq!(theme.style.background, Some(rgb(0xffffff).into()));
+        assert_eq!(theme.style.line_number, Some(rgb(0x999999).into()));
+
+        let syntax = &theme.style.syntax;
+        let comment = syntax.style("comment").unwrap();
+        assert_eq!(comment.color, Some(rgb(0x008000).into()));
+        assert_eq!(comment.font_style, Some(gpui::FontStyle::Italic));
+        let keyword = syntax.style("keyword").unwrap();
+        assert_eq!(keyword.color, Some(rgb(0x0000ff).into()));
+        assert_eq!(keyword.font_weight, Some(FontWeight::BOLD));
+        assert_eq!(
+            syntax.style("function").unwrap().color,
+            Some(rgb(0x795e26).into())
+        );
+        assert_eq!(
+            syntax.style("type").unwrap().color,
+            Some(rgb(0x0000ff).into())
+        );
+        assert_eq!(syntax.style("comment.doc"), Some(comment));
+        assert_eq!(syntax.style("number"), None);
+    }
+
+    #[test]
+    fn test_from_tm_theme() {
+        let theme = HighlightTheme::from_tm_theme(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+            <plist version="1.0">
+            <dict>
+                <key>name</key>
+                <string>Test Dark</string>
+                <key>settings</key>
+                <array>
+                    <dict>
+                        <key>settings</key>
+                        <dict>
+                            <key>background</key>
+                            <string>#1E1E1E</string>
+                            <key>lineHighlight</key>
+                            <string>#2A2A2A</string>
+                        </dict>
+                    </dict>
+                    <dict>
+                        <key>scope</key>
+                        <string>string, constant.numeric</string>
+                        <key>settings</key>
+                        <dict>
+                            <key>foreground</key>
+                            <string>#CE9178</string>
+                        </dict>
+                    </dict>
+                </array>
+            </dict>
+            </plist>"#,
+        )
+        .unwrap();
+
+        assert_eq!(theme.name, "Test Dark");
+        assert_eq!(theme.appearance, crate::ThemeMode::Dark);
+        assert_eq!(theme.style.background, Some(rgb(0x1e1e1e).into()));
+        assert_eq!(theme.style.active_line, Some(rgb(0x2a2a2a).into()));
+        let syntax = &theme.style.syntax;
+        assert_eq!(
+            syntax.style("string").unwrap().color,
+            Some(rgb(0xce9178).into())
+        );
+        assert_eq!(
+            syntax.style("number").unwrap().color,
+            Some(rgb(0xce9178).into())
+        );
+        assert_eq!(syntax.style("keyword"), None);
+    }
+}
@@ -28,6 +28,10 @@ pub struct History<I: HistoryItem> {
     max_undo: usize,
     group_interval: Option<Duration>,
     unique: bool,
+    /// The depth of the nested [`History::start_group`] calls.
+    group_depth: usize,
+    /// The next change will be pushed with a new version, set by [`History::end_group`].
+    separated: bool,
 }
 
 impl<I> History<I>
@@ -44,6 +48,8 @@ where
             max_undo: 1000,
             group_interval: None,
             unique: false,
+            group_depth: 0,
+            separated: false,
         }
     }
 
@@ -67,16 +73,40 @@ where
     }
 
     /// Increment the version number if the last change was made more than `GROUP_INTERVAL` milliseconds ago.
+    ///
+    /// The version is not changed in a group started by [`Self::start_group`].
     fn inc_version(&mut self) -> usize {
         let t = Instant::now();
-        if Some(self.last_changed_at.elapsed()) > self.group_interval {
+        if self.group_depth == 0
+            && (self.separated || Some(self.last_changed_at.elapsed()) > self.group_interval)
+        {
             self.version += 1;
         }
 
+        self.separated = false;
         self.last_changed_at = t;
         self.version
     }
 
+    /// Start a group of changes, the changes pushed until [`Self::end_group`] are undone
+    /// in one step, and they are not grouped with the changes before or after them.
+    ///
+    /// The groups can be nested, only the outermost group takes effect.
+    pub fn start_group(&mut self) {
+        if self.group_depth == 0 {
+            self.version += 1;
+        }
+        self.group_depth += 1;
+    }
+
+    /// End the group of changes started by [`Self::start_group`].
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 {
+            self.separated = true;
+        }
+    }
+
     /// Get the current version number.
     pub fn version(&self) -> usize {
         self.version
@@ -85,8 +115,17 @@ where
     pub fn push(&mut self, item: I) {
         let version = self.inc_version();
 
+        // Drop the oldest changes of the same version together, the group in progress is kept.
         if self.undos.len() >= self.max_undo {
-            self.undos.remove(0);
+            let first_version = self.undos[0].version();
+            if first_version != version {
+                let len = self
+                    .undos
+                    .iter()
+                    .take_while(|c| c.version() == first_version)
+                    .count();
+                self.undos.drain(..len);
+            }
         }
 
         if self.unique {
@@ -119,14 +158,7 @@ where
         if let Some(first_change) = self.undos.pop() {
             let mut changes = vec![first_change.clone()];
             // pick the next all changes with the same version
-            while self
-                .undos
-                .iter()
-                .filter(|c| c.version() == first_change.version())
-                .count()
-                > 0
-            {
-                let change = self.undos.pop().unwrap();
+            while let Some(change) = self.undos.pop_if(|c| c.version() == first_change.version()) {
                 changes.push(change);
             }
 
@@ -141,14 +173,7 @@ where
         if let Some(first_change) = self.redos.pop() {
             let mut changes = vec![first_change.clone()];
             // pick the next all changes with the same version
-            while self
-                .redos
-                .iter()
-                .filter(|c| c.version() == first_change.version())
-                .count()
-                > 0
-            {
-                let change = self.redos.pop().unwrap();
+            while let Some(change) = self.redos.pop_if(|c| c.version() == first_change.version()) {
                 changes.push(change);
             }
             self.undos.extend(changes.clone());
@@ -236,6 +261,75 @@ mod tests {
         assert_eq!(history.undo().is_none(), true);
     }
 
+    #[test]
+    fn test_history_group() {
+        let mut history: History<TabIndex> =
+            History::new().group_interval(std::time::Duration::from_secs(60));
+        history.push(0.into());
+        history.start_group();
+        history.push(1.into());
+        history.start_group();
+        history.push(2.into());
+        history.end_group();
+        history.push(3.into());
+        history.end_group();
+        history.push(4.into());
+
+        let changes = history.undo().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].tab_index, 4);
+
+        let changes = history.undo().unwrap();
+        assert_eq!(
+            changes.iter().map(|c| c.tab_index).collect::<Vec<_>>(),
+            vec![3, 2, 1]
+        );
+
+        let changes = history.undo().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].tab_index, 0);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn test_max_undo_in_group() {
+        let mut history: History<TabIndex> = History::new().max_undo(3);
+        history.start_group();
+        for ix in 0..5 {
+            history.push(ix.into());
+        }
+        history.end_group();
+        // The group in progress is not truncated.
+        assert_eq!(history.undos().len(), 5);
+
+        // The whole oldest group is dropped.
+        history.push(5.into());
+        assert_eq!(history.undos().len(), 1);
+
+        history.start_group();
+        history.push(6.into());
+        history.push(7.into());
+        history.end_group();
+        history.push(8.into());
+        assert_eq!(
+            history
+                .undos()
+                .iter()
+                .map(|c| c.tab_index)
+                .collect::<Vec<_>>(),
+            vec![6, 7, 8]
+        );
+
+        let changes = history.undo().unwrap();
+        assert_eq!(changes[0].tab_index, 8);
+        let changes = history.undo().unwrap();
+        assert_eq!(
+            changes.iter().map(|c| c.tab_index).collect::<Vec<_>>(),
+            vec![7, 6]
+        );
+        assert!(history.undo().is_none());
+    }
+
     #[test]
     fn test_unique_history() {
         let mut history: History<TabIndex> = History::new().max_undo(100).unique();
@@ -0,0 +1,205 @@
+//! Auto indent for the [`InputMode::CodeEditor`](super::mode::InputMode::CodeEditor).
+use std::ops::Range;
+
+use gpui::{Context, EntityInputHandler as _, Window};
+use ropey::Rope;
+
+use crate::highlighter::BracketPair;
+
+use super::{
+    brackets::{char_after, scan_backward},
+    InputState, RopeExt as _,
+};
+
+/// Return the leading whitespaces of the `row`.
+pub(super) fn indent_of_row(text: &Rope, row: usize) -> String {
+    text.slice_line(row)
+        .chars()
+        .take_while(|c| c.is_whitespace() && *c != '\r')
+        .collect()
+}
+
+/// Return the single char (start, end) of the bracket pairs, the quotes are ignored.
+fn bracket_chars(pairs: &[BracketPair]) -> impl Iterator<Item = (char, char)> + '_ {
+    pairs
+        .iter()
+        .filter(|pair| !pair.is_quote())
+        .filter_map(|pair| {
+            let mut start = pair.start.chars();
+            let mut end = pair.end.chars();
+            match (start.next(), start.next(), end.next(), end.next()) {
+                (Some(start), None, Some(end), None) => Some((start, end)),
+                _ => None,
+            }
+        })
+}
+
+/// Return true if the text before the `offset` in the line ends with an opening bracket.
+pub(super) fn ends_with_open_bracket(text: &Rope, offset: usize, pairs: &[BracketPair]) -> bool {
+    let line_start = text.line_start_offset(text.offset_to_row(offset));
+    let before = text.text_for_range(line_start..offset);
+    let Some(last) = before.trim_end().chars().last() else {
+        return false;
+    };
+
+    bracket_chars(pairs).any(|(start, _)| start == last)
+}
+
+/// Return true if the char after the `offset` is a closing bracket.
+pub(super) fn starts_with_close_bracket(text: &Rope, offset: usize, pairs: &[BracketPair]) -> bool {
+    let Some(c) = char_after(text, offset) else {
+        return false;
+    };
+
+    bracket_chars(pairs).any(|(_, end)| end == c)
+}
+
+/// Return the range of the leading whitespaces to replace and the new indent,
+/// when typing the closing bracket `new_text` at the `offset` of a blank line.
+///
+/// The new indent is the same as the line of the matching opening bracket.
+pub(super) fn closing_bracket_indent(
+    text: &Rope,
+    offset: usize,
+    new_text: &str,
+    pairs: &[BracketPair],
+) -> Option<(Range<usize>, String)> {
+    let (start, end) = bracket_chars(pairs).find(|(_, end)| {
+        let mut buf = [0; 4];
+        end.encode_utf8(&mut buf) == new_text
+    })?;
+
+    let line_start = text.line_start_offset(text.offset_to_row(offset));
+    let before = text.text_for_range(line_start..offset);
+    if !before.chars().all(|c| c.is_whitespace()) {
+        return None;
+    }
+
+    let start_offset = scan_backward(text, offset, start, end)?;
+    let indent = indent_of_row(text, text.offset_to_row(start_offset));
+    if indent == before {
+        return None;
+    }
+
+    Some((line_start..offset, indent))
+}
+
+impl InputState {
+    /// Insert a newline at the cursor, keep the indent of the current line,
+    /// and increase the indent if the cursor is inside a block by the language.
+    ///
+    /// The closing bracket after the cursor is moved to another new line, e.g.: `{|}`.
+    pub(super) fn newline_and_indent(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let offset = self.selected_range.start;
+        let indent = self.indent_of_next_line();
+        let Some(tab_size) = self.mode.tab_size().copied() else {
+            return;
+        };
+        if !self.extra_selections.is_empty() {
+            self.replace_text_in_range(None, &format!("\n{}", indent), window, cx);
+            return;
+        }
+
+        let pairs = self.mode.brackets(cx);
+        let increase = self.mode.indent_after(offset) == Some(true)
+            || ends_with_open_bracket(&self.text, offset, pairs);
+        if !increase {
+            self.replace_text_in_range(None, &format!("\n{}", indent), window, cx);
+            return;
+        }
+
+        let current_indent = indent_of_row(&self.text, self.text.offset_to_row(offset));
+        let inner_indent = format!("{}{}", current_indent, tab_size.to_string());
+        if starts_with_close_bracket(&self.text, self.selected_range.end, pairs) {
+            let new_text = format!("\n{}\n{}", inner_indent, current_indent);
+            self.replace_text_in_range(None, &new_text, window, cx);
+            self.move_to(offset + 1 + inner_indent.len(), window, cx);
+        } else {
+            self.replace_text_in_range(None, &format!("\n{}", inner_indent), window, cx);
+        }
+    }
+
+    /// Dedent the line when typing a closing bracket on a blank line,
+    /// to align with the line of the matching opening bracket.
+    ///
+    /// Returns true if the input has been handled.
+    pub(super) fn handle_closing_bracket_indent(
+        &mut self,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        if !self.selected_range.is_empty() || !self.extra_selections.is_empty() {
+            return false;
+        }
+
+        let pairs = self.mode.brackets(cx);
+        let Some((range, indent)) =
+            closing_bracket_indent(&self.text, self.cursor_offset(), new_text, pairs)
+        else {
+            return false;
+        };
+
+        let range_utf16 = self.range_to_utf16(&range);
+        let new_text = format!("{}{}", indent, new_text);
+        self.replace_text_in_range(Some(range_utf16), &new_text, window, cx);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_open_and_close_bracket() {
+        let pairs = BracketPair::defaults();
+        let text = Rope::from_str("fn foo() {\n    bar(  \n}");
+
+        assert!(ends_with_open_bracket(&text, 10, &pairs));
+        assert!(!ends_with_open_bracket(&text, 9, &pairs));
+        assert!(!ends_with_open_bracket(&text, 8, &pairs));
+        assert!(ends_with_open_bracket(&text, 21, &pairs));
+        assert!(!ends_with_open_bracket(&text, 18, &pairs));
+        assert!(!ends_with_open_bracket(&text, 22, &pairs));
+
+        assert!(starts_with_close_bracket(&text, 7, &pairs));
+        assert!(starts_with_close_bracket(&text, 22, &pairs));
+        assert!(!starts_with_close_bracket(&text, 10, &pairs));
+        assert!(!starts_with_close_bracket(&text, 23, &pairs));
+    }
+
+    #[test]
+    fn test_closing_bracket_indent() {
+        let pairs = BracketPair::defaults();
+        let text = Rope::from_str("fn foo() {\n    if a {\n        \n        \n    b\n");
+
+        // Align with the `if` line.
+        assert_eq!(
+            closing_bracket_indent(&text, 39, "}", &pairs),
+            Some((31..39, "    ".into()))
+        );
+        assert_eq!(
+            closing_bracket_indent(&text, 30, "}", &pairs),
+            Some((22..30, "    ".into()))
+        );
+        // Not a blank line.
+        assert_eq!(closing_bracket_indent(&text, 45, "}", &pairs), None);
+        // No matching bracket.
+        assert_eq!(closing_bracket_indent(&text, 39, ")", &pairs), None);
+        assert_eq!(closing_bracket_indent(&text, 39, "\"", &pairs), None);
+
+        // The indent is same.
+        let text = Rope::from_str("{\n    {\n    ");
+        assert_eq!(closing_bracket_indent(&text, 12, "}", &pairs), None);
+
+        // Align with the `{` line, skip the `[`.
+        let text = Rope::from_str("{\n    [\n\n    ");
+        assert_eq!(
+            closing_bracket_indent(&text, 13, "}", &pairs),
+            Some((9..13, "".into()))
+        );
+    }
+}
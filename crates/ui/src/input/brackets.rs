@@ -0,0 +1,559 @@
+//! Bracket matching and auto closing pairs for the [`InputMode::CodeEditor`](super::mode::InputMode::CodeEditor).
+use std::ops::Range;
+
+use gpui::{App, Context, EntityInputHandler as _, Window};
+use ropey::Rope;
+use tree_sitter::{Node, Tree};
+
+use crate::highlighter::BracketPair;
+
+use super::{selection::selections_after_replace, InputState, MoveToMatchingBracket};
+
+/// The max chars to scan for the matching bracket.
+const MAX_SCAN_CHARS: usize = 100_000;
+
+/// Return true if the `text` at the `offset` starts with the `s`.
+fn starts_with_at(text: &Rope, offset: usize, s: &str) -> bool {
+    if offset > text.len_bytes() {
+        return false;
+    }
+
+    let mut chars = text.byte_slice(offset..).chars();
+    s.chars().all(|c| chars.next() == Some(c))
+}
+
+fn char_before(text: &Rope, offset: usize) -> Option<char> {
+    let slice = text.byte_slice(..offset);
+    slice.chars_at(slice.len_chars()).prev()
+}
+
+pub(super) fn char_after(text: &Rope, offset: usize) -> Option<char> {
+    text.byte_slice(offset..).chars().next()
+}
+
+/// Scan forward from the `offset` to find the `end` char that matches the `start` char.
+fn scan_forward(text: &Rope, offset: usize, start: char, end: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut pos = offset;
+    for c in text.byte_slice(offset..).chars().take(MAX_SCAN_CHARS) {
+        if c == end {
+            if depth == 0 {
+                return Some(pos);
+            }
+            depth -= 1;
+        } else if c == start {
+            depth += 1;
+        }
+        pos += c.len_utf8();
+    }
+    None
+}
+
+/// Scan backward from the `offset` to find the `start` char that matches the `end` char.
+pub(super) fn scan_backward(text: &Rope, offset: usize, start: char, end: char) -> Option<usize> {
+    let slice = text.byte_slice(..offset);
+    let mut chars = slice.chars_at(slice.len_chars());
+    let mut depth = 0;
+    let mut pos = offset;
+    for _ in 0..MAX_SCAN_CHARS {
+        let c = chars.prev()?;
+        pos -= c.len_utf8();
+        if c == start {
+            if depth == 0 {
+                return Some(pos);
+            }
+            depth -= 1;
+        } else if c == end {
+            depth += 1;
+        }
+    }
+    None
+}
+
+/// Return the ranges of the bracket next to the `offset` and its matching bracket.
+///
+/// The bracket after the `offset` is checked first, then the bracket before it.
+/// Only the single char brackets (not quotes) are matched.
+///
+/// If the syntax `tree` is parsed from the `text`, the brackets are matched by the tokens of the tree,
+/// so the brackets in the strings and comments are ignored.
+pub(super) fn matching_brackets(
+    text: &Rope,
+    offset: usize,
+    pairs: &[BracketPair],
+    tree: Option<&Tree>,
+) -> Option<(Range<usize>, Range<usize>)> {
+    let pairs = pairs
+        .iter()
+        .filter(|pair| !pair.is_quote())
+        .filter_map(|pair| {
+            let mut start = pair.start.chars();
+            let mut end = pair.end.chars();
+            match (start.next(), start.next(), end.next(), end.next()) {
+                (Some(start), None, Some(end), None) => Some((start, end)),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let before = char_before(text, offset).map(|c| (offset - c.len_utf8(), c));
+    let after = char_after(text, offset).map(|c| (offset, c));
+
+    for (pos, c) in after.into_iter().chain(before) {
+        for (start, end) in pairs.iter().copied() {
+            if c != start && c != end {
+                continue;
+            }
+
+            let bracket = pos..pos + c.len_utf8();
+            let root = tree.map(|tree| tree.root_node());
+            let matching = match root.and_then(|root| bracket_token(root, &bracket, c)) {
+                Some(node) => matching_token(node, start, end),
+                // Not a bracket in the strings or comments.
+                None if root.is_some_and(|root| is_in_literal(root, &bracket)) => None,
+                // No syntax tree, or the grammar has no such token.
+                None if c == start => scan_forward(text, bracket.end, start, end)
+                    .map(|end_pos| end_pos..end_pos + end.len_utf8()),
+                None => scan_backward(text, pos, start, end)
+                    .map(|start_pos| start_pos..start_pos + start.len_utf8()),
+            };
+            if let Some(matching) = matching {
+                return Some((bracket, matching));
+            }
+        }
+    }
+
+    None
+}
+
+/// Return the anonymous token node of the bracket `c` at the `range`.
+fn bracket_token<'a>(root: Node<'a>, range: &Range<usize>, c: char) -> Option<Node<'a>> {
+    let node = root.descendant_for_byte_range(range.start, range.end)?;
+    let mut buf = [0; 4];
+    (!node.is_named() && node.byte_range() == *range && node.kind() == c.encode_utf8(&mut buf))
+        .then_some(node)
+}
+
+/// Return the range of the sibling token that matches the bracket token `node`.
+fn matching_token(node: Node, start: char, end: char) -> Option<Range<usize>> {
+    let parent = node.parent()?;
+    let mut cursor = parent.walk();
+    let siblings = parent.children(&mut cursor).collect::<Vec<_>>();
+    let ix = siblings
+        .iter()
+        .position(|sibling| sibling.id() == node.id())?;
+
+    let (mut start_buf, mut end_buf) = ([0; 4], [0; 4]);
+    let (start, end) = (
+        start.encode_utf8(&mut start_buf),
+        end.encode_utf8(&mut end_buf),
+    );
+    let (same, other, candidates) = if node.kind() == start {
+        (start, end, siblings[ix + 1..].to_vec())
+    } else {
+        (end, start, siblings[..ix].iter().rev().copied().collect())
+    };
+
+    let mut depth = 0;
+    for sibling in candidates {
+        if sibling.is_named() {
+            continue;
+        }
+        if sibling.kind() == same {
+            depth += 1;
+        } else if sibling.kind() == other {
+            if depth == 0 {
+                // The missing token is inserted by the error recovery, it is not in the text.
+                return (!sibling.is_missing()).then(|| sibling.byte_range());
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Return true if the `range` is inside a string or a comment node.
+fn is_in_literal(root: Node, range: &Range<usize>) -> bool {
+    let mut node = root.descendant_for_byte_range(range.start, range.end);
+    while let Some(current) = node {
+        let kind = current.kind();
+        if ["string", "comment", "char", "regex"]
+            .iter()
+            .any(|literal| kind.contains(literal))
+        {
+            return true;
+        }
+        node = current.parent();
+    }
+    false
+}
+
+/// Return the pair to auto close when typing the `new_text` at the `offset`.
+///
+/// The pair is only closed before a whitespace, a closing bracket or the end of the line,
+/// and the quotes are not closed after a word char.
+pub(super) fn auto_close_pair<'a>(
+    text: &Rope,
+    offset: usize,
+    new_text: &str,
+    pairs: &'a [BracketPair],
+) -> Option<&'a BracketPair> {
+    let pair = pairs
+        .iter()
+        .find(|pair| pair.close && &*pair.start == new_text)?;
+
+    let next_ok = match char_after(text, offset) {
+        None => true,
+        Some(c) => {
+            c.is_whitespace()
+                || pairs
+                    .iter()
+                    .any(|pair| !pair.is_quote() && pair.end.starts_with(c))
+        }
+    };
+    if !next_ok {
+        return None;
+    }
+
+    if pair.is_quote() {
+        if let Some(c) = char_before(text, offset) {
+            if c.is_alphanumeric() || c == '_' || pair.start.starts_with(c) {
+                return None;
+            }
+        }
+    }
+
+    Some(pair)
+}
+
+/// Return the auto inserted closing text at the `offset` that typing the `new_text` should just move over.
+///
+/// Only the closing text inserted by [`auto_close_pair`] (the `auto_closed` ranges) is typed over,
+/// the closing text typed by the user is not.
+pub(super) fn type_over_closer(
+    text: &Rope,
+    offset: usize,
+    new_text: &str,
+    auto_closed: &[Range<usize>],
+) -> Option<Range<usize>> {
+    auto_closed
+        .iter()
+        .find(|range| {
+            range.start == offset
+                && range.len() == new_text.len()
+                && starts_with_at(text, offset, new_text)
+        })
+        .cloned()
+}
+
+/// The bracket handling of typing at a selection.
+#[derive(Debug, Clone, PartialEq)]
+enum BracketEdit {
+    /// Wrap the selection with the pair.
+    Wrap(BracketPair),
+    /// Move over the auto inserted closing text at the range.
+    TypeOver(Range<usize>),
+    /// Insert the pair, and put the cursor between them.
+    AutoClose(BracketPair),
+}
+
+/// Return the bracket handling of typing the `new_text` at the `range` of selection.
+fn bracket_edit(
+    text: &Rope,
+    range: &Range<usize>,
+    new_text: &str,
+    pairs: &[BracketPair],
+    auto_closed: &[Range<usize>],
+) -> Option<BracketEdit> {
+    if !range.is_empty() {
+        return pairs
+            .iter()
+            .find(|pair| pair.close && &*pair.start == new_text)
+            .map(|pair| BracketEdit::Wrap(pair.clone()));
+    }
+
+    if let Some(closer) = type_over_closer(text, range.start, new_text, auto_closed) {
+        return Some(BracketEdit::TypeOver(closer));
+    }
+
+    auto_close_pair(text, range.start, new_text, pairs)
+        .map(|pair| BracketEdit::AutoClose(pair.clone()))
+}
+
+/// Return the range of the empty pair around the `offset`, e.g.: `(|)`.
+pub(super) fn empty_pair_around(
+    text: &Rope,
+    offset: usize,
+    pairs: &[BracketPair],
+) -> Option<Range<usize>> {
+    pairs.iter().find_map(|pair| {
+        let start = offset.checked_sub(pair.start.len())?;
+        (pair.close
+            && starts_with_at(text, start, &pair.start)
+            && starts_with_at(text, offset, &pair.end))
+        .then(|| start..offset + pair.end.len())
+    })
+}
+
+impl InputState {
+    /// Return the ranges of the bracket next to the cursor and its matching bracket.
+    pub(super) fn matching_brackets(&self, cx: &App) -> Option<(Range<usize>, Range<usize>)> {
+        if !self.mode.is_code_editor() || !self.selected_range.is_empty() {
+            return None;
+        }
+
+        let pairs = self.mode.brackets(cx);
+        if pairs.is_empty() {
+            return None;
+        }
+
+        let tree = self.mode.syntax_tree();
+        matching_brackets(&self.text, self.cursor_offset(), pairs, tree.as_ref())
+    }
+
+    pub(super) fn move_to_matching_bracket(
+        &mut self,
+        _: &MoveToMatchingBracket,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some((bracket, matching)) = self.matching_brackets(cx) else {
+            return;
+        };
+
+        // Keep the cursor at the same side (inside or outside) of the brackets.
+        let offset = if self.cursor_offset() == bracket.start {
+            matching.end
+        } else {
+            matching.start
+        };
+        self.move_to(offset, window, cx);
+    }
+
+    /// Handle the bracket pairs when typing the `new_text` at each of the selections.
+    ///
+    /// - Wrap the selection with the pair.
+    /// - Type over the auto inserted closing text.
+    /// - Auto insert the closing text.
+    ///
+    /// Returns true if the input has been handled.
+    pub(super) fn handle_bracket_input(
+        &mut self,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let pairs = self.mode.brackets(cx);
+        if pairs.is_empty() {
+            return false;
+        }
+
+        let selections = self.all_selections();
+        let bracket_edits = selections
+            .iter()
+            .map(|range| bracket_edit(&self.text, range, new_text, pairs, &self.auto_closed))
+            .collect::<Vec<_>>();
+        if bracket_edits.iter().all(Option::is_none) {
+            return false;
+        }
+
+        // The edits with the selection (relative to the start of the new text) after each edit.
+        let mut edits = Vec::with_capacity(selections.len());
+        // The index of the auto closing edits, with the length of the closing text.
+        let mut closers = vec![];
+        for (range, bracket_edit) in selections.into_iter().zip(bracket_edits) {
+            let edit = match bracket_edit {
+                Some(BracketEdit::Wrap(pair)) => {
+                    let selected_text = self.text_for_range_utf8(range.clone());
+                    let start = pair.start.len();
+                    let wrapped_text = format!("{}{}{}", pair.start, selected_text, pair.end);
+                    (range, wrapped_text, start..start + selected_text.len())
+                }
+                Some(BracketEdit::TypeOver(closer)) => {
+                    self.auto_closed.retain(|range| *range != closer);
+                    (
+                        range.start..range.start,
+                        String::new(),
+                        closer.len()..closer.len(),
+                    )
+                }
+                Some(BracketEdit::AutoClose(pair)) => {
+                    closers.push((edits.len(), pair.end.len()));
+                    let start = pair.start.len();
+                    (range, format!("{}{}", pair.start, pair.end), start..start)
+                }
+                None => (range, new_text.to_string(), new_text.len()..new_text.len()),
+            };
+            edits.push(edit);
+        }
+
+        let new_selections = selections_after_replace(&edits);
+        self.replace_text_with_selections(edits, window, cx);
+        for (ix, len) in closers.iter() {
+            let start = new_selections[*ix].start;
+            self.auto_closed.push(start..start + len);
+        }
+        if !closers.is_empty() && self.extra_selections.is_empty() {
+            self.handle_completion_input(new_text, window, cx);
+        }
+        true
+    }
+
+    /// Update the auto inserted closing texts for the edit that replaces the `range` with the `new_text`.
+    ///
+    /// The closing texts after the edit are moved, and the ones overlapped with the edit are dropped.
+    pub(super) fn update_auto_closed_for_edit(&mut self, range: &Range<usize>, new_text: &str) {
+        self.auto_closed.retain_mut(|closer| {
+            if range.end <= closer.start {
+                *closer = closer.start + new_text.len() - range.len()
+                    ..closer.end + new_text.len() - range.len();
+                true
+            } else {
+                range.start >= closer.end
+            }
+        });
+    }
+
+    /// Delete the empty pair around the cursor on backspace, returns true if deleted.
+    pub(super) fn delete_bracket_pair(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        if !self.selected_range.is_empty() || !self.extra_selections.is_empty() {
+            return false;
+        }
+
+        let pairs = self.mode.brackets(cx);
+        let Some(range) = empty_pair_around(&self.text, self.cursor_offset(), pairs) else {
+            return false;
+        };
+
+        let range_utf16 = self.range_to_utf16(&range);
+        self.replace_text_in_range(Some(range_utf16), "", window, cx);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tree_sitter::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_matching_brackets() {
+        let pairs = BracketPair::defaults();
+        let source = "fn foo(a: [u8; 2]) {\n    bar(\"(\");\n}";
+        let text = Rope::from_str(source);
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_rust::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        for tree in [None, Some(&tree)] {
+            // The bracket after the cursor.
+            assert_eq!(
+                matching_brackets(&text, 6, &pairs, tree),
+                Some((6..7, 17..18))
+            );
+            // The bracket before the cursor.
+            assert_eq!(
+                matching_brackets(&text, 18, &pairs, tree),
+                Some((17..18, 6..7))
+            );
+            assert_eq!(
+                matching_brackets(&text, 10, &pairs, tree),
+                Some((10..11, 16..17))
+            );
+            assert_eq!(
+                matching_brackets(&text, 19, &pairs, tree),
+                Some((19..20, 35..36))
+            );
+            assert_eq!(
+                matching_brackets(&text, 36, &pairs, tree),
+                Some((35..36, 19..20))
+            );
+            assert_eq!(matching_brackets(&text, 2, &pairs, tree), None);
+        }
+
+        // The bracket in the string is ignored by the syntax tree.
+        assert_eq!(
+            matching_brackets(&text, 29, &pairs, Some(&tree)),
+            Some((28..29, 32..33))
+        );
+        assert_eq!(matching_brackets(&text, 31, &pairs, Some(&tree)), None);
+        // Without the syntax tree, the bracket in the string is counted.
+        assert_eq!(matching_brackets(&text, 29, &pairs, None), None);
+
+        // The quotes are not matched.
+        let text = Rope::from_str("\"a\"");
+        assert_eq!(matching_brackets(&text, 0, &pairs, None), None);
+    }
+
+    #[test]
+    fn test_auto_close_pair() {
+        let pairs = BracketPair::defaults();
+        let text = Rope::from_str("foo bar) don");
+
+        let start = |pair: Option<&BracketPair>| pair.map(|pair| pair.start.clone());
+        assert_eq!(start(auto_close_pair(&text, 0, "(", &pairs)), None);
+        assert_eq!(
+            start(auto_close_pair(&text, 3, "(", &pairs)),
+            Some("(".into())
+        );
+        assert_eq!(
+            start(auto_close_pair(&text, 7, "[", &pairs)),
+            Some("[".into())
+        );
+        assert_eq!(
+            start(auto_close_pair(&text, 12, "{", &pairs)),
+            Some("{".into())
+        );
+        assert_eq!(start(auto_close_pair(&text, 4, "\"", &pairs)), None);
+        assert_eq!(start(auto_close_pair(&text, 12, "'", &pairs)), None);
+        assert_eq!(
+            start(auto_close_pair(&text, 8, "'", &pairs)),
+            Some("'".into())
+        );
+        assert_eq!(start(auto_close_pair(&text, 3, "a", &pairs)), None);
+    }
+
+    #[test]
+    fn test_type_over_and_empty_pair() {
+        let pairs = BracketPair::defaults();
+        let text = Rope::from_str("foo() \"\"");
+
+        // Only the auto inserted closing text is typed over.
+        let auto_closed = vec![4..5];
+        assert_eq!(type_over_closer(&text, 4, ")", &auto_closed), Some(4..5));
+        assert_eq!(type_over_closer(&text, 4, "]", &auto_closed), None);
+        assert_eq!(type_over_closer(&text, 5, ")", &auto_closed), None);
+        assert_eq!(type_over_closer(&text, 7, "\"", &auto_closed), None);
+        assert_eq!(type_over_closer(&text, 7, "\"", &[7..8]), Some(7..8));
+
+        assert_eq!(
+            bracket_edit(&text, &(4..4), ")", &pairs, &auto_closed),
+            Some(BracketEdit::TypeOver(4..5))
+        );
+        assert_eq!(
+            bracket_edit(&text, &(7..7), "\"", &pairs, &auto_closed),
+            None
+        );
+        assert_eq!(
+            bracket_edit(&text, &(0..3), "(", &pairs, &auto_closed),
+            Some(BracketEdit::Wrap(BracketPair::new("(", ")")))
+        );
+        assert_eq!(
+            bracket_edit(&text, &(5..5), "[", &pairs, &auto_closed),
+            Some(BracketEdit::AutoClose(BracketPair::new("[", "]")))
+        );
+
+        assert_eq!(empty_pair_around(&text, 4, &pairs), Some(3..5));
+        assert_eq!(empty_pair_around(&text, 7, &pairs), Some(6..8));
+        assert_eq!(empty_pair_around(&text, 3, &pairs), None);
+        assert_eq!(empty_pair_around(&text, 0, &pairs), None);
+    }
+}
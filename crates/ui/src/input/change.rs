@@ -8,6 +8,10 @@ pub struct Change {
     pub(crate) old_text: String,
     pub(crate) new_range: Range<usize>,
     pub(crate) new_text: String,
+    /// The primary selection before the change, used to restore the cursors on undo.
+    pub(crate) selected_range: Range<usize>,
+    /// The additional selections (multiple cursors) before the change.
+    pub(crate) extra_selections: Vec<Range<usize>>,
     version: usize,
 }
 
@@ -19,13 +23,26 @@ impl Change {
         new_text: &str,
     ) -> Self {
         Self {
+            selected_range: old_range.end..old_range.end,
             old_range,
             old_text: old_text.to_string(),
             new_range,
             new_text: new_text.to_string(),
+            extra_selections: vec![],
             version: 0,
         }
     }
+
+    /// Set the selections before the change.
+    pub fn selections(
+        mut self,
+        selected_range: Range<usize>,
+        extra_selections: Vec<Range<usize>>,
+    ) -> Self {
+        self.selected_range = selected_range;
+        self.extra_selections = extra_selections;
+        self
+    }
 }
 
 impl HistoryItem for Change {
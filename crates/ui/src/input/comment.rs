@@ -0,0 +1,297 @@
+//! Toggle comments for the [`InputMode::CodeEditor`](super::mode::InputMode::CodeEditor).
+use std::{collections::BTreeSet, ops::Range};
+
+use gpui::{Context, EntityInputHandler as _, Window};
+use ropey::Rope;
+
+use super::{InputState, RopeExt as _, ToggleComment};
+
+/// Return the rows (zero based) of the selections, sorted and deduplicated.
+///
+/// The last row is excluded if the selection ends at the start of it.
+pub(super) fn selection_rows(text: &Rope, selections: &[Range<usize>]) -> Vec<usize> {
+    let mut rows = BTreeSet::new();
+    for range in selections {
+        let start_row = text.offset_to_row(range.start);
+        let mut end_row = text.offset_to_row(range.end);
+        if end_row > start_row && text.line_start_offset(end_row) == range.end {
+            end_row -= 1;
+        }
+        rows.extend(start_row..=end_row);
+    }
+
+    rows.into_iter().collect()
+}
+
+/// Return the (line start offset, indent length, content) of the non-blank `rows`.
+fn non_blank_lines(text: &Rope, rows: &[usize]) -> Vec<(usize, usize, String)> {
+    rows.iter()
+        .filter_map(|&row| {
+            let line = text.slice_line(row).to_string();
+            let content = line.trim_start();
+            if content.trim_end().is_empty() {
+                return None;
+            }
+
+            let indent = line.len() - content.len();
+            Some((text.line_start_offset(row), indent, content.to_string()))
+        })
+        .collect()
+}
+
+/// Return the edits to toggle the line comments of the `rows`.
+///
+/// If all the non-blank lines are commented, the comments are removed,
+/// otherwise the `token` is inserted at the min indent of the lines.
+fn toggle_line_comment_edits(
+    text: &Rope,
+    rows: &[usize],
+    token: &str,
+) -> Vec<(Range<usize>, String)> {
+    let lines = non_blank_lines(text, rows);
+    if lines.is_empty() {
+        return vec![];
+    }
+
+    let is_commented = lines
+        .iter()
+        .all(|(_, _, content)| content.starts_with(token));
+    if is_commented {
+        lines
+            .iter()
+            .map(|(line_start, indent, content)| {
+                let start = line_start + indent;
+                let mut len = token.len();
+                if content[len..].starts_with(' ') {
+                    len += 1;
+                }
+                (start..start + len, String::new())
+            })
+            .collect()
+    } else {
+        let min_indent = lines
+            .iter()
+            .map(|(_, indent, _)| *indent)
+            .min()
+            .unwrap_or(0);
+        lines
+            .iter()
+            .map(|(line_start, _, _)| {
+                let start = line_start + min_indent;
+                (start..start, format!("{} ", token))
+            })
+            .collect()
+    }
+}
+
+/// Return the edits to toggle the block comments of the `rows`.
+///
+/// Each group of continuous rows is wrapped by (or unwrapped from) the block comment tokens.
+fn toggle_block_comment_edits(
+    text: &Rope,
+    rows: &[usize],
+    (start_token, end_token): (&str, &str),
+) -> Vec<(Range<usize>, String)> {
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for &row in rows {
+        match groups.last_mut() {
+            Some(group) if group.last().map(|last| last + 1) == Some(row) => group.push(row),
+            _ => groups.push(vec![row]),
+        }
+    }
+
+    let mut edits = vec![];
+    for group in groups {
+        let lines = non_blank_lines(text, &group);
+        let (Some(first), Some(last)) = (lines.first(), lines.last()) else {
+            continue;
+        };
+
+        let start = first.0 + first.1;
+        let end = last.0 + last.1 + last.2.trim_end().len();
+        let content = text.text_for_range(start..end);
+
+        if content.len() >= start_token.len() + end_token.len()
+            && content.starts_with(start_token)
+            && content.ends_with(end_token)
+        {
+            let mut start_len = start_token.len();
+            let mut end_len = end_token.len();
+            let inner = &content[start_len..content.len() - end_len];
+            if inner.len() >= 2 && inner.starts_with(' ') && inner.ends_with(' ') {
+                start_len += 1;
+                end_len += 1;
+            }
+
+            edits.push((start..start + start_len, String::new()));
+            edits.push((end - end_len..end, String::new()));
+        } else {
+            edits.push((start..start, format!("{} ", start_token)));
+            edits.push((end..end, format!(" {}", end_token)));
+        }
+    }
+
+    edits
+}
+
+/// Return the edits to toggle the comments of the `rows`, sorted by the range.
+///
+/// The line comment is preferred, and the block comment is used if there is no line comment.
+pub(super) fn toggle_comment_edits(
+    text: &Rope,
+    rows: &[usize],
+    line_comment: Option<&str>,
+    block_comment: Option<(&str, &str)>,
+) -> Vec<(Range<usize>, String)> {
+    match (line_comment, block_comment) {
+        (Some(token), _) => toggle_line_comment_edits(text, rows, token),
+        (None, Some(tokens)) => toggle_block_comment_edits(text, rows, tokens),
+        (None, None) => vec![],
+    }
+}
+
+/// Return the new offset of the `offset` after applying the sorted `edits`.
+pub(super) fn offset_after_edits(offset: usize, edits: &[(Range<usize>, String)]) -> usize {
+    let mut delta = 0isize;
+    for (range, new_text) in edits {
+        if range.start > offset {
+            break;
+        }
+
+        if range.end <= offset {
+            delta += new_text.len() as isize - range.len() as isize;
+        } else {
+            // The offset is inside the replaced range.
+            return (range.start as isize + delta) as usize + new_text.len();
+        }
+    }
+
+    (offset as isize + delta) as usize
+}
+
+impl InputState {
+    /// Toggle the comments of the selected lines by the comment tokens of the language.
+    pub(super) fn toggle_comment(
+        &mut self,
+        _: &ToggleComment,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.mode.is_code_editor() {
+            return;
+        }
+
+        let (line_comment, block_comment) = self.mode.comment_tokens(cx);
+        let rows = selection_rows(&self.text, &self.all_selections());
+        let edits = toggle_comment_edits(
+            &self.text,
+            &rows,
+            line_comment.as_deref(),
+            block_comment
+                .as_ref()
+                .map(|(start, end)| (&**start, &**end)),
+        );
+        if edits.is_empty() {
+            return;
+        }
+
+        let selected_range = self.selected_range.clone();
+        let extra_selections = self.extra_selections.clone();
+
+        // Replace from the last one, so the offsets of the previous edits are not changed.
+        for (range, new_text) in edits.iter().rev() {
+            let range_utf16 = self.range_to_utf16(range);
+            self.replace_text_in_range(Some(range_utf16), new_text, window, cx);
+        }
+
+        let map_range = |range: &Range<usize>| {
+            offset_after_edits(range.start, &edits)..offset_after_edits(range.end, &edits)
+        };
+        self.selected_range = map_range(&selected_range);
+        self.extra_selections = extra_selections.iter().map(map_range).collect();
+        self.update_preferred_x_offset(cx);
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::*;
+
+    fn apply(text: &str, edits: &[(Range<usize>, String)]) -> String {
+        let mut text = text.to_string();
+        for (range, new_text) in edits.iter().rev() {
+            text.replace_range(range.clone(), new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_selection_rows() {
+        let text = Rope::from_str("a\nb\nc\nd");
+        assert_eq!(selection_rows(&text, &[0..0]), vec![0]);
+        assert_eq!(selection_rows(&text, &[0..3]), vec![0, 1]);
+        // Ends at the start of the row 2.
+        assert_eq!(selection_rows(&text, &[0..4]), vec![0, 1]);
+        assert_eq!(selection_rows(&text, &[4..4, 0..1, 6..7]), vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn test_toggle_line_comment() {
+        let source = "fn foo() {\n    a();\n\n  // b();\n}";
+        let text = Rope::from_str(source);
+
+        let edits = toggle_comment_edits(&text, &[1, 2, 3], Some("//"), None);
+        let commented = apply(source, &edits);
+        assert_eq!(commented, "fn foo() {\n  //   a();\n\n  // // b();\n}");
+
+        let edits = toggle_comment_edits(&Rope::from_str(&commented), &[1, 2, 3], Some("//"), None);
+        assert_eq!(apply(&commented, &edits), source);
+
+        // All lines are commented.
+        let edits = toggle_comment_edits(&text, &[3], Some("//"), None);
+        assert_eq!(apply(source, &edits), "fn foo() {\n    a();\n\n  b();\n}");
+        // The blank lines are ignored.
+        assert!(toggle_comment_edits(&text, &[2], Some("//"), None).is_empty());
+    }
+
+    #[test]
+    fn test_toggle_block_comment() {
+        let source = "<div>\n  <p>Hello</p>  \n  <p>World</p>\n</div>";
+        let text = Rope::from_str(source);
+
+        let edits = toggle_comment_edits(&text, &[1, 2], None, Some(("<!--", "-->")));
+        let commented = apply(source, &edits);
+        assert_eq!(
+            commented,
+            "<div>\n  <!-- <p>Hello</p>  \n  <p>World</p> -->\n</div>"
+        );
+
+        let edits = toggle_comment_edits(
+            &Rope::from_str(&commented),
+            &[1, 2],
+            None,
+            Some(("<!--", "-->")),
+        );
+        assert_eq!(apply(&commented, &edits), source);
+
+        // Each group of continuous rows is commented separately.
+        let edits = toggle_comment_edits(&text, &[0, 2], None, Some(("<!--", "-->")));
+        assert_eq!(
+            apply(source, &edits),
+            "<!-- <div> -->\n  <p>Hello</p>  \n  <!-- <p>World</p> -->\n</div>"
+        );
+    }
+
+    #[test]
+    fn test_offset_after_edits() {
+        let edits = vec![(2..2, "// ".to_string()), (10..13, "".to_string())];
+        assert_eq!(offset_after_edits(0, &edits), 0);
+        assert_eq!(offset_after_edits(2, &edits), 5);
+        assert_eq!(offset_after_edits(5, &edits), 8);
+        assert_eq!(offset_after_edits(11, &edits), 13);
+        assert_eq!(offset_after_edits(15, &edits), 15);
+    }
+}
@@ -0,0 +1,799 @@
+//! The completion provider API and the completion menu for the [`InputState`].
+use std::{ops::Range, rc::Rc};
+
+use anyhow::Result;
+use gpui::{
+    deferred, div, point, prelude::FluentBuilder as _, px, App, AppContext as _, Context, Empty,
+    Entity, EntityInputHandler as _, InteractiveElement as _, IntoElement, MouseButton,
+    ParentElement as _, Pixels, Point, Render, SharedString, StatefulInteractiveElement as _,
+    Styled as _, Task, WeakEntity, Window,
+};
+use ropey::Rope;
+
+use crate::{
+    h_flex,
+    list::{List, ListDelegate, ListItem},
+    text::TextView,
+    ActiveTheme as _,
+};
+
+use super::{rope_ext::RopeExt as _, snippet::parse_snippet, InputState, ShowCompletions};
+
+/// The kind of a [`CompletionItem`], used to show a short label before the item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionItemKind {
+    Text,
+    Keyword,
+    Snippet,
+    Function,
+    Method,
+    Variable,
+    Field,
+    Property,
+    Constant,
+    Struct,
+    Enum,
+    Interface,
+    Module,
+}
+
+impl CompletionItemKind {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Text => "abc",
+            Self::Keyword => "kw",
+            Self::Snippet => "snip",
+            Self::Function => "fn",
+            Self::Method => "fn",
+            Self::Variable => "var",
+            Self::Field => "field",
+            Self::Property => "prop",
+            Self::Constant => "const",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Interface => "trait",
+            Self::Module => "mod",
+        }
+    }
+}
+
+/// The format of the [`CompletionItem::insert_text`] and the [`CompletionItem::text_edit`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InsertTextFormat {
+    /// The text is inserted as it is.
+    #[default]
+    PlainText,
+    /// The text is a snippet with tab stops, see [`InputState::insert_snippet`].
+    Snippet,
+}
+
+/// A text edit to apply to the input text, the `range` is the byte range in the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub new_text: SharedString,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, new_text: impl Into<SharedString>) -> Self {
+        Self {
+            range,
+            new_text: new_text.into(),
+        }
+    }
+}
+
+/// An item of the completion menu.
+#[derive(Debug, Clone)]
+pub struct CompletionItem {
+    /// The label to display in the menu.
+    pub label: SharedString,
+    pub kind: Option<CompletionItemKind>,
+    /// The detail to display after the label, e.g.: the type signature.
+    pub detail: Option<SharedString>,
+    /// The documentation in Markdown, displayed at the side of the menu when the item is selected.
+    pub documentation: Option<SharedString>,
+    /// The text used to filter the items, default is the `label`.
+    pub filter_text: Option<SharedString>,
+    /// The text to insert, default is the `label`.
+    ///
+    /// The inserted text will replace the word before the cursor.
+    pub insert_text: Option<SharedString>,
+    /// The edit to apply when accept the item, this will override the `insert_text`.
+    ///
+    /// If the cursor is moved after the end of the `range` by typing, the range will be extended to the cursor.
+    pub text_edit: Option<TextEdit>,
+    /// The format of the `insert_text` and the `text_edit`, default is [`InsertTextFormat::PlainText`].
+    pub insert_text_format: InsertTextFormat,
+    /// The additional edits to apply when accept the item, e.g.: add an import.
+    ///
+    /// The edits must not overlap with each other and the `text_edit`.
+    pub additional_text_edits: Vec<TextEdit>,
+}
+
+impl CompletionItem {
+    pub fn new(label: impl Into<SharedString>) -> Self {
+        Self {
+            label: label.into(),
+            kind: None,
+            detail: None,
+            documentation: None,
+            filter_text: None,
+            insert_text: None,
+            text_edit: None,
+            insert_text_format: InsertTextFormat::default(),
+            additional_text_edits: vec![],
+        }
+    }
+
+    pub fn kind(mut self, kind: CompletionItemKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<SharedString>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn documentation(mut self, documentation: impl Into<SharedString>) -> Self {
+        self.documentation = Some(documentation.into());
+        self
+    }
+
+    pub fn filter_text(mut self, filter_text: impl Into<SharedString>) -> Self {
+        self.filter_text = Some(filter_text.into());
+        self
+    }
+
+    pub fn insert_text(mut self, insert_text: impl Into<SharedString>) -> Self {
+        self.insert_text = Some(insert_text.into());
+        self
+    }
+
+    pub fn text_edit(mut self, text_edit: TextEdit) -> Self {
+        self.text_edit = Some(text_edit);
+        self
+    }
+
+    pub fn insert_text_format(mut self, format: InsertTextFormat) -> Self {
+        self.insert_text_format = format;
+        self
+    }
+
+    pub fn additional_text_edits(mut self, edits: Vec<TextEdit>) -> Self {
+        self.additional_text_edits = edits;
+        self
+    }
+
+    fn filter_text_or_label(&self) -> &str {
+        self.filter_text.as_ref().unwrap_or(&self.label)
+    }
+}
+
+/// A provider to offer the completions for the [`InputState`].
+///
+/// Set by [`InputState::completion_provider`].
+#[allow(unused)]
+pub trait CompletionProvider {
+    /// Fetch the completion items at the `offset` (the cursor) of the `text`.
+    ///
+    /// The items are filtered by the word before the cursor as the user keeps typing,
+    /// so return all the candidates here.
+    fn completions(
+        &self,
+        text: &Rope,
+        offset: usize,
+        window: &mut Window,
+        cx: &mut Context<InputState>,
+    ) -> Task<Result<Vec<CompletionItem>>>;
+
+    /// Return true to request the completions after the `new_text` is typed at the `offset`.
+    ///
+    /// Default is true when typing a word character or `.`.
+    fn is_completion_trigger(
+        &self,
+        offset: usize,
+        new_text: &str,
+        cx: &mut Context<InputState>,
+    ) -> bool {
+        let mut chars = new_text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => is_word_char(c) || c == '.',
+            _ => false,
+        }
+    }
+}
+
+pub(super) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Return the start offset of the word before the `offset`.
+pub(super) fn word_start(text: &Rope, offset: usize) -> usize {
+    let line_start = text.line_start_offset(text.offset_to_row(offset));
+    let prefix = text.text_for_range(line_start..offset);
+
+    let mut start = offset;
+    for (ix, c) in prefix.char_indices().rev() {
+        if !is_word_char(c) {
+            break;
+        }
+        start = line_start + ix;
+    }
+    start
+}
+
+/// Return the fuzzy match score of the `query` in the `text`, lower is better.
+///
+/// The chars of the `query` must appear in the `text` in order (case insensitive),
+/// the score is the index of the first matched char and the number of gaps.
+fn match_score(text: &str, query: &str) -> Option<(usize, usize)> {
+    let mut query_chars = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut first = None;
+    let mut gaps = 0;
+    let mut last_matched = None;
+
+    for (ix, c) in text.chars().flat_map(char::to_lowercase).enumerate() {
+        let Some(q) = query_chars.peek() else {
+            break;
+        };
+        if c == *q {
+            query_chars.next();
+            first.get_or_insert(ix);
+            if last_matched.is_some_and(|last| last + 1 != ix) {
+                gaps += 1;
+            }
+            last_matched = Some(ix);
+        }
+    }
+
+    if query_chars.peek().is_some() {
+        return None;
+    }
+
+    Some((first.unwrap_or(0), gaps))
+}
+
+/// Filter the `items` by the `query`, returns the indices of the matched items, the best match first.
+pub(super) fn filter_completions(items: &[CompletionItem], query: &str) -> Vec<usize> {
+    let mut matches = items
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, item)| match_score(item.filter_text_or_label(), query).map(|s| (s, ix)))
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.into_iter().map(|(_, ix)| ix).collect()
+}
+
+pub(super) struct CompletionListDelegate {
+    editor: WeakEntity<InputState>,
+    items: Rc<Vec<CompletionItem>>,
+    /// The indices of the `items` matched the query.
+    matches: Vec<usize>,
+    selected_index: Option<usize>,
+}
+
+impl CompletionListDelegate {
+    fn selected_item(&self) -> Option<&CompletionItem> {
+        self.selected_index
+            .and_then(|ix| self.matches.get(ix))
+            .and_then(|ix| self.items.get(*ix))
+    }
+}
+
+impl ListDelegate for CompletionListDelegate {
+    type Item = ListItem;
+
+    fn items_count(&self, _: &App) -> usize {
+        self.matches.len()
+    }
+
+    fn render_item(
+        &self,
+        ix: usize,
+        _: &mut Window,
+        cx: &mut Context<List<Self>>,
+    ) -> Option<Self::Item> {
+        let item = self.items.get(*self.matches.get(ix)?)?;
+
+        Some(
+            ListItem::new(("completion-item", ix))
+                .py_0p5()
+                .text_sm()
+                .child(
+                    div()
+                        .w_8()
+                        .flex_shrink_0()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .children(item.kind.map(|kind| kind.label())),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .overflow_hidden()
+                        .whitespace_nowrap()
+                        .child(item.label.clone()),
+                )
+                .when_some(item.detail.clone(), |this, detail| {
+                    this.child(
+                        div()
+                            .max_w_32()
+                            .overflow_hidden()
+                            .whitespace_nowrap()
+                            .text_ellipsis()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(detail),
+                    )
+                }),
+        )
+    }
+
+    fn set_selected_index(
+        &mut self,
+        ix: Option<usize>,
+        _: &mut Window,
+        _: &mut Context<List<Self>>,
+    ) {
+        self.selected_index = ix;
+    }
+
+    fn confirm(&mut self, _: bool, window: &mut Window, cx: &mut Context<List<Self>>) {
+        let Some(item) = self.selected_item().cloned() else {
+            return;
+        };
+
+        let editor = self.editor.clone();
+        cx.defer_in(window, move |_, window, cx| {
+            _ = editor.update(cx, |editor, cx| {
+                editor.apply_completion(item, window, cx);
+                editor.focus(window, cx);
+            });
+        });
+    }
+}
+
+/// The completion menu, displayed under the word to complete.
+pub(super) struct CompletionMenu {
+    editor: WeakEntity<InputState>,
+    list: Entity<List<CompletionListDelegate>>,
+    /// The start offset of the word to complete.
+    pub(super) start_offset: usize,
+}
+
+impl CompletionMenu {
+    pub(super) fn new(
+        editor: WeakEntity<InputState>,
+        items: Vec<CompletionItem>,
+        start_offset: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let delegate = CompletionListDelegate {
+            editor: editor.clone(),
+            items: Rc::new(items),
+            matches: vec![],
+            selected_index: None,
+        };
+        let list = cx.new(|cx| List::new(delegate, window, cx).no_query().max_h(px(240.)));
+
+        Self {
+            editor,
+            list,
+            start_offset,
+        }
+    }
+
+    /// Filter the items by the `query`, and select the first match.
+    ///
+    /// Returns false if there is no item matched.
+    pub(super) fn update_query(
+        &mut self,
+        query: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        self.list.update(cx, |list, cx| {
+            let delegate = list.delegate_mut();
+            delegate.matches = filter_completions(&delegate.items, query);
+            let has_matches = !delegate.matches.is_empty();
+
+            list.set_selected_index(has_matches.then_some(0), window, cx);
+            list.scroll_to_item(0, window, cx);
+            has_matches
+        })
+    }
+
+    /// Move the selection by `delta` items, wrap around at the ends.
+    pub(super) fn select_by(&mut self, delta: isize, window: &mut Window, cx: &mut Context<Self>) {
+        self.list.update(cx, |list, cx| {
+            let count = list.delegate().matches.len() as isize;
+            if count == 0 {
+                return;
+            }
+
+            let ix = list.selected_index().map_or(0, |ix| ix as isize + delta);
+            let ix = ix.rem_euclid(count) as usize;
+            list.set_selected_index(Some(ix), window, cx);
+            list.scroll_to_selected_item(window, cx);
+            cx.notify();
+        });
+        cx.notify();
+    }
+
+    pub(super) fn selected_item(&self, cx: &App) -> Option<CompletionItem> {
+        self.list.read(cx).delegate().selected_item().cloned()
+    }
+
+    /// The position of the menu, relative to the text element.
+    fn origin(&self, cx: &App) -> Option<Point<Pixels>> {
+        let editor = self.editor.upgrade()?;
+        let editor = editor.read(cx);
+        let line_height = editor.last_layout.as_ref()?.line_height;
+        let (_, _, pos) = editor.line_and_position_for_offset(self.start_offset);
+        let scroll_offset = editor.scroll_handle.offset();
+
+        pos.map(|pos| {
+            pos + point(
+                editor.line_number_width + scroll_offset.x,
+                line_height + scroll_offset.y,
+            )
+        })
+    }
+}
+
+impl Render for CompletionMenu {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let Some(origin) = self.origin(cx) else {
+            return Empty.into_any_element();
+        };
+        let documentation = self
+            .list
+            .read(cx)
+            .delegate()
+            .selected_item()
+            .and_then(|item| item.documentation.clone());
+
+        deferred(
+            h_flex()
+                .id("completion-menu")
+                .occlude()
+                .on_mouse_down(MouseButton::Left, |_, _, cx| cx.stop_propagation())
+                .cursor_default()
+                .absolute()
+                .left(origin.x)
+                .top(origin.y)
+                .items_start()
+                .gap_1()
+                .child(
+                    div()
+                        .w(px(300.))
+                        .py_1()
+                        .bg(cx.theme().popover)
+                        .text_color(cx.theme().popover_foreground)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .rounded(cx.theme().radius)
+                        .shadow_md()
+                        .child(self.list.clone()),
+                )
+                .when_some(documentation, |this, documentation| {
+                    this.child(
+                        div()
+                            .id("completion-documentation")
+                            .w(px(300.))
+                            .max_h(px(240.))
+                            .overflow_y_scroll()
+                            .px_2()
+                            .py_1()
+                            .text_xs()
+                            .bg(cx.theme().popover)
+                            .text_color(cx.theme().popover_foreground)
+                            .border_1()
+                            .border_color(cx.theme().border)
+                            .rounded(cx.theme().radius)
+                            .shadow_md()
+                            .child(TextView::markdown("documentation", documentation)),
+                    )
+                }),
+        )
+        .into_any_element()
+    }
+}
+
+impl InputState {
+    /// Set the [`CompletionProvider`] to offer the completions.
+    pub fn completion_provider(mut self, provider: impl CompletionProvider + 'static) -> Self {
+        self.completion_provider = Some(Rc::new(provider));
+        self
+    }
+
+    /// Set or remove the [`CompletionProvider`] of the input.
+    pub fn set_completion_provider(
+        &mut self,
+        provider: Option<Rc<dyn CompletionProvider>>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.completion_provider = provider;
+        self.hide_completion_menu(cx);
+    }
+
+    pub(super) fn show_completions(
+        &mut self,
+        _: &ShowCompletions,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.request_completions(window, cx);
+    }
+
+    /// Request the completions from the provider at the cursor, and open the menu with the results.
+    ///
+    /// The snippets of the language are appended to the results.
+    fn request_completions(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let snippets = self.snippet_completions(cx);
+        let provider = self.completion_provider.clone();
+        if provider.is_none() && snippets.is_empty() {
+            return;
+        }
+
+        let offset = self.cursor_offset();
+        let task = provider.map(|provider| provider.completions(&self.text, offset, window, cx));
+        self.completion_task = cx.spawn_in(window, async move |this, cx| {
+            let mut items = match task {
+                Some(task) => match task.await {
+                    Ok(items) => items,
+                    Err(err) => {
+                        tracing::error!("failed to fetch completions: {}", err);
+                        return;
+                    }
+                },
+                None => vec![],
+            };
+            items.extend(snippets);
+
+            _ = this.update_in(cx, |this, window, cx| {
+                // The cursor has been moved by other way.
+                if this.cursor_offset() < offset || !this.extra_selections.is_empty() {
+                    return;
+                }
+
+                let editor = cx.entity().downgrade();
+                let start_offset = word_start(&this.text, offset);
+                let menu =
+                    cx.new(|cx| CompletionMenu::new(editor, items, start_offset, window, cx));
+                this.completion_menu = Some(menu);
+                this.update_completion_query(window, cx);
+            });
+        });
+    }
+
+    /// Return the snippets of the language as the completion items.
+    fn snippet_completions(&self, cx: &App) -> Vec<CompletionItem> {
+        self.mode
+            .snippets(cx)
+            .iter()
+            .map(|snippet| {
+                let preview = parse_snippet(&snippet.body, &|_| None).text;
+                let mut item = CompletionItem::new(snippet.prefix.clone())
+                    .kind(CompletionItemKind::Snippet)
+                    .insert_text(snippet.body.clone())
+                    .insert_text_format(InsertTextFormat::Snippet)
+                    .documentation(format!("```\n{}\n```", preview));
+                item.detail = snippet.description.clone();
+                item
+            })
+            .collect()
+    }
+
+    /// Filter the completion menu by the text between the word start and the cursor,
+    /// the menu will be hidden if the cursor is out of the word or there is no item matched.
+    fn update_completion_query(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(menu) = self.completion_menu.clone() else {
+            return;
+        };
+
+        let start_offset = menu.read(cx).start_offset;
+        let offset = self.cursor_offset();
+        let query = if offset >= start_offset {
+            self.text.text_for_range(start_offset..offset)
+        } else {
+            String::new()
+        };
+
+        if offset < start_offset
+            || !query.chars().all(is_word_char)
+            || !menu.update(cx, |menu, cx| menu.update_query(&query, window, cx))
+        {
+            self.hide_completion_menu(cx);
+        }
+        cx.notify();
+    }
+
+    /// Update the completions after the `new_text` is typed at the cursor.
+    pub(super) fn handle_completion_input(
+        &mut self,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.completion_provider.is_none() && self.mode.snippets(cx).is_empty() {
+            return;
+        }
+
+        self.update_completion_query(window, cx);
+        if self.completion_menu.is_some() {
+            return;
+        }
+
+        // Without the provider, only the snippets are offered when typing a word.
+        let is_trigger = match self.completion_provider.clone() {
+            Some(provider) => provider.is_completion_trigger(self.cursor_offset(), new_text, cx),
+            None => {
+                let mut chars = new_text.chars();
+                matches!((chars.next(), chars.next()), (Some(c), None) if is_word_char(c))
+            }
+        };
+        if is_trigger {
+            self.request_completions(window, cx);
+        }
+    }
+
+    /// Hide the completion menu and cancel the pending request, returns true if the menu was open.
+    pub(super) fn hide_completion_menu(&mut self, cx: &mut Context<Self>) -> bool {
+        self.completion_task = Task::ready(());
+        if self.completion_menu.take().is_some() {
+            cx.notify();
+            return true;
+        }
+        false
+    }
+
+    /// Move the selection of the completion menu, returns false if the menu is not open.
+    pub(super) fn select_completion(
+        &mut self,
+        delta: isize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(menu) = self.completion_menu.clone() else {
+            return false;
+        };
+
+        menu.update(cx, |menu, cx| menu.select_by(delta, window, cx));
+        true
+    }
+
+    /// Accept the selected item of the completion menu, returns false if the menu is not open.
+    pub(super) fn confirm_completion(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(menu) = self.completion_menu.clone() else {
+            return false;
+        };
+
+        match menu.read(cx).selected_item(cx) {
+            Some(item) => self.apply_completion(item, window, cx),
+            None => {
+                self.hide_completion_menu(cx);
+            }
+        }
+        true
+    }
+
+    /// Apply the edits of the completion `item`, and move the cursor to the end of the inserted text.
+    ///
+    /// If the `item` is a snippet, the first tab stop is selected instead.
+    pub(super) fn apply_completion(
+        &mut self,
+        item: CompletionItem,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let offset = self.cursor_offset();
+        let start_offset = self
+            .completion_menu
+            .as_ref()
+            .map(|menu| menu.read(cx).start_offset)
+            .unwrap_or_else(|| word_start(&self.text, offset));
+        self.hide_completion_menu(cx);
+
+        let main_edit = match item.text_edit {
+            Some(edit) => {
+                TextEdit::new(edit.range.start..edit.range.end.max(offset), edit.new_text)
+            }
+            None => TextEdit::new(start_offset..offset, item.insert_text.unwrap_or(item.label)),
+        };
+        let snippet = (item.insert_text_format == InsertTextFormat::Snippet)
+            .then(|| self.parse_snippet(&main_edit.new_text, main_edit.range.start, "", cx));
+        let main_edit = match &snippet {
+            Some(snippet) => TextEdit::new(main_edit.range, snippet.text.clone()),
+            None => main_edit,
+        };
+
+        // All the edits are applied as one change, so they are undone in one step.
+        let mut edits = item.additional_text_edits;
+        edits.push(main_edit.clone());
+        edits.sort_by_key(|edit| edit.range.start);
+
+        let mut cursor = main_edit.range.start + main_edit.new_text.len();
+        let mut ranges = vec![];
+        for edit in edits {
+            let range =
+                self.text.clip_offset(edit.range.start)..self.text.clip_offset(edit.range.end);
+            if edit.range.end <= main_edit.range.start && edit != main_edit {
+                cursor = (cursor + edit.new_text.len()).saturating_sub(range.len());
+            }
+            ranges.push((range, edit.new_text.to_string()));
+        }
+        self.replace_text_in_ranges(ranges, window, cx);
+
+        match snippet {
+            Some(snippet) => {
+                let offset = cursor - snippet.text.len();
+                self.start_snippet(snippet, offset, window, cx);
+            }
+            None => self.selected_range = cursor..cursor,
+        }
+        self.update_preferred_x_offset(cx);
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::TestAppContext;
+    use ropey::Rope;
+
+    use super::*;
+    use crate::input::{new_state, Undo};
+
+    #[test]
+    fn test_word_start() {
+        let text = Rope::from_str("let foo_bar = self.baz\n你好");
+        assert_eq!(word_start(&text, 11), 4);
+        assert_eq!(word_start(&text, 4), 4);
+        assert_eq!(word_start(&text, 13), 13);
+        assert_eq!(word_start(&text, 22), 19);
+        assert_eq!(word_start(&text, 29), 23);
+    }
+
+    #[test]
+    fn test_filter_completions() {
+        let items = ["println", "print", "eprintln", "format", "Pin"]
+            .into_iter()
+            .map(CompletionItem::new)
+            .collect::<Vec<_>>();
+
+        assert_eq!(filter_completions(&items, ""), vec![0, 1, 2, 3, 4]);
+        assert_eq!(filter_completions(&items, "print"), vec![0, 1, 2]);
+        assert_eq!(filter_completions(&items, "pln"), vec![0, 2]);
+        assert_eq!(filter_completions(&items, "pin"), vec![4, 0, 1, 2]);
+        assert_eq!(filter_completions(&items, "xyz"), Vec::<usize>::new());
+    }
+
+    #[gpui::test]
+    fn test_undo_completion_in_one_step(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .code_editor("text")
+                .default_value("fn main() {\n    pri\n}")
+        });
+        state.update_in(cx, |state, window, cx| {
+            state.selected_range = 19..19;
+            let item = CompletionItem::new("println!")
+                .additional_text_edits(vec![TextEdit::new(0..0, "use std::io;\n")]);
+            state.apply_completion(item, window, cx);
+            assert_eq!(state.value(), "use std::io;\nfn main() {\n    println!\n}");
+            assert_eq!(state.selected_range, 37..37);
+
+            state.undo(&Undo, window, cx);
+            assert_eq!(state.value(), "fn main() {\n    pri\n}");
+        });
+    }
+}
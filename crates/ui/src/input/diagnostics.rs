@@ -0,0 +1,196 @@
+//! The diagnostics of the [`InputMode::CodeEditor`](super::mode::InputMode::CodeEditor),
+//! requested from a [`DiagnosticsProvider`] (e.g.: a linter) and displayed as [`Marker`]s.
+use std::{rc::Rc, time::Duration};
+
+use anyhow::Result;
+use gpui::{Context, Task, Window};
+use ropey::Rope;
+
+use super::{InputState, Marker};
+
+/// A provider to offer the diagnostics (as [`Marker`]s) for the [`InputState`].
+///
+/// The diagnostics are requested again after the text is changed (debounced),
+/// and the markers are replaced by the results.
+///
+/// Set by [`InputState::diagnostics_provider`].
+pub trait DiagnosticsProvider {
+    /// Fetch the diagnostics of the `text`.
+    ///
+    /// Use `cx.background_spawn` to run the heavy work (e.g.: a linter) in the background.
+    fn diagnostics(
+        &self,
+        text: &Rope,
+        window: &mut Window,
+        cx: &mut Context<InputState>,
+    ) -> Task<Result<Vec<Marker>>>;
+
+    /// The delay to request the diagnostics after the text is changed, default: 300ms.
+    fn debounce(&self) -> Duration {
+        Duration::from_millis(300)
+    }
+}
+
+impl InputState {
+    /// Set the [`DiagnosticsProvider`] to offer the diagnostics, only for [`InputMode::CodeEditor`] mode.
+    ///
+    /// [`InputMode::CodeEditor`]: super::mode::InputMode::CodeEditor
+    pub fn diagnostics_provider(mut self, provider: impl DiagnosticsProvider + 'static) -> Self {
+        self.diagnostics_provider = Some(Rc::new(provider));
+        self
+    }
+
+    /// Set or remove the [`DiagnosticsProvider`] of the input, the diagnostics will be requested immediately.
+    pub fn set_diagnostics_provider(
+        &mut self,
+        provider: Option<Rc<dyn DiagnosticsProvider>>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.diagnostics_provider = provider;
+        self.refresh_diagnostics(window, cx);
+    }
+
+    /// Request the diagnostics from the provider after the debounce delay,
+    /// the pending request (if any) will be canceled.
+    pub(super) fn refresh_diagnostics(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(provider) = self.diagnostics_provider.clone() else {
+            self.diagnostics_task = None;
+            return;
+        };
+
+        self.diagnostics_task = Some(cx.spawn_in(window, async move |this, cx| {
+            cx.background_executor().timer(provider.debounce()).await;
+
+            let Ok(task) = this.update_in(cx, |this, window, cx| {
+                let text = this.text.clone();
+                provider.diagnostics(&text, window, cx)
+            }) else {
+                return;
+            };
+
+            // The task will be dropped (canceled) if the text is changed again,
+            // so the results are always for the current text.
+            match task.await {
+                Ok(markers) => {
+                    _ = this.update_in(cx, |this, window, cx| {
+                        this.set_markers(markers, window, cx);
+                        cx.notify();
+                    });
+                }
+                Err(err) => tracing::error!("failed to fetch diagnostics: {}", err),
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, time::Duration};
+
+    use anyhow::{anyhow, Result};
+    use gpui::{Context, Entity, Task, TestAppContext, VisualTestContext, Window};
+    use ropey::Rope;
+
+    use super::DiagnosticsProvider;
+    use crate::input::{new_state, InputState, Marker};
+
+    /// A provider that returns a marker with the text as the message.
+    #[derive(Clone, Default)]
+    struct TestProvider {
+        calls: Rc<Cell<usize>>,
+        fail: Rc<Cell<bool>>,
+    }
+
+    impl DiagnosticsProvider for TestProvider {
+        fn diagnostics(
+            &self,
+            text: &Rope,
+            _: &mut Window,
+            _: &mut Context<InputState>,
+        ) -> Task<Result<Vec<Marker>>> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fail.get() {
+                return Task::ready(Err(anyhow!("failed to lint")));
+            }
+
+            Task::ready(Ok(vec![Marker::new(
+                "error",
+                (1, 1),
+                (1, 2),
+                text.to_string(),
+            )]))
+        }
+    }
+
+    fn messages(state: &Entity<InputState>, cx: &mut VisualTestContext) -> Vec<String> {
+        state.read_with(cx, |state, _| {
+            state
+                .mode
+                .markers()
+                .map(|markers| markers.iter().map(|m| m.message.to_string()).collect())
+                .unwrap_or_default()
+        })
+    }
+
+    #[gpui::test]
+    fn test_refresh_diagnostics_debounced(cx: &mut TestAppContext) {
+        let provider = TestProvider::default();
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .code_editor("text")
+                .diagnostics_provider(provider.clone())
+        });
+
+        state.update_in(cx, |state, window, cx| {
+            state.replace_text_in_range(None, "a", window, cx);
+            state.replace_text_in_range(None, "b", window, cx);
+        });
+        cx.executor().advance_clock(Duration::from_millis(100));
+        cx.run_until_parked();
+        assert_eq!(provider.calls.get(), 0);
+
+        // Only the last change is requested after the debounce delay.
+        cx.executor().advance_clock(Duration::from_millis(300));
+        cx.run_until_parked();
+        assert_eq!(provider.calls.get(), 1);
+        assert_eq!(messages(&state, cx), vec!["ab"]);
+    }
+
+    #[gpui::test]
+    fn test_diagnostics_provider_error(cx: &mut TestAppContext) {
+        let provider = TestProvider::default();
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .code_editor("text")
+                .diagnostics_provider(provider.clone())
+        });
+
+        state.update_in(cx, |state, window, cx| {
+            state.replace_text_in_range(None, "a", window, cx);
+        });
+        cx.executor().advance_clock(Duration::from_millis(300));
+        cx.run_until_parked();
+        assert_eq!(messages(&state, cx), vec!["a"]);
+
+        // The markers are cleared by the edit, and nothing is set when the provider fails.
+        provider.fail.set(true);
+        state.update_in(cx, |state, window, cx| {
+            state.replace_text_in_range(None, "b", window, cx);
+        });
+        cx.executor().advance_clock(Duration::from_millis(300));
+        cx.run_until_parked();
+        assert_eq!(provider.calls.get(), 2);
+        assert_eq!(messages(&state, cx), Vec::<String>::new());
+
+        // Request again after the provider is recovered.
+        provider.fail.set(false);
+        state.update_in(cx, |state, window, cx| {
+            state.replace_text_in_range(None, "c", window, cx);
+        });
+        cx.executor().advance_clock(Duration::from_millis(300));
+        cx.run_until_parked();
+        assert_eq!(provider.calls.get(), 3);
+        assert_eq!(messages(&state, cx), vec!["abc"]);
+    }
+}
@@ -2,23 +2,33 @@ use std::{ops::Range, rc::Rc};
 
 use gpui::{
     fill, point, px, relative, size, App, Bounds, Corners, Element, ElementId, ElementInputHandler,
-    Entity, GlobalElementId, HighlightStyle, IntoElement, LayoutId, MouseButton, MouseMoveEvent,
-    Path, Pixels, Point, SharedString, Size, Style, TextAlign, TextRun, UnderlineStyle, Window,
-    WrappedLine,
+    Entity, FontStyle, GlobalElementId, HighlightStyle, Hsla, IntoElement, LayoutId, MouseButton,
+    MouseMoveEvent, Path, Pixels, Point, SharedString, Size, Style, TextAlign, TextRun, TextStyle,
+    TransformationMatrix, UnderlineStyle, Window, WrappedLine,
 };
 use smallvec::SmallVec;
 
 use crate::{
-    highlighter::{LanguageRegistry, SyntaxHighlighter},
-    input::blink_cursor::CURSOR_WIDTH,
-    ActiveTheme as _, Root,
+    highlighter::LanguageRegistry, input::blink_cursor::CURSOR_WIDTH, ActiveTheme as _, Root,
 };
 
-use super::{mode::InputMode, InputState, LastLayout};
+use super::{
+    fold_map::fold_marker_path,
+    gutter::{LineStatus, GUTTER_ICON_SIZE, GUTTER_ICON_WIDTH},
+    indent_guides::index_for_column,
+    inlay::InlayStyle,
+    minimap::{MinimapLayout, MINIMAP_WIDTH},
+    mode::InputMode,
+    sticky_scroll::{sticky_rows, MAX_STICKY_ROWS},
+    whitespace::{whitespace_markers, ShowWhitespace},
+    InputState, LastLayout, Rope, RopeExt as _, TabSize,
+};
 
 const RIGHT_MARGIN: Pixels = px(5.);
 const BOTTOM_MARGIN_ROWS: usize = 1;
-const LINE_NUMBER_MARGIN_RIGHT: Pixels = px(10.);
+pub(super) const LINE_NUMBER_MARGIN_RIGHT: Pixels = px(10.);
+/// The width of the fold markers area in the line number gutter.
+pub(super) const FOLD_MARKER_WIDTH: Pixels = px(14.);
 
 pub(super) struct TextElement {
     input: Entity<InputState>,
@@ -53,79 +63,138 @@ impl TextElement {
         });
     }
 
+    /// Calculate the scroll offset to keep the cursor in view vertically.
+    ///
+    /// This is based on the [`TextWrapper`](super::text_wrapper::TextWrapper), so it is
+    /// not required the cursor line to be layouted.
+    fn layout_cursor_scroll_y(
+        &self,
+        line_height: Pixels,
+        bounds: &Bounds<Pixels>,
+        cx: &App,
+    ) -> Point<Pixels> {
+        let input = self.input.read(cx);
+        let mut scroll_offset = input.scroll_handle.offset();
+        if input.is_single_line() {
+            return scroll_offset;
+        }
+
+        let mut selected_range = input.selected_range.clone();
+        if let Some(marked_range) = &input.marked_range {
+            selected_range = marked_range.end..marked_range.end;
+        }
+        let cursor_offset = input.cursor_offset();
+        let cursor_moved = input.last_cursor_offset != Some(cursor_offset);
+        let selection_changed = input.last_selected_range != Some(selected_range);
+        if !cursor_moved && !selection_changed {
+            return scroll_offset;
+        }
+
+        // If the input has a fixed height (Otherwise is auto-grow), we need to add a bottom margin to the input.
+        let bottom_margin = if input.is_auto_grow() {
+            px(0.) + line_height
+        } else {
+            BOTTOM_MARGIN_ROWS * line_height + line_height
+        };
+
+        let display_point = input.text_wrapper.display_point_for_offset(cursor_offset);
+        let cursor_y = display_point.display_row as f32 * line_height;
+        scroll_offset.y =
+            if scroll_offset.y + cursor_y + line_height > bounds.size.height - bottom_margin {
+                // cursor is out of bottom
+                bounds.size.height - bottom_margin - cursor_y
+            } else if scroll_offset.y + cursor_y < px(0.) {
+                // cursor is out of top
+                -cursor_y
+            } else {
+                scroll_offset.y
+            };
+        scroll_offset.y = scroll_offset.y.min(px(0.));
+
+        scroll_offset
+    }
+
     /// Returns the:
     ///
     /// - cursor bounds
     /// - scroll offset
-    /// - current line index
+    ///
+    /// The `lines` only contains the visible lines, the first line is start at `visible_top`
+    /// and the text offset `visible_start`.
     fn layout_cursor(
         &self,
-        lines: &[WrappedLine],
-        line_height: Pixels,
+        last_layout: &LastLayout,
+        mut scroll_offset: Point<Pixels>,
         bounds: &mut Bounds<Pixels>,
         line_number_width: Pixels,
         window: &mut Window,
         cx: &mut App,
-    ) -> (Option<Bounds<Pixels>>, Point<Pixels>, Option<usize>) {
+    ) -> (Option<Bounds<Pixels>>, Point<Pixels>) {
         let input = self.input.read(cx);
+        let line_height = last_layout.line_height;
         let mut selected_range = input.selected_range.clone();
         if let Some(marked_range) = &input.marked_range {
             selected_range = marked_range.end..marked_range.end;
         }
 
         let cursor_offset = input.cursor_offset();
-        let mut current_line_index = None;
-        let mut scroll_offset = input.scroll_handle.offset();
         let mut cursor_bounds = None;
 
-        // If the input has a fixed height (Otherwise is auto-grow), we need to add a bottom margin to the input.
-        let bottom_margin = if input.is_auto_grow() {
-            px(0.) + line_height
-        } else {
-            BOTTOM_MARGIN_ROWS * line_height + line_height
-        };
         // The cursor corresponds to the current cursor position in the text no only the line.
         let mut cursor_pos = None;
+        // The width of the char under the block cursor.
+        let mut block_width = None;
         let mut cursor_start = None;
         let mut cursor_end = None;
 
-        let mut prev_lines_offset = 0;
-        let mut offset_y = px(0.);
-        for (line_ix, line) in lines.iter().enumerate() {
+        let mut offset_y = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
             // break loop if all cursor positions are found
             if cursor_pos.is_some() && cursor_start.is_some() && cursor_end.is_some() {
                 break;
             }
 
+            let prev_lines_offset = last_layout.line_offsets[ix];
             let line_origin = point(px(0.), offset_y);
-            if cursor_pos.is_none() {
-                let offset = cursor_offset.saturating_sub(prev_lines_offset);
+            if cursor_pos.is_none() && cursor_offset >= prev_lines_offset {
+                let offset = last_layout.index_for_offset(ix, cursor_offset - prev_lines_offset);
                 if let Some(pos) = line.position_for_index(offset, line_height) {
-                    current_line_index = Some(line_ix);
                     cursor_pos = Some(line_origin + pos);
+
+                    if input.is_block_cursor() {
+                        let char_len = input
+                            .text
+                            .char_at(cursor_offset)
+                            .filter(|c| *c != '\n')
+                            .map_or(0, |c| c.len_utf8());
+                        let next_offset = last_layout
+                            .index_for_offset(ix, cursor_offset + char_len - prev_lines_offset);
+                        block_width = line
+                            .position_for_index(next_offset, line_height)
+                            .filter(|next| next.y == pos.y && next.x > pos.x)
+                            .map(|next| next.x - pos.x);
+                    }
                 }
             }
-            if cursor_start.is_none() {
-                let offset = selected_range.start.saturating_sub(prev_lines_offset);
+            if cursor_start.is_none() && selected_range.start >= prev_lines_offset {
+                let offset =
+                    last_layout.index_for_offset(ix, selected_range.start - prev_lines_offset);
                 if let Some(pos) = line.position_for_index(offset, line_height) {
                     cursor_start = Some(line_origin + pos);
                 }
             }
-            if cursor_end.is_none() {
-                let offset = selected_range.end.saturating_sub(prev_lines_offset);
+            if cursor_end.is_none() && selected_range.end >= prev_lines_offset {
+                let offset =
+                    last_layout.index_for_offset(ix, selected_range.end - prev_lines_offset);
                 if let Some(pos) = line.position_for_index(offset, line_height) {
                     cursor_end = Some(line_origin + pos);
                 }
             }
 
             offset_y += line.size(line_height).height;
-            // +1 for skip the last `\n`
-            prev_lines_offset += line.len() + 1;
         }
 
-        if let (Some(cursor_pos), Some(cursor_start), Some(cursor_end)) =
-            (cursor_pos, cursor_start, cursor_end)
-        {
+        if let Some(cursor_pos) = cursor_pos {
             let cursor_moved = input.last_cursor_offset != Some(cursor_offset);
             let selection_changed = input.last_selected_range != Some(selected_range.clone());
 
@@ -140,61 +209,48 @@ impl TextElement {
                     } else {
                         scroll_offset.x
                     };
-                scroll_offset.y = if scroll_offset.y + cursor_pos.y + line_height
-                    > bounds.size.height - bottom_margin
-                {
-                    // cursor is out of bottom
-                    bounds.size.height - bottom_margin - cursor_pos.y
-                } else if scroll_offset.y + cursor_pos.y < px(0.) {
-                    // cursor is out of top
-                    scroll_offset.y - cursor_pos.y
-                } else {
-                    scroll_offset.y
-                };
 
                 if input.selection_reversed {
-                    if scroll_offset.x + cursor_start.x < px(0.) {
-                        // selection start is out of left
-                        scroll_offset.x = -cursor_start.x;
-                    }
-                    if scroll_offset.y + cursor_start.y < px(0.) {
-                        // selection start is out of top
-                        scroll_offset.y = -cursor_start.y;
+                    if let Some(cursor_start) = cursor_start {
+                        if scroll_offset.x + cursor_start.x < px(0.) {
+                            // selection start is out of left
+                            scroll_offset.x = -cursor_start.x;
+                        }
                     }
-                } else {
+                } else if let Some(cursor_end) = cursor_end {
                     if scroll_offset.x + cursor_end.x <= px(0.) {
                         // selection end is out of left
                         scroll_offset.x = -cursor_end.x;
                     }
-                    if scroll_offset.y + cursor_end.y <= px(0.) {
-                        // selection end is out of top
-                        scroll_offset.y = -cursor_end.y;
-                    }
                 }
             }
 
             if input.show_cursor(window, cx) {
                 // cursor blink
                 let cursor_height = line_height;
+                let cursor_width = if input.is_block_cursor() {
+                    block_width.unwrap_or(line_height / 2.)
+                } else {
+                    CURSOR_WIDTH
+                };
                 cursor_bounds = Some(Bounds::new(
                     point(
                         bounds.left() + cursor_pos.x + line_number_width + scroll_offset.x,
                         bounds.top() + cursor_pos.y + ((line_height - cursor_height) / 2.),
                     ),
-                    size(CURSOR_WIDTH, cursor_height),
+                    size(cursor_width, cursor_height),
                 ));
             };
         }
 
         bounds.origin = bounds.origin + scroll_offset;
 
-        (cursor_bounds, scroll_offset, current_line_index)
+        (cursor_bounds, scroll_offset)
     }
 
     fn layout_selections(
         &self,
-        lines: &[WrappedLine],
-        line_height: Pixels,
+        last_layout: &LastLayout,
         bounds: &mut Bounds<Pixels>,
         line_number_width: Pixels,
         _: &mut Window,
@@ -217,20 +273,139 @@ impl TextElement {
             (selected_range.end, selected_range.start)
         };
 
-        let mut prev_lines_offset = 0;
+        Self::layout_range(start_ix..end_ix, last_layout, bounds, line_number_width)
+    }
+
+    /// Layout the additional selections and cursors for multiple cursors.
+    ///
+    /// The `bounds` must be the bounds after scrolled.
+    fn layout_extra_selections(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> (Vec<Path<Pixels>>, Vec<Bounds<Pixels>>) {
+        let show_cursor = self.input.read(cx).show_cursor(window, cx);
+        let input = self.input.read(cx);
+        let line_height = last_layout.line_height;
+
+        let mut paths = vec![];
+        let mut cursors = vec![];
+        for range in input.extra_selections.iter() {
+            if !range.is_empty() {
+                if let Some(path) =
+                    Self::layout_range(range.clone(), last_layout, bounds, line_number_width)
+                {
+                    paths.push(path);
+                }
+            }
+
+            if show_cursor {
+                if let Some(pos) = position_for_offset(last_layout, range.end) {
+                    cursors.push(Bounds::new(
+                        bounds.origin + point(line_number_width + pos.x, pos.y),
+                        size(CURSOR_WIDTH, line_height),
+                    ));
+                }
+            }
+        }
+
+        (paths, cursors)
+    }
+
+    /// Layout the search matches in the visible lines.
+    ///
+    /// Returns the paths of the matches, and whether the match is the selected one.
+    fn layout_search_matches(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        cx: &App,
+    ) -> Vec<(Path<Pixels>, bool)> {
+        let input = self.input.read(cx);
+        let Some(search_panel) = input.search_panel.as_ref().map(|panel| panel.read(cx)) else {
+            return vec![];
+        };
+        if !search_panel.open {
+            return vec![];
+        }
+
+        let visible_range_offset = &last_layout.visible_range_offset;
+        let matches = &search_panel.matches;
+        let start = matches.partition_point(|range| range.end < visible_range_offset.start);
+
+        matches[start..]
+            .iter()
+            .take_while(|range| range.start <= visible_range_offset.end)
+            .filter_map(|range| {
+                let path =
+                    Self::layout_range(range.clone(), last_layout, bounds, line_number_width)?;
+                Some((path, *range == input.selected_range))
+            })
+            .collect()
+    }
+
+    /// Layout the bracket next to the cursor and its matching bracket.
+    fn layout_bracket_matches(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        cx: &App,
+    ) -> Vec<Path<Pixels>> {
+        let input = self.input.read(cx);
+        let Some((bracket, matching)) = input.matching_brackets(cx) else {
+            return vec![];
+        };
+
+        [bracket, matching]
+            .into_iter()
+            .filter_map(|range| Self::layout_range(range, last_layout, bounds, line_number_width))
+            .collect()
+    }
+
+    /// Layout the path of the `range` in the visible lines.
+    fn layout_range(
+        range: Range<usize>,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+    ) -> Option<Path<Pixels>> {
+        let (start_ix, end_ix) = (range.start, range.end);
+
+        // The range is out of the visible lines.
+        let visible_range_offset = &last_layout.visible_range_offset;
+        if end_ix < visible_range_offset.start || start_ix > visible_range_offset.end {
+            return None;
+        }
+
+        let line_height = last_layout.line_height;
         let mut line_corners = vec![];
 
-        let mut offset_y = px(0.);
-        for line in lines.iter() {
+        let mut offset_y = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let prev_lines_offset = last_layout.line_offsets[ix];
+            // The rest of the range is hidden by the folded regions.
+            if end_ix < prev_lines_offset {
+                break;
+            }
+
             let line_size = line.size(line_height);
             let line_wrap_width = line_size.width;
 
             let line_origin = point(px(0.), offset_y);
 
-            let line_cursor_start =
-                line.position_for_index(start_ix.saturating_sub(prev_lines_offset), line_height);
-            let line_cursor_end =
-                line.position_for_index(end_ix.saturating_sub(prev_lines_offset), line_height);
+            let line_cursor_start = line.position_for_index(
+                last_layout.index_for_offset(ix, start_ix.saturating_sub(prev_lines_offset)),
+                line_height,
+            );
+            let line_cursor_end = line.position_for_index(
+                last_layout.index_for_offset(ix, end_ix.saturating_sub(prev_lines_offset)),
+                line_height,
+            );
 
             if line_cursor_start.is_some() || line_cursor_end.is_some() {
                 let start = line_cursor_start
@@ -280,8 +455,6 @@ impl TextElement {
             }
 
             offset_y += line_size.height;
-            // +1 for skip the last `\n`
-            prev_lines_offset += line.len() + 1;
         }
 
         let mut points = vec![];
@@ -326,115 +499,754 @@ impl TextElement {
         builder.build().ok()
     }
 
+    /// Layout the fold markers in the line number gutter, and the placeholders after the
+    /// first line of the folded regions.
+    ///
+    /// The `bounds` must be the bounds after scrolled.
+    fn layout_folds(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        font_size: Pixels,
+        window: &mut Window,
+        cx: &App,
+    ) -> (
+        Vec<(Path<Pixels>, bool)>,
+        Vec<(Bounds<Pixels>, WrappedLine)>,
+    ) {
+        let input = self.input.read(cx);
+        let folds = &input.text_wrapper.folds;
+        let mut marker_paths = vec![];
+        let mut placeholders = vec![];
+        if !input.mode.is_code_editor() || folds.ranges().is_empty() {
+            return (marker_paths, placeholders);
+        }
+
+        let line_height = last_layout.line_height;
+        let marker_x = line_number_width - LINE_NUMBER_MARGIN_RIGHT - FOLD_MARKER_WIDTH / 2.;
+        let mut offset_y = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let row = last_layout.visible_rows[ix];
+            let line_origin = bounds.origin + point(px(0.), offset_y);
+            offset_y += line.size(line_height).height;
+
+            if folds.range_at(row).is_none() {
+                continue;
+            }
+            let folded = folds.is_folded(row);
+
+            if input.mode.line_number() {
+                let center = line_origin + point(marker_x, line_height / 2.);
+                if let Some(path) = fold_marker_path(center, folded) {
+                    marker_paths.push((path, folded));
+                }
+            }
+
+            if folded {
+                let Some(pos) = line.position_for_index(line.len(), line_height) else {
+                    continue;
+                };
+                let text: SharedString = "⋯".into();
+                let Ok(mut lines) = window.text_system().shape_text(
+                    text.clone(),
+                    font_size,
+                    &[TextRun {
+                        len: text.len(),
+                        font: window.text_style().font(),
+                        color: cx.theme().muted_foreground,
+                        background_color: None,
+                        underline: None,
+                        strikethrough: None,
+                    }],
+                    None,
+                    None,
+                ) else {
+                    continue;
+                };
+                let Some(placeholder) = lines.pop() else {
+                    continue;
+                };
+
+                let origin = line_origin + point(line_number_width + pos.x + px(6.), pos.y);
+                placeholders.push((
+                    Bounds::new(origin, size(placeholder.width() + px(8.), line_height)),
+                    placeholder,
+                ));
+            }
+        }
+
+        (marker_paths, placeholders)
+    }
+
+    /// Layout the gutter decorations of the visible lines.
+    ///
+    /// Returns the icons (bounds, svg path, color) and the status bars (bounds, color).
+    ///
+    /// The `bounds` must be the bounds after scrolled.
+    fn layout_gutter_decorations(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        gutter_icon_width: Pixels,
+        cx: &App,
+    ) -> (
+        Vec<(Bounds<Pixels>, SharedString, Hsla)>,
+        Vec<(Bounds<Pixels>, Hsla)>,
+    ) {
+        let input = self.input.read(cx);
+        let mut icons = vec![];
+        let mut bars = vec![];
+        if !input.mode.line_number() || input.gutter_decorations.is_empty() {
+            return (icons, bars);
+        }
+
+        let line_height = last_layout.line_height;
+        let bar_x = line_number_width - LINE_NUMBER_MARGIN_RIGHT + px(2.);
+        let mut offset_y = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let row = last_layout.visible_rows[ix];
+            let line_origin = bounds.origin + point(px(0.), offset_y);
+            let height = line.size(line_height).height;
+            offset_y += height;
+
+            for decoration in input.gutter_decorations.iter() {
+                if decoration.line != row {
+                    continue;
+                }
+
+                if let Some(icon) = decoration.icon.clone() {
+                    let origin = line_origin
+                        + point(
+                            (gutter_icon_width - GUTTER_ICON_SIZE) / 2.,
+                            (line_height - GUTTER_ICON_SIZE) / 2.,
+                        );
+                    let color = decoration.icon_color.unwrap_or(cx.theme().foreground);
+                    icons.push((
+                        Bounds::new(origin, size(GUTTER_ICON_SIZE, GUTTER_ICON_SIZE)),
+                        icon,
+                        color,
+                    ));
+                }
+
+                let bar = match decoration.status {
+                    Some(LineStatus::Added) => Some((
+                        Bounds::new(line_origin + point(bar_x, px(0.)), size(px(3.), height)),
+                        cx.theme().success,
+                    )),
+                    Some(LineStatus::Modified) => Some((
+                        Bounds::new(line_origin + point(bar_x, px(0.)), size(px(3.), height)),
+                        cx.theme().info,
+                    )),
+                    // Paint at the top of the line, the deleted lines are before this line.
+                    Some(LineStatus::Deleted) => Some((
+                        Bounds::new(line_origin + point(bar_x, px(-1.5)), size(px(6.), px(3.))),
+                        cx.theme().danger,
+                    )),
+                    None => None,
+                };
+                bars.extend(bar);
+            }
+        }
+
+        (icons, bars)
+    }
+
+    /// Layout the annotations after the end of the visible lines.
+    ///
+    /// The `bounds` must be the bounds after scrolled.
+    fn layout_line_annotations(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        font_size: Pixels,
+        window: &mut Window,
+        cx: &App,
+    ) -> Vec<(Point<Pixels>, WrappedLine)> {
+        let input = self.input.read(cx);
+        let mut annotations = vec![];
+        if !input.mode.is_code_editor() || input.line_annotations.is_empty() {
+            return annotations;
+        }
+
+        let mut font = window.text_style().font();
+        font.style = FontStyle::Italic;
+
+        let line_height = last_layout.line_height;
+        let mut offset_y = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let row = last_layout.visible_rows[ix];
+            let line_origin = bounds.origin + point(px(0.), offset_y);
+            offset_y += line.size(line_height).height;
+
+            let Some(annotation) = input
+                .line_annotations
+                .iter()
+                .find(|annotation| annotation.line == row)
+            else {
+                continue;
+            };
+            let Some(pos) = line.position_for_index(line.len(), line_height) else {
+                continue;
+            };
+
+            let text = annotation.text.clone();
+            let Ok(mut lines) = window.text_system().shape_text(
+                text.clone(),
+                font_size,
+                &[TextRun {
+                    len: text.len(),
+                    font: font.clone(),
+                    color: annotation.color.unwrap_or(cx.theme().muted_foreground),
+                    background_color: None,
+                    underline: None,
+                    strikethrough: None,
+                }],
+                None,
+                None,
+            ) else {
+                continue;
+            };
+            let Some(shaped) = lines.pop() else {
+                continue;
+            };
+
+            // Keep the space for the placeholder of the folded region.
+            let margin = if input.text_wrapper.folds.is_folded(row) {
+                px(48.)
+            } else {
+                px(24.)
+            };
+            let origin = line_origin + point(line_number_width + pos.x + margin, pos.y);
+            annotations.push((origin, shaped));
+        }
+
+        annotations
+    }
+
+    /// Layout the indent guides of the visible lines, returns the (bounds, is active) of the guides.
+    ///
+    /// The `bounds` must be the bounds after scrolled.
+    fn layout_indent_guides(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        font_size: Pixels,
+        window: &mut Window,
+        cx: &App,
+    ) -> Vec<(Bounds<Pixels>, bool)> {
+        let input = self.input.read(cx);
+        let mut guides = vec![];
+        if !input.indent_guides
+            || !input.is_multi_line()
+            || input.masked
+            || input.text.len_bytes() == 0
+        {
+            return guides;
+        }
+
+        let tab_size = input
+            .mode
+            .tab_size()
+            .map_or(TabSize::default().tab_size, |tab| tab.tab_size)
+            .max(1);
+        // The width of a column, for the blank lines that shorter than the indent.
+        let space_width = window
+            .text_system()
+            .shape_text(
+                " ".into(),
+                font_size,
+                &[TextRun {
+                    len: 1,
+                    font: window.text_style().font(),
+                    color: gpui::black(),
+                    background_color: None,
+                    underline: None,
+                    strikethrough: None,
+                }],
+                None,
+                None,
+            )
+            .ok()
+            .and_then(|lines| lines.first().map(|line| line.width()))
+            .unwrap_or(font_size / 2.);
+        let active_guide = input.active_indent_guide();
+
+        let line_height = last_layout.line_height;
+        let mut offset_y = last_layout.visible_top;
+        // The x position of each indent level of the previous line.
+        let mut level_xs: Vec<Pixels> = vec![];
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let row = last_layout.visible_rows[ix];
+            let line_origin = bounds.origin + point(line_number_width, offset_y);
+            let height = line.size(line_height).height;
+            offset_y += height;
+
+            let level = input.indent_level(row);
+            let line_text = input.text.slice_line(row);
+            for guide_ix in 0..level {
+                let col = guide_ix * tab_size;
+                let x = index_for_column(line_text.chars(), col, tab_size)
+                    .and_then(|index| {
+                        line.position_for_index(
+                            last_layout.index_for_offset(ix, index),
+                            line_height,
+                        )
+                    })
+                    .map(|pos| pos.x)
+                    .or(level_xs.get(guide_ix).copied())
+                    .unwrap_or(space_width * col as f32);
+                if guide_ix < level_xs.len() {
+                    level_xs[guide_ix] = x;
+                } else {
+                    level_xs.push(x);
+                }
+
+                let is_active = active_guide.as_ref().map_or(false, |(active_ix, rows)| {
+                    *active_ix == guide_ix && rows.contains(&row)
+                });
+                guides.push((
+                    Bounds::new(line_origin + point(x, px(0.)), size(px(1.), height)),
+                    is_active,
+                ));
+            }
+        }
+
+        guides
+    }
+
+    /// Layout the markers of the whitespaces in the visible lines.
+    ///
+    /// The `bounds` must be the bounds after scrolled.
+    fn layout_whitespaces(
+        &self,
+        last_layout: &LastLayout,
+        bounds: &Bounds<Pixels>,
+        line_number_width: Pixels,
+        font_size: Pixels,
+        window: &mut Window,
+        cx: &App,
+    ) -> Vec<(Point<Pixels>, WrappedLine)> {
+        let input = self.input.read(cx);
+        let mut markers = vec![];
+        if input.show_whitespace == ShowWhitespace::None
+            || !input.is_multi_line()
+            || input.masked
+            || input.text.len_bytes() == 0
+        {
+            return markers;
+        }
+
+        let mut selections = input.extra_selections.clone();
+        selections.push(input.selected_range.clone());
+        selections.sort_by_key(|range| range.start);
+
+        let font = window.text_style().font();
+        let color = cx.theme().muted_foreground.opacity(0.6);
+        let shape_marker = |text: &'static str| {
+            window
+                .text_system()
+                .shape_text(
+                    text.into(),
+                    font_size,
+                    &[TextRun {
+                        len: text.len(),
+                        font: font.clone(),
+                        color,
+                        background_color: None,
+                        underline: None,
+                        strikethrough: None,
+                    }],
+                    None,
+                    None,
+                )
+                .ok()
+                .and_then(|mut lines| lines.pop())
+        };
+        let (Some(space_marker), Some(tab_marker)) = (shape_marker("·"), shape_marker("→"))
+        else {
+            return markers;
+        };
+
+        let line_height = last_layout.line_height;
+        let mut offset_y = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let row = last_layout.visible_rows[ix];
+            let line_start = last_layout.line_offsets[ix];
+            let line_origin = bounds.origin + point(line_number_width, offset_y);
+            offset_y += line.size(line_height).height;
+
+            let line_text = input.text.slice_line(row).to_string();
+            for (offset, c) in
+                whitespace_markers(&line_text, line_start, input.show_whitespace, &selections)
+            {
+                // The index after the whitespace, the inlay at the offset is displayed before it.
+                let end_index = last_layout.index_for_offset(ix, offset - line_start + 1);
+                let (Some(start), Some(end)) = (
+                    line.position_for_index(end_index - 1, line_height),
+                    line.position_for_index(end_index, line_height),
+                ) else {
+                    continue;
+                };
+
+                let marker = if c == '\t' {
+                    &tab_marker
+                } else {
+                    &space_marker
+                };
+                let cell_width = if end.y == start.y {
+                    end.x - start.x
+                } else {
+                    marker.width()
+                };
+                let x = start.x + (cell_width - marker.width()).max(px(0.)) / 2.;
+                markers.push((line_origin + point(x, start.y), marker.clone()));
+            }
+        }
+
+        markers
+    }
+
+    /// Layout the line numbers and the text of the sticky lines, the text is not wrapped.
+    fn layout_sticky_lines(
+        &self,
+        last_layout: &LastLayout,
+        font_size: Pixels,
+        window: &mut Window,
+        cx: &App,
+    ) -> Vec<(Option<WrappedLine>, WrappedLine)> {
+        let input = self.input.read(cx);
+        let text_style = window.text_style();
+        let run = TextRun {
+            len: 0,
+            font: text_style.font(),
+            color: cx.theme().foreground,
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+        };
+
+        let mut sticky_lines = vec![];
+        for &row in last_layout.sticky_rows.iter() {
+            let line_number = if input.mode.line_number() {
+                let text: SharedString = format!("{:>4}", row + 1).into();
+                window
+                    .text_system()
+                    .shape_text(
+                        text.clone(),
+                        font_size,
+                        &[TextRun {
+                            len: text.len(),
+                            color: cx.theme().muted_foreground,
+                            ..run.clone()
+                        }],
+                        None,
+                        None,
+                    )
+                    .ok()
+                    .and_then(|mut lines| lines.pop())
+            } else {
+                None
+            };
+
+            let range = input.text.line_start_offset(row)..input.text.line_end_offset(row);
+            let styles = input.mode.highlight_styles(&range, cx);
+            let runs = build_runs(
+                range.len(),
+                range.start,
+                &run,
+                &text_style,
+                Some(&styles[..]),
+                None,
+            );
+            let text: SharedString = input.text.text_for_range(range).into();
+            let Some(line) = window
+                .text_system()
+                .shape_text(text, font_size, &runs, None, None)
+                .ok()
+                .and_then(|mut lines| lines.pop())
+            else {
+                continue;
+            };
+
+            sticky_lines.push((line_number, line));
+        }
+
+        sticky_lines
+    }
+
     /// Calculate the visible range of lines in the viewport.
     ///
     /// The visible range is based on unwrapped lines (Zero based).
+    ///
+    /// Returns the visible range and the top position of the first visible line.
     fn calculate_visible_range(
         &self,
         state: &InputState,
+        scroll_top: Pixels,
         line_height: Pixels,
         input_height: Pixels,
-    ) -> Range<usize> {
+    ) -> (Range<usize>, Pixels) {
         if state.is_single_line() {
-            return 0..1;
+            return (0..1, px(0.));
         }
 
-        let scroll_top = -state.scroll_handle.offset().y;
-        let total_lines = state.text_wrapper.lines.len();
-        let mut visible_range = 0..total_lines;
-        let mut line_top = px(0.);
-        for (ix, line) in state.text_wrapper.lines.iter().enumerate() {
-            line_top += line.height(line_height);
-
-            if line_top < scroll_top {
-                visible_range.start = ix;
-            }
-
-            if line_top > scroll_top + input_height {
-                visible_range.end = (ix + 1).min(total_lines);
-                break;
-            }
+        let total_lines = state.text_wrapper.lines.len().max(1);
+        let total_display_rows = state.text_wrapper.len();
+        if total_display_rows == 0 {
+            return (0..total_lines, px(0.));
         }
 
-        visible_range
+        // Clamp to the last display row to keep it visible when scrolled over the end.
+        let first_display_row =
+            ((scroll_top / line_height).floor().max(0.) as usize).min(total_display_rows - 1);
+        let first = state
+            .text_wrapper
+            .display_point_for_display_row(first_display_row);
+        let visible_top = line_height * (first.display_row - first.wrap_row);
+
+        let last_display_row = ((scroll_top + input_height) / line_height).ceil() as usize;
+        let end = if last_display_row >= total_display_rows {
+            total_lines
+        } else {
+            state
+                .text_wrapper
+                .display_point_for_display_row(last_display_row.saturating_sub(1))
+                .row
+                + 1
+        };
+        let visible_range = first.row..end.max(first.row + 1);
+
+        (visible_range, visible_top)
     }
 
-    /// First usize is the offset of skipped.
+    /// Returns the highlight styles of the visible lines, the range is in the entire text.
     fn highlight_lines(
         &mut self,
-        visible_range: &Range<usize>,
+        visible_rows: &[usize],
+        visible_range_offset: &Range<usize>,
         cx: &mut App,
-    ) -> Option<(usize, Vec<(Range<usize>, HighlightStyle)>)> {
+    ) -> Option<Vec<(Range<usize>, HighlightStyle)>> {
         let theme = LanguageRegistry::global(cx)
             .theme(cx.theme().is_dark())
             .clone();
-        self.input.update(cx, |state, cx| match &state.mode {
+        self.input.update(cx, |state, _| match &state.mode {
             InputMode::CodeEditor {
-                language,
                 highlighter,
                 markers,
                 ..
             } => {
-                // Init highlighter if not initialized
+                // The highlighter is created by `InputState::parse_syntax` in render.
                 let mut highlighter = highlighter.borrow_mut();
-                if highlighter.is_none() {
-                    highlighter.replace(SyntaxHighlighter::new(language, cx));
-                };
-                let Some(highlighter) = highlighter.as_ref() else {
+                let Some(highlighter) = highlighter.as_mut() else {
                     return None;
                 };
+                highlighter.set_visible_range(visible_range_offset.clone());
 
-                let mut offset = 0;
-                let mut skipped_offset = 0;
                 let mut styles = vec![];
-
-                for (ix, line) in state.text.split('\n').enumerate() {
+                for &row in visible_rows {
                     // +1 for last `\n`.
-                    let line_len = line.len() + 1;
-                    if ix < visible_range.start {
-                        offset += line_len;
-                        skipped_offset = offset;
-                        continue;
-                    }
-                    if ix > visible_range.end {
-                        break;
-                    }
-
-                    let range = offset..offset + line_len;
+                    let range =
+                        state.text.line_start_offset(row)..state.text.line_start_offset(row + 1);
                     let line_styles = highlighter.styles(&range, &theme);
                     styles = gpui::combine_highlights(styles, line_styles).collect();
-
-                    offset = range.end;
                 }
 
                 let mut marker_styles = vec![];
                 for marker in markers.iter() {
                     if let Some(range) = &marker.range {
-                        if range.start < skipped_offset {
+                        if range.end <= visible_range_offset.start
+                            || range.start >= visible_range_offset.end
+                        {
                             continue;
                         }
 
-                        let node_range = range.start..range.end;
-                        if node_range.start >= visible_range.start
-                            || node_range.end <= visible_range.end
-                        {
-                            marker_styles
-                                .push((node_range, marker.severity.highlight_style(&theme)));
-                        }
+                        marker_styles
+                            .push((range.clone(), marker.severity.highlight_style(&theme)));
                     }
                 }
 
                 styles = gpui::combine_highlights(marker_styles, styles).collect();
 
-                Some((skipped_offset, styles))
+                Some(styles)
             }
             _ => None,
         })
     }
 }
 
+/// Return the byte ranges of the continuous `rows`, the `\n` at the end are excluded.
+fn visible_segments(text: &Rope, rows: &[usize]) -> Vec<Range<usize>> {
+    let mut segments: Vec<Range<usize>> = vec![];
+    let mut prev_row = None;
+    for &row in rows {
+        let end = text.line_end_offset(row);
+        match segments.last_mut() {
+            Some(segment) if prev_row.map_or(false, |prev_row| prev_row + 1 == row) => {
+                segment.end = end;
+            }
+            _ => segments.push(text.line_start_offset(row)..end),
+        }
+        prev_row = Some(row);
+    }
+    segments
+}
+
+/// Split the `segment` by the offsets of the sorted `inlays`.
+///
+/// Returns the text ranges, each of them is followed by an inlay, except the last one.
+fn split_by_inlays<'a>(
+    segment: &Range<usize>,
+    inlays: &'a [(usize, SharedString, InlayStyle)],
+) -> Vec<(Range<usize>, Option<&'a (usize, SharedString, InlayStyle)>)> {
+    let mut pieces = vec![];
+    let mut start = segment.start;
+    for inlay in inlays
+        .iter()
+        .filter(|(offset, _, _)| segment.start <= *offset && *offset <= segment.end)
+    {
+        pieces.push((start..inlay.0, Some(inlay)));
+        start = inlay.0;
+    }
+    pieces.push((start..segment.end, None));
+    pieces
+}
+
+/// Return the text run of an inlay, the hints are displayed with a background,
+/// and the ghost text is displayed in italic.
+fn inlay_run(len: usize, style: InlayStyle, base_run: &TextRun, cx: &App) -> TextRun {
+    let theme = cx.theme();
+    match style {
+        InlayStyle::Hint => TextRun {
+            len,
+            color: theme.muted_foreground,
+            background_color: Some(theme.muted),
+            ..base_run.clone()
+        },
+        InlayStyle::Ghost => {
+            let mut font = base_run.font.clone();
+            font.style = FontStyle::Italic;
+            TextRun {
+                len,
+                font,
+                color: theme.muted_foreground,
+                ..base_run.clone()
+            }
+        }
+    }
+}
+
+/// Return the position of the `offset` relative to the text top, if the offset is in the visible lines.
+fn position_for_offset(last_layout: &LastLayout, offset: usize) -> Option<Point<Pixels>> {
+    let line_height = last_layout.line_height;
+    let mut offset_y = last_layout.visible_top;
+    for (ix, line) in last_layout.lines.iter().enumerate() {
+        let prev_lines_offset = last_layout.line_offsets[ix];
+        if offset >= prev_lines_offset {
+            let index = last_layout.index_for_offset(ix, offset - prev_lines_offset);
+            if let Some(pos) = line.position_for_index(index, line_height) {
+                return Some(point(pos.x, pos.y + offset_y));
+            }
+        }
+
+        offset_y += line.size(line_height).height;
+    }
+
+    None
+}
+
+/// Build the text runs for the visible text.
+///
+/// - `offset` is the offset of the visible text in the entire text.
+/// - `styles` is the highlight styles, the range is in the entire text.
+/// - `marked_range` is the IME marked range in the entire text.
+///
+/// The total length of the returned runs is always equal to `len`.
+fn build_runs(
+    len: usize,
+    offset: usize,
+    base_run: &TextRun,
+    text_style: &TextStyle,
+    styles: Option<&[(Range<usize>, HighlightStyle)]>,
+    marked_range: Option<&Range<usize>>,
+) -> Vec<TextRun> {
+    let mut runs: Vec<TextRun> = vec![];
+    let mut last_end = 0;
+    for (range, style) in styles.unwrap_or_default() {
+        let start = range.start.saturating_sub(offset).clamp(last_end, len);
+        let end = range.end.saturating_sub(offset).min(len);
+        if end <= start {
+            continue;
+        }
+
+        if start > last_end {
+            runs.push(TextRun {
+                len: start - last_end,
+                ..base_run.clone()
+            });
+        }
+
+        runs.push(text_style.clone().highlight(*style).to_run(end - start));
+        last_end = end;
+    }
+    if last_end < len {
+        runs.push(TextRun {
+            len: len - last_end,
+            ..base_run.clone()
+        });
+    }
+
+    let Some(marked_range) = marked_range else {
+        return runs;
+    };
+
+    // Split the runs by the marked range, and add underline to the marked text.
+    let marked_range = marked_range.start.saturating_sub(offset).min(len)
+        ..marked_range.end.saturating_sub(offset).min(len);
+    if marked_range.is_empty() {
+        return runs;
+    }
+
+    let mut marked_runs = vec![];
+    let mut run_start = 0;
+    for run in runs {
+        let run_range = run_start..run_start + run.len;
+        run_start = run_range.end;
+
+        let points = [
+            run_range.start,
+            marked_range.start.clamp(run_range.start, run_range.end),
+            marked_range.end.clamp(run_range.start, run_range.end),
+            run_range.end,
+        ];
+        for pair in points.windows(2) {
+            if pair[1] <= pair[0] {
+                continue;
+            }
+
+            let mut run = TextRun {
+                len: pair[1] - pair[0],
+                ..run.clone()
+            };
+            if pair[0] >= marked_range.start && pair[1] <= marked_range.end {
+                run.underline = Some(UnderlineStyle {
+                    thickness: px(1.),
+                    color: Some(run.color),
+                    wavy: false,
+                });
+            }
+            marked_runs.push(run);
+        }
+    }
+
+    marked_runs
+}
+
 pub(super) struct PrepaintState {
-    /// The lines of entire lines.
+    /// The lines of the visible lines in the viewport.
     last_layout: LastLayout,
     /// The lines only contains the visible lines in the viewport, based on `visible_range`.
     line_numbers: Option<Vec<SmallVec<[WrappedLine; 1]>>>,
@@ -446,6 +1258,33 @@ pub(super) struct PrepaintState {
     /// line index (zero based), no wrap, same line as the cursor.
     current_line_index: Option<usize>,
     selection_path: Option<Path<Pixels>>,
+    /// The paths of the search matches, and whether the match is selected.
+    search_match_paths: Vec<(Path<Pixels>, bool)>,
+    /// The paths of the bracket next to the cursor and its matching bracket.
+    bracket_match_paths: Vec<Path<Pixels>>,
+    /// The paths of the additional selections for multiple cursors.
+    extra_selection_paths: Vec<Path<Pixels>>,
+    /// The bounds of the additional cursors.
+    extra_cursor_bounds: Vec<Bounds<Pixels>>,
+    /// The paths of the fold markers in the line number gutter, and whether the region is folded.
+    fold_marker_paths: Vec<(Path<Pixels>, bool)>,
+    /// The placeholders after the first line of the folded regions.
+    fold_placeholders: Vec<(Bounds<Pixels>, WrappedLine)>,
+    /// The width of the icons area in the left of the line number gutter.
+    gutter_icon_width: Pixels,
+    /// The icons of the gutter decorations, (bounds, svg path, color).
+    gutter_icons: Vec<(Bounds<Pixels>, SharedString, Hsla)>,
+    /// The status bars of the gutter decorations.
+    gutter_bars: Vec<(Bounds<Pixels>, Hsla)>,
+    /// The annotations after the end of lines.
+    line_annotations: Vec<(Point<Pixels>, WrappedLine)>,
+    /// The indent guides, and whether the guide is active.
+    indent_guides: Vec<(Bounds<Pixels>, bool)>,
+    /// The markers of the whitespaces.
+    whitespace_markers: Vec<(Point<Pixels>, WrappedLine)>,
+    /// The line number and the text of the sticky lines.
+    sticky_lines: Vec<(Option<WrappedLine>, WrappedLine)>,
+    minimap: Option<MinimapLayout>,
     bounds: Bounds<Pixels>,
 }
 
@@ -535,34 +1374,11 @@ impl Element for TextElement {
         window: &mut Window,
         cx: &mut App,
     ) -> Self::PrepaintState {
-        let state = self.input.read(cx);
         let line_height = window.line_height();
-
-        let visible_range = self.calculate_visible_range(&state, line_height, bounds.size.height);
-        let highlight_styles = self.highlight_lines(&visible_range, cx);
-
-        let multi_line = self.input.read(cx).is_multi_line();
-        let input = self.input.read(cx);
-        let text = input.text.clone();
-        let is_empty = text.is_empty();
-        let placeholder = self.placeholder.clone();
         let style = window.text_style();
         let font_size = style.font_size.to_pixels(window.rem_size());
         let mut bounds = bounds;
 
-        let (display_text, text_color) = if is_empty {
-            (placeholder, cx.theme().muted_foreground)
-        } else if input.masked {
-            (
-                "*".repeat(text.chars().count()).into(),
-                cx.theme().foreground,
-            )
-        } else {
-            (text.clone(), cx.theme().foreground)
-        };
-
-        let text_style = window.text_style();
-
         // Calculate the width of the line numbers
         let empty_line_number = window
             .text_system()
@@ -581,12 +1397,145 @@ impl Element for TextElement {
                 None,
             )
             .unwrap();
-        let line_number_width = if input.mode.line_number() {
-            empty_line_number.last().unwrap().width() + LINE_NUMBER_MARGIN_RIGHT
+        // The icons area of the gutter decorations, only if there is any decoration.
+        let gutter_icon_width = if self.input.read(cx).mode.line_number()
+            && !self.input.read(cx).gutter_decorations.is_empty()
+        {
+            GUTTER_ICON_WIDTH
+        } else {
+            px(0.)
+        };
+        let line_number_width = if self.input.read(cx).mode.line_number() {
+            gutter_icon_width
+                + empty_line_number.last().unwrap().width()
+                + FOLD_MARKER_WIDTH
+                + LINE_NUMBER_MARGIN_RIGHT
+        } else {
+            px(0.)
+        };
+
+        let multi_line = self.input.read(cx).is_multi_line();
+        let minimap_width = if self.input.read(cx).mode.minimap() {
+            MINIMAP_WIDTH
         } else {
             px(0.)
         };
+        let wrap_width = if multi_line {
+            Some(bounds.size.width - line_number_width - minimap_width - RIGHT_MARGIN)
+        } else {
+            None
+        };
+
+        // Keep the text wrapper in sync with the wrap width of the text layout.
+        self.input.update(cx, |state, cx| {
+            state.text_wrapper.set_wrap_width(wrap_width, cx);
+            if state.text_wrapper.is_empty() {
+                state.text_wrapper.update_all(&state.text, cx);
+            }
+            state.mode.update_auto_grow(&state.text_wrapper);
+        });
+
+        let scroll_offset = self.layout_cursor_scroll_y(line_height, &bounds, cx);
+        let (visible_range, visible_top) = self.calculate_visible_range(
+            self.input.read(cx),
+            -scroll_offset.y,
+            line_height,
+            bounds.size.height,
+        );
+
+        let input = self.input.read(cx);
+        // The rows hidden by the folded regions are not displayed.
+        let visible_rows: Vec<usize> = if input.is_single_line() {
+            vec![0]
+        } else {
+            visible_range
+                .clone()
+                .filter(|row| !input.text_wrapper.is_hidden(*row))
+                .collect()
+        };
+        let line_offsets: Vec<usize> = visible_rows
+            .iter()
+            .map(|row| input.text.line_start_offset(*row))
+            .collect();
+        let visible_segments = if input.is_single_line() {
+            vec![0..input.text.len_bytes()]
+        } else {
+            visible_segments(&input.text, &visible_rows)
+        };
+        let visible_range_offset = visible_segments.first().map_or(0, |segment| segment.start)
+            ..visible_segments.last().map_or(0, |segment| segment.end);
+        let highlight_styles = self.highlight_lines(&visible_rows, &visible_range_offset, cx);
+
+        let input = self.input.read(cx);
+        let sticky_rows = if input.mode.sticky_scroll() {
+            let first_display_row = (-scroll_offset.y / line_height).floor() as usize;
+            let max_rows = MAX_STICKY_ROWS.min((bounds.size.height / line_height / 3.) as usize);
+            sticky_rows(input.text_wrapper.folds.ranges(), max_rows, |ix| {
+                input
+                    .text_wrapper
+                    .display_point_for_display_row(first_display_row + ix)
+                    .row
+            })
+        } else {
+            vec![]
+        };
+        let minimap = input.layout_minimap(&bounds, -scroll_offset.y, line_height, cx);
+
+        let input = self.input.read(cx);
+        let is_empty = input.text.len_bytes() == 0;
+        let placeholder = self.placeholder.clone();
+        // The inlays in the folded regions are not displayed.
+        let inlays = if is_empty {
+            vec![]
+        } else {
+            input
+                .inlays_in_range(&visible_range_offset)
+                .into_iter()
+                .filter(|(offset, _, _)| {
+                    visible_segments
+                        .iter()
+                        .any(|segment| segment.start <= *offset && *offset <= segment.end)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (display_text, text_color): (SharedString, _) = if is_empty {
+            (placeholder, cx.theme().muted_foreground)
+        } else if input.masked {
+            (
+                "*".repeat(
+                    input
+                        .text
+                        .byte_slice(visible_range_offset.clone())
+                        .len_chars(),
+                )
+                .into(),
+                cx.theme().foreground,
+            )
+        } else {
+            (
+                visible_segments
+                    .iter()
+                    .map(|segment| {
+                        split_by_inlays(segment, &inlays)
+                            .into_iter()
+                            .map(|(range, inlay)| {
+                                let text = input.text.text_for_range(range);
+                                match inlay {
+                                    Some((_, inlay_text, _)) => format!("{}{}", text, inlay_text),
+                                    None => text,
+                                }
+                            })
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .into(),
+                cx.theme().foreground,
+            )
+        };
 
+        let text_style = window.text_style();
         let run = TextRun {
             len: display_text.len(),
             font: style.font(),
@@ -595,77 +1544,46 @@ impl Element for TextElement {
             underline: None,
             strikethrough: None,
         };
-        let marked_run = TextRun {
-            len: 0,
-            font: style.font(),
-            color: text_color,
-            background_color: None,
-            underline: Some(UnderlineStyle {
-                thickness: px(1.),
-                color: Some(text_color),
-                wavy: false,
-            }),
-            strikethrough: None,
-        };
 
-        let runs = if !is_empty {
-            if let Some((skipped_offset, highlight_styles)) = highlight_styles {
-                let mut runs = vec![];
-                if skipped_offset > 0 {
+        let runs = if is_empty {
+            vec![run]
+        } else if input.masked {
+            build_runs(
+                display_text.len(),
+                visible_range_offset.start,
+                &run,
+                &text_style,
+                highlight_styles.as_deref(),
+                input.marked_range.as_ref(),
+            )
+        } else {
+            let mut runs = vec![];
+            for (ix, segment) in visible_segments.iter().enumerate() {
+                // The `\n` to join the segments.
+                if ix > 0 {
                     runs.push(TextRun {
-                        len: skipped_offset,
+                        len: 1,
                         ..run.clone()
                     });
                 }
-
-                runs.extend(highlight_styles.iter().map(|(range, style)| {
-                    let mut run = text_style.clone().highlight(*style).to_run(range.len());
-                    if let Some(marked_range) = &input.marked_range {
-                        if range.start >= marked_range.start && range.end <= marked_range.end {
-                            run.color = marked_run.color;
-                            run.strikethrough = marked_run.strikethrough;
-                            run.underline = marked_run.underline;
-                        }
+                for (range, inlay) in split_by_inlays(segment, &inlays) {
+                    runs.extend(build_runs(
+                        range.len(),
+                        range.start,
+                        &run,
+                        &text_style,
+                        highlight_styles.as_deref(),
+                        input.marked_range.as_ref(),
+                    ));
+                    if let Some((_, inlay_text, inlay_style)) = inlay {
+                        runs.push(inlay_run(inlay_text.len(), *inlay_style, &run, cx));
                     }
-
-                    run
-                }));
-
-                runs.into_iter().filter(|run| run.len > 0).collect()
-            } else {
-                vec![run]
-            }
-        } else if let Some(marked_range) = &input.marked_range {
-            // IME marked text
-            vec![
-                TextRun {
-                    len: marked_range.start,
-                    ..run.clone()
-                },
-                TextRun {
-                    len: marked_range.end - marked_range.start,
-                    underline: marked_run.underline,
-                    ..run.clone()
-                },
-                TextRun {
-                    len: display_text.len() - marked_range.end,
-                    ..run.clone()
-                },
-            ]
-            .into_iter()
-            .filter(|run| run.len > 0)
-            .collect()
-        } else {
-            vec![run]
-        };
-
-        let wrap_width = if multi_line {
-            Some(bounds.size.width - line_number_width - RIGHT_MARGIN)
-        } else {
-            None
+                }
+            }
+            runs
         };
 
-        // NOTE: If there have about 10K lines, this will take about 5~6ms.
+        // NOTE: Only the visible lines are shaped, so this is fast for large text.
         // let measure = Measure::new("shape_text");
         let lines = window
             .text_system()
@@ -673,13 +1591,11 @@ impl Element for TextElement {
             .expect("failed to shape text");
         // measure.end();
 
-        let total_wrapped_lines = lines
-            .iter()
-            .map(|line| {
-                // +1 is the first line, `wrap_boundaries` is the wrapped lines after the `\n`.
-                1 + line.wrap_boundaries.len()
-            })
-            .sum::<usize>();
+        let total_wrapped_lines = if multi_line {
+            input.text_wrapper.len()
+        } else {
+            1
+        };
 
         let max_line_width = lines
             .iter()
@@ -720,27 +1636,51 @@ impl Element for TextElement {
         // | 114   | (429.85938 px, 20.0)  | 1    |
         // | 115   | (11.3125 px, 40.0)    | 2    |
 
-        // Calculate the scroll offset to keep the cursor in view
+        let mut line_inlays = vec![vec![]; line_offsets.len()];
+        for (offset, text, _) in inlays.iter() {
+            let ix = line_offsets
+                .partition_point(|line_offset| line_offset <= offset)
+                .saturating_sub(1);
+            line_inlays[ix].push((offset - line_offsets[ix], text.len()));
+        }
 
-        let (cursor_bounds, cursor_scroll_offset, current_line_index) = self.layout_cursor(
-            &lines,
+        let last_layout = LastLayout {
+            lines: Rc::new(lines),
             line_height,
-            &mut bounds,
-            line_number_width,
-            window,
-            cx,
-        );
+            visible_range,
+            visible_rows: Rc::new(visible_rows),
+            line_offsets: Rc::new(line_offsets),
+            visible_range_offset,
+            visible_top,
+            inlays: Rc::new(line_inlays),
+            sticky_rows: Rc::new(sticky_rows),
+        };
 
-        let selection_path = self.layout_selections(
-            &lines,
-            line_height,
+        // Calculate the scroll offset to keep the cursor in view
+        let (cursor_bounds, cursor_scroll_offset) = self.layout_cursor(
+            &last_layout,
+            scroll_offset,
             &mut bounds,
             line_number_width,
             window,
             cx,
         );
 
+        let selection_path =
+            self.layout_selections(&last_layout, &mut bounds, line_number_width, window, cx);
+        let search_match_paths =
+            self.layout_search_matches(&last_layout, &bounds, line_number_width, cx);
+        let bracket_match_paths =
+            self.layout_bracket_matches(&last_layout, &bounds, line_number_width, cx);
+        let (extra_selection_paths, extra_cursor_bounds) =
+            self.layout_extra_selections(&last_layout, &bounds, line_number_width, window, cx);
+
         let input = self.input.read(cx);
+        let current_line_index = if multi_line {
+            Some(input.text.offset_to_row(input.cursor_offset()))
+        } else {
+            None
+        };
         let line_numbers = if input.mode.line_number() {
             let mut line_numbers = vec![];
             let run_len = 4;
@@ -762,13 +1702,8 @@ impl Element for TextElement {
             }];
 
             // build line numbers
-            for (ix, line) in lines
-                .iter()
-                .skip(visible_range.start)
-                .take(visible_range.len())
-                .enumerate()
-            {
-                let ix = ix + visible_range.start;
+            for (ix, line) in last_layout.lines.iter().enumerate() {
+                let ix = last_layout.visible_rows[ix];
                 let line_no = ix + 1;
 
                 let mut line_no_text = format!("{:>4}", line_no);
@@ -793,13 +1728,53 @@ impl Element for TextElement {
             None
         };
 
+        let (fold_marker_paths, fold_placeholders) = self.layout_folds(
+            &last_layout,
+            &bounds,
+            line_number_width,
+            font_size,
+            window,
+            cx,
+        );
+
+        let (gutter_icons, gutter_bars) = self.layout_gutter_decorations(
+            &last_layout,
+            &bounds,
+            line_number_width,
+            gutter_icon_width,
+            cx,
+        );
+        let line_annotations = self.layout_line_annotations(
+            &last_layout,
+            &bounds,
+            line_number_width,
+            font_size,
+            window,
+            cx,
+        );
+        let indent_guides = self.layout_indent_guides(
+            &last_layout,
+            &bounds,
+            line_number_width,
+            font_size,
+            window,
+            cx,
+        );
+        let whitespace_markers = self.layout_whitespaces(
+            &last_layout,
+            &bounds,
+            line_number_width,
+            font_size,
+            window,
+            cx,
+        );
+        let sticky_lines = self.layout_sticky_lines(&last_layout, font_size, window, cx);
+        // Highlight the ranges requested by the layout in the background.
+        self.input.update(cx, |state, cx| state.parse_syntax(cx));
+
         PrepaintState {
             bounds,
-            last_layout: LastLayout {
-                lines: Rc::new(lines),
-                line_height,
-                visible_range,
-            },
+            last_layout,
             scroll_size,
             line_numbers,
             line_number_width,
@@ -807,6 +1782,20 @@ impl Element for TextElement {
             cursor_scroll_offset,
             current_line_index,
             selection_path,
+            search_match_paths,
+            bracket_match_paths,
+            extra_selection_paths,
+            extra_cursor_bounds,
+            fold_marker_paths,
+            fold_placeholders,
+            gutter_icon_width,
+            gutter_icons,
+            gutter_bars,
+            line_annotations,
+            indent_guides,
+            whitespace_markers,
+            sticky_lines,
+            minimap,
         }
     }
 
@@ -824,7 +1813,7 @@ impl Element for TextElement {
         let focused = focus_handle.is_focused(window);
         let bounds = prepaint.bounds;
         let selected_range = self.input.read(cx).selected_range.clone();
-        let visible_range = &prepaint.last_layout.visible_range;
+        let visible_top = prepaint.last_layout.visible_top;
 
         window.handle_input(
             &focus_handle,
@@ -860,11 +1849,6 @@ impl Element for TextElement {
         let line_height = window.line_height();
         let origin = bounds.origin;
 
-        let mut invisible_top_padding = px(0.);
-        for line in prepaint.last_layout.lines.iter().take(visible_range.start) {
-            invisible_top_padding += line.size(line_height).height;
-        }
-
         let mut mask_offset_y = px(0.);
         if self.input.read(cx).masked {
             // Move down offset for vertical centering the *****
@@ -882,19 +1866,23 @@ impl Element for TextElement {
 
         let mut offset_y = px(0.);
         if let Some(line_numbers) = prepaint.line_numbers.as_ref() {
-            offset_y += invisible_top_padding;
+            offset_y += visible_top;
 
             // Each item is the normal lines.
             for (ix, lines) in line_numbers.iter().enumerate() {
-                let is_active = prepaint.current_line_index == Some(visible_range.start + ix);
+                let is_active =
+                    prepaint.current_line_index == Some(prepaint.last_layout.visible_rows[ix]);
                 for line in lines {
-                    let p = point(origin.x, origin.y + offset_y);
+                    let p = point(origin.x + prepaint.gutter_icon_width, origin.y + offset_y);
                     let line_size = line.size(line_height);
                     // Paint the current line background
                     if is_active {
                         if let Some(bg_color) = active_line_color {
                             window.paint_quad(fill(
-                                Bounds::new(p, size(bounds.size.width, line_height)),
+                                Bounds::new(
+                                    point(origin.x, p.y),
+                                    size(bounds.size.width, line_height),
+                                ),
                                 bg_color,
                             ));
                         }
@@ -905,37 +1893,157 @@ impl Element for TextElement {
             }
         }
 
+        // Paint fold markers
+        for (path, folded) in prepaint.fold_marker_paths.drain(..) {
+            let color = if folded {
+                cx.theme().foreground
+            } else {
+                cx.theme().muted_foreground
+            };
+            window.paint_path(path, color);
+        }
+
+        // Paint gutter decorations
+        for (bounds, color) in prepaint.gutter_bars.drain(..) {
+            window.paint_quad(fill(bounds, color));
+        }
+        for (bounds, path, color) in prepaint.gutter_icons.drain(..) {
+            _ = window.paint_svg(bounds, path, TransformationMatrix::unit(), color, cx);
+        }
+
+        // Paint indent guides
+        for (bounds, active) in prepaint.indent_guides.drain(..) {
+            let color = if active {
+                cx.theme().muted_foreground.opacity(0.6)
+            } else {
+                cx.theme().border
+            };
+            window.paint_quad(fill(bounds, color));
+        }
+
+        // Paint search matches
+        for (path, selected) in prepaint.search_match_paths.drain(..) {
+            let color = if selected {
+                cx.theme().warning.opacity(0.4)
+            } else {
+                cx.theme().warning.opacity(0.2)
+            };
+            window.paint_path(path, color);
+        }
+
+        // Paint matching brackets
+        for path in prepaint.bracket_match_paths.drain(..) {
+            window.paint_path(path, cx.theme().muted_foreground.opacity(0.2));
+        }
+
         // Paint selections
+        for path in prepaint.extra_selection_paths.drain(..) {
+            window.paint_path(path, cx.theme().selection);
+        }
         if let Some(path) = prepaint.selection_path.take() {
             window.paint_path(path, cx.theme().selection);
         }
 
         // Paint text
-        let mut offset_y = mask_offset_y + invisible_top_padding;
+        let mut offset_y = mask_offset_y + visible_top;
 
-        for line in prepaint
-            .last_layout
-            .iter()
-            .skip(visible_range.start)
-            .take(visible_range.len())
-        {
+        for line in prepaint.last_layout.iter() {
             let p = point(origin.x + prepaint.line_number_width, origin.y + offset_y);
             _ = line.paint(p, line_height, TextAlign::Left, None, window, cx);
             offset_y += line.size(line_height).height;
         }
 
+        // Paint the markers of the whitespaces
+        for (origin, marker) in prepaint.whitespace_markers.drain(..) {
+            _ = marker.paint(origin, line_height, TextAlign::Left, None, window, cx);
+        }
+
+        // Paint the placeholders of the folded regions
+        for (bounds, line) in prepaint.fold_placeholders.drain(..) {
+            window.paint_quad(fill(bounds, cx.theme().muted).corner_radii(px(3.)));
+            let p = point(bounds.origin.x + px(4.), bounds.origin.y);
+            _ = line.paint(p, line_height, TextAlign::Left, None, window, cx);
+        }
+
+        // Paint the annotations after the end of lines
+        for (origin, line) in prepaint.line_annotations.drain(..) {
+            _ = line.paint(origin, line_height, TextAlign::Left, None, window, cx);
+        }
+
         if focused {
             if let Some(mut cursor_bounds) = prepaint.cursor_bounds.take() {
                 cursor_bounds.origin.y += prepaint.cursor_scroll_offset.y;
+                let caret = if self.input.read(cx).is_block_cursor() {
+                    cx.theme().caret.opacity(0.5)
+                } else {
+                    cx.theme().caret
+                };
+                window.paint_quad(fill(cursor_bounds, caret));
+            }
+            for cursor_bounds in prepaint.extra_cursor_bounds.drain(..) {
                 window.paint_quad(fill(cursor_bounds, cx.theme().caret));
             }
         }
 
+        // Paint the sticky lines over the text at the top.
+        if !prepaint.sticky_lines.is_empty() {
+            let minimap_width = prepaint
+                .minimap
+                .as_ref()
+                .map_or(px(0.), |minimap| minimap.bounds.size.width);
+            let sticky_bounds = Bounds::new(
+                input_bounds.origin,
+                size(
+                    input_bounds.size.width - minimap_width,
+                    line_height * prepaint.sticky_lines.len() as f32,
+                ),
+            );
+            window.paint_quad(fill(sticky_bounds, cx.theme().background));
+            window.paint_quad(fill(
+                Bounds::new(
+                    sticky_bounds.bottom_left(),
+                    size(sticky_bounds.size.width, px(1.)),
+                ),
+                cx.theme().border,
+            ));
+
+            let mut offset_y = px(0.);
+            for (line_number, line) in prepaint.sticky_lines.drain(..) {
+                let y = input_bounds.top() + offset_y;
+                if let Some(line_number) = line_number {
+                    let p = point(origin.x + prepaint.gutter_icon_width, y);
+                    _ = line_number.paint(p, line_height, TextAlign::Left, None, window, cx);
+                }
+                let p = point(origin.x + prepaint.line_number_width, y);
+                _ = line.paint(p, line_height, TextAlign::Left, None, window, cx);
+                offset_y += line_height;
+            }
+        }
+
+        // Paint the minimap
+        if let Some(minimap) = prepaint.minimap.take() {
+            window.paint_quad(fill(minimap.bounds, cx.theme().background));
+            window.paint_quad(fill(
+                Bounds::new(
+                    minimap.bounds.origin,
+                    size(px(1.), minimap.bounds.size.height),
+                ),
+                cx.theme().border,
+            ));
+            for (bounds, color) in minimap.blocks {
+                window.paint_quad(fill(bounds, color));
+            }
+            window.paint_quad(fill(
+                minimap.viewport,
+                cx.theme().muted_foreground.opacity(0.15),
+            ));
+        }
+
         self.input.update(cx, |input, cx| {
             input.last_layout = Some(prepaint.last_layout.clone());
             input.last_bounds = Some(bounds);
             input.last_cursor_offset = Some(input.cursor_offset());
-            input.set_input_bounds(input_bounds, cx);
+            input.set_input_bounds(input_bounds);
             input.last_selected_range = Some(selected_range);
             input.scroll_size = prepaint.scroll_size;
             input.line_number_width = prepaint.line_number_width;
@@ -0,0 +1,428 @@
+//! The state of code folding, the ranges are rows (zero based, split by `\n`).
+//!
+//! For a fold range `start..end`, the `start` row is keep visible as the header of the region,
+//! and the rows in `start + 1..=end` are hidden when it is folded.
+use std::ops::Range;
+
+use gpui::{point, px, Context, Pixels, Point, Window};
+
+use super::{
+    element::{FOLD_MARKER_WIDTH, LINE_NUMBER_MARGIN_RIGHT},
+    Fold, FoldAll, InputState, RopeExt as _, Unfold, UnfoldAll,
+};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct FoldMap {
+    /// The foldable ranges, sorted by the start row, and only one range for each start row.
+    ranges: Vec<Range<usize>>,
+    /// The folded ranges, sorted by the start row.
+    folded: Vec<Range<usize>>,
+    /// The outermost folded ranges (not nested in another folded range), sorted by the start row.
+    outer: Vec<Range<usize>>,
+}
+
+impl FoldMap {
+    /// Return the foldable ranges.
+    pub(super) fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// Return the folded ranges.
+    pub(super) fn folded(&self) -> &[Range<usize>] {
+        &self.folded
+    }
+
+    /// Set the foldable ranges, for example the result of the tree-sitter folds query.
+    ///
+    /// The ranges that in the same start row will keep the largest one, and the ranges
+    /// of a single line are ignored.
+    ///
+    /// The folded ranges that are no longer foldable will be unfolded.
+    pub(super) fn set_ranges(&mut self, mut ranges: Vec<Range<usize>>) {
+        ranges.retain(|range| range.end > range.start);
+        ranges.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+        ranges.dedup_by_key(|range| range.start);
+        self.ranges = ranges;
+
+        let ranges = &self.ranges;
+        self.folded.retain_mut(
+            |folded| match ranges.get(Self::index_of(ranges, folded.start)) {
+                Some(range) if range.start == folded.start => {
+                    folded.end = range.end;
+                    true
+                }
+                _ => false,
+            },
+        );
+        self.update_outer();
+    }
+
+    /// Return the index of the first range that start at or after the `row`.
+    fn index_of(ranges: &[Range<usize>], row: usize) -> usize {
+        ranges.partition_point(|range| range.start < row)
+    }
+
+    /// Return the foldable range that start at the `row`.
+    pub(super) fn range_at(&self, row: usize) -> Option<&Range<usize>> {
+        self.ranges
+            .get(Self::index_of(&self.ranges, row))
+            .filter(|range| range.start == row)
+    }
+
+    /// Return true if the region start at the `row` is folded.
+    pub(super) fn is_folded(&self, row: usize) -> bool {
+        self.folded
+            .get(Self::index_of(&self.folded, row))
+            .map_or(false, |range| range.start == row)
+    }
+
+    /// Return the outermost folded range that hides the `row`.
+    pub(super) fn folded_range_at(&self, row: usize) -> Option<&Range<usize>> {
+        let ix = Self::index_of(&self.outer, row);
+        self.outer[..ix]
+            .last()
+            .filter(|range| range.start < row && row <= range.end)
+    }
+
+    /// Update the outermost folded ranges, must be called after the `folded` is changed.
+    fn update_outer(&mut self) {
+        self.outer.clear();
+        for range in self.folded.iter() {
+            match self.outer.last() {
+                Some(last) if range.end <= last.end => {}
+                _ => self.outer.push(range.clone()),
+            }
+        }
+    }
+
+    /// Fold the innermost foldable range that contains the `row`.
+    ///
+    /// Returns true if a range has been folded.
+    pub(super) fn fold(&mut self, row: usize) -> bool {
+        let Some(range) = self
+            .ranges
+            .iter()
+            .rev()
+            .find(|range| range.start <= row && row <= range.end && !self.is_folded(range.start))
+            .cloned()
+        else {
+            return false;
+        };
+
+        let ix = Self::index_of(&self.folded, range.start);
+        self.folded.insert(ix, range);
+        self.update_outer();
+        true
+    }
+
+    /// Unfold the innermost folded range that contains the `row`.
+    ///
+    /// Returns true if a range has been unfolded.
+    pub(super) fn unfold(&mut self, row: usize) -> bool {
+        let Some(ix) = self
+            .folded
+            .iter()
+            .rposition(|range| range.start <= row && row <= range.end)
+        else {
+            return false;
+        };
+
+        self.folded.remove(ix);
+        self.update_outer();
+        true
+    }
+
+    /// Fold or unfold the range that start at the `row`.
+    ///
+    /// Returns true if the folded ranges has been changed.
+    pub(super) fn toggle(&mut self, row: usize) -> bool {
+        if self.is_folded(row) {
+            self.folded.remove(Self::index_of(&self.folded, row));
+            self.update_outer();
+            return true;
+        }
+
+        let Some(range) = self.range_at(row).cloned() else {
+            return false;
+        };
+        let ix = Self::index_of(&self.folded, range.start);
+        self.folded.insert(ix, range);
+        self.update_outer();
+        true
+    }
+
+    /// Unfold all the folded ranges that hides any row in the `rows`.
+    ///
+    /// Returns true if any range has been unfolded.
+    pub(super) fn unfold_rows(&mut self, rows: Range<usize>) -> bool {
+        let len = self.folded.len();
+        let rows_end = rows.end.max(rows.start + 1);
+        self.folded
+            .retain(|range| !(range.start + 1 < rows_end && rows.start <= range.end));
+        self.update_outer();
+        self.folded.len() != len
+    }
+
+    /// Fold all the foldable ranges.
+    pub(super) fn fold_all(&mut self) -> bool {
+        let changed = self.folded != self.ranges;
+        self.folded = self.ranges.clone();
+        self.update_outer();
+        changed
+    }
+
+    /// Unfold all the folded ranges.
+    pub(super) fn unfold_all(&mut self) -> bool {
+        let changed = !self.folded.is_empty();
+        self.folded.clear();
+        self.outer.clear();
+        changed
+    }
+
+    /// Update the ranges after the rows `start_row..=old_end_row` (in the old text) were
+    /// replaced by the rows `start_row..=new_end_row`.
+    ///
+    /// - The ranges after the edit are moved.
+    /// - The ranges that the edit changes the hidden rows are removed.
+    pub(super) fn edit(&mut self, start_row: usize, old_end_row: usize, new_end_row: usize) {
+        for ranges in [&mut self.ranges, &mut self.folded] {
+            ranges.retain_mut(|range| {
+                if old_end_row <= range.start {
+                    range.start = range.start + new_end_row - old_end_row;
+                    range.end = range.end + new_end_row - old_end_row;
+                    true
+                } else {
+                    start_row > range.end
+                }
+            });
+        }
+        self.update_outer();
+    }
+
+    /// Return the hidden row ranges of the folded ranges, the nested ranges are merged.
+    pub(super) fn hidden_rows(&self) -> Vec<Range<usize>> {
+        let mut rows: Vec<Range<usize>> = vec![];
+        for range in self.folded.iter() {
+            let hidden = range.start + 1..range.end + 1;
+            match rows.last_mut() {
+                Some(last) if hidden.start <= last.end => last.end = last.end.max(hidden.end),
+                _ => rows.push(hidden),
+            }
+        }
+        rows
+    }
+}
+
+impl InputState {
+    /// Fold the innermost region that contains the cursor.
+    pub(super) fn fold(&mut self, _: &Fold, _: &mut Window, cx: &mut Context<Self>) {
+        let row = self.text.offset_to_row(self.cursor_offset());
+        self.update_folds(|folds| folds.fold(row), cx);
+    }
+
+    /// Unfold the innermost folded region that contains the cursor.
+    pub(super) fn unfold(&mut self, _: &Unfold, _: &mut Window, cx: &mut Context<Self>) {
+        let row = self.text.offset_to_row(self.cursor_offset());
+        self.update_folds(|folds| folds.unfold(row), cx);
+    }
+
+    pub(super) fn fold_all(&mut self, _: &FoldAll, _: &mut Window, cx: &mut Context<Self>) {
+        self.update_folds(|folds| folds.fold_all(), cx);
+    }
+
+    pub(super) fn unfold_all(&mut self, _: &UnfoldAll, _: &mut Window, cx: &mut Context<Self>) {
+        self.update_folds(|folds| folds.unfold_all(), cx);
+    }
+
+    /// Fold or unfold the region that start at the `row`.
+    pub(super) fn toggle_fold_at_row(&mut self, row: usize, cx: &mut Context<Self>) {
+        self.update_folds(|folds| folds.toggle(row), cx);
+    }
+
+    /// Unfold the folded regions that hide any part of the `range`.
+    pub(super) fn unfold_range(&mut self, range: &Range<usize>) {
+        let start_row = self.text.offset_to_row(range.start);
+        let end_row = self.text.offset_to_row(range.end);
+        if self.text_wrapper.folds.unfold_rows(start_row..end_row + 1) {
+            self.text_wrapper.apply_folds();
+        }
+    }
+
+    fn update_folds(&mut self, f: impl FnOnce(&mut FoldMap) -> bool, cx: &mut Context<Self>) {
+        if !self.mode.is_code_editor() || !f(&mut self.text_wrapper.folds) {
+            return;
+        }
+        self.text_wrapper.apply_folds();
+
+        // Move the cursor out of the folded region.
+        let row = self.text.offset_to_row(self.cursor_offset());
+        if let Some(range) = self.text_wrapper.folds.folded_range_at(row) {
+            let offset = self.text.line_end_offset(range.start);
+            self.selected_range = offset..offset;
+            self.selection_reversed = false;
+        }
+        cx.notify();
+    }
+
+    /// Update the foldable ranges by the syntax tree of the code editor.
+    pub(super) fn update_fold_ranges(&mut self) {
+        if let Some(ranges) = self.mode.fold_ranges() {
+            self.text_wrapper.set_fold_ranges(ranges);
+        }
+    }
+
+    /// Return the `offset` that out of the folded regions, the cursor can not move into them.
+    ///
+    /// If the offset is after the cursor, return the start of the line after the region,
+    /// otherwise return the end of the first line of the region.
+    pub(super) fn offset_out_of_folds(&self, offset: usize) -> usize {
+        let row = self.text.offset_to_row(offset);
+        let Some(range) = self.text_wrapper.folds.folded_range_at(row) else {
+            return offset;
+        };
+
+        if offset > self.cursor_offset() && range.end + 1 < self.text.lines_len() {
+            self.text.line_start_offset(range.end + 1)
+        } else {
+            self.text.line_end_offset(range.start)
+        }
+    }
+
+    /// Return the row of the fold marker at the mouse `position` in the line number gutter.
+    pub(super) fn fold_marker_row_for_position(&self, position: Point<Pixels>) -> Option<usize> {
+        if !self.mode.line_number() {
+            return None;
+        }
+
+        let bounds = self.last_bounds.as_ref()?;
+        let last_layout = self.last_layout.as_ref()?;
+        let line_height = last_layout.line_height;
+
+        let marker_right = self.line_number_width - LINE_NUMBER_MARGIN_RIGHT;
+        let pos = position - bounds.origin;
+        if pos.x < marker_right - FOLD_MARKER_WIDTH || pos.x >= marker_right {
+            return None;
+        }
+
+        let mut line_top = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let line_bottom = line_top + line.size(line_height).height;
+            // Only the first line of the soft wrapped lines has the marker.
+            if pos.y >= line_top && pos.y < line_top + line_height {
+                let row = last_layout.visible_rows[ix];
+                return self.text_wrapper.folds.range_at(row).map(|_| row);
+            }
+            line_top = line_bottom;
+        }
+
+        None
+    }
+}
+
+/// Build the chevron path of the fold marker, pointing right if the region is folded,
+/// otherwise pointing down.
+pub(super) fn fold_marker_path(center: Point<Pixels>, folded: bool) -> Option<gpui::Path<Pixels>> {
+    let size = px(3.5);
+    let mut builder = gpui::PathBuilder::stroke(px(1.5));
+    if folded {
+        builder.move_to(point(center.x - size / 2., center.y - size));
+        builder.line_to(point(center.x + size / 2., center.y));
+        builder.line_to(point(center.x - size / 2., center.y + size));
+    } else {
+        builder.move_to(point(center.x - size, center.y - size / 2.));
+        builder.line_to(point(center.x, center.y + size / 2.));
+        builder.line_to(point(center.x + size, center.y - size / 2.));
+    }
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FoldMap;
+
+    fn fold_map() -> FoldMap {
+        let mut fold_map = FoldMap::default();
+        // 0 fn foo() {
+        // 1     if true {
+        // 2         bar();
+        // 3     }
+        // 4 }
+        // 5
+        // 6 fn bar() {
+        // 7 }
+        fold_map.set_ranges(vec![6..7, 0..4, 1..3, 0..4, 1..2, 5..5]);
+        fold_map
+    }
+
+    #[test]
+    fn test_set_ranges() {
+        let mut fold_map = fold_map();
+        assert_eq!(fold_map.ranges(), &[0..4, 1..3, 6..7]);
+        assert_eq!(fold_map.range_at(1), Some(&(1..3)));
+        assert_eq!(fold_map.range_at(2), None);
+
+        assert!(fold_map.toggle(1));
+        assert!(fold_map.toggle(6));
+        fold_map.set_ranges(vec![0..4, 1..2]);
+        assert_eq!(fold_map.folded(), &[1..2]);
+    }
+
+    #[test]
+    fn test_fold_and_unfold() {
+        let mut fold_map = fold_map();
+        assert!(fold_map.fold(2));
+        assert_eq!(fold_map.folded(), &[1..3]);
+        assert!(fold_map.is_folded(1));
+        assert_eq!(fold_map.folded_range_at(2), Some(&(1..3)));
+        assert_eq!(fold_map.folded_range_at(1), None);
+
+        // Fold the outer one, which the inner one is already folded.
+        assert!(fold_map.fold(1));
+        assert_eq!(fold_map.folded(), &[0..4, 1..3]);
+        assert!(!fold_map.fold(1));
+        assert!(!fold_map.fold(5));
+        assert_eq!(fold_map.folded_range_at(2), Some(&(0..4)));
+        assert_eq!(fold_map.hidden_rows(), vec![1..5]);
+
+        assert!(fold_map.unfold(0));
+        assert_eq!(fold_map.folded(), &[1..3]);
+        assert!(fold_map.unfold(2));
+        assert!(!fold_map.unfold(2));
+
+        assert!(fold_map.fold_all());
+        assert_eq!(fold_map.hidden_rows(), vec![1..5, 7..8]);
+        assert_eq!(fold_map.folded_range_at(3), Some(&(0..4)));
+        assert_eq!(fold_map.folded_range_at(5), None);
+        assert_eq!(fold_map.folded_range_at(6), None);
+        assert_eq!(fold_map.folded_range_at(7), Some(&(6..7)));
+        assert!(fold_map.unfold_rows(7..7));
+        assert_eq!(fold_map.folded(), &[0..4, 1..3]);
+        assert!(fold_map.unfold_all());
+        assert!(!fold_map.unfold_all());
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut fold_map = fold_map();
+        fold_map.fold_all();
+
+        // Insert a line at the top.
+        fold_map.edit(0, 0, 1);
+        assert_eq!(fold_map.folded(), &[1..5, 2..4, 7..8]);
+        assert!(fold_map.is_folded(2));
+        assert_eq!(fold_map.folded_range_at(3), Some(&(1..5)));
+
+        // Edit in the header line.
+        fold_map.edit(7, 7, 7);
+        assert_eq!(fold_map.folded(), &[1..5, 2..4, 7..8]);
+
+        // Edit after the folded ranges.
+        fold_map.edit(6, 6, 6);
+        assert_eq!(fold_map.folded(), &[1..5, 2..4, 7..8]);
+
+        // Delete the hidden line.
+        fold_map.edit(3, 4, 3);
+        assert_eq!(fold_map.folded(), &[6..7]);
+        assert_eq!(fold_map.ranges(), &[6..7]);
+    }
+}
@@ -0,0 +1,276 @@
+//! The decorations of the lines in the code editor, e.g.: breakpoints, git changes and blame.
+//!
+//! The lines are zero based, and they are moved with the text when the buffer is edited.
+use std::{ops::Range, rc::Rc};
+
+use gpui::{px, App, Context, Hsla, Pixels, Point, SharedString, Window};
+
+use crate::IconName;
+
+use super::{InputState, RopeExt as _};
+
+/// The width of the icons area in the left of the line number gutter.
+pub(super) const GUTTER_ICON_WIDTH: Pixels = px(18.);
+/// The size of the icon in the gutter.
+pub(super) const GUTTER_ICON_SIZE: Pixels = px(12.);
+
+/// The status of a changed line, painted as a colored bar in the gutter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStatus {
+    Added,
+    Modified,
+    /// The lines are deleted before this line.
+    Deleted,
+}
+
+/// A decoration of a line in the line number gutter of the code editor.
+///
+/// For example, a breakpoint icon, or a bar to show the line is added or modified.
+#[derive(Clone)]
+pub struct GutterDecoration {
+    /// The line (zero based) of the decoration.
+    pub line: usize,
+    /// The svg path of the icon in the Assets bundle, e.g.: `icons/circle-x.svg`.
+    pub icon: Option<SharedString>,
+    /// The color of the icon, default is the foreground color.
+    pub icon_color: Option<Hsla>,
+    /// The status of the line to paint the bar.
+    pub status: Option<LineStatus>,
+    pub(super) on_click: Option<Rc<dyn Fn(usize, &mut Window, &mut App)>>,
+}
+
+impl GutterDecoration {
+    pub fn new(line: usize) -> Self {
+        Self {
+            line,
+            icon: None,
+            icon_color: None,
+            status: None,
+            on_click: None,
+        }
+    }
+
+    /// Set the icon of the decoration.
+    pub fn icon(mut self, icon: IconName) -> Self {
+        self.icon = Some(icon.path());
+        self
+    }
+
+    /// Set the color of the icon.
+    pub fn icon_color(mut self, color: impl Into<Hsla>) -> Self {
+        self.icon_color = Some(color.into());
+        self
+    }
+
+    /// Set the status of the line.
+    pub fn status(mut self, status: LineStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Set the handler when the icon is clicked, the first argument is the current line of the decoration.
+    pub fn on_click(mut self, handler: impl Fn(usize, &mut Window, &mut App) + 'static) -> Self {
+        self.on_click = Some(Rc::new(handler));
+        self
+    }
+}
+
+/// An annotation displayed after the end of a line, e.g.: the git blame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineAnnotation {
+    /// The line (zero based) of the annotation.
+    pub line: usize,
+    pub text: SharedString,
+    /// The text color, default is the muted foreground color.
+    pub color: Option<Hsla>,
+}
+
+impl LineAnnotation {
+    pub fn new(line: usize, text: impl Into<SharedString>) -> Self {
+        Self {
+            line,
+            text: text.into(),
+            color: None,
+        }
+    }
+
+    /// Set the text color of the annotation.
+    pub fn color(mut self, color: impl Into<Hsla>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+}
+
+/// Return the new line of the `line` after the rows `start_row..=old_end_row` (in the old text)
+/// were replaced by the rows `start_row..=new_end_row`.
+///
+/// The `from_line_start` is true if the edit starts at the beginning of the `start_row`.
+///
+/// - The text before the edit in the `start_row` is kept, so the line is not moved.
+/// - The text after the edit in the `old_end_row` is moved to the `new_end_row`.
+/// - Other lines in the edit are removed, returns `None`.
+pub(super) fn line_after_edit(
+    line: usize,
+    start_row: usize,
+    old_end_row: usize,
+    new_end_row: usize,
+    from_line_start: bool,
+) -> Option<usize> {
+    if line < start_row || (line == start_row && !from_line_start) {
+        Some(line)
+    } else if line == old_end_row {
+        Some(new_end_row)
+    } else if line > old_end_row {
+        Some(line + new_end_row - old_end_row)
+    } else {
+        None
+    }
+}
+
+impl InputState {
+    /// Set the gutter decorations, only for [`InputMode::CodeEditor`] mode with line number.
+    ///
+    /// The icons area is added to the gutter when there is any decoration.
+    ///
+    /// [`InputMode::CodeEditor`]: super::mode::InputMode::CodeEditor
+    pub fn set_gutter_decorations(
+        &mut self,
+        decorations: Vec<GutterDecoration>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.gutter_decorations = decorations;
+        cx.notify();
+    }
+
+    /// Return the gutter decorations, the lines are updated when the text is edited.
+    pub fn gutter_decorations(&self) -> &[GutterDecoration] {
+        &self.gutter_decorations
+    }
+
+    /// Set the annotations displayed after the end of lines, only for [`InputMode::CodeEditor`] mode.
+    ///
+    /// [`InputMode::CodeEditor`]: super::mode::InputMode::CodeEditor
+    pub fn set_line_annotations(
+        &mut self,
+        annotations: Vec<LineAnnotation>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.line_annotations = annotations;
+        cx.notify();
+    }
+
+    /// Return the line annotations, the lines are updated when the text is edited.
+    pub fn line_annotations(&self) -> &[LineAnnotation] {
+        &self.line_annotations
+    }
+
+    /// Move the lines of the decorations and annotations before the `range` (in the current text)
+    /// is replaced by the `new_text`.
+    pub(super) fn update_decorations_for_edit(&mut self, range: &Range<usize>, new_text: &str) {
+        if self.gutter_decorations.is_empty() && self.line_annotations.is_empty() {
+            return;
+        }
+
+        let start_row = self.text.offset_to_row(range.start);
+        let old_end_row = self.text.offset_to_row(range.end);
+        let new_end_row = start_row + new_text.matches('\n').count();
+        let from_line_start = self.text.line_start_offset(start_row) == range.start;
+        let update_line = |line: &mut usize| {
+            line_after_edit(*line, start_row, old_end_row, new_end_row, from_line_start)
+                .map(|new_line| *line = new_line)
+                .is_some()
+        };
+
+        self.gutter_decorations
+            .retain_mut(|decoration| update_line(&mut decoration.line));
+        self.line_annotations
+            .retain_mut(|annotation| update_line(&mut annotation.line));
+    }
+
+    /// Return the clickable decoration in the icons area of the gutter at the mouse `position`.
+    pub(super) fn gutter_decoration_for_position(
+        &self,
+        position: Point<Pixels>,
+    ) -> Option<&GutterDecoration> {
+        if !self.mode.line_number() || self.gutter_decorations.is_empty() {
+            return None;
+        }
+
+        let bounds = self.last_bounds.as_ref()?;
+        let last_layout = self.last_layout.as_ref()?;
+        let line_height = last_layout.line_height;
+
+        let pos = position - bounds.origin;
+        if pos.x < px(0.) || pos.x >= GUTTER_ICON_WIDTH {
+            return None;
+        }
+
+        let mut line_top = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let line_bottom = line_top + line.size(line_height).height;
+            if pos.y >= line_top && pos.y < line_bottom {
+                let row = last_layout.visible_rows[ix];
+                return self
+                    .gutter_decorations
+                    .iter()
+                    .find(|decoration| decoration.line == row && decoration.on_click.is_some());
+            }
+            line_top = line_bottom;
+        }
+
+        None
+    }
+
+    /// Call the click handler of the decoration at the mouse `position`, returns true if clicked.
+    pub(super) fn click_gutter_decoration(
+        &mut self,
+        position: Point<Pixels>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(decoration) = self.gutter_decoration_for_position(position) else {
+            return false;
+        };
+        let Some(on_click) = decoration.on_click.clone() else {
+            return false;
+        };
+
+        // Defer to let the handler able to update the state.
+        let line = decoration.line;
+        window.defer(cx, move |window, cx| on_click(line, window, cx));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::line_after_edit;
+
+    #[test]
+    fn test_line_after_edit() {
+        // Typing in the line 2.
+        assert_eq!(line_after_edit(1, 2, 2, 2, false), Some(1));
+        assert_eq!(line_after_edit(2, 2, 2, 2, false), Some(2));
+        assert_eq!(line_after_edit(3, 2, 2, 2, false), Some(3));
+
+        // Insert a newline at the start of the line 2.
+        assert_eq!(line_after_edit(2, 2, 2, 3, true), Some(3));
+        assert_eq!(line_after_edit(5, 2, 2, 3, true), Some(6));
+        // Insert a newline in the middle of the line 2.
+        assert_eq!(line_after_edit(2, 2, 2, 3, false), Some(2));
+        assert_eq!(line_after_edit(3, 2, 2, 3, false), Some(4));
+
+        // Delete the line 2 (from the start of the line 2 to the start of the line 3).
+        assert_eq!(line_after_edit(2, 2, 3, 2, true), None);
+        assert_eq!(line_after_edit(3, 2, 3, 2, true), Some(2));
+        assert_eq!(line_after_edit(6, 2, 3, 2, true), Some(5));
+
+        // Delete the lines 2..=4 to join the line 1 and 5.
+        assert_eq!(line_after_edit(1, 1, 5, 1, false), Some(1));
+        assert_eq!(line_after_edit(3, 1, 5, 1, false), None);
+        assert_eq!(line_after_edit(5, 1, 5, 1, false), Some(1));
+        assert_eq!(line_after_edit(7, 1, 5, 1, false), Some(3));
+    }
+}
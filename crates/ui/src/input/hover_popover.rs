@@ -1,30 +1,90 @@
-use std::rc::Rc;
+use std::{ops::Range, rc::Rc, time::Duration};
 
+use anyhow::Result;
 use gpui::{
-    canvas, deferred, div, px, App, AppContext as _, Bounds, Context, Empty, Entity,
-    InteractiveElement, IntoElement, ParentElement as _, Pixels, Point, Render, Styled, Window,
+    canvas, deferred, div, prelude::FluentBuilder as _, px, App, AppContext as _, Bounds, Context,
+    Empty, Entity, InteractiveElement, IntoElement, ParentElement as _, Pixels, Point, Render,
+    SharedString, Styled, Task, Window,
 };
+use ropey::Rope;
 
 use crate::{
     highlighter::LanguageRegistry,
-    input::{InputState, Marker},
+    input::{
+        completion::{is_word_char, word_start},
+        InputState, Marker,
+    },
     text::TextView,
     ActiveTheme as _, StyledExt as _,
 };
 
-pub struct DiagnosticPopover {
+/// The delay to request the hover after the mouse is moved.
+const HOVER_DELAY: Duration = Duration::from_millis(300);
+
+/// The hover content returned by the [`HoverProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hover {
+    /// The content in Markdown.
+    pub contents: SharedString,
+    /// The byte range of the text that the hover is for, e.g.: the hovered word.
+    ///
+    /// The popover is displayed above the start of the range, default is the hovered offset.
+    pub range: Option<Range<usize>>,
+}
+
+impl Hover {
+    pub fn new(contents: impl Into<SharedString>) -> Self {
+        Self {
+            contents: contents.into(),
+            range: None,
+        }
+    }
+
+    pub fn range(mut self, range: Range<usize>) -> Self {
+        self.range = Some(range);
+        self
+    }
+}
+
+/// A provider to offer the hover content for any position of the [`InputState`].
+///
+/// Set by [`InputState::hover_provider`].
+pub trait HoverProvider {
+    /// Fetch the hover content at the `offset` of the `text`, return `None` if there is nothing to show.
+    fn hover(
+        &self,
+        text: &Rope,
+        offset: usize,
+        window: &mut Window,
+        cx: &mut Context<InputState>,
+    ) -> Task<Result<Option<Hover>>>;
+}
+
+/// The popover to show the diagnostic message of a [`Marker`] and the content of the [`Hover`].
+pub struct HoverPopover {
     state: Entity<InputState>,
-    pub(super) marker: Rc<Marker>,
+    /// The range of the text that the popover is for.
+    pub(super) range: Range<usize>,
+    marker: Option<Rc<Marker>>,
+    hover: Option<Hover>,
     bounds: Bounds<Pixels>,
     open: bool,
 }
 
-impl DiagnosticPopover {
-    pub fn new(marker: &Marker, state: Entity<InputState>, cx: &mut App) -> Entity<Self> {
-        let marker = Rc::new(marker.clone());
+impl HoverPopover {
+    pub fn new(
+        range: Range<usize>,
+        marker: Option<&Marker>,
+        hover: Option<Hover>,
+        state: Entity<InputState>,
+        cx: &mut App,
+    ) -> Entity<Self> {
+        let marker = marker.map(|marker| Rc::new(marker.clone()));
 
         cx.new(|_| Self {
+            range,
             marker,
+            hover,
             state,
             bounds: Bounds::default(),
             open: true,
@@ -32,15 +92,12 @@ impl DiagnosticPopover {
     }
 
     fn origin(&self, cx: &App) -> Option<Point<Pixels>> {
-        let Some(range) = self.marker.range.as_ref() else {
-            return None;
-        };
         let line_number_width = self.state.read(cx).line_number_width;
 
         let (_, _, start_pos) = self
             .state
             .read(cx)
-            .line_and_position_for_offset(range.start);
+            .line_and_position_for_offset(self.range.start);
 
         start_pos.map(|pos| pos + Point::new(line_number_width, px(0.)))
     }
@@ -55,24 +112,23 @@ impl DiagnosticPopover {
         cx.notify();
     }
 
-    pub(super) fn check_to_hide(&mut self, mouse_position: Point<Pixels>, cx: &mut Context<Self>) {
-        if !self.open {
-            return;
-        }
+    pub(super) fn is_open(&self) -> bool {
+        self.open
+    }
 
+    /// Return true if the `mouse_position` is in the popover (with a small padding).
+    pub(super) fn contains(&self, mouse_position: Point<Pixels>) -> bool {
         let padding = px(5.);
         let bounds = Bounds {
             origin: self.bounds.origin.map(|v| v - padding),
             size: self.bounds.size.map(|v| v + padding * 2.),
         };
 
-        if !bounds.contains(&mouse_position) {
-            self.hide(cx);
-        }
+        bounds.contains(&mouse_position)
     }
 }
 
-impl Render for DiagnosticPopover {
+impl Render for HoverPopover {
     fn render(&mut self, window: &mut Window, cx: &mut gpui::Context<Self>) -> impl IntoElement {
         if !self.open {
             return Empty.into_any_element();
@@ -81,25 +137,33 @@ impl Render for DiagnosticPopover {
         let view = cx.entity();
         let theme = LanguageRegistry::global(cx).theme(cx.theme().is_dark());
 
-        let message = self.marker.message.clone();
         let Some(pos) = self.origin(cx) else {
             return Empty.into_any_element();
         };
-        let (border, bg, fg) = (
-            self.marker.severity.border(theme),
-            self.marker.severity.bg(theme),
-            self.marker.severity.fg(theme),
-        );
+        // Use the marker colors if there is only the diagnostic message.
+        let (border, bg, fg) = match (&self.marker, &self.hover) {
+            (Some(marker), None) => (
+                marker.severity.border(theme),
+                marker.severity.bg(theme),
+                marker.severity.fg(theme),
+            ),
+            _ => (
+                cx.theme().border,
+                cx.theme().popover,
+                cx.theme().popover_foreground,
+            ),
+        };
 
         let scroll_origin = self.state.read(cx).scroll_handle.offset();
 
         let y = pos.y - self.bounds.size.height + scroll_origin.y;
         let x = pos.x + scroll_origin.x;
         let max_width = px(500.).min(window.bounds().size.width - x);
+        let has_marker = self.marker.is_some();
 
         deferred(
             div()
-                .id("code-editor-diagnostic-popover")
+                .id("code-editor-hover-popover")
                 .absolute()
                 .left(x)
                 .top(y)
@@ -113,7 +177,25 @@ impl Render for DiagnosticPopover {
                 .border_color(border)
                 .rounded(cx.theme().radius)
                 .shadow_xs()
-                .child(TextView::markdown("message", message))
+                .when_some(self.marker.clone(), |this, marker| {
+                    this.child(
+                        div()
+                            .text_color(marker.severity.fg(theme))
+                            .child(TextView::markdown("message", marker.message.clone())),
+                    )
+                })
+                .when_some(self.hover.clone(), |this, hover| {
+                    this.child(
+                        div()
+                            .when(has_marker, |this| {
+                                this.mt_0p5()
+                                    .pt_0p5()
+                                    .border_t_1()
+                                    .border_color(cx.theme().border)
+                            })
+                            .child(TextView::markdown("hover", hover.contents)),
+                    )
+                })
                 .child(
                     canvas(
                         move |bounds, _, cx| view.update(cx, |r, _| r.bounds = bounds),
@@ -125,10 +207,287 @@ impl Render for DiagnosticPopover {
                     .size_full(),
                 )
                 .on_mouse_down_out(cx.listener(|this, _, _, cx| {
-                    this.open = false;
-                    cx.notify();
+                    this.hide(cx);
+                    // Request the hover again when the mouse moves over the word.
+                    this.state.update(cx, |state, _| state.hover_range = None);
                 })),
         )
         .into_any_element()
     }
 }
+
+impl InputState {
+    /// Set the [`HoverProvider`] to offer the hover content.
+    pub fn hover_provider(mut self, provider: impl HoverProvider + 'static) -> Self {
+        self.hover_provider = Some(Rc::new(provider));
+        self
+    }
+
+    /// Set or remove the [`HoverProvider`] of the input.
+    pub fn set_hover_provider(
+        &mut self,
+        provider: Option<Rc<dyn HoverProvider>>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.hover_provider = provider;
+        self.hover_range = None;
+        self.hover_task = Task::ready(());
+        cx.notify();
+    }
+
+    /// Show the popover for the marker at the `offset`.
+    pub(super) fn show_marker_popover(&mut self, offset: usize, cx: &mut Context<Self>) -> bool {
+        let Some(marker) = self.mode.marker_for_offset(offset) else {
+            return false;
+        };
+        let Some(range) = marker.range.clone() else {
+            return false;
+        };
+
+        if let Some(hover_popover) = self.hover_popover.as_ref() {
+            if hover_popover.read(cx).range == range {
+                hover_popover.update(cx, |this, cx| {
+                    this.show(cx);
+                });
+
+                return true;
+            }
+        }
+
+        // The marker popover replaces the hover popover.
+        self.hover_range = None;
+        self.hover_popover = Some(HoverPopover::new(
+            range,
+            Some(marker),
+            None,
+            cx.entity(),
+            cx,
+        ));
+        cx.notify();
+        true
+    }
+
+    /// Hide the popover when the mouse is moved out of it, but keep it open when the mouse
+    /// is in the hovered range (the popover is displayed above the range).
+    pub(super) fn check_to_hide_hover(
+        &mut self,
+        offset: Option<usize>,
+        mouse_position: Point<Pixels>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(hover_popover) = self.hover_popover.clone() else {
+            return;
+        };
+        let in_hover_range = offset.map_or(false, |offset| self.is_in_hover_range(offset));
+        let hover_popover = hover_popover.read(cx);
+        if !hover_popover.is_open() || in_hover_range || hover_popover.contains(mouse_position) {
+            return;
+        }
+
+        self.hide_hover_popover(cx);
+    }
+
+    /// Hide the popover, the hover will be requested again when the mouse is moved over the text.
+    pub(super) fn hide_hover_popover(&mut self, cx: &mut Context<Self>) {
+        self.hover_range = None;
+        if let Some(hover_popover) = self.hover_popover.as_ref() {
+            hover_popover.update(cx, |this, cx| this.hide(cx));
+        }
+    }
+
+    fn is_in_hover_range(&self, offset: usize) -> bool {
+        self.hover_range
+            .as_ref()
+            .map_or(false, |range| range.start <= offset && offset <= range.end)
+    }
+
+    /// Request the hover at the `offset` after a delay, the previous request will be canceled.
+    pub(super) fn request_hover(
+        &mut self,
+        offset: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(provider) = self.hover_provider.clone() else {
+            return;
+        };
+        if self.is_in_hover_range(offset) {
+            return;
+        }
+
+        self.hover_range = Some(word_range_at(&self.text, offset));
+        self.hover_task = cx.spawn_in(window, async move |this, cx| {
+            cx.background_executor().timer(HOVER_DELAY).await;
+
+            let Ok(task) = this.update_in(cx, |this, window, cx| {
+                let text = this.text.clone();
+                provider.hover(&text, offset, window, cx)
+            }) else {
+                return;
+            };
+
+            let hover = match task.await {
+                Ok(Some(hover)) => hover,
+                Ok(None) => return,
+                Err(err) => {
+                    tracing::error!("failed to fetch hover: {}", err);
+                    return;
+                }
+            };
+
+            _ = this.update(cx, |this, cx| {
+                let range = hover.range.clone().unwrap_or(offset..offset);
+                if hover.range.is_some() {
+                    this.hover_range = Some(range.clone());
+                }
+                let marker = this.mode.marker_for_offset(offset).cloned();
+                this.hover_popover = Some(HoverPopover::new(
+                    range,
+                    marker.as_ref(),
+                    Some(hover),
+                    cx.entity(),
+                    cx,
+                ));
+                cx.notify();
+            });
+        });
+    }
+
+    /// Cancel the pending hover request and hide the popover.
+    pub(super) fn hide_hover(&mut self, cx: &mut Context<Self>) {
+        self.hover_range = None;
+        self.hover_task = Task::ready(());
+        if self.hover_popover.take().is_some() {
+            cx.notify();
+        }
+    }
+}
+
+/// Return the range of the word at the `offset`, the empty range at the `offset` if it's not in a word.
+fn word_range_at(text: &Rope, offset: usize) -> Range<usize> {
+    let end = offset
+        + text
+            .chars_at(text.byte_to_char(offset))
+            .take_while(|c| is_word_char(*c))
+            .map(char::len_utf8)
+            .sum::<usize>();
+
+    word_start(text, offset)..end
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, time::Duration};
+
+    use anyhow::{anyhow, Result};
+    use gpui::{Context, Entity, Task, TestAppContext, VisualTestContext, Window};
+    use ropey::Rope;
+
+    use super::{word_range_at, Hover, HoverProvider, HOVER_DELAY};
+    use crate::input::{new_state, InputState};
+
+    #[derive(Clone, Default)]
+    struct TestProvider {
+        calls: Rc<Cell<usize>>,
+        fail: Rc<Cell<bool>>,
+    }
+
+    impl HoverProvider for TestProvider {
+        fn hover(
+            &self,
+            _: &Rope,
+            offset: usize,
+            _: &mut Window,
+            _: &mut Context<InputState>,
+        ) -> Task<Result<Option<Hover>>> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fail.get() {
+                return Task::ready(Err(anyhow!("failed to hover")));
+            }
+
+            Task::ready(Ok(Some(Hover::new(format!("hover at {}", offset)))))
+        }
+    }
+
+    fn request_hover(state: &Entity<InputState>, offset: usize, cx: &mut VisualTestContext) {
+        state.update_in(cx, |state, window, cx| {
+            state.request_hover(offset, window, cx)
+        });
+        cx.executor()
+            .advance_clock(HOVER_DELAY + Duration::from_millis(1));
+        cx.run_until_parked();
+    }
+
+    fn is_popover_open(state: &Entity<InputState>, cx: &mut VisualTestContext) -> bool {
+        state.read_with(cx, |state, cx| {
+            state
+                .hover_popover
+                .as_ref()
+                .map_or(false, |popover| popover.read(cx).is_open())
+        })
+    }
+
+    #[test]
+    fn test_word_range_at() {
+        let text = Rope::from_str("let foo_bar = 1;");
+        assert_eq!(word_range_at(&text, 4), 4..11);
+        assert_eq!(word_range_at(&text, 6), 4..11);
+        assert_eq!(word_range_at(&text, 11), 4..11);
+        assert_eq!(word_range_at(&text, 12), 12..12);
+    }
+
+    #[gpui::test]
+    fn test_hover_dedup_in_word(cx: &mut TestAppContext) {
+        let provider = TestProvider::default();
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .code_editor("text")
+                .hover_provider(provider.clone())
+                .default_value("let foo = 1;")
+        });
+
+        request_hover(&state, 4, cx);
+        assert_eq!(provider.calls.get(), 1);
+        assert!(is_popover_open(&state, cx));
+
+        // Move in the same word.
+        request_hover(&state, 5, cx);
+        request_hover(&state, 7, cx);
+        assert_eq!(provider.calls.get(), 1);
+
+        // Move to another word.
+        request_hover(&state, 10, cx);
+        assert_eq!(provider.calls.get(), 2);
+
+        // Request again in the same word after the popover is hidden.
+        state.update(cx, |state, cx| state.hide_hover_popover(cx));
+        assert!(!is_popover_open(&state, cx));
+        request_hover(&state, 10, cx);
+        assert_eq!(provider.calls.get(), 3);
+        assert!(is_popover_open(&state, cx));
+    }
+
+    #[gpui::test]
+    fn test_hover_provider_error(cx: &mut TestAppContext) {
+        let provider = TestProvider::default();
+        provider.fail.set(true);
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .code_editor("text")
+                .hover_provider(provider.clone())
+                .default_value("let foo = 1;")
+        });
+
+        request_hover(&state, 4, cx);
+        assert_eq!(provider.calls.get(), 1);
+        assert!(!is_popover_open(&state, cx));
+
+        // The hover is requested again after the popover is hidden (e.g.: on edit).
+        provider.fail.set(false);
+        state.update(cx, |state, cx| state.hide_hover(cx));
+        request_hover(&state, 4, cx);
+        assert_eq!(provider.calls.get(), 2);
+        assert!(is_popover_open(&state, cx));
+    }
+}
@@ -0,0 +1,226 @@
+//! The vertical indent guides of the multi-line input, the guide of the scope that contains
+//! the cursor is highlighted.
+//!
+//! The indent levels are counted by the [`super::TabSize`], the blank lines use the smaller
+//! level of the surrounding lines to keep the guides continuous.
+use std::ops::Range;
+
+use gpui::{Context, Window};
+
+use super::{InputState, RopeExt as _, TabSize};
+
+/// Return the indent width (in columns) of the line `chars`, the tab is expanded to the next tab stop.
+///
+/// Returns `None` if the line is blank.
+pub(super) fn indent_columns(chars: impl Iterator<Item = char>, tab_size: usize) -> Option<usize> {
+    let mut cols = 0;
+    for c in chars {
+        match c {
+            ' ' => cols += 1,
+            '\t' => cols += tab_size - cols % tab_size,
+            '\r' | '\n' => return None,
+            _ => return Some(cols),
+        }
+    }
+
+    None
+}
+
+/// Return the byte index of the `col` in the leading whitespaces of the line `chars`.
+///
+/// Returns `None` if the leading whitespaces are shorter than the `col`.
+pub(super) fn index_for_column(
+    chars: impl Iterator<Item = char>,
+    col: usize,
+    tab_size: usize,
+) -> Option<usize> {
+    let mut cols = 0;
+    let mut ix = 0;
+    for c in chars {
+        if cols >= col {
+            return Some(ix);
+        }
+
+        match c {
+            ' ' => cols += 1,
+            '\t' => cols += tab_size - cols % tab_size,
+            _ => return None,
+        }
+        ix += c.len_utf8();
+    }
+
+    (cols >= col).then_some(ix)
+}
+
+/// Return the indent level of the `row`, the blank line use the smaller level of the previous
+/// and the next non-blank lines.
+///
+/// - `total_rows` is the number of rows of the text.
+/// - `level_at` returns the indent level of the row, `None` if the row is blank.
+pub(super) fn indent_level(
+    row: usize,
+    total_rows: usize,
+    level_at: impl Fn(usize) -> Option<usize>,
+) -> usize {
+    if let Some(level) = level_at(row) {
+        return level;
+    }
+
+    let prev = (0..row).rev().find_map(&level_at);
+    let next = (row + 1..total_rows).find_map(&level_at);
+    prev.unwrap_or(0).min(next.unwrap_or(0))
+}
+
+/// Return the active indent guide (the guide index, rows range) of the scope that contains the `row`.
+///
+/// If the next row is more indented, the `row` is the header of the scope below it.
+pub(super) fn active_indent_guide(
+    row: usize,
+    total_rows: usize,
+    level_at: impl Fn(usize) -> usize,
+) -> Option<(usize, Range<usize>)> {
+    let mut level = level_at(row);
+    let mut start = row;
+    if row + 1 < total_rows && level_at(row + 1) > level {
+        level += 1;
+        start = row + 1;
+    }
+    if level == 0 {
+        return None;
+    }
+
+    let mut end = start + 1;
+    while start > 0 && level_at(start - 1) >= level {
+        start -= 1;
+    }
+    while end < total_rows && level_at(end) >= level {
+        end += 1;
+    }
+
+    Some((level - 1, start..end))
+}
+
+impl InputState {
+    /// Set to show the indent guides, only for the multi-line input.
+    ///
+    /// Default: false
+    pub fn indent_guides(mut self, indent_guides: bool) -> Self {
+        self.indent_guides = indent_guides;
+        self
+    }
+
+    /// Set to show the indent guides, only for the multi-line input.
+    pub fn set_indent_guides(
+        &mut self,
+        indent_guides: bool,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.indent_guides = indent_guides;
+        cx.notify();
+    }
+
+    /// Return the indent level of the `row`, `None` if the row is blank.
+    fn indent_level_at(&self, row: usize) -> Option<usize> {
+        let tab_size = self
+            .mode
+            .tab_size()
+            .map_or(TabSize::default().tab_size, |tab| tab.tab_size)
+            .max(1);
+        indent_columns(self.text.slice_line(row).chars(), tab_size).map(|cols| cols / tab_size)
+    }
+
+    /// Return the indent level of the `row`, see [`indent_level`].
+    pub(super) fn indent_level(&self, row: usize) -> usize {
+        indent_level(row, self.text.lines_len(), |row| self.indent_level_at(row))
+    }
+
+    /// Return the active indent guide (the guide index, rows range) of the scope that contains the cursor.
+    pub(super) fn active_indent_guide(&self) -> Option<(usize, Range<usize>)> {
+        let row = self.text.offset_to_row(self.cursor_offset());
+        active_indent_guide(row, self.text.lines_len(), |row| self.indent_level(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indent_columns() {
+        assert_eq!(indent_columns("foo".chars(), 4), Some(0));
+        assert_eq!(indent_columns("    foo".chars(), 4), Some(4));
+        assert_eq!(indent_columns("\tfoo".chars(), 4), Some(4));
+        assert_eq!(indent_columns("  \tfoo".chars(), 4), Some(4));
+        assert_eq!(indent_columns("\t  foo".chars(), 2), Some(4));
+        assert_eq!(indent_columns("".chars(), 4), None);
+        assert_eq!(indent_columns("    ".chars(), 4), None);
+        assert_eq!(indent_columns("  \n".chars(), 4), None);
+    }
+
+    #[test]
+    fn test_index_for_column() {
+        assert_eq!(index_for_column("    foo".chars(), 0, 4), Some(0));
+        assert_eq!(index_for_column("    foo".chars(), 2, 2), Some(2));
+        assert_eq!(index_for_column("    foo".chars(), 4, 4), Some(4));
+        assert_eq!(index_for_column("    foo".chars(), 8, 4), None);
+        assert_eq!(index_for_column("\t\tfoo".chars(), 4, 4), Some(1));
+        assert_eq!(index_for_column("\t\tfoo".chars(), 8, 4), Some(2));
+        assert_eq!(index_for_column("    ".chars(), 4, 4), Some(4));
+        assert_eq!(index_for_column("".chars(), 4, 4), None);
+    }
+
+    #[test]
+    fn test_indent_level() {
+        // 0 def foo():
+        // 1     if a:
+        // 2         b()
+        // 3
+        // 4         c()
+        // 5
+        // 6     d()
+        // 7
+        let levels = [
+            Some(0),
+            Some(1),
+            Some(2),
+            None,
+            Some(2),
+            None,
+            Some(1),
+            None,
+        ];
+        let level_at = |row: usize| levels[row];
+
+        assert_eq!(indent_level(2, levels.len(), level_at), 2);
+        assert_eq!(indent_level(3, levels.len(), level_at), 2);
+        assert_eq!(indent_level(5, levels.len(), level_at), 1);
+        assert_eq!(indent_level(7, levels.len(), level_at), 0);
+
+        let levels = (0..levels.len())
+            .map(|row| indent_level(row, levels.len(), level_at))
+            .collect::<Vec<_>>();
+        let level_at = |row: usize| levels[row];
+        assert_eq!(
+            active_indent_guide(0, levels.len(), level_at),
+            Some((0, 1..7))
+        );
+        assert_eq!(
+            active_indent_guide(1, levels.len(), level_at),
+            Some((1, 2..5))
+        );
+        assert_eq!(
+            active_indent_guide(4, levels.len(), level_at),
+            Some((1, 2..5))
+        );
+        assert_eq!(
+            active_indent_guide(5, levels.len(), level_at),
+            Some((0, 1..7))
+        );
+        assert_eq!(
+            active_indent_guide(6, levels.len(), level_at),
+            Some((0, 1..7))
+        );
+        assert_eq!(active_indent_guide(7, levels.len(), level_at), None);
+    }
+}
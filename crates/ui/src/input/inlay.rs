@@ -0,0 +1,296 @@
+//! The non-editable virtual text displayed inline, e.g.: inlay hints and ghost text.
+//!
+//! The inlays are anchored to the offsets of the text, they are not a part of the text,
+//! so the cursor skips over them, and they are excluded when copying.
+use std::ops::Range;
+
+use gpui::{App, Context, EntityInputHandler as _, SharedString, Window};
+
+use super::{AcceptGhostText, InputState, RopeExt as _};
+
+/// The kind of the [`InlayHint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InlayHintKind {
+    /// The type of a variable, e.g.: `: i32`.
+    #[default]
+    Type,
+    /// The name of a parameter, e.g.: `name: `.
+    Parameter,
+}
+
+/// A hint displayed inline before the text at the `offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlayHint {
+    /// The byte offset of the text to display the hint.
+    pub offset: usize,
+    pub label: SharedString,
+    pub kind: InlayHintKind,
+}
+
+impl InlayHint {
+    pub fn new(offset: usize, label: impl Into<SharedString>) -> Self {
+        Self {
+            offset,
+            label: label.into(),
+            kind: InlayHintKind::default(),
+        }
+    }
+
+    /// Set the kind of the hint, default is [`InlayHintKind::Type`].
+    pub fn kind(mut self, kind: InlayHintKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// The suggested text displayed at the `offset`, it can be accepted to insert into the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GhostText {
+    /// The byte offset of the text to insert the suggestion.
+    pub offset: usize,
+    pub text: SharedString,
+}
+
+/// The style of an inlay to display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum InlayStyle {
+    Hint,
+    Ghost,
+}
+
+/// Return the new offset of the `offset` after the `range` was replaced by the text of `new_len`.
+///
+/// Returns `None` if the offset is inside the replaced range.
+pub(super) fn offset_after_edit(
+    offset: usize,
+    range: &Range<usize>,
+    new_len: usize,
+) -> Option<usize> {
+    if offset < range.start {
+        Some(offset)
+    } else if offset >= range.end {
+        Some(offset + new_len - range.len())
+    } else {
+        None
+    }
+}
+
+/// Return the index in the line layout of the `offset` (relative to the line start),
+/// the `inlays` (offset, len) before the offset are counted.
+pub(super) fn index_for_offset(inlays: &[(usize, usize)], offset: usize) -> usize {
+    offset
+        + inlays
+            .iter()
+            .take_while(|(inlay_offset, _)| *inlay_offset < offset)
+            .map(|(_, len)| len)
+            .sum::<usize>()
+}
+
+/// Return the offset (relative to the line start) of the `index` in the line layout,
+/// the index inside an inlay is moved to the offset of the inlay.
+pub(super) fn offset_for_index(inlays: &[(usize, usize)], index: usize) -> usize {
+    let mut inlays_len = 0;
+    for (offset, len) in inlays {
+        let start = offset + inlays_len;
+        if index < start {
+            break;
+        }
+        if index < start + len {
+            return *offset;
+        }
+        inlays_len += len;
+    }
+
+    index - inlays_len
+}
+
+impl InputState {
+    /// Set the inlay hints, only for [`InputMode::CodeEditor`] mode.
+    ///
+    /// The hints are moved with the text when the text is edited, and the hints inside
+    /// the edited range are removed.
+    ///
+    /// The line breaks in the labels are displayed as spaces.
+    ///
+    /// [`InputMode::CodeEditor`]: super::mode::InputMode::CodeEditor
+    pub fn set_inlay_hints(
+        &mut self,
+        hints: Vec<InlayHint>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let mut hints = hints;
+        for hint in hints.iter_mut() {
+            hint.offset = self.text.clip_offset(hint.offset);
+            // The hint is displayed in one line.
+            if hint.label.contains(['\r', '\n']) {
+                hint.label = hint.label.replace(['\r', '\n'], " ").into();
+            }
+        }
+        hints.sort_by_key(|hint| hint.offset);
+        self.inlay_hints = hints;
+        self.update_wrapped_inlays(cx);
+        cx.notify();
+    }
+
+    /// Return the inlay hints.
+    pub fn inlay_hints(&self) -> &[InlayHint] {
+        &self.inlay_hints
+    }
+
+    /// Set the ghost text at the `offset`, for example an AI suggestion.
+    ///
+    /// Only the first line is displayed, press `tab` to accept the entire text.
+    ///
+    /// The ghost text is cleared when the text is changed or the cursor is moved.
+    pub fn set_ghost_text(
+        &mut self,
+        offset: usize,
+        text: impl Into<SharedString>,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let text: SharedString = text.into();
+        self.ghost_text = (!text.is_empty()).then(|| GhostText {
+            offset: self.text.clip_offset(offset),
+            text,
+        });
+        self.update_wrapped_inlays(cx);
+        cx.notify();
+    }
+
+    /// Return the ghost text.
+    pub fn ghost_text(&self) -> Option<&GhostText> {
+        self.ghost_text.as_ref()
+    }
+
+    /// Clear the ghost text, returns true if there was a ghost text.
+    pub fn clear_ghost_text(&mut self, cx: &mut Context<Self>) -> bool {
+        if self.ghost_text.take().is_some() {
+            self.update_wrapped_inlays(cx);
+            cx.notify();
+            return true;
+        }
+        false
+    }
+
+    pub(super) fn accept_ghost_text(
+        &mut self,
+        _: &AcceptGhostText,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.apply_ghost_text(window, cx);
+    }
+
+    /// Insert the ghost text into the text, returns true if there was a ghost text.
+    pub(super) fn apply_ghost_text(&mut self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(ghost_text) = self.ghost_text.take() else {
+            return false;
+        };
+
+        let offset = ghost_text.offset.min(self.text.len_bytes());
+        let range_utf16 = self.range_to_utf16(&(offset..offset));
+        self.replace_text_in_range(Some(range_utf16), &ghost_text.text, window, cx);
+        self.move_to(offset + ghost_text.text.len(), window, cx);
+        true
+    }
+
+    /// Move the inlay hints before the `range` is replaced by the `new_text`.
+    pub(super) fn update_inlays_for_edit(&mut self, range: &Range<usize>, new_text: &str) {
+        self.ghost_text = None;
+        if self.inlay_hints.is_empty() {
+            return;
+        }
+
+        self.inlay_hints.retain_mut(|hint| {
+            offset_after_edit(hint.offset, range, new_text.len())
+                .map(|offset| hint.offset = offset)
+                .is_some()
+        });
+    }
+
+    /// Update the inlays of the [`TextWrapper`](super::text_wrapper::TextWrapper),
+    /// the inlays are wrapped with the text, so the lines must be re-wrapped when they are changed.
+    pub(super) fn update_wrapped_inlays(&mut self, cx: &mut App) {
+        let inlays = self
+            .inlays_in_range(&(0..self.text.len_bytes()))
+            .into_iter()
+            .map(|(offset, text, _)| (offset, text))
+            .collect();
+        self.text_wrapper.set_inlays(inlays, cx);
+    }
+
+    /// Return the inlays (offset, text, style) in the `range` to display, sorted by the offset.
+    pub(super) fn inlays_in_range(
+        &self,
+        range: &Range<usize>,
+    ) -> Vec<(usize, SharedString, InlayStyle)> {
+        if !self.mode.is_code_editor() || self.masked {
+            return vec![];
+        }
+
+        let start = self
+            .inlay_hints
+            .partition_point(|hint| hint.offset < range.start);
+        let mut inlays: Vec<_> = self.inlay_hints[start..]
+            .iter()
+            .take_while(|hint| hint.offset <= range.end)
+            .map(|hint| (hint.offset, hint.label.clone(), InlayStyle::Hint))
+            .collect();
+
+        if let Some(ghost_text) = &self.ghost_text {
+            if range.start <= ghost_text.offset && ghost_text.offset <= range.end {
+                // Only the first line is displayed.
+                let text = ghost_text.text.lines().next().unwrap_or_default();
+                let text: SharedString = if ghost_text.text.contains('\n') {
+                    format!("{}…", text).into()
+                } else {
+                    text.to_string().into()
+                };
+                let ix = inlays.partition_point(|(offset, _, _)| *offset <= ghost_text.offset);
+                inlays.insert(ix, (ghost_text.offset, text, InlayStyle::Ghost));
+            }
+        }
+
+        inlays
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_after_edit() {
+        assert_eq!(offset_after_edit(2, &(5..5), 3), Some(2));
+        assert_eq!(offset_after_edit(5, &(5..5), 3), Some(8));
+        assert_eq!(offset_after_edit(10, &(5..7), 0), Some(8));
+        assert_eq!(offset_after_edit(6, &(5..7), 0), None);
+        assert_eq!(offset_after_edit(5, &(5..7), 1), None);
+        assert_eq!(offset_after_edit(7, &(5..7), 1), Some(6));
+    }
+
+    #[test]
+    fn test_index_and_offset() {
+        // "let a = foo(1);" with the hints: "let a: i32 = foo(x: 1);"
+        let inlays = vec![(5, 5), (12, 3)];
+
+        assert_eq!(index_for_offset(&inlays, 0), 0);
+        assert_eq!(index_for_offset(&inlays, 5), 5);
+        assert_eq!(index_for_offset(&inlays, 6), 11);
+        assert_eq!(index_for_offset(&inlays, 12), 17);
+        assert_eq!(index_for_offset(&inlays, 13), 21);
+        assert_eq!(index_for_offset(&[], 13), 13);
+
+        assert_eq!(offset_for_index(&inlays, 0), 0);
+        assert_eq!(offset_for_index(&inlays, 5), 5);
+        // Inside the inlay.
+        assert_eq!(offset_for_index(&inlays, 7), 5);
+        assert_eq!(offset_for_index(&inlays, 10), 5);
+        assert_eq!(offset_for_index(&inlays, 11), 6);
+        assert_eq!(offset_for_index(&inlays, 18), 12);
+        assert_eq!(offset_for_index(&inlays, 21), 13);
+        assert_eq!(offset_for_index(&[], 13), 13);
+    }
+}
@@ -0,0 +1,163 @@
+//! The keymap modes of the [`InputState`], the Vim and Emacs key bindings are bound in the
+//! `vim` and `emacs` key contexts, so they only take effect on the editor that enabled them.
+use gpui::{App, Context, KeyBinding, KeyContext, NoAction, Window};
+
+use super::{
+    state::CONTEXT, vim::VimMode, Backspace, Copy, Cut, Delete, DeleteToEndOfLine,
+    DeleteToNextWordEnd, DeleteToPreviousWordStart, Escape, Find, FindPrevious, InputState,
+    MoveDown, MoveEnd, MoveHome, MoveLeft, MovePageDown, MovePageUp, MoveRight, MoveToEnd,
+    MoveToNextWord, MoveToPreviousWord, MoveToStart, MoveUp, Paste, Redo, SelectAll, SelectDown,
+    SelectLeft, SelectRight, SelectToEnd, SelectToEndOfLine, SelectToNextWordEnd,
+    SelectToPreviousWordStart, SelectToStart, SelectToStartOfLine, SelectUp, SetMark, Undo,
+};
+
+const EMACS_CONTEXT: &str = "Input && emacs";
+const EMACS_MARK_CONTEXT: &str = "Input && emacs && emacs_mark";
+const VIM_CONTEXT: &str = "Input && vim";
+const VIM_NORMAL_CONTEXT: &str = "Input && vim && vim_mode != insert";
+
+/// The key bindings mode of the [`InputState`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeymapMode {
+    /// The default key bindings.
+    #[default]
+    Default,
+    /// The modal editing of Vim, start in the normal mode.
+    Vim,
+    /// The Emacs style key bindings, use `ctrl-space` to set the mark.
+    Emacs,
+}
+
+pub(super) fn init(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("ctrl-f", MoveRight, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-b", MoveLeft, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-n", MoveDown, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-p", MoveUp, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-a", MoveHome, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-e", MoveEnd, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt-f", MoveToNextWord, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt-b", MoveToPreviousWord, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt-<", MoveToStart, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt->", MoveToEnd, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-v", MovePageDown, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt-v", MovePageUp, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-d", Delete, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-h", Backspace, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt-d", DeleteToNextWordEnd, Some(EMACS_CONTEXT)),
+        KeyBinding::new(
+            "alt-backspace",
+            DeleteToPreviousWordStart,
+            Some(EMACS_CONTEXT),
+        ),
+        KeyBinding::new("ctrl-k", DeleteToEndOfLine, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-w", Cut, Some(EMACS_CONTEXT)),
+        KeyBinding::new("alt-w", Copy, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-y", Paste, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-/", Undo, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-_", Undo, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-x u", Undo, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-x h", SelectAll, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-g", Escape, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-s", Find, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-r", FindPrevious, Some(EMACS_CONTEXT)),
+        KeyBinding::new("ctrl-space", SetMark, Some(EMACS_CONTEXT)),
+        // Extend the selection from the mark.
+        KeyBinding::new("ctrl-f", SelectRight, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("ctrl-b", SelectLeft, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("ctrl-n", SelectDown, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("ctrl-p", SelectUp, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("ctrl-a", SelectToStartOfLine, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("ctrl-e", SelectToEndOfLine, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("alt-f", SelectToNextWordEnd, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("alt-b", SelectToPreviousWordStart, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("alt-<", SelectToStart, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("alt->", SelectToEnd, Some(EMACS_MARK_CONTEXT)),
+        KeyBinding::new("ctrl-[", Escape, Some(VIM_CONTEXT)),
+        // The other keys of the normal and visual mode are handled by `InputState::handle_vim_key`.
+        KeyBinding::new("enter", MoveDown, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("backspace", MoveLeft, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-r", Redo, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-d", MovePageDown, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-f", MovePageDown, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-u", MovePageUp, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-b", MovePageUp, Some(VIM_NORMAL_CONTEXT)),
+        // Disable the default key bindings that change the text in the normal and visual mode.
+        KeyBinding::new("tab", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("shift-tab", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("secondary-enter", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("cmd-backspace", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("cmd-delete", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("alt-backspace", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-backspace", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("alt-delete", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-delete", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("cmd-]", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-]", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("cmd-[", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("cmd-x", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-x", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("cmd-v", NoAction, Some(VIM_NORMAL_CONTEXT)),
+        KeyBinding::new("ctrl-v", NoAction, Some(VIM_NORMAL_CONTEXT)),
+    ]);
+}
+
+impl InputState {
+    /// Set the key bindings mode.
+    ///
+    /// Default: [`KeymapMode::Default`]
+    pub fn keymap_mode(mut self, keymap: KeymapMode) -> Self {
+        self.keymap = keymap;
+        self.reset_vim();
+        self
+    }
+
+    /// Set the key bindings mode.
+    ///
+    /// The Vim mode is reset to the normal mode.
+    pub fn set_keymap_mode(&mut self, keymap: KeymapMode, _: &mut Window, cx: &mut Context<Self>) {
+        self.keymap = keymap;
+        self.emacs_mark = false;
+        self.reset_vim();
+        cx.notify();
+    }
+
+    /// Return the key bindings mode.
+    pub fn keymap(&self) -> KeymapMode {
+        self.keymap
+    }
+
+    /// Return the key context of the input, with the keymap mode.
+    pub(super) fn key_context(&self) -> KeyContext {
+        let mut context = KeyContext::default();
+        context.add(CONTEXT);
+
+        match self.keymap {
+            KeymapMode::Default => {}
+            KeymapMode::Vim => {
+                context.add("vim");
+                let mode = match self.vim.mode {
+                    VimMode::Normal => "normal",
+                    VimMode::Insert => "insert",
+                    VimMode::Visual | VimMode::VisualLine => "visual",
+                };
+                context.set("vim_mode", mode);
+            }
+            KeymapMode::Emacs => {
+                context.add("emacs");
+                if self.emacs_mark {
+                    context.add("emacs_mark");
+                }
+            }
+        }
+
+        context
+    }
+
+    /// Set the mark to extend the selection by the movement keys, or unset the mark if it was set.
+    pub(super) fn set_mark(&mut self, _: &SetMark, window: &mut Window, cx: &mut Context<Self>) {
+        let offset = self.cursor_offset();
+        self.move_to(offset, window, cx);
+        self.emacs_mark = !self.emacs_mark;
+    }
+}
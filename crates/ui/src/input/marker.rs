@@ -1,6 +1,8 @@
-use crate::{highlighter::HighlightTheme, input::InputState};
+use crate::{
+    highlighter::HighlightTheme,
+    input::{InputState, RopeExt as _},
+};
 use gpui::{px, HighlightStyle, Hsla, SharedString, UnderlineStyle};
-use itertools::Itertools;
 use std::ops::Range;
 
 /// Marker represents a diagnostic message, such as an error or warning, in the code editor.
@@ -33,42 +35,26 @@ impl Marker {
 
     /// Prepare the marker to convert line, column to byte offsets.
     pub(super) fn prepare(&mut self, state: &InputState) {
-        let Some(start_line) = state
-            .text_wrapper
-            .lines
-            .get(self.start.line.saturating_sub(1))
-        else {
+        let text = &state.text;
+        let start_row = self.start.line.saturating_sub(1);
+        let end_row = self.end.line.saturating_sub(1);
+        if start_row >= text.lines_len() || end_row >= text.lines_len() {
             return;
-        };
-
-        let Some(start_line_str) = state.text.get(start_line.range.clone()) else {
-            return;
-        };
-
-        let Some(end_line) = state
-            .text_wrapper
-            .lines
-            .get(self.end.line.saturating_sub(1))
-        else {
-            return;
-        };
-        let Some(end_line_str) = state.text.get(end_line.range.clone()) else {
-            return;
-        };
+        }
 
-        let start_byte = start_line.range.start
-            + start_line_str
+        let start_byte = text.line_start_offset(start_row)
+            + text
+                .slice_line(start_row)
                 .chars()
                 .take(self.start.column.saturating_sub(1))
-                .counts_by(|c| c.len_utf8())
-                .values()
+                .map(|c| c.len_utf8())
                 .sum::<usize>();
-        let end_byte = end_line.range.start
-            + end_line_str
+        let end_byte = text.line_start_offset(end_row)
+            + text
+                .slice_line(end_row)
                 .chars()
                 .take(self.end.column.saturating_sub(1))
-                .counts_by(|c| c.len_utf8())
-                .values()
+                .map(|c| c.len_utf8())
                 .sum::<usize>();
 
         self.range = Some(start_byte..end_byte);
@@ -0,0 +1,325 @@
+//! The minimap of the code editor, a downscaled overview of the text in the right side.
+//!
+//! Each character is displayed as a block of `1px` width, colored by the syntax highlight,
+//! the viewport of the editor is displayed as a rectangle that can be dragged to scroll.
+use std::ops::Range;
+
+use gpui::{point, px, size, App, Bounds, Context, Hsla, Pixels, Point};
+
+use super::{InputState, RopeExt as _};
+use crate::ActiveTheme as _;
+
+/// The width of the minimap.
+pub(super) const MINIMAP_WIDTH: Pixels = px(100.);
+const MINIMAP_LINE_HEIGHT: Pixels = px(2.);
+const MINIMAP_CHAR_WIDTH: Pixels = px(1.);
+const MINIMAP_PADDING: Pixels = px(6.);
+
+/// The size of the editor and the minimap, in rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MinimapMetrics {
+    /// The number of the display rows of the text.
+    total_rows: f32,
+    /// The number of rows can be displayed in the viewport of the editor.
+    viewport_rows: f32,
+    /// The number of rows can be displayed in the minimap.
+    minimap_rows: f32,
+}
+
+impl MinimapMetrics {
+    fn new(total_rows: usize, height: Pixels, line_height: Pixels) -> Self {
+        Self {
+            total_rows: total_rows as f32,
+            viewport_rows: height / line_height,
+            minimap_rows: height / MINIMAP_LINE_HEIGHT,
+        }
+    }
+
+    fn max_scroll_top(&self) -> f32 {
+        (self.total_rows - self.viewport_rows).max(0.)
+    }
+
+    /// Return the row displayed at the top of the minimap, by the `scroll_top` (in rows) of the editor.
+    ///
+    /// If the minimap can not display all rows, it is scrolled proportionally with the editor.
+    fn top_row(&self, scroll_top: f32) -> f32 {
+        let max_scroll_top = self.max_scroll_top();
+        if self.total_rows <= self.minimap_rows || max_scroll_top <= 0. {
+            return 0.;
+        }
+
+        (scroll_top / max_scroll_top).clamp(0., 1.) * (self.total_rows - self.minimap_rows)
+    }
+
+    /// Return the scroll top (in rows) of the editor to center the viewport at the `y`
+    /// (in rows, relative to the minimap top).
+    fn scroll_top_for_y(&self, y: f32) -> f32 {
+        let max_scroll_top = self.max_scroll_top();
+        // The `top_row` is `scroll_top * ratio`.
+        let ratio = if self.total_rows > self.minimap_rows && max_scroll_top > 0. {
+            (self.total_rows - self.minimap_rows) / max_scroll_top
+        } else {
+            0.
+        };
+
+        ((y - self.viewport_rows / 2.) / (1. - ratio).max(f32::EPSILON)).clamp(0., max_scroll_top)
+    }
+}
+
+/// The layout of the minimap to paint.
+pub(super) struct MinimapLayout {
+    pub(super) bounds: Bounds<Pixels>,
+    /// The blocks of the text, (bounds, color).
+    pub(super) blocks: Vec<(Bounds<Pixels>, Hsla)>,
+    /// The bounds of the viewport of the editor.
+    pub(super) viewport: Bounds<Pixels>,
+}
+
+/// Return the bounds of the minimap in the right side of the input `bounds`.
+fn minimap_bounds(bounds: &Bounds<Pixels>) -> Bounds<Pixels> {
+    Bounds::new(
+        point(bounds.right() - MINIMAP_WIDTH, bounds.top()),
+        size(MINIMAP_WIDTH, bounds.size.height),
+    )
+}
+
+impl InputState {
+    /// Return the minimap bounds, if the minimap is enabled.
+    pub(super) fn minimap_bounds(&self) -> Option<Bounds<Pixels>> {
+        if !self.mode.minimap() {
+            return None;
+        }
+
+        Some(minimap_bounds(&self.input_bounds))
+    }
+
+    /// Return the (display row, range in the entire text) of the soft wrapped lines in the `display_rows`.
+    fn display_lines(&self, display_rows: Range<usize>) -> Vec<(usize, Range<usize>)> {
+        let mut lines = vec![];
+        if display_rows.start >= self.text_wrapper.len() {
+            return lines;
+        }
+
+        // Start from the line of the first display row, found by the index of the wrapped lines.
+        let first = self
+            .text_wrapper
+            .display_point_for_display_row(display_rows.start);
+        let mut display_row = first.display_row - first.wrap_row;
+        for (row, line) in self.text_wrapper.lines.iter().enumerate().skip(first.row) {
+            if display_row >= display_rows.end {
+                break;
+            }
+
+            let lines_len = line.lines_len();
+            let line_start = self.text.line_start_offset(row);
+            for (wrap_row, range) in line.wrapped_lines.iter().take(lines_len).enumerate() {
+                if display_rows.contains(&(display_row + wrap_row)) {
+                    lines.push((
+                        display_row + wrap_row,
+                        line_start + range.start..line_start + range.end,
+                    ));
+                }
+            }
+            display_row += lines_len;
+        }
+
+        lines
+    }
+
+    /// Layout the minimap in the right side of the input `bounds` (not scrolled).
+    pub(super) fn layout_minimap(
+        &self,
+        bounds: &Bounds<Pixels>,
+        scroll_top: Pixels,
+        line_height: Pixels,
+        cx: &App,
+    ) -> Option<MinimapLayout> {
+        if !self.mode.minimap() {
+            return None;
+        }
+
+        let bounds = minimap_bounds(bounds);
+        let metrics = MinimapMetrics::new(self.text_wrapper.len(), bounds.size.height, line_height);
+        let scroll_top = scroll_top / line_height;
+        let top_row = metrics.top_row(scroll_top);
+
+        let first_row = top_row.floor() as usize;
+        let lines =
+            self.display_lines(first_row..first_row + metrics.minimap_rows.ceil() as usize + 1);
+        let styles = match (lines.first(), lines.last()) {
+            (Some((_, first)), Some((_, last))) => {
+                self.mode.highlight_styles(&(first.start..last.end), cx)
+            }
+            _ => vec![],
+        };
+
+        let tab_size = self.mode.tab_size().map_or(4, |tab| tab.tab_size).max(1);
+        let max_cols = ((MINIMAP_WIDTH - MINIMAP_PADDING * 2.) / MINIMAP_CHAR_WIDTH) as usize;
+        let foreground = cx.theme().foreground;
+        let mut blocks = vec![];
+        let mut style_ix = 0;
+        for (display_row, range) in lines {
+            let y = bounds.top() + MINIMAP_LINE_HEIGHT * (display_row as f32 - top_row);
+            let mut push_block = |cols: Range<usize>, color: Hsla| {
+                blocks.push((
+                    Bounds::new(
+                        point(
+                            bounds.left()
+                                + MINIMAP_PADDING
+                                + MINIMAP_CHAR_WIDTH * cols.start as f32,
+                            y,
+                        ),
+                        size(
+                            MINIMAP_CHAR_WIDTH * cols.len() as f32,
+                            MINIMAP_LINE_HEIGHT - px(0.5),
+                        ),
+                    ),
+                    color.opacity(0.6),
+                ));
+            };
+
+            // The continuous characters (columns range) with the same color.
+            let mut block: Option<(Range<usize>, Hsla)> = None;
+            let mut col = 0;
+            let mut offset = range.start;
+            for c in self.text.byte_slice(range).chars() {
+                if col >= max_cols {
+                    break;
+                }
+
+                let width = if c == '\t' {
+                    tab_size - col % tab_size
+                } else {
+                    1
+                };
+
+                if c.is_whitespace() {
+                    if let Some((cols, color)) = block.take() {
+                        push_block(cols, color);
+                    }
+                } else {
+                    while styles
+                        .get(style_ix)
+                        .map_or(false, |(range, _)| range.end <= offset)
+                    {
+                        style_ix += 1;
+                    }
+                    let color = styles
+                        .get(style_ix)
+                        .filter(|(range, _)| range.start <= offset)
+                        .and_then(|(_, style)| style.color)
+                        .unwrap_or(foreground);
+
+                    match block.as_mut() {
+                        Some((cols, block_color)) if cols.end == col && *block_color == color => {
+                            cols.end = col + width;
+                        }
+                        _ => {
+                            if let Some((cols, color)) = block.take() {
+                                push_block(cols, color);
+                            }
+                            block = Some((col..col + width, color));
+                        }
+                    }
+                }
+
+                col += width;
+                offset += c.len_utf8();
+            }
+            if let Some((cols, color)) = block.take() {
+                push_block(cols, color);
+            }
+        }
+
+        let viewport = Bounds::new(
+            point(
+                bounds.left(),
+                bounds.top() + MINIMAP_LINE_HEIGHT * (scroll_top - top_row),
+            ),
+            size(
+                bounds.size.width,
+                MINIMAP_LINE_HEIGHT * metrics.viewport_rows.min(metrics.total_rows),
+            ),
+        );
+
+        Some(MinimapLayout {
+            bounds,
+            blocks,
+            viewport,
+        })
+    }
+
+    /// Scroll the editor to center the viewport at the mouse `position` in the minimap.
+    ///
+    /// Returns false if the position is out of the minimap.
+    pub(super) fn scroll_to_minimap_position(
+        &mut self,
+        position: Point<Pixels>,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(bounds) = self.minimap_bounds() else {
+            return false;
+        };
+        let Some(line_height) = self.last_layout.as_ref().map(|layout| layout.line_height) else {
+            return false;
+        };
+        if !self.minimap_dragging && !bounds.contains(&position) {
+            return false;
+        }
+
+        let metrics = MinimapMetrics::new(self.text_wrapper.len(), bounds.size.height, line_height);
+        let y = (position.y - bounds.top()) / MINIMAP_LINE_HEIGHT;
+        let scroll_top = metrics.scroll_top_for_y(y);
+
+        let offset = self.scroll_handle.offset();
+        self.update_scroll_offset(Some(point(offset.x, -line_height * scroll_top)), cx);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MinimapMetrics;
+
+    #[test]
+    fn test_minimap_metrics() {
+        let metrics = MinimapMetrics {
+            total_rows: 1000.,
+            viewport_rows: 40.,
+            minimap_rows: 400.,
+        };
+
+        assert_eq!(metrics.top_row(0.), 0.);
+        assert_eq!(metrics.top_row(960.), 600.);
+        assert_eq!(metrics.top_row(2000.), 600.);
+        assert_eq!(metrics.top_row(480.), 300.);
+
+        // The viewport is centered at the `y` in the minimap.
+        for y in [100., 200., 300.] {
+            let scroll_top = metrics.scroll_top_for_y(y);
+            let center = scroll_top + metrics.viewport_rows / 2. - metrics.top_row(scroll_top);
+            assert!((center - y).abs() < 0.001, "y: {}, center: {}", y, center);
+        }
+        assert_eq!(metrics.scroll_top_for_y(0.), 0.);
+        assert_eq!(metrics.scroll_top_for_y(400.), 960.);
+
+        // The minimap can display all rows.
+        let metrics = MinimapMetrics {
+            total_rows: 100.,
+            viewport_rows: 40.,
+            minimap_rows: 400.,
+        };
+        assert_eq!(metrics.top_row(60.), 0.);
+        assert_eq!(metrics.scroll_top_for_y(50.), 30.);
+        assert_eq!(metrics.scroll_top_for_y(90.), 60.);
+
+        // The text is shorter than the viewport.
+        let metrics = MinimapMetrics {
+            total_rows: 10.,
+            viewport_rows: 40.,
+            minimap_rows: 400.,
+        };
+        assert_eq!(metrics.top_row(0.), 0.);
+        assert_eq!(metrics.scroll_top_for_y(30.), 0.);
+    }
+}
@@ -1,22 +1,65 @@
+mod auto_indent;
 mod blink_cursor;
+mod brackets;
 mod change;
 mod clear_button;
+mod comment;
+mod completion;
+mod diagnostics;
 mod element;
+mod fold_map;
+mod gutter;
 mod hover_popover;
+mod indent_guides;
+mod inlay;
+mod keymap;
 mod marker;
 mod mask_pattern;
+mod minimap;
 mod mode;
 mod number_input;
 mod otp_input;
+mod outline;
+mod rope_ext;
+mod search;
+mod selection;
+mod snippet;
 mod state;
+mod sticky_scroll;
+mod syntax_selection;
 mod text_input;
 mod text_wrapper;
+mod vim;
+mod whitespace;
 
 pub(crate) use clear_button::*;
+pub use completion::{
+    CompletionItem, CompletionItemKind, CompletionProvider, InsertTextFormat, TextEdit,
+};
+pub use diagnostics::DiagnosticsProvider;
+pub use gutter::{GutterDecoration, LineAnnotation, LineStatus};
+pub use hover_popover::{Hover, HoverProvider};
+pub use inlay::{GhostText, InlayHint, InlayHintKind};
+pub use keymap::KeymapMode;
 pub use marker::*;
 pub use mask_pattern::MaskPattern;
 pub use mode::TabSize;
 pub use number_input::{NumberInput, NumberInputEvent, StepAction};
 pub use otp_input::*;
+pub use outline::OutlinePanel;
+pub use rope_ext::RopeExt;
+pub use ropey::Rope;
 pub use state::*;
 pub use text_input::*;
+pub use vim::VimMode;
+pub use whitespace::ShowWhitespace;
+
+/// Create the [`InputState`] built by the `build` in a test window.
+#[cfg(test)]
+fn new_state<'a>(
+    cx: &'a mut gpui::TestAppContext,
+    build: impl FnOnce(InputState) -> InputState,
+) -> (gpui::Entity<InputState>, &'a mut gpui::VisualTestContext) {
+    cx.update(crate::init);
+    cx.add_window_view(|window, cx| build(InputState::new(window, cx)))
+}
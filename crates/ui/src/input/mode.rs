@@ -1,9 +1,18 @@
 use std::rc::Rc;
 use std::{cell::RefCell, ops::Range};
 
-use gpui::{App, DefiniteLength, SharedString};
+use gpui::{App, DefiniteLength, HighlightStyle, SharedString};
+use ropey::Rope;
+use tree_sitter::Tree;
 
-use crate::{highlighter::SyntaxHighlighter, input::marker::Marker};
+use crate::{
+    highlighter::{
+        BracketPair, ExportFormat, LanguageRegistry, OutlineItem, ParseJob, ParsedSyntax, Snippet,
+        SyntaxHighlighter,
+    },
+    input::marker::Marker,
+    ActiveTheme as _,
+};
 
 use super::text_wrapper::TextWrapper;
 
@@ -49,7 +58,13 @@ pub enum InputMode {
         height: Option<DefiniteLength>,
         /// Show line number
         line_number: bool,
+        /// Show the minimap in the right side
+        minimap: bool,
+        /// Pin the headers of the enclosing scopes at the top when scrolling
+        sticky_scroll: bool,
         language: SharedString,
+        /// Detect the language when the text is set or pasted into the empty editor
+        detect_language: bool,
         highlighter: Rc<RefCell<Option<SyntaxHighlighter>>>,
         markers: Rc<Vec<Marker>>,
     },
@@ -97,7 +112,7 @@ impl InputMode {
     }
 
     pub(super) fn update_auto_grow(&mut self, text_wrapper: &TextWrapper) {
-        let wrapped_lines = text_wrapper.wrapped_lines.len();
+        let wrapped_lines = text_wrapper.len();
         self.set_rows(wrapped_lines);
     }
 
@@ -150,6 +165,22 @@ impl InputMode {
         }
     }
 
+    #[inline]
+    pub(super) fn minimap(&self) -> bool {
+        match self {
+            InputMode::CodeEditor { minimap, .. } => *minimap,
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub(super) fn sticky_scroll(&self) -> bool {
+        match self {
+            InputMode::CodeEditor { sticky_scroll, .. } => *sticky_scroll,
+            _ => false,
+        }
+    }
+
     #[inline]
     pub(super) fn tab_size(&self) -> Option<&TabSize> {
         match self {
@@ -159,14 +190,27 @@ impl InputMode {
         }
     }
 
-    pub(super) fn update_highlighter(
+    /// Apply an edit to the highlighter of [`InputMode::CodeEditor`], the text is parsed later
+    /// in the background, see [`InputMode::parse_job`].
+    pub(super) fn edit_highlighter(
         &mut self,
         selected_range: &Range<usize>,
-        full_text: SharedString,
+        full_text: &Rope,
         new_text: &str,
-        cx: &mut App,
     ) {
-        match &self {
+        if let InputMode::CodeEditor { highlighter, .. } = &self {
+            if let Some(highlighter) = highlighter.borrow_mut().as_mut() {
+                highlighter.edit(selected_range, full_text, new_text);
+            }
+        }
+    }
+
+    /// Return a job to parse the text of [`InputMode::CodeEditor`] in the background,
+    /// the highlighter is created if not yet.
+    ///
+    /// Returns `None` if there is nothing to parse or highlight, or the text is being parsed.
+    pub(super) fn parse_job(&self, full_text: &Rope, cx: &App) -> Option<ParseJob> {
+        match self {
             InputMode::CodeEditor {
                 language,
                 highlighter,
@@ -174,15 +218,179 @@ impl InputMode {
             } => {
                 let mut highlighter = highlighter.borrow_mut();
                 if highlighter.is_none() {
-                    let new_highlighter = SyntaxHighlighter::new(language, cx);
+                    let mut new_highlighter = SyntaxHighlighter::new(language, cx);
+                    new_highlighter.set_text(full_text);
                     highlighter.replace(new_highlighter);
                 }
 
-                if let Some(highlighter) = highlighter.as_mut() {
-                    highlighter.update(selected_range, full_text, new_text, cx);
-                }
+                highlighter
+                    .as_mut()
+                    .and_then(|highlighter| highlighter.parse_job())
             }
-            _ => {}
+            _ => None,
+        }
+    }
+
+    /// Apply the result of the background parsing, returns true if the syntax tree or
+    /// the highlight styles have been updated.
+    pub(super) fn apply_parsed(&self, parsed: ParsedSyntax) -> bool {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => highlighter
+                .borrow_mut()
+                .as_mut()
+                .is_some_and(|highlighter| highlighter.apply_parsed(parsed)),
+            _ => false,
+        }
+    }
+
+    /// Return the highlight styles of the `range` (in the entire text) by the current theme,
+    /// only for [`InputMode::CodeEditor`].
+    ///
+    /// The range that is not highlighted yet is requested to highlight in the background,
+    /// see [`InputMode::parse_job`].
+    pub(super) fn highlight_styles(
+        &self,
+        range: &Range<usize>,
+        cx: &App,
+    ) -> Vec<(Range<usize>, HighlightStyle)> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => {
+                let theme = LanguageRegistry::global(cx).theme(cx.theme().is_dark());
+                let mut highlighter = highlighter.borrow_mut();
+                let Some(highlighter) = highlighter.as_mut() else {
+                    return vec![];
+                };
+                highlighter.request_highlight(range.clone());
+                highlighter.styles(range, theme)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Export the `range` of the text with the highlight styles, only for [`InputMode::CodeEditor`].
+    pub(super) fn export(
+        &self,
+        range: Range<usize>,
+        format: ExportFormat,
+        cx: &App,
+    ) -> Option<String> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => {
+                let theme = LanguageRegistry::global(cx).theme(cx.theme().is_dark());
+                let mut highlighter = highlighter.borrow_mut();
+                let highlighter = highlighter.as_mut()?;
+                Some(highlighter.export(range, format, theme))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the symbols of the outline from the syntax tree, only for [`InputMode::CodeEditor`].
+    pub(super) fn outline(&self) -> Vec<OutlineItem> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => highlighter
+                .borrow()
+                .as_ref()
+                .map(|highlighter| highlighter.outline())
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    /// Return the language and the version of the parsed syntax tree, to know when the tree is changed.
+    pub(super) fn syntax_version(&self) -> Option<(SharedString, usize)> {
+        match self {
+            InputMode::CodeEditor {
+                language,
+                highlighter,
+                ..
+            } => highlighter
+                .borrow()
+                .as_ref()
+                .and_then(|highlighter| highlighter.parsed_version())
+                .map(|version| (language.clone(), version)),
+            _ => None,
+        }
+    }
+
+    /// Return the foldable row ranges from the syntax tree, only for [`InputMode::CodeEditor`].
+    pub(super) fn fold_ranges(&self) -> Option<Vec<Range<usize>>> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => highlighter
+                .borrow()
+                .as_ref()
+                .map(|highlighter| highlighter.fold_ranges().to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Return true if the new line should be indented when inserting a newline at the `offset`,
+    /// by the indents query of the language, only for [`InputMode::CodeEditor`].
+    pub(super) fn indent_after(&self, offset: usize) -> Option<bool> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => highlighter
+                .borrow()
+                .as_ref()
+                .and_then(|highlighter| highlighter.indent_after(offset)),
+            _ => None,
+        }
+    }
+
+    /// Return the syntax tree if it is parsed from the current text, only for [`InputMode::CodeEditor`].
+    pub(super) fn syntax_tree(&self) -> Option<Tree> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => highlighter
+                .borrow()
+                .as_ref()
+                .and_then(|highlighter| highlighter.tree().cloned()),
+            _ => None,
+        }
+    }
+
+    /// Return the range of the smallest syntax node that contains the `range` and is larger than it,
+    /// only for [`InputMode::CodeEditor`].
+    pub(super) fn larger_node_range(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        match self {
+            InputMode::CodeEditor { highlighter, .. } => highlighter
+                .borrow()
+                .as_ref()
+                .and_then(|highlighter| highlighter.larger_node_range(range)),
+            _ => None,
+        }
+    }
+
+    /// Return the line comment token and the block comment tokens of the language,
+    /// only for [`InputMode::CodeEditor`].
+    pub(super) fn comment_tokens(
+        &self,
+        cx: &App,
+    ) -> (Option<SharedString>, Option<(SharedString, SharedString)>) {
+        match self {
+            InputMode::CodeEditor { language, .. } => LanguageRegistry::global(cx)
+                .language(language)
+                .map(|config| (config.line_comment.clone(), config.block_comment.clone()))
+                .unwrap_or_default(),
+            _ => (None, None),
+        }
+    }
+
+    /// Return the bracket pairs of the language, only for [`InputMode::CodeEditor`].
+    pub(super) fn brackets<'a>(&self, cx: &'a App) -> &'a [BracketPair] {
+        match self {
+            InputMode::CodeEditor { language, .. } => LanguageRegistry::global(cx)
+                .language(language)
+                .map_or(&[], |config| config.brackets.as_slice()),
+            _ => &[],
+        }
+    }
+
+    /// Return the snippets registered for the language, only for [`InputMode::CodeEditor`].
+    pub(super) fn snippets<'a>(&self, cx: &'a App) -> &'a [Snippet] {
+        match self {
+            InputMode::CodeEditor { language, .. } => {
+                LanguageRegistry::global(cx).snippets(language)
+            }
+            _ => &[],
         }
     }
 
@@ -0,0 +1,194 @@
+//! The outline of the [`InputMode::CodeEditor`](super::mode::InputMode::CodeEditor), and the
+//! [`OutlinePanel`] to show it in the dock.
+use std::ops::Range;
+
+use gpui::{
+    div, prelude::FluentBuilder as _, px, AnyElement, App, Axis, Context, Entity, EventEmitter,
+    FocusHandle, Focusable, InteractiveElement as _, IntoElement, ParentElement as _, Render,
+    SharedString, Styled as _, Subscription, Window,
+};
+use rust_i18n::t;
+
+use crate::{
+    dock::{Panel, PanelEvent},
+    highlighter::OutlineItem,
+    list::ListItem,
+    v_flex, ActiveTheme as _, StyledExt as _,
+};
+
+use super::InputState;
+
+impl InputState {
+    /// Return the symbols of the outline, e.g.: functions, structs, Markdown headings and JSON keys,
+    /// only for the code editor.
+    ///
+    /// The symbols are found by the outline query of the language, see [`OutlineItem`].
+    pub fn outline(&self) -> Vec<OutlineItem> {
+        self.mode.outline()
+    }
+
+    /// Move the cursor to the `range` of a symbol and select it, the folded rows are unfolded.
+    pub(super) fn select_outline_item(&mut self, range: Range<usize>, cx: &mut Context<Self>) {
+        self.unfold_range(&range);
+        self.marked_range = None;
+        self.selection_reversed = false;
+        self.selected_range = range;
+        self.extra_selections.clear();
+        cx.notify();
+    }
+}
+
+/// A panel shows the outline of the bound [`InputState`], it can be added into the dock.
+///
+/// Click a symbol to move the cursor to it, and the symbol contains the cursor is highlighted.
+///
+/// ```ignore
+/// let editor = cx.new(|cx| InputState::new(window, cx).code_editor("rust"));
+/// let outline = cx.new(|cx| OutlinePanel::new(&editor, window, cx));
+/// ```
+pub struct OutlinePanel {
+    focus_handle: FocusHandle,
+    input: Entity<InputState>,
+    items: Vec<OutlineItem>,
+    /// The language and the version of the syntax tree that the `items` is built from.
+    syntax_version: Option<(SharedString, usize)>,
+    /// The range of the innermost symbol contains the cursor.
+    active_range: Option<Range<usize>>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl OutlinePanel {
+    pub fn new(input: &Entity<InputState>, _: &mut Window, cx: &mut Context<Self>) -> Self {
+        let _subscriptions = vec![cx.observe(input, |this, _, cx| this.update_outline(cx))];
+
+        let mut this = Self {
+            focus_handle: cx.focus_handle(),
+            input: input.clone(),
+            items: vec![],
+            syntax_version: None,
+            active_range: None,
+            _subscriptions,
+        };
+        this.update_outline(cx);
+        this
+    }
+
+    /// Rebuild the items when the syntax tree is changed, and update the symbol contains the cursor.
+    ///
+    /// The input is notified on every cursor blink, so only notify when the outline is changed.
+    fn update_outline(&mut self, cx: &mut Context<Self>) {
+        let input = self.input.read(cx);
+        let mut changed = false;
+
+        let syntax_version = input.mode.syntax_version();
+        if syntax_version != self.syntax_version {
+            self.syntax_version = syntax_version;
+            self.items = input.outline();
+            changed = true;
+        }
+
+        let active_range = OutlineItem::path_at(&self.items, input.cursor_offset())
+            .last()
+            .map(|item| item.range.clone());
+        if active_range != self.active_range {
+            self.active_range = active_range;
+            changed = true;
+        }
+
+        if changed {
+            cx.notify();
+        }
+    }
+
+    fn select_item(&mut self, range: Range<usize>, window: &mut Window, cx: &mut Context<Self>) {
+        self.input.update(cx, |input, cx| {
+            input.select_outline_item(range, cx);
+            input.focus(window, cx);
+        });
+    }
+
+    fn render_items(
+        &self,
+        items: &[OutlineItem],
+        depth: usize,
+        rows: &mut Vec<AnyElement>,
+        cx: &mut Context<Self>,
+    ) {
+        for item in items {
+            let ix = rows.len();
+            let active = self.active_range.as_ref() == Some(&item.range);
+            let name_range = item.name_range.clone();
+
+            rows.push(
+                ListItem::new(("outline-item", ix))
+                    .py_0p5()
+                    .pl(px(8.) + px(12.) * depth as f32)
+                    .text_sm()
+                    .selected(active)
+                    .when(active, |this| this.bg(cx.theme().list_active))
+                    .child(
+                        div()
+                            .flex_1()
+                            .overflow_hidden()
+                            .whitespace_nowrap()
+                            .text_ellipsis()
+                            .child(item.name.clone()),
+                    )
+                    .suffix({
+                        let kind = item.kind.clone();
+                        move |_, cx| {
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(kind.clone())
+                        }
+                    })
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.select_item(name_range.clone(), window, cx);
+                    }))
+                    .into_any_element(),
+            );
+
+            self.render_items(&item.children, depth + 1, rows, cx);
+        }
+    }
+}
+
+impl Panel for OutlinePanel {
+    fn panel_name(&self) -> &'static str {
+        "OutlinePanel"
+    }
+
+    fn title(&self, _: &Window, _: &App) -> AnyElement {
+        SharedString::from(t!("Input.Outline")).into_any_element()
+    }
+}
+
+impl EventEmitter<PanelEvent> for OutlinePanel {}
+
+impl Focusable for OutlinePanel {
+    fn focus_handle(&self, _: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for OutlinePanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut rows = vec![];
+        self.render_items(&self.items, 0, &mut rows, cx);
+
+        v_flex()
+            .id("outline-panel")
+            .track_focus(&self.focus_handle)
+            .size_full()
+            .when(rows.is_empty(), |this| {
+                this.p_2()
+                    .text_sm()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(SharedString::from(t!("Input.No symbols")))
+            })
+            .when(!rows.is_empty(), |this| {
+                this.child(v_flex().p_1().children(rows).scrollable(Axis::Vertical))
+            })
+    }
+}
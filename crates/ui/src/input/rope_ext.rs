@@ -0,0 +1,169 @@
+use std::ops::Range;
+
+use ropey::{Rope, RopeSlice};
+
+/// Extension methods for [`Rope`] to work with UTF-8 byte offsets and lines.
+///
+/// All the `offset` in this trait are UTF-8 byte offsets, and `row` is the 0-based line index
+/// (split by `\n`).
+pub trait RopeExt {
+    /// Return the number of lines, an empty text has 1 line.
+    fn lines_len(&self) -> usize;
+
+    /// Return the row (0-based) of the given offset.
+    fn offset_to_row(&self, offset: usize) -> usize;
+
+    /// Return the start offset of the row.
+    fn line_start_offset(&self, row: usize) -> usize;
+
+    /// Return the end offset of the row, the `\n` is not included.
+    fn line_end_offset(&self, row: usize) -> usize;
+
+    /// Return the text of the row, the `\n` is not included.
+    fn slice_line(&self, row: usize) -> RopeSlice<'_>;
+
+    /// Return the text in the range as String.
+    fn text_for_range(&self, range: Range<usize>) -> String;
+
+    /// Return the char at the offset.
+    fn char_at(&self, offset: usize) -> Option<char>;
+
+    /// Clip the offset to the previous char boundary, and clamp it to the text length.
+    fn clip_offset(&self, offset: usize) -> usize;
+
+    /// Convert the UTF-8 offset to UTF-16 offset.
+    fn offset_to_offset_utf16(&self, offset: usize) -> usize;
+
+    /// Convert the UTF-16 offset to UTF-8 offset.
+    fn offset_utf16_to_offset(&self, offset_utf16: usize) -> usize;
+
+    /// Replace the text in the range with the new text.
+    fn replace(&mut self, range: Range<usize>, new_text: &str);
+}
+
+impl RopeExt for Rope {
+    fn lines_len(&self) -> usize {
+        self.len_lines()
+    }
+
+    fn offset_to_row(&self, offset: usize) -> usize {
+        self.byte_to_line(offset.min(self.len_bytes()))
+    }
+
+    fn line_start_offset(&self, row: usize) -> usize {
+        if row >= self.len_lines() {
+            return self.len_bytes();
+        }
+
+        self.line_to_byte(row)
+    }
+
+    fn line_end_offset(&self, row: usize) -> usize {
+        if row + 1 >= self.len_lines() {
+            return self.len_bytes();
+        }
+
+        // -1 for skip the `\n`
+        self.line_to_byte(row + 1).saturating_sub(1)
+    }
+
+    fn slice_line(&self, row: usize) -> RopeSlice<'_> {
+        let start = self.line_start_offset(row);
+        let end = self.line_end_offset(row);
+        self.byte_slice(start..end)
+    }
+
+    fn text_for_range(&self, range: Range<usize>) -> String {
+        let start = self.clip_offset(range.start);
+        let end = self.clip_offset(range.end).max(start);
+        self.byte_slice(start..end).to_string()
+    }
+
+    fn char_at(&self, offset: usize) -> Option<char> {
+        if offset >= self.len_bytes() {
+            return None;
+        }
+
+        self.get_char(self.byte_to_char(offset))
+    }
+
+    fn clip_offset(&self, offset: usize) -> usize {
+        let offset = offset.min(self.len_bytes());
+        self.char_to_byte(self.byte_to_char(offset))
+    }
+
+    fn offset_to_offset_utf16(&self, offset: usize) -> usize {
+        let offset = offset.min(self.len_bytes());
+        self.char_to_utf16_cu(self.byte_to_char(offset))
+    }
+
+    fn offset_utf16_to_offset(&self, offset_utf16: usize) -> usize {
+        let offset_utf16 = offset_utf16.min(self.len_utf16_cu());
+        self.char_to_byte(self.utf16_cu_to_char(offset_utf16))
+    }
+
+    fn replace(&mut self, range: Range<usize>, new_text: &str) {
+        let start = self.byte_to_char(self.clip_offset(range.start));
+        let end = self.byte_to_char(self.clip_offset(range.end)).max(start);
+        self.remove(start..end);
+        self.insert(start, new_text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::RopeExt as _;
+
+    #[test]
+    fn test_lines() {
+        let rope = Rope::from_str("Hello\r\n世界\n\nfoo");
+        assert_eq!(rope.lines_len(), 4);
+        assert_eq!(rope.line_start_offset(1), 7);
+        assert_eq!(rope.line_end_offset(0), 6);
+        assert_eq!(rope.line_end_offset(1), 13);
+        assert_eq!(rope.slice_line(0).to_string(), "Hello\r");
+        assert_eq!(rope.slice_line(1).to_string(), "世界");
+        assert_eq!(rope.slice_line(2).to_string(), "");
+        assert_eq!(rope.slice_line(3).to_string(), "foo");
+        assert_eq!(rope.line_start_offset(10), rope.len_bytes());
+
+        assert_eq!(rope.offset_to_row(0), 0);
+        assert_eq!(rope.offset_to_row(6), 0);
+        assert_eq!(rope.offset_to_row(7), 1);
+        assert_eq!(rope.offset_to_row(100), 3);
+
+        let rope = Rope::from_str("");
+        assert_eq!(rope.lines_len(), 1);
+        assert_eq!(rope.slice_line(0).to_string(), "");
+    }
+
+    #[test]
+    fn test_offset_utf16() {
+        let rope = Rope::from_str("a💝b世");
+        assert_eq!(rope.offset_to_offset_utf16(0), 0);
+        assert_eq!(rope.offset_to_offset_utf16(1), 1);
+        assert_eq!(rope.offset_to_offset_utf16(5), 3);
+        assert_eq!(rope.offset_to_offset_utf16(6), 4);
+        assert_eq!(rope.offset_to_offset_utf16(9), 5);
+        assert_eq!(rope.offset_utf16_to_offset(3), 5);
+        assert_eq!(rope.offset_utf16_to_offset(5), 9);
+        assert_eq!(rope.offset_utf16_to_offset(100), 9);
+        assert_eq!(rope.clip_offset(3), 1);
+        assert_eq!(rope.char_at(1), Some('💝'));
+        assert_eq!(rope.char_at(9), None);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut rope = Rope::from_str("Hello world");
+        rope.replace(6..11, "世界");
+        assert_eq!(rope.to_string(), "Hello 世界");
+        rope.replace(5..5, ",");
+        assert_eq!(rope.to_string(), "Hello, 世界");
+        rope.replace(0..7, "");
+        assert_eq!(rope.to_string(), "世界");
+        assert_eq!(rope.text_for_range(3..6), "界");
+    }
+}
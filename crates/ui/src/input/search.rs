@@ -0,0 +1,958 @@
+//! The find / replace panel for the [`InputMode::CodeEditor`](super::mode::InputMode::CodeEditor).
+use std::{ops::Range, rc::Rc, time::Duration};
+
+use gpui::{
+    div, prelude::FluentBuilder as _, px, App, AppContext as _, Context, Entity,
+    EntityInputHandler as _, InteractiveElement as _, IntoElement, KeyBinding, MouseButton,
+    ParentElement as _, Render, SharedString, Styled as _, Subscription, Task, Timer, WeakEntity,
+    Window,
+};
+use regex::{Regex, RegexBuilder};
+use ropey::Rope;
+use rust_i18n::t;
+
+use crate::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, IconName, Selectable as _, Sizable as _,
+};
+
+use super::{
+    Escape, Find, FindNext, FindPrevious, IndentInline, InputEvent, InputState, MoveDown, MoveUp,
+    OutdentInline, Replace, SelectAll, TextInput,
+};
+
+const CONTEXT: &str = "SearchPanel";
+/// The delay to search again after the editor text is changed.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(100);
+/// The max bytes of the text to search at once, the chunks are split at the line ends.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub(super) fn init(cx: &mut App) {
+    cx.bind_keys([KeyBinding::new("shift-enter", FindPrevious, Some(CONTEXT))]);
+}
+
+/// The options for searching text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct SearchOptions {
+    /// Match with case sensitive, default: false
+    pub(super) case_sensitive: bool,
+    /// Only match the whole word, default: false
+    pub(super) whole_word: bool,
+    /// Use the query as a regular expression, default: false
+    pub(super) regex: bool,
+}
+
+/// A compiled search query.
+#[derive(Debug, Clone)]
+pub(super) struct SearchQuery {
+    regex: Regex,
+    options: SearchOptions,
+}
+
+impl SearchQuery {
+    /// Build the query, returns `None` if the `query` is empty.
+    pub(super) fn new(query: &str, options: SearchOptions) -> Result<Option<Self>, regex::Error> {
+        if query.is_empty() {
+            return Ok(None);
+        }
+
+        let pattern = if options.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .build()?;
+
+        Ok(Some(Self { regex, options }))
+    }
+
+    /// Find all matches in the `text`, the empty matches are ignored.
+    pub(super) fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        let mut matches = vec![];
+        self.find_in(text, 0, &mut matches);
+        matches
+    }
+
+    /// Find all matches in the `text` rope.
+    ///
+    /// A plain query never matches across lines (the search input is single line),
+    /// so the text is searched chunk by chunk without collecting the entire text.
+    pub(super) fn find_all_in_rope(&self, text: &Rope) -> Vec<Range<usize>> {
+        if self.options.regex {
+            return self.find_all(&text.to_string());
+        }
+
+        let mut matches = vec![];
+        let mut chunk = String::new();
+        let mut chunk_start = 0;
+        for line in text.lines() {
+            chunk.extend(line.chunks());
+            if chunk.len() >= CHUNK_SIZE {
+                self.find_in(&chunk, chunk_start, &mut matches);
+                chunk_start += chunk.len();
+                chunk.clear();
+            }
+        }
+        self.find_in(&chunk, chunk_start, &mut matches);
+        matches
+    }
+
+    /// Push the matches in the `text` (start at the `offset` of the entire text) to the `matches`.
+    fn find_in(&self, text: &str, offset: usize, matches: &mut Vec<Range<usize>>) {
+        let mut start = 0;
+        while let Some(m) = self.regex.find_at(text, start) {
+            if !m.is_empty() && (!self.options.whole_word || is_whole_word(text, &m.range())) {
+                matches.push(offset + m.start()..offset + m.end());
+                start = m.end();
+                continue;
+            }
+
+            // Search again from the next char, a shorter match may be a whole word.
+            match text[m.start()..].chars().next() {
+                Some(c) => start = m.start() + c.len_utf8(),
+                None => break,
+            }
+        }
+    }
+
+    /// Return true if the `range` of the `text` is a match of the query.
+    pub(super) fn is_match(&self, text: &str, range: &Range<usize>) -> bool {
+        if range.end > text.len()
+            || !text.is_char_boundary(range.start)
+            || !text.is_char_boundary(range.end)
+        {
+            return false;
+        }
+
+        self.regex.find_at(text, range.start).map_or(false, |m| {
+            m.range() == *range && (!self.options.whole_word || is_whole_word(text, range))
+        })
+    }
+
+    /// Return the text to replace the match at `range` with.
+    ///
+    /// In regex mode, the `$1`, `${name}` in the `replacement` will be expanded by the captures.
+    pub(super) fn replacement_for(
+        &self,
+        text: &str,
+        range: &Range<usize>,
+        replacement: &str,
+    ) -> String {
+        if !self.options.regex {
+            return replacement.to_string();
+        }
+
+        match self.regex.captures_at(text, range.start) {
+            Some(caps) if caps.get(0).map(|m| m.range()).as_ref() == Some(range) => {
+                let mut new_text = String::new();
+                caps.expand(replacement, &mut new_text);
+                new_text
+            }
+            _ => replacement.to_string(),
+        }
+    }
+
+    /// Return the edits to replace all matches in the `text`, one edit for each match.
+    pub(super) fn replace_all(
+        &self,
+        text: &Rope,
+        replacement: &str,
+    ) -> Vec<(Range<usize>, String)> {
+        if !self.options.regex {
+            return self
+                .find_all_in_rope(text)
+                .into_iter()
+                .map(|range| (range, replacement.to_string()))
+                .collect();
+        }
+
+        let text = text.to_string();
+        self.find_all(&text)
+            .into_iter()
+            .map(|range| {
+                let new_text = self.replacement_for(&text, &range, replacement);
+                (range, new_text)
+            })
+            .collect()
+    }
+}
+
+/// Return true if the match at the `range` is not a part of a word.
+///
+/// The edge of the match is a word boundary if the char at either side of it is not a word char,
+/// so the query start or end with a punctuation (e.g.: `->`) can be matched.
+fn is_whole_word(text: &str, range: &Range<usize>) -> bool {
+    fn is_word_char(c: Option<char>) -> bool {
+        c.map_or(false, |c| c.is_alphanumeric() || c == '_')
+    }
+
+    let matched = &text[range.clone()];
+    let start_ok = !is_word_char(text[..range.start].chars().next_back())
+        || !is_word_char(matched.chars().next());
+    let end_ok = !is_word_char(text[range.end..].chars().next())
+        || !is_word_char(matched.chars().next_back());
+    start_ok && end_ok
+}
+
+/// Return the index of the first match that starts at or after the `offset`,
+/// wrap around to the first match.
+fn next_match_index(matches: &[Range<usize>], offset: usize) -> Option<usize> {
+    if matches.is_empty() {
+        return None;
+    }
+
+    let ix = matches.partition_point(|range| range.start < offset);
+    Some(if ix < matches.len() { ix } else { 0 })
+}
+
+/// Return the index of the last match that ends at or before the `offset`,
+/// wrap around to the last match.
+fn previous_match_index(matches: &[Range<usize>], offset: usize) -> Option<usize> {
+    if matches.is_empty() {
+        return None;
+    }
+
+    let ix = matches.partition_point(|range| range.end <= offset);
+    Some(if ix > 0 { ix - 1 } else { matches.len() - 1 })
+}
+
+/// The find / replace panel, displayed at the top right of the code editor.
+pub(super) struct SearchPanel {
+    editor: WeakEntity<InputState>,
+    search_input: Entity<InputState>,
+    replace_input: Entity<InputState>,
+    options: SearchOptions,
+    query: Option<SearchQuery>,
+    /// The ranges of all matches in the editor text.
+    pub(super) matches: Rc<Vec<Range<usize>>>,
+    /// Is the query an invalid regex.
+    invalid: bool,
+    pub(super) open: bool,
+    show_replace: bool,
+    /// Select the first match from the cursor after the pending search is finished.
+    select_after_search: bool,
+    search_task: Task<()>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl SearchPanel {
+    pub(super) fn new(
+        editor: &Entity<InputState>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Self {
+        let search_input = cx.new(|cx| InputState::new(window, cx).placeholder(t!("Input.Find")));
+        let replace_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder(t!("Input.Replace")));
+
+        let _subscriptions = vec![
+            cx.subscribe_in(
+                &search_input,
+                window,
+                |this, _, event, window, cx| match event {
+                    InputEvent::Change => {
+                        this.update_query(cx);
+                        this.refresh_matches(Duration::ZERO, true, window, cx);
+                    }
+                    InputEvent::PressEnter { secondary } => {
+                        if *secondary {
+                            this.select_previous(window, cx);
+                        } else {
+                            this.select_next(window, cx);
+                        }
+                    }
+                    _ => {}
+                },
+            ),
+            cx.subscribe_in(&replace_input, window, |this, _, event, window, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    this.replace_next(window, cx);
+                }
+            }),
+            // Keep the matches in sync with the editor text.
+            cx.subscribe_in(editor, window, |this, _, event, window, cx| {
+                if let InputEvent::Change = event {
+                    // The ranges of the matches are out of date until the search is finished.
+                    if !this.matches.is_empty() {
+                        this.matches = Rc::new(vec![]);
+                        cx.notify();
+                    }
+                    if this.open {
+                        this.refresh_matches(SEARCH_DEBOUNCE, false, window, cx);
+                    }
+                }
+            }),
+        ];
+
+        Self {
+            editor: editor.downgrade(),
+            search_input,
+            replace_input,
+            options: SearchOptions::default(),
+            query: None,
+            matches: Rc::new(vec![]),
+            invalid: false,
+            open: false,
+            show_replace: false,
+            select_after_search: false,
+            search_task: Task::ready(()),
+            _subscriptions,
+        }
+    }
+
+    /// Open the panel and focus the search input.
+    ///
+    /// The `selected_text` (from the editor) will be used as the query if it's not empty.
+    pub(super) fn show(
+        &mut self,
+        replace: bool,
+        selected_text: Option<String>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open = true;
+        if replace {
+            self.show_replace = true;
+        }
+
+        self.search_input.update(cx, |input, cx| {
+            if let Some(selected_text) = selected_text {
+                input.set_value(selected_text, window, cx);
+            }
+            input.select_all(&SelectAll, window, cx);
+            input.focus(window, cx);
+        });
+        self.update_query(cx);
+        self.refresh_matches(Duration::ZERO, false, window, cx);
+        cx.notify();
+    }
+
+    /// Close the panel and focus back to the editor.
+    fn hide(&mut self, _: &Escape, window: &mut Window, cx: &mut Context<Self>) {
+        self.open = false;
+        if let Some(editor) = self.editor.upgrade() {
+            editor.update(cx, |editor, cx| {
+                editor.focus(window, cx);
+                cx.notify();
+            });
+        }
+        cx.notify();
+    }
+
+    fn update_query(&mut self, cx: &mut Context<Self>) {
+        let query = self.search_input.read(cx).value().clone();
+        match SearchQuery::new(&query, self.options) {
+            Ok(query) => {
+                self.query = query;
+                self.invalid = false;
+            }
+            Err(_) => {
+                self.query = None;
+                self.invalid = true;
+            }
+        }
+    }
+
+    /// Search the editor text in the background after the `delay`, and repaint the match
+    /// highlights in the editor, the pending search is canceled.
+    ///
+    /// If `select` is true, the first match from the cursor will be selected after searching.
+    fn refresh_matches(
+        &mut self,
+        delay: Duration,
+        select: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+
+        self.select_after_search |= select;
+        let text = editor.read(cx).text.clone();
+        let query = self.query.clone();
+        self.search_task = cx.spawn_in(window, async move |this, cx| {
+            if !delay.is_zero() {
+                Timer::after(delay).await;
+            }
+
+            let matches = match query {
+                Some(query) => {
+                    cx.background_spawn(async move { query.find_all_in_rope(&text) })
+                        .await
+                }
+                None => vec![],
+            };
+
+            _ = this.update_in(cx, |this, window, cx| {
+                this.matches = Rc::new(matches);
+                if std::mem::take(&mut this.select_after_search) {
+                    this.select_match_from_cursor(window, cx);
+                }
+                if let Some(editor) = this.editor.upgrade() {
+                    editor.update(cx, |_, cx| cx.notify());
+                }
+                cx.notify();
+            });
+        });
+    }
+
+    /// Return the range of the next match after the `selected_range`.
+    pub(super) fn next_match(&self, selected_range: &Range<usize>) -> Option<Range<usize>> {
+        next_match_index(&self.matches, selected_range.end).map(|ix| self.matches[ix].clone())
+    }
+
+    /// Return the range of the previous match before the `selected_range`.
+    pub(super) fn previous_match(&self, selected_range: &Range<usize>) -> Option<Range<usize>> {
+        previous_match_index(&self.matches, selected_range.start).map(|ix| self.matches[ix].clone())
+    }
+
+    fn toggle_option(
+        &mut self,
+        f: impl FnOnce(&mut SearchOptions),
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        f(&mut self.options);
+        self.update_query(cx);
+        self.refresh_matches(Duration::ZERO, true, window, cx);
+    }
+
+    /// Select the first match from the start of the editor selection, used for incremental search.
+    fn select_match_from_cursor(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+
+        let offset = editor.read(cx).selected_range.start;
+        if let Some(ix) = next_match_index(&self.matches, offset) {
+            self.select_range(self.matches[ix].clone(), window, cx);
+        }
+    }
+
+    fn select_next(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+
+        if let Some(range) = self.next_match(&editor.read(cx).selected_range) {
+            self.select_range(range, window, cx);
+        }
+    }
+
+    fn select_previous(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+
+        if let Some(range) = self.previous_match(&editor.read(cx).selected_range) {
+            self.select_range(range, window, cx);
+        }
+    }
+
+    fn select_range(&mut self, range: Range<usize>, _: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+
+        editor.update(cx, |editor, cx| {
+            editor.select_search_match(range, cx);
+        });
+        cx.notify();
+    }
+
+    /// Replace the current match (the selected match in the editor), and select the next match.
+    ///
+    /// If there is no match selected, just select the next match.
+    fn replace_next(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+        let Some(query) = self.query.clone() else {
+            return;
+        };
+
+        let selected_range = editor.read(cx).selected_range.clone();
+        let text = editor.read(cx).text.to_string();
+        if !self.matches.contains(&selected_range) || !query.is_match(&text, &selected_range) {
+            return self.select_next(window, cx);
+        }
+
+        let replacement = self.replace_input.read(cx).value().clone();
+        editor.update(cx, |editor, cx| {
+            let new_text = query.replacement_for(&text, &selected_range, &replacement);
+            let range_utf16 = editor.range_to_utf16(&selected_range);
+            editor.replace_text_in_range(Some(range_utf16), &new_text, window, cx);
+        });
+
+        self.refresh_matches(Duration::ZERO, true, window, cx);
+    }
+
+    /// Replace all matches as a single change, so that it can be undone in one step.
+    fn replace_all(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.editor.upgrade() else {
+            return;
+        };
+        let Some(query) = self.query.clone() else {
+            return;
+        };
+
+        let replacement = self.replace_input.read(cx).value().clone();
+        let text = editor.read(cx).text.clone();
+        let edits = query.replace_all(&text, &replacement);
+        if edits.is_empty() {
+            return;
+        }
+
+        editor.update(cx, |editor, cx| {
+            editor.replace_text_in_ranges(edits, window, cx);
+        });
+
+        self.refresh_matches(Duration::ZERO, false, window, cx);
+    }
+
+    fn render_toggle(
+        &self,
+        id: &'static str,
+        label: &'static str,
+        tooltip: SharedString,
+        selected: bool,
+        f: fn(&mut SearchOptions),
+        cx: &mut Context<Self>,
+    ) -> Button {
+        Button::new(id)
+            .label(label)
+            .xsmall()
+            .ghost()
+            .selected(selected)
+            .tooltip(tooltip)
+            .on_click(cx.listener(move |this, _, window, cx| {
+                this.toggle_option(f, window, cx);
+            }))
+    }
+}
+
+impl Render for SearchPanel {
+    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let selected_range = self
+            .editor
+            .upgrade()
+            .map(|editor| editor.read(cx).selected_range.clone())
+            .unwrap_or_default();
+        let current_ix = self.matches.iter().position(|r| *r == selected_range);
+        let status: SharedString = if self.invalid {
+            t!("Input.Invalid regex").into()
+        } else if self.matches.is_empty() {
+            t!("Input.No results").into()
+        } else {
+            match current_ix {
+                Some(ix) => format!("{} / {}", ix + 1, self.matches.len()),
+                None => format!("{}", self.matches.len()),
+            }
+            .into()
+        };
+        let has_matches = !self.matches.is_empty();
+
+        v_flex()
+            .id("search-panel")
+            .key_context(CONTEXT)
+            .on_action(cx.listener(Self::hide))
+            .on_action(cx.listener(|this, _: &FindPrevious, window, cx| {
+                this.select_previous(window, cx);
+            }))
+            .on_action(cx.listener(|this, _: &MoveUp, window, cx| {
+                this.select_previous(window, cx);
+            }))
+            .on_action(cx.listener(|this, _: &MoveDown, window, cx| {
+                this.select_next(window, cx);
+            }))
+            // Avoid the unhandled actions of the inputs bubble up to the editor.
+            .on_action(|_: &IndentInline, _, _| {})
+            .on_action(|_: &OutdentInline, _, _| {})
+            .occlude()
+            .on_mouse_down(MouseButton::Left, |_, _, cx| cx.stop_propagation())
+            .cursor_default()
+            .absolute()
+            .top_1()
+            .right_4()
+            .w(px(420.))
+            .p_1()
+            .gap_1()
+            .bg(cx.theme().popover)
+            .text_color(cx.theme().popover_foreground)
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded(cx.theme().radius)
+            .shadow_md()
+            .child(
+                h_flex()
+                    .gap_1()
+                    .child(
+                        Button::new("toggle-replace")
+                            .icon(if self.show_replace {
+                                IconName::ChevronDown
+                            } else {
+                                IconName::ChevronRight
+                            })
+                            .xsmall()
+                            .ghost()
+                            .tooltip(t!("Input.Toggle Replace"))
+                            .on_click(cx.listener(|this, _, _, cx| {
+                                this.show_replace = !this.show_replace;
+                                cx.notify();
+                            })),
+                    )
+                    .child(
+                        div().flex_1().child(
+                            TextInput::new(&self.search_input).xsmall().suffix(
+                                h_flex()
+                                    .child(self.render_toggle(
+                                        "case-sensitive",
+                                        "Aa",
+                                        t!("Input.Match Case").into(),
+                                        self.options.case_sensitive,
+                                        |options| options.case_sensitive = !options.case_sensitive,
+                                        cx,
+                                    ))
+                                    .child(self.render_toggle(
+                                        "whole-word",
+                                        "ab",
+                                        t!("Input.Match Whole Word").into(),
+                                        self.options.whole_word,
+                                        |options| options.whole_word = !options.whole_word,
+                                        cx,
+                                    ))
+                                    .child(self.render_toggle(
+                                        "regex",
+                                        ".*",
+                                        t!("Input.Use Regular Expression").into(),
+                                        self.options.regex,
+                                        |options| options.regex = !options.regex,
+                                        cx,
+                                    )),
+                            ),
+                        ),
+                    )
+                    .child(
+                        div()
+                            .w_16()
+                            .text_xs()
+                            .when(self.invalid || !has_matches, |this| {
+                                this.text_color(cx.theme().danger)
+                            })
+                            .child(status),
+                    )
+                    .child(
+                        Button::new("previous")
+                            .icon(IconName::ArrowUp)
+                            .xsmall()
+                            .ghost()
+                            .disabled(!has_matches)
+                            .tooltip(t!("Input.Previous Match"))
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.select_previous(window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("next")
+                            .icon(IconName::ArrowDown)
+                            .xsmall()
+                            .ghost()
+                            .disabled(!has_matches)
+                            .tooltip(t!("Input.Next Match"))
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.select_next(window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("close")
+                            .icon(IconName::Close)
+                            .xsmall()
+                            .ghost()
+                            .tooltip(t!("Input.Close"))
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.hide(&Escape, window, cx);
+                            })),
+                    ),
+            )
+            .when(self.show_replace, |this| {
+                this.child(
+                    h_flex()
+                        .gap_1()
+                        .pl_6()
+                        .child(
+                            div()
+                                .flex_1()
+                                .child(TextInput::new(&self.replace_input).xsmall()),
+                        )
+                        .child(
+                            Button::new("replace")
+                                .label(t!("Input.Replace"))
+                                .xsmall()
+                                .ghost()
+                                .disabled(!has_matches)
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.replace_next(window, cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("replace-all")
+                                .label(t!("Input.Replace All"))
+                                .xsmall()
+                                .ghost()
+                                .disabled(!has_matches)
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.replace_all(window, cx);
+                                })),
+                        ),
+                )
+            })
+    }
+}
+
+impl InputState {
+    pub(super) fn find(&mut self, _: &Find, window: &mut Window, cx: &mut Context<Self>) {
+        self.show_search_panel(false, window, cx);
+    }
+
+    pub(super) fn find_and_replace(
+        &mut self,
+        _: &Replace,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.show_search_panel(true, window, cx);
+    }
+
+    pub(super) fn find_next(&mut self, _: &FindNext, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(search_panel) = self.search_panel.clone() else {
+            return self.show_search_panel(false, window, cx);
+        };
+
+        if let Some(range) = search_panel.read(cx).next_match(&self.selected_range) {
+            self.select_search_match(range, cx);
+        }
+    }
+
+    pub(super) fn find_previous(
+        &mut self,
+        _: &FindPrevious,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(search_panel) = self.search_panel.clone() else {
+            return self.show_search_panel(false, window, cx);
+        };
+
+        if let Some(range) = search_panel.read(cx).previous_match(&self.selected_range) {
+            self.select_search_match(range, cx);
+        }
+    }
+
+    fn show_search_panel(&mut self, replace: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let editor = cx.entity();
+        let search_panel = self
+            .search_panel
+            .get_or_insert_with(|| cx.new(|cx| SearchPanel::new(&editor, window, cx)))
+            .clone();
+
+        let selected_text = if self.selected_range.is_empty() {
+            None
+        } else {
+            Some(self.text_for_range_utf8(self.selected_range.clone()))
+                .filter(|text| !text.contains('\n'))
+        };
+
+        search_panel.update(cx, |panel, cx| {
+            panel.show(replace, selected_text, window, cx);
+        });
+        cx.notify();
+    }
+
+    /// Select the match `range` and scroll it into view.
+    pub(super) fn select_search_match(&mut self, range: Range<usize>, cx: &mut Context<Self>) {
+        // Reveal the match if it is in the folded regions.
+        self.unfold_range(&range);
+        self.marked_range = None;
+        self.selection_reversed = false;
+        self.selected_range = range;
+        self.extra_selections.clear();
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::{next_match_index, previous_match_index, SearchOptions, SearchQuery, CHUNK_SIZE};
+
+    fn find_all<'a>(query: &str, options: SearchOptions, text: &'a str) -> Vec<&'a str> {
+        let query = SearchQuery::new(query, options).unwrap().unwrap();
+        query
+            .find_all(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_search_query() {
+        let text = "let foo = Foo::new();\nlet food = foo.bar();";
+
+        assert!(SearchQuery::new("", SearchOptions::default())
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            find_all("foo", SearchOptions::default(), text),
+            vec!["foo", "Foo", "foo", "foo"]
+        );
+
+        let options = SearchOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        assert_eq!(find_all("foo", options, text), vec!["foo", "foo", "foo"]);
+
+        let options = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        assert_eq!(find_all("foo", options, text), vec!["foo", "Foo", "foo"]);
+        // The query start or end with a non-word char.
+        assert_eq!(find_all("->", options, "a->b -> c--d"), vec!["->", "->"]);
+        assert_eq!(find_all("foo(", options, "foo(a) xfoo(b)"), vec!["foo("]);
+
+        // Not a regex, the special chars are escaped.
+        assert_eq!(
+            find_all("()", SearchOptions::default(), text),
+            vec!["()", "()"]
+        );
+
+        let options = SearchOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_all(r"fo+d?", options, text),
+            vec!["foo", "Foo", "food", "foo"]
+        );
+        assert_eq!(find_all(r"^let", options, text), vec!["let", "let"]);
+        // Empty matches are ignored.
+        assert_eq!(find_all(r"x*", options, text), Vec::<&str>::new());
+
+        assert!(SearchQuery::new("(", options).is_err());
+    }
+
+    #[test]
+    fn test_find_all_in_rope() {
+        let line = "let foo = bar(foo);\n";
+        let text = line.repeat(CHUNK_SIZE / line.len() * 3);
+        let rope = Rope::from_str(&text);
+
+        for options in [
+            SearchOptions::default(),
+            SearchOptions {
+                whole_word: true,
+                ..Default::default()
+            },
+            SearchOptions {
+                regex: true,
+                ..Default::default()
+            },
+        ] {
+            let query = SearchQuery::new("foo", options).unwrap().unwrap();
+            let matches = query.find_all_in_rope(&rope);
+            assert_eq!(matches.len(), text.matches("foo").count());
+            assert_eq!(matches, query.find_all(&text));
+        }
+    }
+
+    #[test]
+    fn test_replace() {
+        let text = "let foo = Foo::new();\nlet food = foo.bar();";
+
+        let query = SearchQuery::new("foo", SearchOptions::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(query.replacement_for(text, &(4..7), "$0bar"), "$0bar");
+        let rope = Rope::from_str(text);
+        assert_eq!(
+            query.replace_all(&rope, "bar"),
+            vec![
+                (4..7, "bar".to_string()),
+                (10..13, "bar".to_string()),
+                (26..29, "bar".to_string()),
+                (33..36, "bar".to_string()),
+            ]
+        );
+
+        let options = SearchOptions {
+            regex: true,
+            whole_word: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new(r"(\w+)::new", options).unwrap().unwrap();
+        assert_eq!(
+            query.replacement_for(text, &(10..18), "${1}::default"),
+            "Foo::default"
+        );
+        assert_eq!(
+            query.replace_all(&rope, "$1::default"),
+            vec![(10..18, "Foo::default".to_string())]
+        );
+
+        let query = SearchQuery::new("bar", options).unwrap().unwrap();
+        assert_eq!(
+            query.replace_all(&rope, "baz"),
+            vec![(37..40, "baz".to_string())]
+        );
+        let query = SearchQuery::new("none", options).unwrap().unwrap();
+        assert_eq!(query.replace_all(&rope, "baz"), vec![]);
+    }
+
+    #[test]
+    fn test_is_match() {
+        let text = "let foo = Foo::new();\nlet food = foo.bar();";
+
+        let query = SearchQuery::new("foo", SearchOptions::default())
+            .unwrap()
+            .unwrap();
+        assert!(query.is_match(text, &(4..7)));
+        assert!(query.is_match(text, &(10..13)));
+        assert!(!query.is_match(text, &(3..6)));
+        assert!(!query.is_match(text, &(4..8)));
+        // The range is out of the text, e.g.: the text has been changed.
+        assert!(!query.is_match(text, &(text.len() - 1..text.len() + 2)));
+        assert!(!query.is_match("foo", &(4..7)));
+
+        let options = SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+        let query = SearchQuery::new("foo", options).unwrap().unwrap();
+        assert!(query.is_match(text, &(4..7)));
+        assert!(!query.is_match(text, &(26..29)));
+    }
+
+    #[test]
+    fn test_match_index() {
+        let matches = vec![0..3, 5..8, 10..13];
+
+        assert_eq!(next_match_index(&matches, 0), Some(0));
+        assert_eq!(next_match_index(&matches, 3), Some(1));
+        assert_eq!(next_match_index(&matches, 8), Some(2));
+        assert_eq!(next_match_index(&matches, 11), Some(0));
+        assert_eq!(next_match_index(&[], 0), None);
+
+        assert_eq!(previous_match_index(&matches, 0), Some(2));
+        assert_eq!(previous_match_index(&matches, 5), Some(0));
+        assert_eq!(previous_match_index(&matches, 10), Some(1));
+        assert_eq!(previous_match_index(&matches, 20), Some(2));
+        assert_eq!(previous_match_index(&[], 0), None);
+    }
+}
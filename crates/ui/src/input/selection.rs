@@ -0,0 +1,222 @@
+//! Helpers for multiple cursors (selections) editing.
+use std::ops::Range;
+
+use ropey::Rope;
+
+use super::RopeExt as _;
+
+/// Sort the selections and merge the overlapped ones.
+///
+/// A cursor (empty selection) at the edge of another selection is merged into it.
+pub(super) fn merge_selections(mut selections: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    selections.sort_by_key(|range| (range.start, range.end));
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(selections.len());
+    for range in selections {
+        if let Some(last) = merged.last_mut() {
+            if range.start < last.end
+                || range.start == last.start
+                || (range.start == last.end && (range.is_empty() || last.start == last.end))
+            {
+                last.end = last.end.max(range.end);
+                continue;
+            }
+        }
+        merged.push(range);
+    }
+
+    merged
+}
+
+/// Return the selections after each range of the `edits` (sorted and not overlapped)
+/// is replaced by its new text.
+///
+/// The selection of each edit is relative to the start of its new text.
+pub(super) fn selections_after_replace(
+    edits: &[(Range<usize>, String, Range<usize>)],
+) -> Vec<Range<usize>> {
+    let mut delta: isize = 0;
+    edits
+        .iter()
+        .map(|(range, new_text, selection)| {
+            let start = (range.start as isize + delta) as usize;
+            delta += new_text.len() as isize - range.len() as isize;
+            start + selection.start..start + selection.end
+        })
+        .collect()
+}
+
+/// Split the pasted `text` into lines if the number of lines equals to the `count` of cursors,
+/// so that each cursor gets one line.
+pub(super) fn split_for_cursors(text: &str, count: usize) -> Option<Vec<String>> {
+    if count < 2 {
+        return None;
+    }
+
+    let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+    (lines.len() == count).then_some(lines)
+}
+
+/// Return the range of the next occurrence of the `query` after the `offset` (wrap around),
+/// the occurrences that already in `selections` are skipped.
+pub(super) fn next_occurrence(
+    text: &str,
+    query: &str,
+    offset: usize,
+    selections: &[Range<usize>],
+) -> Option<Range<usize>> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let offset = offset.min(text.len());
+    let after = text[offset..]
+        .match_indices(query)
+        .map(|(ix, _)| offset + ix);
+    let before = text
+        .match_indices(query)
+        .map(|(ix, _)| ix)
+        .take_while(|ix| *ix < offset);
+
+    after
+        .chain(before)
+        .map(|ix| ix..ix + query.len())
+        .find(|range| !selections.contains(range))
+}
+
+/// Return the (row, column) of the offset, the column is counted by chars.
+fn row_column(text: &Rope, offset: usize) -> (usize, usize) {
+    let row = text.offset_to_row(offset);
+    let line_start = text.line_start_offset(row);
+    let column = text.byte_slice(line_start..offset).len_chars();
+    (row, column)
+}
+
+/// Return the selections of the box (column) selection from the `anchor` to the `head`.
+///
+/// There is one selection for each line, ordered from the anchor line to the head line.
+pub(super) fn box_selections(text: &Rope, anchor: usize, head: usize) -> Vec<Range<usize>> {
+    let (anchor_row, anchor_column) = row_column(text, anchor);
+    let (head_row, head_column) = row_column(text, head);
+    let start_column = anchor_column.min(head_column);
+    let end_column = anchor_column.max(head_column);
+
+    let rows: Vec<usize> = if anchor_row <= head_row {
+        (anchor_row..=head_row).collect()
+    } else {
+        (head_row..=anchor_row).rev().collect()
+    };
+
+    rows.into_iter()
+        .map(|row| {
+            let line_start = text.line_start_offset(row);
+            let line = text.slice_line(row);
+            let len_chars = line.len_chars();
+            let start = line_start + line.char_to_byte(start_column.min(len_chars));
+            let end = line_start + line.char_to_byte(end_column.min(len_chars));
+            start..end
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use super::*;
+
+    #[test]
+    fn test_merge_selections() {
+        assert_eq!(
+            merge_selections(vec![10..12, 0..0, 3..6, 5..8, 0..0]),
+            vec![0..0, 3..8, 10..12]
+        );
+        // The cursor at the edge of a selection.
+        assert_eq!(merge_selections(vec![3..6, 6..6, 2..2]), vec![2..2, 3..6]);
+        // The adjacent selections are kept.
+        assert_eq!(merge_selections(vec![3..6, 6..8]), vec![3..6, 6..8]);
+    }
+
+    /// The edits with the cursor at the end of the new text.
+    fn edits(ranges: &[Range<usize>], new_text: &str) -> Vec<(Range<usize>, String, Range<usize>)> {
+        ranges
+            .iter()
+            .map(|range| {
+                (
+                    range.clone(),
+                    new_text.to_string(),
+                    new_text.len()..new_text.len(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_selections_after_replace() {
+        assert_eq!(
+            selections_after_replace(&edits(&[0..0, 5..5, 9..9], "a")),
+            vec![1..1, 7..7, 12..12]
+        );
+        assert_eq!(
+            selections_after_replace(&edits(&[1..3, 5..6, 9..9], "")),
+            vec![1..1, 3..3, 6..6]
+        );
+        assert_eq!(
+            selections_after_replace(&edits(&[1..3, 5..6], "abc")),
+            vec![4..4, 9..9]
+        );
+        // Wrap the selections, and move over the text without editing.
+        assert_eq!(
+            selections_after_replace(&[
+                (0..1, "(a)".into(), 1..2),
+                (4..4, "".into(), 1..1),
+                (6..8, "[bc]".into(), 1..3),
+            ]),
+            vec![1..2, 7..7, 9..11]
+        );
+    }
+
+    #[test]
+    fn test_split_for_cursors() {
+        assert_eq!(
+            split_for_cursors("foo\nbar\n", 2),
+            Some(vec!["foo".to_string(), "bar".to_string()])
+        );
+        assert_eq!(
+            split_for_cursors("foo\r\n\nbaz", 3),
+            Some(vec!["foo".to_string(), "".to_string(), "baz".to_string()])
+        );
+        assert_eq!(split_for_cursors("foo\nbar", 3), None);
+        assert_eq!(split_for_cursors("foo", 1), None);
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let text = "foo bar foo baz foo";
+        assert_eq!(next_occurrence(text, "foo", 3, &[0..3]), Some(8..11));
+        assert_eq!(
+            next_occurrence(text, "foo", 11, &[0..3, 8..11]),
+            Some(16..19)
+        );
+        assert_eq!(next_occurrence(text, "foo", 19, &[16..19]), Some(0..3));
+        assert_eq!(
+            next_occurrence(text, "foo", 19, &[0..3, 8..11, 16..19]),
+            None
+        );
+        assert_eq!(next_occurrence(text, "", 0, &[]), None);
+    }
+
+    #[test]
+    fn test_box_selections() {
+        let text = Rope::from_str("hello\nhi\n你好世界\nworld");
+
+        // From (0, 1) to (3, 3)
+        assert_eq!(
+            box_selections(&text, 1, 25),
+            vec![1..3, 7..8, 12..18, 23..25]
+        );
+        // From (2, 3) to (0, 1), the order is from the anchor line.
+        assert_eq!(box_selections(&text, 18, 1), vec![12..18, 7..8, 1..3]);
+        assert_eq!(box_selections(&text, 2, 2), vec![2..2]);
+    }
+}
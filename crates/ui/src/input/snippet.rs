@@ -0,0 +1,661 @@
+//! The snippets in the VS Code snippet syntax, e.g.: `for ${1:item} in ${2:iter} {\n\t$0\n}`.
+//!
+//! - `$1`, `${1}`: the tab stops, visited in order by `tab` and `shift-tab`, `$0` is the final one.
+//! - `${1:name}`: the tab stop with a placeholder, the placeholders can be nested.
+//! - `${1|one,two|}`: the tab stop with the choices to select.
+//! - `$NAME`, `${NAME:default}`: the variables, e.g.: `TM_SELECTED_TEXT`.
+//!
+//! The tab stops with the same index are mirrored, they are edited together.
+use std::{collections::BTreeMap, collections::HashMap, ops::Range};
+
+use gpui::{AppContext as _, Context, Window};
+
+use super::{
+    completion::{CompletionItem, CompletionMenu},
+    InputState, RopeExt as _,
+};
+
+/// The tab stop of a parsed snippet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TabStop {
+    /// The ranges of the tab stop and its mirrors.
+    pub(super) ranges: Vec<Range<usize>>,
+    pub(super) choices: Vec<String>,
+}
+
+/// The text and the tab stops of a snippet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ParsedSnippet {
+    pub(super) text: String,
+    /// The tab stops in the visiting order, the final tab stop `$0` is the last one.
+    pub(super) tab_stops: Vec<TabStop>,
+}
+
+#[derive(Debug, Clone)]
+enum Element {
+    Text(String),
+    TabStop {
+        index: usize,
+        placeholder: Vec<Element>,
+        choices: Vec<String>,
+    },
+    /// The unknown variable, replaced by its default.
+    Variable(Vec<Element>),
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    resolve: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Parse the elements until the end, or the `}` if `nested`.
+    fn parse(&mut self, nested: bool) -> Vec<Element> {
+        let mut elements = vec![];
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if nested && c == '}' {
+                break;
+            }
+
+            self.pos += 1;
+            match c {
+                '\\' => match self.peek() {
+                    Some(c @ ('$' | '}' | '\\')) => {
+                        self.pos += 1;
+                        text.push(c);
+                    }
+                    _ => text.push('\\'),
+                },
+                '$' => {
+                    let start = self.pos;
+                    match self.parse_dollar() {
+                        Some(element) => {
+                            if !text.is_empty() {
+                                elements.push(Element::Text(std::mem::take(&mut text)));
+                            }
+                            elements.push(element);
+                        }
+                        None => {
+                            // Not a valid syntax, keep the `$` as text.
+                            self.pos = start;
+                            text.push('$');
+                        }
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            elements.push(Element::Text(text));
+        }
+        elements
+    }
+
+    /// Parse the tab stop or variable after the `$`.
+    fn parse_dollar(&mut self) -> Option<Element> {
+        let braced = self.eat('{');
+
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if !digits.is_empty() {
+            let index = digits.parse().ok()?;
+            let mut placeholder = vec![];
+            let mut choices = vec![];
+            if braced {
+                if self.eat(':') {
+                    placeholder = self.parse(true);
+                } else if self.eat('|') {
+                    choices = self.parse_choices()?;
+                }
+                if !self.eat('}') {
+                    return None;
+                }
+            }
+
+            return Some(Element::TabStop {
+                index,
+                placeholder,
+                choices,
+            });
+        }
+
+        if !self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        {
+            return None;
+        }
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let mut default = vec![];
+        if braced {
+            if self.eat(':') {
+                default = self.parse(true);
+            }
+            if !self.eat('}') {
+                return None;
+            }
+        }
+
+        // The unknown variable is replaced by its default, or the empty text.
+        Some(match (self.resolve)(&name) {
+            Some(value) => Element::Text(value),
+            None => Element::Variable(default),
+        })
+    }
+
+    /// Parse the choices after the `|` until the `|`, the `,` separates the choices.
+    fn parse_choices(&mut self) -> Option<Vec<String>> {
+        let mut choices = vec![];
+        let mut choice = String::new();
+        loop {
+            match self.peek()? {
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(c @ ('$' | '}' | '\\' | ',' | '|')) => {
+                            self.pos += 1;
+                            choice.push(c);
+                        }
+                        _ => choice.push('\\'),
+                    }
+                }
+                ',' => {
+                    self.pos += 1;
+                    choices.push(std::mem::take(&mut choice));
+                }
+                '|' => {
+                    self.pos += 1;
+                    choices.push(choice);
+                    return Some(choices);
+                }
+                c => {
+                    self.pos += 1;
+                    choice.push(c);
+                }
+            }
+        }
+    }
+}
+
+/// Render the `elements` into the `text`, and collect the ranges of the tab stops.
+///
+/// The `placeholders` are the texts of the tab stops, used for the mirrors without placeholder.
+fn render(
+    elements: &[Element],
+    text: &mut String,
+    tab_stops: &mut BTreeMap<usize, TabStop>,
+    placeholders: &mut HashMap<usize, String>,
+) {
+    for element in elements {
+        match element {
+            Element::Text(s) => text.push_str(s),
+            Element::Variable(default) => render(default, text, tab_stops, placeholders),
+            Element::TabStop {
+                index,
+                placeholder,
+                choices,
+            } => {
+                let start = text.len();
+                if let Some(choice) = choices.first() {
+                    text.push_str(choice);
+                } else if !placeholder.is_empty() {
+                    render(placeholder, text, tab_stops, placeholders);
+                } else if let Some(placeholder) = placeholders.get(index) {
+                    text.push_str(placeholder);
+                }
+
+                if start < text.len() {
+                    placeholders
+                        .entry(*index)
+                        .or_insert_with(|| text[start..].to_string());
+                }
+                let tab_stop = tab_stops.entry(*index).or_insert_with(|| TabStop {
+                    ranges: vec![],
+                    choices: vec![],
+                });
+                tab_stop.ranges.push(start..text.len());
+                if tab_stop.choices.is_empty() {
+                    tab_stop.choices = choices.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Parse the `snippet`, the variables are resolved by `resolve`.
+pub(super) fn parse_snippet(
+    snippet: &str,
+    resolve: &dyn Fn(&str) -> Option<String>,
+) -> ParsedSnippet {
+    let mut parser = Parser {
+        chars: snippet.chars().collect(),
+        pos: 0,
+        resolve,
+    };
+    let elements = parser.parse(false);
+
+    // Render twice, so the mirrors before the placeholder can use the placeholder text.
+    let mut placeholders = HashMap::new();
+    render(
+        &elements,
+        &mut String::new(),
+        &mut BTreeMap::new(),
+        &mut placeholders,
+    );
+    let mut text = String::new();
+    let mut tab_stops = BTreeMap::new();
+    render(&elements, &mut text, &mut tab_stops, &mut placeholders);
+
+    let final_tab_stop = tab_stops.remove(&0).unwrap_or_else(|| TabStop {
+        ranges: vec![text.len()..text.len()],
+        choices: vec![],
+    });
+    let mut tab_stops = tab_stops.into_values().collect::<Vec<_>>();
+    tab_stops.push(final_tab_stop);
+
+    ParsedSnippet { text, tab_stops }
+}
+
+/// Indent the lines (except the first one) of the snippet with the `indent`,
+/// and replace the leading tabs of the lines with the `tab`.
+pub(super) fn indent_snippet(snippet: ParsedSnippet, indent: &str, tab: &str) -> ParsedSnippet {
+    let mut text = String::with_capacity(snippet.text.len());
+    // The new offset of each byte offset of the text.
+    let mut offsets = vec![0; snippet.text.len() + 1];
+    let mut line_start = true;
+    for (ix, c) in snippet.text.char_indices() {
+        if c == '\n' {
+            offsets[ix] = text.len();
+            text.push(c);
+            text.push_str(indent);
+            line_start = true;
+            continue;
+        }
+
+        offsets[ix] = text.len();
+        if line_start && c == '\t' {
+            text.push_str(tab);
+        } else {
+            line_start &= c == ' ';
+            text.push(c);
+        }
+        for offset in &mut offsets[ix + 1..ix + c.len_utf8()] {
+            *offset = text.len();
+        }
+    }
+    offsets[snippet.text.len()] = text.len();
+
+    let tab_stops = snippet
+        .tab_stops
+        .into_iter()
+        .map(|tab_stop| TabStop {
+            ranges: tab_stop
+                .ranges
+                .iter()
+                .map(|range| offsets[range.start]..offsets[range.end])
+                .collect(),
+            ..tab_stop
+        })
+        .collect();
+
+    ParsedSnippet { text, tab_stops }
+}
+
+/// Return the new range of the tab stop `range` after the `edit` range was replaced by the text of `new_len`.
+///
+/// - `active` is true if the `range` is a range of the current tab stop, the edit at its boundary is inside it.
+/// - Returns `None` if the range is overlapped (not contains) with the edit, the tab stop is removed.
+pub(super) fn tab_stop_after_edit(
+    range: &Range<usize>,
+    edit: &Range<usize>,
+    new_len: usize,
+    active: bool,
+) -> Option<Range<usize>> {
+    let contains = range.start <= edit.start && edit.end <= range.end;
+    if active && contains {
+        Some(range.start..range.end + new_len - edit.len())
+    } else if edit.end <= range.start {
+        Some(range.start + new_len - edit.len()..range.end + new_len - edit.len())
+    } else if edit.start >= range.end {
+        Some(range.clone())
+    } else if contains {
+        Some(range.start..range.end + new_len - edit.len())
+    } else {
+        None
+    }
+}
+
+/// The snippet in editing, to move between the tab stops.
+pub(super) struct SnippetState {
+    /// The tab stops in the visiting order, with the ranges in the entire text.
+    tab_stops: Vec<TabStop>,
+    active_ix: usize,
+}
+
+impl InputState {
+    /// Insert the `snippet` in the VS Code snippet syntax at the cursor, replacing the selected text.
+    ///
+    /// After insertion, the first tab stop is selected, `tab` and `shift-tab` move between the tab stops.
+    ///
+    /// The supported variables: `TM_SELECTED_TEXT`, `TM_CURRENT_LINE`, `TM_LINE_INDEX`, `TM_LINE_NUMBER`,
+    /// `LINE_COMMENT`, `BLOCK_COMMENT_START` and `BLOCK_COMMENT_END`.
+    pub fn insert_snippet(
+        &mut self,
+        snippet: impl AsRef<str>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.disabled {
+            return;
+        }
+
+        let range = self.selected_range.clone();
+        let selected_text = self.text.text_for_range(range.clone());
+        let snippet = self.parse_snippet(snippet.as_ref(), range.start, &selected_text, cx);
+
+        let range_utf16 = self.range_to_utf16(&range);
+        self.replace_text_in_range(Some(range_utf16), &snippet.text, window, cx);
+        self.start_snippet(snippet, range.start, window, cx);
+    }
+
+    /// Parse the `snippet` to insert at the `offset`, and indent it as the line of the `offset`.
+    pub(super) fn parse_snippet(
+        &self,
+        snippet: &str,
+        offset: usize,
+        selected_text: &str,
+        cx: &Context<Self>,
+    ) -> ParsedSnippet {
+        let row = self.text.offset_to_row(offset);
+        let line = self.text.slice_line(row).to_string();
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (line_comment, block_comment) = self.mode.comment_tokens(cx);
+
+        let resolve = |name: &str| -> Option<String> {
+            match name {
+                "TM_SELECTED_TEXT" => Some(selected_text.to_string()),
+                "TM_CURRENT_LINE" => Some(line.to_string()),
+                "TM_LINE_INDEX" => Some(row.to_string()),
+                "TM_LINE_NUMBER" => Some((row + 1).to_string()),
+                "LINE_COMMENT" => line_comment.as_ref().map(|token| token.to_string()),
+                "BLOCK_COMMENT_START" => block_comment.as_ref().map(|(start, _)| start.to_string()),
+                "BLOCK_COMMENT_END" => block_comment.as_ref().map(|(_, end)| end.to_string()),
+                _ => None,
+            }
+        };
+
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        let tab = self
+            .mode
+            .tab_size()
+            .copied()
+            .unwrap_or_default()
+            .to_string();
+        indent_snippet(parse_snippet(snippet, &resolve), indent, &tab)
+    }
+
+    /// Start editing the `snippet` that has been inserted at the `offset`, and select the first tab stop.
+    pub(super) fn start_snippet(
+        &mut self,
+        snippet: ParsedSnippet,
+        offset: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let tab_stops = snippet
+            .tab_stops
+            .into_iter()
+            .map(|tab_stop| TabStop {
+                ranges: tab_stop
+                    .ranges
+                    .iter()
+                    .map(|range| offset + range.start..offset + range.end)
+                    .collect(),
+                ..tab_stop
+            })
+            .collect();
+
+        self.snippet = Some(SnippetState {
+            tab_stops,
+            active_ix: 0,
+        });
+        self.select_tab_stop(0, window, cx);
+    }
+
+    /// Update the ranges of the tab stops before the `range` is replaced by the `new_text`.
+    pub(super) fn update_snippet_for_edit(&mut self, range: &Range<usize>, new_text: &str) {
+        let Some(snippet) = self.snippet.as_mut() else {
+            return;
+        };
+
+        for (ix, tab_stop) in snippet.tab_stops.iter_mut().enumerate() {
+            let active = ix == snippet.active_ix;
+            tab_stop.ranges = tab_stop
+                .ranges
+                .iter()
+                .filter_map(|r| tab_stop_after_edit(r, range, new_text.len(), active))
+                .collect();
+        }
+
+        if snippet
+            .tab_stops
+            .iter()
+            .all(|tab_stop| tab_stop.ranges.is_empty())
+        {
+            self.snippet = None;
+        }
+    }
+
+    /// Select the tab stop at `ix`, the mirrors are selected as the extra selections.
+    ///
+    /// The snippet is finished if the final tab stop is selected.
+    fn select_tab_stop(&mut self, ix: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(snippet) = self.snippet.as_mut() else {
+            return;
+        };
+        let Some(tab_stop) = snippet.tab_stops.get(ix).cloned() else {
+            return;
+        };
+        snippet.active_ix = ix;
+        if ix + 1 == snippet.tab_stops.len() {
+            self.snippet = None;
+        }
+
+        let mut ranges = tab_stop.ranges.into_iter();
+        let Some(range) = ranges.next() else {
+            return;
+        };
+        self.hide_completion_menu(cx);
+        self.selected_range = range.clone();
+        self.selection_reversed = false;
+        self.extra_selections = ranges.collect();
+        self.update_preferred_x_offset(cx);
+        self.update_scroll_offset(None, cx);
+
+        if !tab_stop.choices.is_empty() {
+            let items = tab_stop
+                .choices
+                .into_iter()
+                .map(CompletionItem::new)
+                .collect();
+            let editor = cx.entity().downgrade();
+            let menu = cx.new(|cx| {
+                let mut menu = CompletionMenu::new(editor, items, range.start, window, cx);
+                menu.update_query("", window, cx);
+                menu
+            });
+            self.completion_menu = Some(menu);
+        }
+        cx.notify();
+    }
+
+    /// Move to the next (or previous if `backward`) tab stop of the snippet in editing.
+    ///
+    /// Returns false if there is no snippet in editing, or the cursor is moved out of the snippet.
+    pub(super) fn move_to_tab_stop(
+        &mut self,
+        backward: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(snippet) = self.snippet.as_ref() else {
+            return false;
+        };
+
+        let ranges = snippet
+            .tab_stops
+            .iter()
+            .flat_map(|tab_stop| &tab_stop.ranges);
+        let start = ranges.clone().map(|range| range.start).min().unwrap_or(0);
+        let end = ranges.map(|range| range.end).max().unwrap_or(0);
+        let cursor = self.cursor_offset();
+        if cursor < start || cursor > end {
+            self.snippet = None;
+            return false;
+        }
+
+        let active_ix = snippet.active_ix;
+        let is_available = |ix: &usize| !snippet.tab_stops[*ix].ranges.is_empty();
+        let ix = if backward {
+            (0..active_ix).rev().find(is_available)
+        } else {
+            (active_ix + 1..snippet.tab_stops.len()).find(is_available)
+        };
+
+        match ix {
+            Some(ix) => self.select_tab_stop(ix, window, cx),
+            None if backward => {}
+            None => {
+                self.snippet = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Finish the snippet in editing, returns true if there was a snippet.
+    pub(super) fn finish_snippet(&mut self, cx: &mut Context<Self>) -> bool {
+        if self.snippet.take().is_some() {
+            self.extra_selections.clear();
+            cx.notify();
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(snippet: &str) -> ParsedSnippet {
+        parse_snippet(snippet, &|name| {
+            (name == "TM_SELECTED_TEXT").then(|| "foo".to_string())
+        })
+    }
+
+    fn ranges(snippet: &ParsedSnippet) -> Vec<Vec<Range<usize>>> {
+        snippet
+            .tab_stops
+            .iter()
+            .map(|tab_stop| tab_stop.ranges.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_snippet() {
+        let snippet = parse("fn ${1:name}($2) -> ${3:()} {\n\t$0\n}");
+        assert_eq!(snippet.text, "fn name() -> () {\n\t\n}");
+        assert_eq!(
+            ranges(&snippet),
+            vec![vec![3..7], vec![8..8], vec![13..15], vec![19..19]]
+        );
+
+        // The implicit final tab stop.
+        let snippet = parse("$2 and $1");
+        assert_eq!(snippet.text, " and ");
+        assert_eq!(ranges(&snippet), vec![vec![5..5], vec![0..0], vec![5..5]]);
+
+        // Nested placeholders.
+        let snippet = parse("${1:foo ${2:bar}}");
+        assert_eq!(snippet.text, "foo bar");
+        assert_eq!(ranges(&snippet), vec![vec![0..7], vec![4..7], vec![7..7]]);
+
+        // Mirrors use the text of the placeholder.
+        let snippet = parse("$1 = ${1:value}; $1");
+        assert_eq!(snippet.text, "value = value; value");
+        assert_eq!(
+            ranges(&snippet),
+            vec![vec![0..5, 8..13, 15..20], vec![20..20]]
+        );
+
+        // Choices.
+        let snippet = parse("${1|one,two\\,three|}");
+        assert_eq!(snippet.text, "one");
+        assert_eq!(
+            snippet.tab_stops[0].choices,
+            vec!["one".to_string(), "two,three".to_string()]
+        );
+
+        // Variables.
+        let snippet = parse("[$TM_SELECTED_TEXT] ${UNKNOWN:default} $UNKNOWN");
+        assert_eq!(snippet.text, "[foo] default ");
+        assert_eq!(ranges(&snippet), vec![vec![14..14]]);
+
+        // Escapes and invalid syntax.
+        let snippet = parse("\\$1 \\} \\\\ $ ${1 ${a");
+        assert_eq!(snippet.text, "$1 } \\ $ ${1 ${a");
+        assert_eq!(ranges(&snippet), vec![vec![16..16]]);
+    }
+
+    #[test]
+    fn test_indent_snippet() {
+        let snippet = parse("if $1 {\n\t$0\n}");
+        let snippet = indent_snippet(snippet, "    ", "  ");
+        assert_eq!(snippet.text, "if  {\n      \n    }");
+        assert_eq!(ranges(&snippet), vec![vec![3..3], vec![12..12]]);
+
+        let snippet = parse("a\t$1\n\tb");
+        let snippet = indent_snippet(snippet, "\t", "\t");
+        assert_eq!(snippet.text, "a\t\n\t\tb");
+        assert_eq!(ranges(&snippet), vec![vec![2..2], vec![6..6]]);
+    }
+
+    #[test]
+    fn test_tab_stop_after_edit() {
+        // Typing in the active tab stop.
+        assert_eq!(tab_stop_after_edit(&(3..7), &(3..7), 1, true), Some(3..4));
+        assert_eq!(tab_stop_after_edit(&(3..3), &(3..3), 1, true), Some(3..4));
+        assert_eq!(tab_stop_after_edit(&(3..7), &(7..7), 2, true), Some(3..9));
+        // The edit at the boundary of the other tab stop.
+        assert_eq!(tab_stop_after_edit(&(7..7), &(7..7), 2, false), Some(9..9));
+        assert_eq!(tab_stop_after_edit(&(3..7), &(7..7), 2, false), Some(3..7));
+        assert_eq!(tab_stop_after_edit(&(3..7), &(4..5), 0, false), Some(3..6));
+        assert_eq!(tab_stop_after_edit(&(3..7), &(0..1), 0, false), Some(2..6));
+        // Overlapped.
+        assert_eq!(tab_stop_after_edit(&(3..7), &(2..4), 1, false), None);
+        assert_eq!(tab_stop_after_edit(&(4..7), &(0..7), 1, true), None);
+    }
+}
@@ -3,9 +3,10 @@
 //! Based on the `Input` example from the `gpui` crate.
 //! https://github.com/zed-industries/zed/blob/main/crates/gpui/examples/input.rs
 use gpui::Action;
+use ropey::Rope;
 use serde::Deserialize;
 use smallvec::SmallVec;
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::ops::{Deref, Range};
 use std::rc::Rc;
 use unicode_segmentation::*;
@@ -15,7 +16,8 @@ use gpui::{
     ClipboardItem, Context, Entity, EntityInputHandler, EventEmitter, FocusHandle, Focusable,
     InteractiveElement as _, IntoElement, KeyBinding, KeyDownEvent, MouseButton, MouseDownEvent,
     MouseMoveEvent, MouseUpEvent, ParentElement as _, Pixels, Point, Render, ScrollHandle,
-    ScrollWheelEvent, SharedString, Styled as _, Subscription, UTF16Selection, Window, WrappedLine,
+    ScrollWheelEvent, SharedString, Styled as _, Subscription, Task, TextRun, UTF16Selection,
+    Window, WrappedLine,
 };
 
 // TODO:
@@ -24,15 +26,34 @@ use gpui::{
 use super::{
     blink_cursor::BlinkCursor,
     change::Change,
+    completion::{CompletionMenu, CompletionProvider},
     element::TextElement,
+    inlay::{self, GhostText, InlayHint},
+    keymap::{self, KeymapMode},
     mask_pattern::MaskPattern,
     mode::{InputMode, TabSize},
     number_input,
+    rope_ext::RopeExt as _,
+    search::{self, SearchPanel},
+    selection::{
+        box_selections, merge_selections, next_occurrence, selections_after_replace,
+        split_for_cursors,
+    },
+    snippet::SnippetState,
     text_wrapper::TextWrapper,
+    vim::VimState,
+    whitespace::ShowWhitespace,
 };
-use crate::input::hover_popover::DiagnosticPopover;
+use crate::input::diagnostics::DiagnosticsProvider;
+use crate::input::gutter::{GutterDecoration, LineAnnotation};
+use crate::input::hover_popover::{HoverPopover, HoverProvider};
 use crate::input::marker::Marker;
-use crate::{history::History, scroll::ScrollbarState, Root};
+use crate::{
+    highlighter::{ExportFormat, Language, LanguageRegistry},
+    history::History,
+    scroll::ScrollbarState,
+    Root,
+};
 
 #[derive(Action, Clone, PartialEq, Eq, Deserialize)]
 #[action(namespace = input, no_json)]
@@ -75,6 +96,7 @@ actions!(
         SelectToNextWordEnd,
         ShowCharacterPalette,
         Copy,
+        CopyAsRichText,
         Cut,
         Paste,
         Undo,
@@ -85,14 +107,33 @@ actions!(
         MoveToEnd,
         MoveToPreviousWord,
         MoveToNextWord,
-        Escape
+        Escape,
+        Find,
+        Replace,
+        FindNext,
+        FindPrevious,
+        SelectNextOccurrence,
+        Fold,
+        Unfold,
+        FoldAll,
+        UnfoldAll,
+        ShowCompletions,
+        MoveToMatchingBracket,
+        ToggleComment,
+        AcceptGhostText,
+        SetMark,
+        SelectLargerSyntaxNode,
+        SelectSmallerSyntaxNode
     ]
 );
 
 #[derive(Clone)]
 pub enum InputEvent {
-    Change(SharedString),
-    PressEnter { secondary: bool },
+    /// The text is changed, read it by [`InputState::value`] or [`InputState::unmask_value`].
+    Change,
+    PressEnter {
+        secondary: bool,
+    },
     Focus,
     Blur,
 }
@@ -208,19 +249,104 @@ pub fn init(cx: &mut App) {
         KeyBinding::new("ctrl-z", Undo, Some(CONTEXT)),
         #[cfg(not(target_os = "macos"))]
         KeyBinding::new("ctrl-y", Redo, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-f", Find, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-f", Find, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-alt-f", Replace, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-h", Replace, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-g", FindNext, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("f3", FindNext, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-shift-g", FindPrevious, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("shift-f3", FindPrevious, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-d", SelectNextOccurrence, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-d", SelectNextOccurrence, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-alt-[", Fold, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-shift-[", Fold, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-alt-]", Unfold, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-shift-]", Unfold, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-k cmd-0", FoldAll, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-k ctrl-0", FoldAll, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-k cmd-j", UnfoldAll, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-k ctrl-j", UnfoldAll, Some(CONTEXT)),
+        KeyBinding::new("ctrl-space", ShowCompletions, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-shift-\\", MoveToMatchingBracket, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-shift-\\", MoveToMatchingBracket, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-/", ToggleComment, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-/", ToggleComment, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("ctrl-shift-right", SelectLargerSyntaxNode, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("alt-shift-right", SelectLargerSyntaxNode, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("ctrl-shift-left", SelectSmallerSyntaxNode, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("alt-shift-left", SelectSmallerSyntaxNode, Some(CONTEXT)),
     ]);
 
     number_input::init(cx);
+    search::init(cx);
+    keymap::init(cx);
 }
 
 #[derive(Clone)]
 pub(super) struct LastLayout {
-    /// The last layout lines.
+    /// The last layout lines, only contains the visible lines in the viewport.
     pub(super) lines: Rc<SmallVec<[WrappedLine; 1]>>,
     /// The line_height of text layout, this will change will InputElement painted.
     pub(super) line_height: Pixels,
     /// The visible range (no wrap) of lines in the viewport.
     pub(super) visible_range: Range<usize>,
+    /// The rows (no wrap) of the `lines`, the rows hidden by the folded regions are skipped.
+    pub(super) visible_rows: Rc<Vec<usize>>,
+    /// The start offset of each of the `lines` in the entire text.
+    pub(super) line_offsets: Rc<Vec<usize>>,
+    /// The byte range of the text of the visible lines.
+    pub(super) visible_range_offset: Range<usize>,
+    /// The top position of the first visible line, relative to the text top.
+    pub(super) visible_top: Pixels,
+    /// The inlays (offset relative to the line start, length) of each of the `lines`, sorted by the offset.
+    pub(super) inlays: Rc<Vec<Vec<(usize, usize)>>>,
+    /// The rows pinned at the top of the viewport by the sticky scroll.
+    pub(super) sticky_rows: Rc<Vec<usize>>,
+}
+
+impl LastLayout {
+    /// Return the index in the `ix` line layout of the `offset` (relative to the line start).
+    pub(super) fn index_for_offset(&self, ix: usize, offset: usize) -> usize {
+        match self.inlays.get(ix) {
+            Some(inlays) => inlay::index_for_offset(inlays, offset),
+            None => offset,
+        }
+    }
+
+    /// Return the offset (relative to the line start) of the `index` in the `ix` line layout.
+    pub(super) fn offset_for_index(&self, ix: usize, index: usize) -> usize {
+        match self.inlays.get(ix) {
+            Some(inlays) => inlay::offset_for_index(inlays, index),
+            None => index,
+        }
+    }
 }
 
 impl Deref for LastLayout {
@@ -235,7 +361,10 @@ impl Deref for LastLayout {
 pub struct InputState {
     pub(super) focus_handle: FocusHandle,
     pub(super) mode: InputMode,
-    pub(super) text: SharedString,
+    pub(super) text: Rope,
+    /// The text collected into a string, it is built at the first [`InputState::value`] call
+    /// after each change.
+    value: OnceCell<SharedString>,
     pub(super) text_wrapper: TextWrapper,
     pub(super) history: History<Change>,
     pub(super) blink_cursor: Entity<BlinkCursor>,
@@ -248,6 +377,12 @@ pub struct InputState {
     /// Range for save the selected word, use to keep word range when drag move.
     pub(super) selected_word_range: Option<Range<usize>>,
     pub(super) selection_reversed: bool,
+    /// The additional selections for multiple cursors, sorted and not overlapped.
+    ///
+    /// The `selected_range` is the primary selection (the newest cursor).
+    pub(super) extra_selections: Vec<Range<usize>>,
+    /// The anchor offset of the box selection, set when alt + mouse down.
+    pub(super) box_select_anchor: Option<usize>,
     /// The marked range is the temporary insert text on IME typing.
     pub(super) marked_range: Option<Range<usize>>,
     pub(super) last_layout: Option<LastLayout>,
@@ -258,6 +393,8 @@ pub struct InputState {
     pub(super) last_bounds: Option<Bounds<Pixels>>,
     pub(super) last_selected_range: Option<Range<usize>>,
     pub(super) selecting: bool,
+    /// The viewport in the minimap is dragging.
+    pub(super) minimap_dragging: bool,
     pub(super) disabled: bool,
     pub(super) masked: bool,
     pub(super) clean_on_escape: bool,
@@ -274,10 +411,42 @@ pub struct InputState {
     pub(super) placeholder: SharedString,
 
     /// Popover
-    diagnostic_popover: Option<Entity<DiagnosticPopover>>,
-
-    /// To remember the horizontal column (x-coordinate) of the cursor position for keep column for move up/down.
+    pub(super) hover_popover: Option<Entity<HoverPopover>>,
+    pub(super) hover_provider: Option<Rc<dyn HoverProvider>>,
+    /// The range (the word at the offset) of the last hover request, to avoid requesting again
+    /// when the mouse moves in the same word.
+    pub(super) hover_range: Option<Range<usize>>,
+    pub(super) hover_task: Task<()>,
+    pub(super) diagnostics_provider: Option<Rc<dyn DiagnosticsProvider>>,
+    /// The pending diagnostics request, `None` if never requested.
+    pub(super) diagnostics_task: Option<Task<()>>,
+    /// The background parsing of the syntax tree, see [`crate::highlighter::SyntaxHighlighter::parse_job`].
+    pub(super) parse_task: Task<()>,
+    /// The find / replace panel, only for the code editor.
+    pub(super) search_panel: Option<Entity<SearchPanel>>,
+    pub(super) completion_provider: Option<Rc<dyn CompletionProvider>>,
+    pub(super) completion_menu: Option<Entity<CompletionMenu>>,
+    pub(super) gutter_decorations: Vec<GutterDecoration>,
+    pub(super) line_annotations: Vec<LineAnnotation>,
+    /// The inlay hints sorted by the offset.
+    pub(super) inlay_hints: Vec<InlayHint>,
+    pub(super) ghost_text: Option<GhostText>,
+    pub(super) indent_guides: bool,
+    pub(super) show_whitespace: ShowWhitespace,
+    /// The snippet in editing, to move between the tab stops.
+    pub(super) snippet: Option<SnippetState>,
+    /// The ranges of the closing texts inserted by the auto closing pairs, to type over them.
+    pub(super) auto_closed: Vec<Range<usize>>,
+    pub(super) keymap: KeymapMode,
+    pub(super) vim: VimState,
+    /// The mark of the Emacs keymap is set, the movement keys extend the selection.
+    pub(super) emacs_mark: bool,
+    /// The selections before each expand, to shrink the selection back.
+    pub(super) selection_stack: Vec<Range<usize>>,
+
+    /// To remember the horizontal column (x-coordinate, relative to the line start) of the cursor position for keep column for move up/down.
     preferred_x_offset: Option<Pixels>,
+    pub(super) completion_task: Task<()>,
     _subscriptions: Vec<Subscription>,
 }
 
@@ -308,13 +477,16 @@ impl InputState {
             }),
             cx.on_focus(&focus_handle, window, Self::on_focus),
             cx.on_blur(&focus_handle, window, Self::on_blur),
+            // Repaint with the new highlight theme.
+            cx.observe_global::<LanguageRegistry>(|_, cx| cx.notify()),
         ];
 
         let text_style = window.text_style();
 
         Self {
             focus_handle: focus_handle.clone(),
-            text: "".into(),
+            text: Rope::new(),
+            value: OnceCell::new(),
             text_wrapper: TextWrapper::new(
                 text_style.font(),
                 text_style.font_size.to_pixels(window.rem_size()),
@@ -325,9 +497,12 @@ impl InputState {
             selected_range: 0..0,
             selected_word_range: None,
             selection_reversed: false,
+            extra_selections: vec![],
+            box_select_anchor: None,
             marked_range: None,
             input_bounds: Bounds::default(),
             selecting: false,
+            minimap_dragging: false,
             disabled: false,
             masked: false,
             clean_on_escape: false,
@@ -346,7 +521,29 @@ impl InputState {
             line_number_width: px(0.),
             placeholder: SharedString::default(),
             mask_pattern: MaskPattern::default(),
-            diagnostic_popover: None,
+            hover_popover: None,
+            hover_provider: None,
+            hover_range: None,
+            hover_task: Task::ready(()),
+            diagnostics_provider: None,
+            diagnostics_task: None,
+            parse_task: Task::ready(()),
+            search_panel: None,
+            completion_provider: None,
+            completion_menu: None,
+            completion_task: Task::ready(()),
+            gutter_decorations: vec![],
+            line_annotations: vec![],
+            inlay_hints: vec![],
+            ghost_text: None,
+            indent_guides: false,
+            show_whitespace: ShowWhitespace::default(),
+            snippet: None,
+            auto_closed: vec![],
+            keymap: KeymapMode::default(),
+            vim: VimState::default(),
+            emacs_mark: false,
+            selection_stack: vec![],
             _subscriptions,
         }
     }
@@ -390,15 +587,29 @@ impl InputState {
     ///
     /// - Syntax Highlighting
     /// - Auto Indent
+    /// - Toggle Comment
     /// - Line Number
+    /// - Find and Replace
+    /// - Multiple Cursors
+    /// - Code Folding
+    /// - Bracket Matching and Auto Closing Pairs
+    /// - Gutter Decorations and Line Annotations
+    /// - Inlay Hints and Ghost Text
+    /// - Minimap and Sticky Scroll
+    /// - Snippets
+    /// - Vim and Emacs Keymaps
+    /// - Expand and Shrink Selection by the Syntax Tree
     pub fn code_editor(mut self, language: impl Into<SharedString>) -> Self {
         let language: SharedString = language.into();
         self.mode = InputMode::CodeEditor {
             rows: 2,
             tab: TabSize::default(),
             language,
+            detect_language: false,
             highlighter: Rc::new(RefCell::new(None)),
             line_number: true,
+            minimap: false,
+            sticky_scroll: false,
             height: Some(relative(1.)),
             markers: Rc::new(vec![]),
         };
@@ -427,6 +638,72 @@ impl InputState {
         cx.notify();
     }
 
+    /// Set enable/disable the minimap, only for [`InputMode::CodeEditor`] mode.
+    ///
+    /// Default: false
+    pub fn minimap(mut self, minimap: bool) -> Self {
+        if let InputMode::CodeEditor { minimap: m, .. } = &mut self.mode {
+            *m = minimap;
+        }
+        self
+    }
+
+    /// Set enable/disable to detect the language by [`Language::detect`], only for [`InputMode::CodeEditor`] mode.
+    ///
+    /// The language is detected when the value is set or the text is pasted into the empty editor,
+    /// the language of [`InputState::code_editor`] is kept if nothing is detected.
+    ///
+    /// Default: false
+    pub fn detect_language(mut self, detect_language: bool) -> Self {
+        if let InputMode::CodeEditor {
+            detect_language: d, ..
+        } = &mut self.mode
+        {
+            *d = detect_language;
+        }
+        self
+    }
+
+    /// Set minimap, only for [`InputMode::CodeEditor`] mode.
+    pub fn set_minimap(&mut self, minimap: bool, _: &mut Window, cx: &mut Context<Self>) {
+        if let InputMode::CodeEditor { minimap: m, .. } = &mut self.mode {
+            *m = minimap;
+        }
+        cx.notify();
+    }
+
+    /// Set enable/disable the sticky scroll, only for [`InputMode::CodeEditor`] mode.
+    ///
+    /// When enabled, the headers of the enclosing scopes (e.g.: function, class) of the
+    /// first visible line are pinned at the top of the editor.
+    ///
+    /// Default: false
+    pub fn sticky_scroll(mut self, sticky_scroll: bool) -> Self {
+        if let InputMode::CodeEditor {
+            sticky_scroll: s, ..
+        } = &mut self.mode
+        {
+            *s = sticky_scroll;
+        }
+        self
+    }
+
+    /// Set sticky scroll, only for [`InputMode::CodeEditor`] mode.
+    pub fn set_sticky_scroll(
+        &mut self,
+        sticky_scroll: bool,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let InputMode::CodeEditor {
+            sticky_scroll: s, ..
+        } = &mut self.mode
+        {
+            *s = sticky_scroll;
+        }
+        cx.notify();
+    }
+
     /// Set the tab size for the input.
     ///
     /// Only for [`InputMode::MultiLine`] and [`InputMode::CodeEditor`] mode.
@@ -477,6 +754,8 @@ impl InputState {
             }
             _ => {}
         }
+        // Drop the parsing of the old highlighter.
+        self.parse_task = Task::ready(());
         cx.notify();
     }
 
@@ -487,9 +766,34 @@ impl InputState {
             }
             _ => {}
         }
+        // Drop the parsing of the old highlighter.
+        self.parse_task = Task::ready(());
         cx.notify();
     }
 
+    /// Parse the text of the code editor in the background, and highlight the visible range.
+    ///
+    /// The edits during the parsing are applied to its result, then parse again on the next render.
+    pub(super) fn parse_syntax(&mut self, cx: &mut Context<Self>) {
+        let Some(job) = self.mode.parse_job(&self.text, cx) else {
+            return;
+        };
+
+        self.parse_task = cx.spawn(async move |this, cx| {
+            let parsed = cx.background_spawn(async move { job.parse() }).await;
+
+            _ = this.update(cx, |this, cx| {
+                if this.mode.apply_parsed(parsed) {
+                    this.update_fold_ranges();
+                    cx.notify();
+                } else {
+                    // The result is outdated, parse the current text.
+                    this.parse_syntax(cx);
+                }
+            });
+        });
+    }
+
     /// Set markers, only for [`InputMode::CodeEditor`] mode.
     ///
     /// For example to set the diagnostic markers in the code editor.
@@ -513,26 +817,28 @@ impl InputState {
     }
 
     /// Called after moving the cursor. Updates preferred_x_offset if we know where the cursor now is.
-    fn update_preferred_x_offset(&mut self, _cx: &mut Context<Self>) {
-        let (Some(_), Some(bounds)) = (&self.last_layout, &self.last_bounds) else {
+    pub(super) fn update_preferred_x_offset(&mut self, _cx: &mut Context<Self>) {
+        if self.last_layout.is_none() {
             return;
-        };
+        }
 
         // Find which line and sub-line the cursor is on and its position
         let (_, _, cursor_pos) = self.line_and_position_for_offset(self.cursor_offset());
 
         if let Some(pos) = cursor_pos {
-            self.preferred_x_offset = Some(pos.x + bounds.origin.x);
+            self.preferred_x_offset = Some(pos.x);
         }
     }
 
     /// Find which line and sub-line the given offset belongs to, along with the position within that sub-line.
     ///
+    /// Only the visible lines of the last layout are considered.
+    ///
     /// Returns:
     ///
     /// - The index of the line (zero-based) containing the offset.
     /// - The index of the sub-line (zero-based) within the line containing the offset.
-    /// - The position of the offset.
+    /// - The position of the offset, relative to the top of the text.
     pub(super) fn line_and_position_for_offset(
         &self,
         offset: usize,
@@ -542,22 +848,80 @@ impl InputState {
         };
         let line_height = last_layout.line_height;
 
-        let mut prev_lines_offset = 0;
-        let mut y_offset = px(0.);
-        for (line_index, line) in last_layout.lines.iter().enumerate() {
-            let local_offset = offset.saturating_sub(prev_lines_offset);
+        let mut y_offset = last_layout.visible_top;
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let line_offset = last_layout.line_offsets[ix];
+            if offset < line_offset {
+                break;
+            }
+
+            let local_offset = last_layout.index_for_offset(ix, offset - line_offset);
             if let Some(pos) = line.position_for_index(local_offset, line_height) {
                 let sub_line_index = (pos.y.0 / line_height.0) as usize;
                 let adjusted_pos = point(pos.x, pos.y + y_offset);
-                return (line_index, sub_line_index, Some(adjusted_pos));
+                return (
+                    last_layout.visible_rows[ix],
+                    sub_line_index,
+                    Some(adjusted_pos),
+                );
             }
 
             y_offset += line.size(line_height).height;
-            prev_lines_offset += line.len() + 1;
         }
         (0, 0, None)
     }
 
+    /// Layout a line (split by `\n`) with the soft wrap and the inlays in it, the result is not
+    /// contains the highlight styles.
+    ///
+    /// This is used to find the position of the cursor in the line that may not visible.
+    ///
+    /// Returns the layout and the inlays (offset, len) of the line, to map the offsets in the line
+    /// by [`inlay::index_for_offset`] and [`inlay::offset_for_index`].
+    fn layout_row(
+        &self,
+        row: usize,
+        window: &mut Window,
+    ) -> Option<(WrappedLine, Vec<(usize, usize)>)> {
+        let line = self.text.slice_line(row).to_string();
+        let inlays = self.text_wrapper.line_inlays(row);
+        let mut text = String::with_capacity(line.len());
+        let mut start = 0;
+        for (offset, inlay) in inlays.iter() {
+            text.push_str(&line[start..*offset]);
+            text.push_str(inlay);
+            start = *offset;
+        }
+        text.push_str(&line[start..]);
+        let inlays = inlays
+            .iter()
+            .map(|(offset, inlay)| (*offset, inlay.len()))
+            .collect();
+
+        let text: SharedString = text.into();
+        let run = TextRun {
+            len: text.len(),
+            font: self.text_wrapper.font.clone(),
+            color: gpui::black(),
+            background_color: None,
+            underline: None,
+            strikethrough: None,
+        };
+
+        window
+            .text_system()
+            .shape_text(
+                text,
+                self.text_wrapper.font_size,
+                &[run],
+                self.text_wrapper.wrap_width,
+                None,
+            )
+            .ok()
+            .and_then(|mut lines| lines.pop())
+            .map(|line| (line, inlays))
+    }
+
     /// Move the cursor vertically by one line (up or down) while preserving the column if possible.
     ///
     /// move_lines: Number of lines to move vertically (positive for down, negative for up).
@@ -566,89 +930,68 @@ impl InputState {
             return;
         }
 
-        let (Some(last_layout), Some(bounds)) = (&self.last_layout, &self.last_bounds) else {
+        let Some(last_layout) = &self.last_layout else {
             return;
         };
 
         let offset = self.cursor_offset();
-        let preferred_x_offset = self.preferred_x_offset;
         let line_height = last_layout.line_height;
-        let (current_line, current_sub_line, current_pos) =
-            self.line_and_position_for_offset(offset);
+        let preferred_x_offset = self.preferred_x_offset;
+        let current = self.text_wrapper.display_point_for_offset(offset);
 
-        let Some(current_pos) = current_pos else {
-            return;
+        let current_x = match preferred_x_offset {
+            Some(x) => x,
+            None => {
+                let Some((line, inlays)) = self.layout_row(current.row, window) else {
+                    return;
+                };
+                let local_offset = offset.saturating_sub(self.text.line_start_offset(current.row));
+                line.position_for_index(inlay::index_for_offset(&inlays, local_offset), line_height)
+                    .map(|pos| pos.x)
+                    .unwrap_or_default()
+            }
         };
 
-        let current_x = self
-            .preferred_x_offset
-            .unwrap_or_else(|| current_pos.x + bounds.origin.x);
-
-        let mut new_line = current_line;
-        let mut new_sub_line = current_sub_line as i32;
-        new_sub_line += if move_lines > 0 { 1 } else { -1 };
-
         // Handle moving above the first line
-        if move_lines < 0 && current_line == 0 && new_sub_line < 0 {
+        if move_lines < 0 && current.display_row == 0 {
             // Move cursor to the beginning of the text
             self.move_to(0, window, cx);
-            self.preferred_x_offset = preferred_x_offset;
+            self.preferred_x_offset = Some(current_x);
             return;
         }
 
-        if new_sub_line < 0 {
-            new_line = new_line
-                .saturating_add_signed(move_lines)
-                .max(0)
-                .min(last_layout.lines.len().saturating_sub(1));
-            new_sub_line = last_layout.lines[new_line].wrap_boundaries.len() as i32;
-        } else {
-            let max_sub_line = last_layout.lines[current_line].wrap_boundaries.len() as i32;
-            if new_sub_line > max_sub_line {
-                if new_line < last_layout.lines.len() - 1 {
-                    new_line = new_line
-                        .saturating_add_signed(move_lines)
-                        .max(0)
-                        .min(last_layout.lines.len().saturating_sub(1));
-                    new_sub_line = 0;
-                } else {
-                    new_sub_line = max_sub_line;
-                }
-            }
-        }
+        let max_display_row = self.text_wrapper.len().saturating_sub(1);
+        let new_display_row = current
+            .display_row
+            .saturating_add_signed(move_lines)
+            .min(max_display_row);
 
         // If after adjustment, still at the same position, do not proceed
-        if new_line == current_line && new_sub_line == current_sub_line as i32 {
+        if new_display_row == current.display_row {
             return;
         }
 
-        let target_line = &last_layout.lines[new_line];
-        let line_x = current_x - bounds.origin.x;
-        let target_sub_line = new_sub_line as usize;
-
-        let approx_pos = point(line_x, px(target_sub_line as f32 * line_height.0));
-        let index_res = target_line.index_for_position(approx_pos, line_height);
+        let target = self
+            .text_wrapper
+            .display_point_for_display_row(new_display_row);
+        let Some((target_line, inlays)) = self.layout_row(target.row, window) else {
+            return;
+        };
 
-        let new_local_index = match index_res {
+        let approx_pos = point(current_x, px(target.wrap_row as f32 * line_height.0));
+        let new_index = match target_line.index_for_position(approx_pos, line_height) {
             Ok(i) => i,
             Err(i) => i,
         };
+        let new_local_index = inlay::offset_for_index(&inlays, new_index);
 
-        let mut prev_lines_offset = 0;
-        for (_, line) in last_layout
-            .lines
-            .iter()
-            .enumerate()
-            .take_while(|(i, _)| *i < new_line)
-        {
-            prev_lines_offset += line.len() + 1;
-        }
+        let line_start = self.text.line_start_offset(target.row);
+        let new_offset = self.text.clip_offset(line_start + new_local_index);
 
-        let new_offset = (prev_lines_offset + new_local_index).min(self.text.len());
         self.selected_range = new_offset..new_offset;
         self.pause_blink_cursor(cx);
         // Set back the preferred_x_offset
-        self.preferred_x_offset = preferred_x_offset;
+        self.preferred_x_offset = Some(current_x);
         cx.notify();
     }
 
@@ -679,21 +1022,63 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
+        // Keep the decorations and annotations of the lines, they are not moved by replacing the entire text.
+        let mut gutter_decorations = std::mem::take(&mut self.gutter_decorations);
+        let mut line_annotations = std::mem::take(&mut self.line_annotations);
         self.history.ignore = true;
         self.replace_text(value, window, cx);
         self.history.ignore = false;
+        let lines = self.text.lines_len();
+        gutter_decorations.retain(|decoration| decoration.line < lines);
+        line_annotations.retain(|annotation| annotation.line < lines);
+        self.gutter_decorations = gutter_decorations;
+        self.line_annotations = line_annotations;
         // Ensure cursor to start when set text
         if self.is_single_line() {
-            self.selected_range = self.text.len()..self.text.len();
+            self.selected_range = self.text.len_bytes()..self.text.len_bytes();
         } else {
             self.selected_range = 0..0;
         }
         // Move scroll to top
         self.scroll_handle.set_offset(point(px(0.), px(0.)));
+        self.update_detected_language(cx);
 
         cx.notify();
     }
 
+    /// Detect the language of the text and update the highlighter, if [`InputState::detect_language`] is enabled.
+    fn update_detected_language(&mut self, cx: &mut Context<Self>) {
+        let InputMode::CodeEditor {
+            detect_language: true,
+            language,
+            ..
+        } = &self.mode
+        else {
+            return;
+        };
+
+        // Only the first and the last lines are used to detect, see `Language::detect_ranked`.
+        const HEAD_LINES: usize = 100;
+        const TAIL_LINES: usize = 5;
+        let lines = self.text.lines_len();
+        let mut text = self
+            .text
+            .text_for_range(0..self.text.line_start_offset(HEAD_LINES));
+        if lines > HEAD_LINES {
+            let tail_start = lines.saturating_sub(TAIL_LINES).max(HEAD_LINES);
+            text.push_str(
+                &self
+                    .text
+                    .text_for_range(self.text.line_start_offset(tail_start)..self.text.len_bytes()),
+            );
+        }
+
+        let detected = Language::detect(None, &text);
+        if detected != Language::Plain && detected.name() != language.as_ref() {
+            self.set_highlighter(detected.name(), cx);
+        }
+    }
+
     /// Insert text at the current cursor position.
     ///
     /// And the cursor will be moved to the end of inserted text.
@@ -730,7 +1115,7 @@ impl InputState {
         cx: &mut Context<Self>,
     ) {
         let text: SharedString = text.into();
-        let range = 0..self.text.chars().map(|c| c.len_utf16()).sum();
+        let range = 0..self.text.len_utf16_cu();
         self.replace_text_in_range(Some(range), &text, window, cx);
         self.reset_highlighter(cx);
     }
@@ -765,6 +1150,7 @@ impl InputState {
     /// Set the password masked state of the input field.
     pub fn set_masked(&mut self, masked: bool, _: &mut Window, cx: &mut Context<Self>) {
         self.masked = masked;
+        self.update_wrapped_inlays(cx);
         cx.notify();
     }
 
@@ -804,19 +1190,29 @@ impl InputState {
 
     /// Set the default value of the input field.
     pub fn default_value(mut self, value: impl Into<SharedString>) -> Self {
-        self.text = value.into();
-        self.text_wrapper.text = self.text.clone();
+        let value: SharedString = value.into();
+        self.text = Rope::from_str(&value);
+        self.value = OnceCell::from(value);
         self
     }
 
     /// Return the value of the input field.
+    ///
+    /// The text is collected into a string at the first call after a change,
+    /// use [`Self::text`] to access the text without copy.
     pub fn value(&self) -> &SharedString {
+        self.value
+            .get_or_init(|| SharedString::from(self.text.to_string()))
+    }
+
+    /// Return the text [`Rope`] of the input field.
+    pub fn text(&self) -> &Rope {
         &self.text
     }
 
     /// Return the value without mask.
     pub fn unmask_value(&self) -> SharedString {
-        self.mask_pattern.unmask(&self.text).into()
+        self.mask_pattern.unmask(self.value()).into()
     }
 
     /// Focus the input field.
@@ -825,54 +1221,68 @@ impl InputState {
     }
 
     pub(super) fn left(&mut self, _: &MoveLeft, window: &mut Window, cx: &mut Context<Self>) {
-        self.pause_blink_cursor(cx);
-        if self.selected_range.is_empty() {
-            self.move_to(self.previous_boundary(self.cursor_offset()), window, cx);
-        } else {
-            self.move_to(self.selected_range.start, window, cx)
-        }
+        self.move_each_selection(window, cx, |this, window, cx| {
+            this.pause_blink_cursor(cx);
+            if this.selected_range.is_empty() {
+                this.move_to(this.previous_boundary(this.cursor_offset()), window, cx);
+            } else {
+                this.move_to(this.selected_range.start, window, cx)
+            }
+        });
     }
 
     pub(super) fn right(&mut self, _: &MoveRight, window: &mut Window, cx: &mut Context<Self>) {
-        self.pause_blink_cursor(cx);
-        if self.selected_range.is_empty() {
-            self.move_to(self.next_boundary(self.selected_range.end), window, cx);
-        } else {
-            self.move_to(self.selected_range.end, window, cx)
-        }
+        self.move_each_selection(window, cx, |this, window, cx| {
+            this.pause_blink_cursor(cx);
+            if this.selected_range.is_empty() {
+                this.move_to(this.next_boundary(this.selected_range.end), window, cx);
+            } else {
+                this.move_to(this.selected_range.end, window, cx)
+            }
+        });
     }
 
     pub(super) fn up(&mut self, _: &MoveUp, window: &mut Window, cx: &mut Context<Self>) {
+        if self.select_completion(-1, window, cx) {
+            return;
+        }
         if self.is_single_line() {
             return;
         }
 
-        if !self.selected_range.is_empty() {
-            self.move_to(
-                self.previous_boundary(self.selected_range.start.saturating_sub(1)),
-                window,
-                cx,
-            );
-        }
-        self.pause_blink_cursor(cx);
-        self.move_vertical(-1, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            if !this.selected_range.is_empty() {
+                this.move_to(
+                    this.previous_boundary(this.selected_range.start.saturating_sub(1)),
+                    window,
+                    cx,
+                );
+            }
+            this.pause_blink_cursor(cx);
+            this.move_vertical(-1, window, cx);
+        });
     }
 
     pub(super) fn down(&mut self, _: &MoveDown, window: &mut Window, cx: &mut Context<Self>) {
+        if self.select_completion(1, window, cx) {
+            return;
+        }
         if self.is_single_line() {
             return;
         }
 
-        if !self.selected_range.is_empty() {
-            self.move_to(
-                self.next_boundary(self.selected_range.end.saturating_sub(1)),
-                window,
-                cx,
-            );
-        }
+        self.move_each_selection(window, cx, |this, window, cx| {
+            if !this.selected_range.is_empty() {
+                this.move_to(
+                    this.next_boundary(this.selected_range.end.saturating_sub(1)),
+                    window,
+                    cx,
+                );
+            }
 
-        self.pause_blink_cursor(cx);
-        self.move_vertical(1, window, cx);
+            this.pause_blink_cursor(cx);
+            this.move_vertical(1, window, cx);
+        });
     }
 
     pub(super) fn page_up(&mut self, _: &MovePageUp, window: &mut Window, cx: &mut Context<Self>) {
@@ -912,7 +1322,9 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.select_to(self.previous_boundary(self.cursor_offset()), window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            this.select_to(this.previous_boundary(this.cursor_offset()), window, cx);
+        });
     }
 
     pub(super) fn select_right(
@@ -921,15 +1333,19 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.select_to(self.next_boundary(self.cursor_offset()), window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            this.select_to(this.next_boundary(this.cursor_offset()), window, cx);
+        });
     }
 
     pub(super) fn select_up(&mut self, _: &SelectUp, window: &mut Window, cx: &mut Context<Self>) {
         if self.is_single_line() {
             return;
         }
-        let offset = self.start_of_line(window, cx).saturating_sub(1);
-        self.select_to(self.previous_boundary(offset), window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.start_of_line().saturating_sub(1);
+            this.select_to(this.previous_boundary(offset), window, cx);
+        });
     }
 
     pub(super) fn select_down(
@@ -941,8 +1357,10 @@ impl InputState {
         if self.is_single_line() {
             return;
         }
-        let offset = (self.end_of_line(window, cx) + 1).min(self.text.len());
-        self.select_to(self.next_boundary(offset), window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = (this.end_of_line() + 1).min(this.text.len_bytes());
+            this.select_to(this.next_boundary(offset), window, cx);
+        });
     }
 
     pub(super) fn select_all(
@@ -952,19 +1370,23 @@ impl InputState {
         cx: &mut Context<Self>,
     ) {
         self.move_to(0, window, cx);
-        self.select_to(self.text.len(), window, cx)
+        self.select_to(self.text.len_bytes(), window, cx)
     }
 
     pub(super) fn home(&mut self, _: &MoveHome, window: &mut Window, cx: &mut Context<Self>) {
-        self.pause_blink_cursor(cx);
-        let offset = self.start_of_line(window, cx);
-        self.move_to(offset, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            this.pause_blink_cursor(cx);
+            let offset = this.start_of_line();
+            this.move_to(offset, window, cx);
+        });
     }
 
     pub(super) fn end(&mut self, _: &MoveEnd, window: &mut Window, cx: &mut Context<Self>) {
-        self.pause_blink_cursor(cx);
-        let offset = self.end_of_line(window, cx);
-        self.move_to(offset, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            this.pause_blink_cursor(cx);
+            let offset = this.end_of_line();
+            this.move_to(offset, window, cx);
+        });
     }
 
     pub(super) fn move_to_start(
@@ -982,7 +1404,7 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let end = self.text.len();
+        let end = self.text.len_bytes();
         self.move_to(end, window, cx);
     }
 
@@ -992,8 +1414,10 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let offset = self.previous_start_of_word();
-        self.move_to(offset, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.previous_start_of_word();
+            this.move_to(offset, window, cx);
+        });
     }
 
     pub(super) fn move_to_next_word(
@@ -1002,8 +1426,10 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let offset = self.next_end_of_word();
-        self.move_to(offset, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.next_end_of_word();
+            this.move_to(offset, window, cx);
+        });
     }
 
     pub(super) fn select_to_start(
@@ -1021,7 +1447,7 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let end = self.text.len();
+        let end = self.text.len_bytes();
         self.select_to(end, window, cx);
     }
 
@@ -1031,8 +1457,10 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let offset = self.start_of_line(window, cx);
-        self.select_to(self.previous_boundary(offset), window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.start_of_line();
+            this.select_to(this.previous_boundary(offset), window, cx);
+        });
     }
 
     pub(super) fn select_to_end_of_line(
@@ -1041,8 +1469,10 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let offset = self.end_of_line(window, cx);
-        self.select_to(self.next_boundary(offset), window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.end_of_line();
+            this.select_to(this.next_boundary(offset), window, cx);
+        });
     }
 
     pub(super) fn select_to_previous_word(
@@ -1051,8 +1481,10 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let offset = self.previous_start_of_word();
-        self.select_to(offset, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.previous_start_of_word();
+            this.select_to(offset, window, cx);
+        });
     }
 
     pub(super) fn select_to_next_word(
@@ -1061,140 +1493,115 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let offset = self.next_end_of_word();
-        self.select_to(offset, window, cx);
+        self.move_each_selection(window, cx, |this, window, cx| {
+            let offset = this.next_end_of_word();
+            this.select_to(offset, window, cx);
+        });
     }
 
     /// Return the start offset of the previous word.
     fn previous_start_of_word(&mut self) -> usize {
         let offset = self.selected_range.start;
-        let prev_str = self.text_for_range_utf8(0..offset);
-        UnicodeSegmentation::split_word_bound_indices(prev_str)
-            .filter(|(_, s)| !s.trim_start().is_empty())
-            .next_back()
-            .map(|(i, _)| i)
-            .unwrap_or(0)
+        let mut row = self.text.offset_to_row(offset);
+        let mut end = offset;
+        loop {
+            let line_start = self.text.line_start_offset(row);
+            let prev_str = self.text.text_for_range(line_start..end);
+            if let Some(ix) = UnicodeSegmentation::split_word_bound_indices(prev_str.as_str())
+                .filter(|(_, s)| !s.trim_start().is_empty())
+                .next_back()
+                .map(|(i, _)| i)
+            {
+                return line_start + ix;
+            }
+
+            if row == 0 {
+                return 0;
+            }
+            row -= 1;
+            end = self.text.line_end_offset(row);
+        }
     }
 
     /// Return the next end offset of the next word.
     fn next_end_of_word(&mut self) -> usize {
         let offset = self.cursor_offset();
-        let next_str = self.text_for_range_utf8(offset..self.text.len());
-        UnicodeSegmentation::split_word_bound_indices(next_str)
-            .find(|(_, s)| !s.trim_start().is_empty())
-            .map(|(i, s)| offset + i + s.len())
-            .unwrap_or(self.text.len())
+        let mut row = self.text.offset_to_row(offset);
+        let mut start = offset;
+        loop {
+            let line_end = self.text.line_end_offset(row);
+            let next_str = self.text.text_for_range(start..line_end);
+            if let Some(end) = UnicodeSegmentation::split_word_bound_indices(next_str.as_str())
+                .find(|(_, s)| !s.trim_start().is_empty())
+                .map(|(i, s)| start + i + s.len())
+            {
+                return end;
+            }
+
+            row += 1;
+            if row >= self.text.lines_len() {
+                return self.text.len_bytes();
+            }
+            start = self.text.line_start_offset(row);
+        }
     }
 
     /// Get start of line
-    fn start_of_line(&mut self, window: &mut Window, cx: &mut Context<Self>) -> usize {
+    fn start_of_line(&self) -> usize {
         if self.is_single_line() {
             return 0;
         }
 
-        let offset = self.previous_boundary(self.cursor_offset());
-        let line = self
-            .text_for_range(self.range_to_utf16(&(0..offset + 1)), &mut None, window, cx)
-            .unwrap_or_default()
-            .rfind('\n')
-            .map(|i| i + 1)
-            .unwrap_or(0);
-        line
+        let row = self.text.offset_to_row(self.cursor_offset());
+        self.text.line_start_offset(row)
     }
 
     /// Get start line of selection start or end (The min value).
     ///
     /// This is means is always get the first line of selection.
-    fn start_of_line_of_selection(&mut self, window: &mut Window, cx: &mut Context<Self>) -> usize {
+    fn start_of_line_of_selection(&self) -> usize {
         if self.is_single_line() {
             return 0;
         }
 
-        let mut offset =
-            self.previous_boundary(self.selected_range.start.min(self.selected_range.end));
-        if self.text.chars().nth(offset) == Some('\r') {
-            offset += 1;
-        }
-
-        let line = self
-            .text_for_range(self.range_to_utf16(&(0..offset + 1)), &mut None, window, cx)
-            .unwrap_or_default()
-            .rfind('\n')
-            .map(|i| i + 1)
-            .unwrap_or(0);
-        line
+        let offset = self.selected_range.start.min(self.selected_range.end);
+        let row = self.text.offset_to_row(offset);
+        self.text.line_start_offset(row)
     }
 
     /// Get end of line
-    fn end_of_line(&mut self, window: &mut Window, cx: &mut Context<Self>) -> usize {
+    fn end_of_line(&self) -> usize {
         if self.is_single_line() {
-            return self.text.len();
-        }
-
-        let offset = self.next_boundary(self.cursor_offset());
-        // ignore if offset is "\n"
-        if self
-            .text_for_range(
-                self.range_to_utf16(&(offset.saturating_sub(1)..offset)),
-                &mut None,
-                window,
-                cx,
-            )
-            .unwrap_or_default()
-            .eq("\n")
-        {
-            return offset;
+            return self.text.len_bytes();
         }
 
-        let line = self
-            .text_for_range(
-                self.range_to_utf16(&(offset..self.text.len())),
-                &mut None,
-                window,
-                cx,
-            )
-            .unwrap_or_default()
-            .find('\n')
-            .map(|i| i + offset)
-            .unwrap_or(self.text.len());
-        line
+        let row = self.text.offset_to_row(self.cursor_offset());
+        self.text.line_end_offset(row)
     }
 
     /// Get indent string of next line.
     ///
     /// To get current and next line indent, to return more depth one.
-    pub(super) fn indent_of_next_line(
-        &mut self,
-        window: &mut Window,
-        cx: &mut Context<Self>,
-    ) -> String {
+    pub(super) fn indent_of_next_line(&self) -> String {
         if self.is_single_line() {
             return "".into();
         }
 
-        let mut current_indent = String::new();
-        let mut next_indent = String::new();
-        let current_line_start_pos = self.start_of_line(window, cx);
-        let next_line_start_pos = self.end_of_line(window, cx);
-        for c in self.text.chars().skip(current_line_start_pos) {
-            if !c.is_whitespace() {
-                break;
-            }
-            if c == '\n' || c == '\r' {
-                break;
-            }
-            current_indent.push(c);
-        }
+        let indent_of_row = |row: usize| -> String {
+            self.text
+                .slice_line(row)
+                .chars()
+                .take_while(|c| c.is_whitespace() && *c != '\r')
+                .collect()
+        };
 
-        for c in self.text.chars().skip(next_line_start_pos) {
-            if !c.is_whitespace() {
-                break;
-            }
-            if c == '\n' || c == '\r' {
-                break;
-            }
-            next_indent.push(c);
-        }
+        let row = self.text.offset_to_row(self.cursor_offset());
+        let current_indent = indent_of_row(row);
+        let next_indent = if row + 1 < self.text.lines_len() {
+            indent_of_row(row + 1)
+        } else {
+            String::new()
+        };
 
         if next_indent.len() > current_indent.len() {
             return next_indent;
@@ -1204,6 +1611,28 @@ impl InputState {
     }
 
     pub(super) fn backspace(&mut self, _: &Backspace, window: &mut Window, cx: &mut Context<Self>) {
+        if !self.extra_selections.is_empty() {
+            let ranges = self
+                .all_selections()
+                .into_iter()
+                .map(|range| {
+                    if range.is_empty() {
+                        self.previous_boundary(range.start)..range.start
+                    } else {
+                        range
+                    }
+                })
+                .collect();
+            self.replace_text_at_selections(ranges, "", window, cx);
+            self.pause_blink_cursor(cx);
+            return;
+        }
+
+        if self.delete_bracket_pair(window, cx) {
+            self.pause_blink_cursor(cx);
+            return;
+        }
+
         if self.selected_range.is_empty() {
             self.select_to(self.previous_boundary(self.cursor_offset()), window, cx)
         }
@@ -1212,6 +1641,23 @@ impl InputState {
     }
 
     pub(super) fn delete(&mut self, _: &Delete, window: &mut Window, cx: &mut Context<Self>) {
+        if !self.extra_selections.is_empty() {
+            let ranges = self
+                .all_selections()
+                .into_iter()
+                .map(|range| {
+                    if range.is_empty() {
+                        range.start..self.next_boundary(range.start)
+                    } else {
+                        range
+                    }
+                })
+                .collect();
+            self.replace_text_at_selections(ranges, "", window, cx);
+            self.pause_blink_cursor(cx);
+            return;
+        }
+
         if self.selected_range.is_empty() {
             self.select_to(self.next_boundary(self.cursor_offset()), window, cx)
         }
@@ -1225,7 +1671,7 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let mut offset = self.start_of_line(window, cx);
+        let mut offset = self.start_of_line();
         if offset == self.cursor_offset() {
             offset = offset.saturating_sub(1);
         }
@@ -1245,9 +1691,9 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let mut offset = self.end_of_line(window, cx);
+        let mut offset = self.end_of_line();
         if offset == self.cursor_offset() {
-            offset = (offset + 1).clamp(0, self.text.len());
+            offset = (offset + 1).clamp(0, self.text.len_bytes());
         }
         self.replace_text_in_range(
             Some(self.range_to_utf16(&(self.cursor_offset()..offset))),
@@ -1291,17 +1737,16 @@ impl InputState {
     }
 
     pub(super) fn enter(&mut self, action: &Enter, window: &mut Window, cx: &mut Context<Self>) {
+        if self.confirm_completion(window, cx) {
+            return;
+        }
+
         if self.is_multi_line() {
-            // Get current line indent
-            let indent = if self.mode.is_code_editor() {
-                self.indent_of_next_line(window, cx)
+            if self.mode.is_code_editor() {
+                self.newline_and_indent(window, cx);
             } else {
-                "".to_string()
-            };
-
-            // Add newline and indent
-            let new_line_text = format!("\n{}", indent);
-            self.replace_text_in_range(None, &new_line_text, window, cx);
+                self.replace_text_in_range(None, "\n", window, cx);
+            }
         }
 
         cx.emit(InputEvent::PressEnter {
@@ -1315,11 +1760,21 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        self.indent(false, window, cx);
-    }
-
-    pub(super) fn indent_block(&mut self, _: &Indent, window: &mut Window, cx: &mut Context<Self>) {
-        self.indent(true, window, cx);
+        if self.confirm_completion(window, cx) {
+            return;
+        }
+        if self.apply_ghost_text(window, cx) {
+            return;
+        }
+        if self.move_to_tab_stop(false, window, cx) {
+            return;
+        }
+
+        self.indent(false, window, cx);
+    }
+
+    pub(super) fn indent_block(&mut self, _: &Indent, window: &mut Window, cx: &mut Context<Self>) {
+        self.indent(true, window, cx);
     }
 
     pub(super) fn outdent_inline(
@@ -1328,6 +1783,10 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
+        if self.move_to_tab_stop(true, window, cx) {
+            return;
+        }
+
         self.outdent(false, window, cx);
     }
 
@@ -1351,7 +1810,7 @@ impl InputState {
         let is_selected = !self.selected_range.is_empty();
 
         if is_selected || block {
-            let start_offset = self.start_of_line_of_selection(window, cx);
+            let start_offset = self.start_of_line_of_selection();
             let mut offset = start_offset;
 
             let selected_text = self
@@ -1407,7 +1866,7 @@ impl InputState {
         let is_selected = !self.selected_range.is_empty();
 
         if is_selected || block {
-            let start_offset = self.start_of_line_of_selection(window, cx);
+            let start_offset = self.start_of_line_of_selection();
             let mut offset = start_offset;
 
             let selected_text = self
@@ -1445,9 +1904,9 @@ impl InputState {
         } else {
             // Selected none
             let start_offset = self.selected_range.start;
-            let offset = self.start_of_line_of_selection(window, cx);
+            let offset = self.start_of_line_of_selection();
             if self
-                .text_for_range_utf8(offset..self.text.len())
+                .text_for_range_utf8(offset..offset + tab_indent.len())
                 .starts_with(tab_indent.as_ref())
             {
                 self.replace_text_in_range(
@@ -1468,6 +1927,26 @@ impl InputState {
     }
 
     pub(super) fn escape(&mut self, _: &Escape, window: &mut Window, cx: &mut Context<Self>) {
+        if self.hide_completion_menu(cx) {
+            return;
+        }
+        if self.clear_ghost_text(cx) {
+            return;
+        }
+        if self.finish_snippet(cx) {
+            return;
+        }
+        if self.vim_escape(window, cx) {
+            return;
+        }
+        self.emacs_mark = false;
+
+        if !self.extra_selections.is_empty() {
+            self.extra_selections.clear();
+            cx.notify();
+            return;
+        }
+
         if self.marked_range.is_some() {
             self.unmark_text(window, cx);
         }
@@ -1496,6 +1975,29 @@ impl InputState {
             }
         }
 
+        // Click or drag the minimap to scroll.
+        self.minimap_dragging = false;
+        if self.scroll_to_minimap_position(event.position, cx) {
+            self.minimap_dragging = true;
+            return;
+        }
+
+        // Click the sticky line to move to the header of the scope.
+        if self.click_sticky_row(event.position, window, cx) {
+            return;
+        }
+
+        // Click the decoration icon in the gutter.
+        if self.click_gutter_decoration(event.position, window, cx) {
+            return;
+        }
+
+        // Click the fold marker in the gutter to fold or unfold the region.
+        if let Some(row) = self.fold_marker_row_for_position(event.position) {
+            self.toggle_fold_at_row(row, cx);
+            return;
+        }
+
         self.selecting = true;
         let offset = self.index_for_mouse_position(event.position, window, cx);
         // Double click to select word
@@ -1504,6 +2006,13 @@ impl InputState {
             return;
         }
 
+        // Alt + click to add a cursor, and alt + drag to select a box.
+        if event.modifiers.alt && self.is_multi_line() {
+            self.add_cursor(offset, cx);
+            self.box_select_anchor = Some(offset);
+            return;
+        }
+
         if event.modifiers.shift {
             self.select_to(offset, window, cx);
         } else {
@@ -1518,7 +2027,9 @@ impl InputState {
         _cx: &mut Context<Self>,
     ) {
         self.selecting = false;
+        self.minimap_dragging = false;
         self.selected_word_range = None;
+        self.box_select_anchor = None;
     }
 
     pub(super) fn on_mouse_move(
@@ -1528,25 +2039,19 @@ impl InputState {
         cx: &mut Context<Self>,
     ) {
         let offset = self.index_for_mouse_position(event.position, window, cx);
-        if let Some(marker) = self.mode.marker_for_offset(offset) {
-            if let Some(diagnostic_popover) = self.diagnostic_popover.as_ref() {
-                if diagnostic_popover.read(cx).marker.range == marker.range {
-                    diagnostic_popover.update(cx, |this, cx| {
-                        this.show(cx);
-                    });
+        let over_text = self.last_bounds.map_or(false, |bounds| {
+            bounds.contains(&event.position)
+                && event.position.x >= bounds.left() + self.line_number_width
+        }) && !self
+            .minimap_bounds()
+            .map_or(false, |bounds| bounds.contains(&event.position));
 
-                    return;
-                }
-            }
+        if !self.show_marker_popover(offset, cx) {
+            self.check_to_hide_hover(over_text.then_some(offset), event.position, cx);
+        }
 
-            self.diagnostic_popover = Some(DiagnosticPopover::new(marker, cx.entity(), cx));
-            cx.notify();
-        } else {
-            if let Some(diagnostic_popover) = self.diagnostic_popover.as_mut() {
-                diagnostic_popover.update(cx, |this, cx| {
-                    this.check_to_hide(event.position, cx);
-                })
-            }
+        if over_text && !self.selecting {
+            self.request_hover(offset, window, cx);
         }
     }
 
@@ -1563,10 +2068,14 @@ impl InputState {
             .unwrap_or(window.line_height());
         let delta = event.delta.pixel_delta(line_height);
         self.update_scroll_offset(Some(self.scroll_handle.offset() + delta), cx);
-        self.diagnostic_popover = None;
+        self.hide_hover(cx);
     }
 
-    fn update_scroll_offset(&mut self, offset: Option<Point<Pixels>>, cx: &mut Context<Self>) {
+    pub(super) fn update_scroll_offset(
+        &mut self,
+        offset: Option<Point<Pixels>>,
+        cx: &mut Context<Self>,
+    ) {
         let mut offset = offset.unwrap_or(self.scroll_handle.offset());
 
         let safe_y_range =
@@ -1590,24 +2099,51 @@ impl InputState {
     }
 
     pub(super) fn copy(&mut self, _: &Copy, _: &mut Window, cx: &mut Context<Self>) {
+        self.emacs_mark = false;
         if self.selected_range.is_empty() {
             return;
         }
 
-        let selected_text = self
-            .text_for_range_utf8(self.selected_range.clone())
-            .to_string();
+        let selected_text = self.text_for_range_utf8(self.selected_range.clone());
         cx.write_to_clipboard(ClipboardItem::new_string(selected_text));
     }
 
+    /// Copy the selected text (or the entire text if nothing is selected) with the highlight styles,
+    /// only for the code editor.
+    ///
+    /// The HTML is written as the metadata of the plain text, because GPUI clipboard has no HTML format,
+    /// other apps can only paste the plain text.
+    pub(super) fn copy_as_rich_text(
+        &mut self,
+        _: &CopyAsRichText,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.emacs_mark = false;
+        let range = if self.selected_range.is_empty() {
+            0..self.text.len_bytes()
+        } else {
+            self.selected_range.clone()
+        };
+        if range.is_empty() {
+            return;
+        }
+
+        let text = self.text_for_range_utf8(range.clone());
+        let Some(html) = self.mode.export(range, ExportFormat::Html, cx) else {
+            cx.write_to_clipboard(ClipboardItem::new_string(text));
+            return;
+        };
+        cx.write_to_clipboard(ClipboardItem::new_string_with_metadata(text, html));
+    }
+
     pub(super) fn cut(&mut self, _: &Cut, window: &mut Window, cx: &mut Context<Self>) {
+        self.emacs_mark = false;
         if self.selected_range.is_empty() {
             return;
         }
 
-        let selected_text = self
-            .text_for_range_utf8(self.selected_range.clone())
-            .to_string();
+        let selected_text = self.text_for_range_utf8(self.selected_range.clone());
         cx.write_to_clipboard(ClipboardItem::new_string(selected_text));
         self.replace_text_in_range(None, "", window, cx);
     }
@@ -1619,10 +2155,277 @@ impl InputState {
                 new_text = new_text.replace('\n', "");
             }
 
+            // Paste one line for each cursor if the lines count matches the cursors.
+            if !self.extra_selections.is_empty() && self.marked_range.is_none() {
+                let selections = self.all_selections();
+                if let Some(lines) = split_for_cursors(&new_text, selections.len()) {
+                    let edits = selections.into_iter().zip(lines).collect();
+                    return self.replace_text_at_cursors(edits, window, cx);
+                }
+            }
+
+            let was_empty = self.text.len_bytes() == 0;
             self.replace_text_in_range(None, &new_text, window, cx);
+            if was_empty {
+                self.update_detected_language(cx);
+            }
         }
     }
 
+    /// Return all the selections (the primary and the additional), sorted and merged.
+    pub(super) fn all_selections(&self) -> Vec<Range<usize>> {
+        let mut selections = self.extra_selections.clone();
+        selections.push(self.selected_range.clone());
+        merge_selections(selections)
+    }
+
+    /// Return the ranges of the IME marked text at all the cursors, sorted by the offset.
+    ///
+    /// The marked text is only tracked for the primary cursor, the additional cursors
+    /// are kept at the end of their marked text, see [`Self::replace_and_mark_text_in_range`].
+    fn marked_ranges(&self) -> Vec<Range<usize>> {
+        let Some(marked_range) = self.marked_range.clone() else {
+            return vec![];
+        };
+
+        let mut ranges = self
+            .extra_selections
+            .iter()
+            .map(|extra| extra.end.saturating_sub(marked_range.len())..extra.end)
+            .collect::<Vec<_>>();
+        ranges.push(marked_range);
+        merge_selections(ranges)
+    }
+
+    /// Apply the cursor motion `f` to each of the selections (multiple cursors).
+    ///
+    /// The `f` moves the primary selection, e.g.: by [`Self::move_to`] or [`Self::select_to`],
+    /// the additional selections share the direction of the primary selection.
+    fn move_each_selection(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+        f: impl Fn(&mut Self, &mut Window, &mut Context<Self>),
+    ) {
+        let extra_selections = std::mem::take(&mut self.extra_selections);
+        if extra_selections.is_empty() {
+            return f(self, window, cx);
+        }
+
+        let selected_range = self.selected_range.clone();
+        let selection_reversed = self.selection_reversed;
+        let preferred_x_offset = self.preferred_x_offset;
+
+        let mut selections = Vec::with_capacity(extra_selections.len());
+        for range in extra_selections {
+            self.selected_range = range;
+            self.selection_reversed = selection_reversed;
+            self.preferred_x_offset = None;
+            f(self, window, cx);
+            selections.push(self.selected_range.clone());
+        }
+
+        self.selected_range = selected_range;
+        self.selection_reversed = selection_reversed;
+        self.preferred_x_offset = preferred_x_offset;
+        f(self, window, cx);
+
+        let primary = self.selected_range.clone();
+        selections.retain(|range| {
+            *range != primary && !(range.start < primary.end && primary.start < range.end)
+        });
+        self.extra_selections = merge_selections(selections);
+    }
+
+    /// Add a cursor at the offset, the current primary cursor will be kept as an additional cursor.
+    fn add_cursor(&mut self, offset: usize, cx: &mut Context<Self>) {
+        let offset = offset.min(self.text.len_bytes());
+        let mut selections = self.all_selections();
+        selections.retain(|range| !(range.start <= offset && offset <= range.end));
+
+        self.extra_selections = selections;
+        self.selected_range = offset..offset;
+        self.selection_reversed = false;
+        self.marked_range = None;
+        self.pause_blink_cursor(cx);
+        cx.notify();
+    }
+
+    /// Add a selection for the next occurrence of the current selected text.
+    ///
+    /// If there is no selected text, select the word at the cursor.
+    pub(super) fn select_next_occurrence(
+        &mut self,
+        _: &SelectNextOccurrence,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.selected_range.is_empty() {
+            self.select_word(self.cursor_offset(), window, cx);
+            self.selected_word_range = None;
+            return;
+        }
+
+        let query = self.text_for_range_utf8(self.selected_range.clone());
+        let selections = self.all_selections();
+        let Some(range) = next_occurrence(
+            &self.text.to_string(),
+            &query,
+            self.selected_range.end,
+            &selections,
+        ) else {
+            return;
+        };
+
+        self.extra_selections = selections;
+        self.selected_range = range;
+        self.selection_reversed = false;
+        self.pause_blink_cursor(cx);
+        cx.notify();
+    }
+
+    /// Replace the text of all the `selections` with the `new_text` for multiple cursors editing,
+    /// and then put a cursor after each of the new text.
+    ///
+    /// The changes are pushed into the history at same time, so they will be undone in one step.
+    fn replace_text_at_selections(
+        &mut self,
+        selections: Vec<Range<usize>>,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let edits = merge_selections(selections)
+            .into_iter()
+            .map(|range| (range, new_text.to_string()))
+            .collect();
+        self.replace_text_at_cursors(edits, window, cx);
+    }
+
+    /// Replace each range of the `edits` (sorted and not overlapped) with its new text for multiple
+    /// cursors editing, and then put a cursor after each of the new text.
+    fn replace_text_at_cursors(
+        &mut self,
+        edits: Vec<(Range<usize>, String)>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let edits = edits
+            .into_iter()
+            .map(|(range, new_text)| {
+                let len = new_text.len();
+                (range, new_text, len..len)
+            })
+            .collect();
+        self.replace_text_with_selections(edits, window, cx);
+    }
+
+    /// Replace each range of the `edits` (sorted and not overlapped) with its new text, and then
+    /// select the selection of each edit (relative to the start of its new text).
+    ///
+    /// The edit with an empty range and an empty new text only moves the cursor.
+    pub(super) fn replace_text_with_selections(
+        &mut self,
+        edits: Vec<(Range<usize>, String, Range<usize>)>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.disabled {
+            return;
+        }
+
+        self.hide_completion_menu(cx);
+        let primary_ix = edits
+            .iter()
+            .position(|(range, _, _)| {
+                range.start <= self.cursor_offset() && self.cursor_offset() <= range.end
+            })
+            .unwrap_or(edits.len().saturating_sub(1));
+
+        // Replace from the last one, so the offsets of the previous selections are not changed.
+        let mut changed = false;
+        self.history.start_group();
+        for (range, new_text, _) in edits.iter().rev() {
+            if range.is_empty() && new_text.is_empty() {
+                continue;
+            }
+            self.push_history(range, new_text, window, cx);
+            self.edit_text(range, new_text, cx);
+            changed = true;
+        }
+        self.history.end_group();
+
+        let mut selections = selections_after_replace(&edits);
+        self.selected_range = selections.remove(primary_ix);
+        self.selection_reversed = false;
+        self.extra_selections = merge_selections(selections);
+
+        self.mode.clear_markers();
+        self.marked_range.take();
+        self.update_preferred_x_offset(cx);
+        self.update_scroll_offset(None, cx);
+        if changed {
+            self.mode.update_auto_grow(&self.text_wrapper);
+            cx.emit(InputEvent::Change);
+            self.refresh_diagnostics(window, cx);
+        }
+        cx.notify();
+    }
+
+    /// Replace the text in each range of the `edits` (sorted and not overlapped) with its new text.
+    ///
+    /// The changes are pushed into the history as one group, so they will be undone in one step,
+    /// and the cursor is moved to the end of the first new text.
+    pub(super) fn replace_text_in_ranges(
+        &mut self,
+        edits: Vec<(Range<usize>, String)>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.disabled || edits.is_empty() {
+            return;
+        }
+
+        self.hide_completion_menu(cx);
+        self.history.start_group();
+        for (range, new_text) in edits.iter().rev() {
+            self.push_history(range, new_text, window, cx);
+            self.edit_text(range, new_text, cx);
+        }
+        self.history.end_group();
+
+        let (range, new_text) = &edits[0];
+        let offset = range.start + new_text.len();
+        self.selected_range = offset..offset;
+        self.selection_reversed = false;
+        self.extra_selections.clear();
+
+        self.mode.clear_markers();
+        self.marked_range.take();
+        self.update_preferred_x_offset(cx);
+        self.update_scroll_offset(None, cx);
+        self.mode.update_auto_grow(&self.text_wrapper);
+        cx.emit(InputEvent::Change);
+        self.refresh_diagnostics(window, cx);
+        cx.notify();
+    }
+
+    /// Replace the `range` of the text with the `new_text`, and update the states that
+    /// anchored to the text (decorations, inlays, snippet, highlighter and wrapped lines),
+    /// the hover popover is hidden.
+    fn edit_text(&mut self, range: &Range<usize>, new_text: &str, cx: &mut Context<Self>) {
+        self.hide_hover(cx);
+        self.update_decorations_for_edit(range, new_text);
+        self.update_inlays_for_edit(range, new_text);
+        self.update_snippet_for_edit(range, new_text);
+        self.update_auto_closed_for_edit(range, new_text);
+        self.text.replace(range.clone(), new_text);
+        self.value.take();
+        self.mode.edit_highlighter(range, &self.text, new_text);
+        self.text_wrapper.update(&self.text, range, new_text, cx);
+        self.update_wrapped_inlays(cx);
+    }
+
     fn push_history(
         &mut self,
         range: &Range<usize>,
@@ -1640,34 +2443,83 @@ impl InputState {
 
         let new_range = range.start..range.start + new_text.len();
 
-        self.history.push(Change::new(
-            range.clone(),
-            &old_text,
-            new_range.clone(),
-            new_text,
-        ));
+        self.history.push(
+            Change::new(range.clone(), &old_text, new_range.clone(), new_text)
+                .selections(self.selected_range.clone(), self.extra_selections.clone()),
+        );
     }
 
     pub(super) fn undo(&mut self, _: &Undo, window: &mut Window, cx: &mut Context<Self>) {
-        self.history.ignore = true;
         if let Some(changes) = self.history.undo() {
-            for change in changes {
-                let range_utf16 = self.range_to_utf16(&change.new_range);
-                self.replace_text_in_range(Some(range_utf16), &change.old_text, window, cx);
+            self.apply_history_changes(
+                changes
+                    .iter()
+                    .map(|change| (change.new_range.clone(), change.old_text.as_str())),
+                window,
+                cx,
+            );
+
+            // Restore all the cursors before the multiple cursors editing.
+            if let Some(change) = changes
+                .last()
+                .filter(|change| !change.extra_selections.is_empty())
+            {
+                self.selected_range = change.selected_range.clone();
+                self.selection_reversed = false;
+                self.extra_selections = change.extra_selections.clone();
             }
         }
-        self.history.ignore = false;
     }
 
     pub(super) fn redo(&mut self, _: &Redo, window: &mut Window, cx: &mut Context<Self>) {
-        self.history.ignore = true;
         if let Some(changes) = self.history.redo() {
-            for change in changes {
-                let range_utf16 = self.range_to_utf16(&change.old_range);
-                self.replace_text_in_range(Some(range_utf16), &change.new_text, window, cx);
-            }
+            self.apply_history_changes(
+                changes
+                    .iter()
+                    .map(|change| (change.old_range.clone(), change.new_text.as_str())),
+                window,
+                cx,
+            );
         }
-        self.history.ignore = false;
+    }
+
+    /// Replace the text of the changes undone or redone in one step, in the order of the changes,
+    /// and emit the change event once.
+    ///
+    /// The cursor is moved to the end of the last new text.
+    fn apply_history_changes<'a>(
+        &mut self,
+        edits: impl Iterator<Item = (Range<usize>, &'a str)>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.disabled {
+            return;
+        }
+
+        let mut offset = None;
+        for (range, new_text) in edits {
+            self.edit_text(&range, new_text, cx);
+            offset = Some(range.start + new_text.len());
+        }
+        let Some(offset) = offset else {
+            return;
+        };
+
+        self.hide_completion_menu(cx);
+        self.mode.clear_markers();
+        self.selected_range = offset..offset;
+        self.selection_reversed = false;
+        self.extra_selections.clear();
+        self.marked_range.take();
+        self.emacs_mark = false;
+        self.selection_stack.clear();
+        self.update_preferred_x_offset(cx);
+        self.update_scroll_offset(None, cx);
+        self.mode.update_auto_grow(&self.text_wrapper);
+        cx.emit(InputEvent::Change);
+        self.refresh_diagnostics(window, cx);
+        cx.notify();
     }
 
     /// Move the cursor to the given offset.
@@ -1675,9 +2527,12 @@ impl InputState {
     /// The offset is the UTF-8 offset.
     ///
     /// Ensure the offset use self.next_boundary or self.previous_boundary to get the correct offset.
-    fn move_to(&mut self, offset: usize, _: &mut Window, cx: &mut Context<Self>) {
-        let offset = offset.clamp(0, self.text.len());
+    pub(super) fn move_to(&mut self, offset: usize, _: &mut Window, cx: &mut Context<Self>) {
+        let offset = self.offset_out_of_folds(offset.clamp(0, self.text.len_bytes()));
         self.selected_range = offset..offset;
+        self.extra_selections.clear();
+        self.clear_ghost_text(cx);
+        self.hide_completion_menu(cx);
         self.pause_blink_cursor(cx);
         self.update_preferred_x_offset(cx);
         cx.notify()
@@ -1702,7 +2557,7 @@ impl InputState {
         _cx: &App,
     ) -> usize {
         // If the text is empty, always return 0
-        if self.text.is_empty() {
+        if self.text.len_bytes() == 0 {
             return 0;
         }
 
@@ -1726,10 +2581,12 @@ impl InputState {
         // - included the scroll offset.
         let inner_position = position - bounds.origin - point(self.line_number_width, px(0.));
 
-        let mut index = 0;
-        let mut y_offset = px(0.);
+        let mut index = last_layout.visible_range_offset.start;
+        let mut y_offset = last_layout.visible_top;
 
-        for (_, line) in last_layout.lines.iter().enumerate() {
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            // The rows between the lines may be hidden by the folded regions.
+            index = last_layout.line_offsets[ix];
             let line_origin = self.line_origin_with_y_offset(&mut y_offset, &line, line_height);
             let pos = inner_position - line_origin;
 
@@ -1738,14 +2595,15 @@ impl InputState {
                 return line.unwrapped_layout.closest_index_for_x(pos.x);
             }
 
+            // The index in the line layout is mapped to the text offset, to skip the inlays.
             let index_result = line.closest_index_for_position(pos, line_height);
             if let Ok(v) = index_result {
-                index += v;
+                index += last_layout.offset_for_index(ix, v);
                 break;
             } else if let Ok(_) = line.index_for_position(point(px(0.), pos.y), line_height) {
                 // Click in the this line but not in the text, move cursor to the end of the line.
                 // The fallback index is saved in Err from `index_for_position` method.
-                index += index_result.unwrap_err();
+                index += last_layout.offset_for_index(ix, index_result.unwrap_err());
                 break;
             } else if line.text.trim_end_matches(|c| c == '\r').len() == 0 {
                 // empty line on Windows is `\r`, other is ''
@@ -1754,20 +2612,20 @@ impl InputState {
                     size: gpui::size(bounds.size.width, line_height),
                 };
                 let pos = inner_position;
-                index += line.len();
+                index += last_layout.offset_for_index(ix, line.len());
                 if line_bounds.contains(&pos) {
                     break;
                 }
             } else {
-                index += line.len();
+                index += last_layout.offset_for_index(ix, line.len());
             }
 
             // +1 for revert `lines` split `\n`
             index += 1;
         }
 
-        if index > self.text.len() {
-            self.text.len()
+        if index > self.text.len_bytes() {
+            self.text.len_bytes()
         } else {
             index
         }
@@ -1800,7 +2658,8 @@ impl InputState {
     ///
     /// Ensure the offset use self.next_boundary or self.previous_boundary to get the correct offset.
     fn select_to(&mut self, offset: usize, _: &mut Window, cx: &mut Context<Self>) {
-        let offset = offset.clamp(0, self.text.len());
+        let offset = self.offset_out_of_folds(offset.clamp(0, self.text.len_bytes()));
+        self.extra_selections.clear();
         if self.selection_reversed {
             self.selected_range.start = offset
         } else {
@@ -1832,7 +2691,7 @@ impl InputState {
     /// The offset is the UTF-8 offset.
     ///
     /// FIXME: When click on a non-word character, the word is not selected.
-    fn select_word(&mut self, offset: usize, window: &mut Window, cx: &mut Context<Self>) {
+    fn select_word(&mut self, offset: usize, _: &mut Window, cx: &mut Context<Self>) {
         #[inline(always)]
         fn is_word(c: char) -> bool {
             c.is_alphanumeric() || matches!(c, '_')
@@ -1840,17 +2699,9 @@ impl InputState {
 
         let mut start = offset;
         let mut end = start;
-        let prev_text = self
-            .text_for_range(self.range_to_utf16(&(0..start)), &mut None, window, cx)
-            .unwrap_or_default();
-        let next_text = self
-            .text_for_range(
-                self.range_to_utf16(&(end..self.text.len())),
-                &mut None,
-                window,
-                cx,
-            )
-            .unwrap_or_default();
+        let row = self.text.offset_to_row(offset);
+        let prev_text = self.text_for_range_utf8(self.text.line_start_offset(row)..start);
+        let next_text = self.text_for_range_utf8(end..self.text.line_end_offset(row));
 
         let prev_chars = prev_text.chars().rev();
         let next_chars = next_text.chars();
@@ -1890,33 +2741,11 @@ impl InputState {
     }
 
     pub(super) fn offset_from_utf16(&self, offset: usize) -> usize {
-        let mut utf8_offset = 0;
-        let mut utf16_count = 0;
-
-        for ch in self.text.chars() {
-            if utf16_count >= offset {
-                break;
-            }
-            utf16_count += ch.len_utf16();
-            utf8_offset += ch.len_utf8();
-        }
-
-        utf8_offset
+        self.text.offset_utf16_to_offset(offset)
     }
 
     pub(super) fn offset_to_utf16(&self, offset: usize) -> usize {
-        let mut utf16_offset = 0;
-        let mut utf8_count = 0;
-
-        for ch in self.text.chars() {
-            if utf8_count >= offset {
-                break;
-            }
-            utf8_count += ch.len_utf8();
-            utf16_offset += ch.len_utf16();
-        }
-
-        utf16_offset
+        self.text.offset_to_offset_utf16(offset)
     }
 
     pub(super) fn range_to_utf16(&self, range: &Range<usize>) -> Range<usize> {
@@ -1927,19 +2756,40 @@ impl InputState {
         self.offset_from_utf16(range_utf16.start)..self.offset_from_utf16(range_utf16.end)
     }
 
+    /// Return the range of the line (include the `\n`) that contains the offset.
+    fn line_range_with_newline(&self, offset: usize) -> Range<usize> {
+        let row = self.text.offset_to_row(offset);
+        self.text.line_start_offset(row)..self.text.line_start_offset(row + 1)
+    }
+
     fn previous_boundary(&self, offset: usize) -> usize {
+        let offset = self.text.clip_offset(offset);
+        if offset == 0 {
+            return 0;
+        }
+
+        // The grapheme before the offset must be in the line of `offset - 1`.
+        let line_range = self.line_range_with_newline(offset - 1);
+        let local_offset = offset - line_range.start;
         self.text
+            .text_for_range(line_range.clone())
             .grapheme_indices(true)
             .rev()
-            .find_map(|(idx, _)| (idx < offset).then_some(idx))
-            .unwrap_or(0)
+            .find_map(|(idx, _)| (idx < local_offset).then_some(idx))
+            .map(|idx| line_range.start + idx)
+            .unwrap_or(line_range.start)
     }
 
     fn next_boundary(&self, offset: usize) -> usize {
+        let offset = self.text.clip_offset(offset);
+        let line_range = self.line_range_with_newline(offset);
+        let local_offset = offset - line_range.start;
         self.text
+            .text_for_range(line_range.clone())
             .grapheme_indices(true)
-            .find_map(|(idx, _)| (idx > offset).then_some(idx))
-            .unwrap_or(self.text.len())
+            .find_map(|(idx, _)| (idx > local_offset).then_some(idx))
+            .map(|idx| line_range.start + idx)
+            .unwrap_or(line_range.end)
     }
 
     /// Returns the true to let InputElement to render cursor, when Input is focused and current BlinkCursor is visible.
@@ -1956,6 +2806,7 @@ impl InputState {
 
     fn on_blur(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         self.unselect(window, cx);
+        self.hide_completion_menu(cx);
         self.blink_cursor.update(cx, |cursor, cx| {
             cursor.stop(cx);
         });
@@ -1971,8 +2822,18 @@ impl InputState {
         });
     }
 
-    pub(super) fn on_key_down(&mut self, _: &KeyDownEvent, _: &mut Window, cx: &mut Context<Self>) {
+    pub(super) fn on_key_down(
+        &mut self,
+        event: &KeyDownEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
         self.pause_blink_cursor(cx);
+
+        // Do not insert the keys typed in the normal and visual mode of Vim.
+        if self.handle_vim_key(event, window, cx) {
+            cx.stop_propagation();
+        }
     }
 
     pub(super) fn on_drag_move(
@@ -1981,7 +2842,12 @@ impl InputState {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        if self.text.is_empty() {
+        if self.minimap_dragging {
+            self.scroll_to_minimap_position(event.position, cx);
+            return;
+        }
+
+        if self.text.len_bytes() == 0 {
             return;
         }
 
@@ -1998,6 +2864,17 @@ impl InputState {
         }
 
         let offset = self.index_for_mouse_position(event.position, window, cx);
+        if let Some(anchor) = self.box_select_anchor {
+            if offset != anchor {
+                let mut selections = box_selections(&self.text, anchor, offset);
+                self.selected_range = selections.pop().unwrap_or(offset..offset);
+                self.selection_reversed = false;
+                self.extra_selections = merge_selections(selections);
+                cx.notify();
+            }
+            return;
+        }
+
         self.select_to(offset, window, cx);
     }
 
@@ -2052,21 +2929,17 @@ impl InputState {
         cx.notify();
     }
 
-    pub(super) fn set_input_bounds(&mut self, new_bounds: Bounds<Pixels>, cx: &mut Context<Self>) {
-        let wrap_width_changed = self.input_bounds.size.width != new_bounds.size.width;
+    pub(super) fn set_input_bounds(&mut self, new_bounds: Bounds<Pixels>) {
         self.input_bounds = new_bounds;
+    }
 
-        // Update text_wrapper wrap_width if changed.
-        if wrap_width_changed {
-            self.text_wrapper
-                .set_wrap_width(Some(new_bounds.size.width), cx);
-            self.mode.update_auto_grow(&self.text_wrapper);
-        }
+    pub(super) fn text_for_range_utf8(&self, range: impl Into<Range<usize>>) -> String {
+        self.text.text_for_range(range.into())
     }
 
-    fn text_for_range_utf8(&mut self, range: impl Into<Range<usize>>) -> &str {
-        let range = self.range_from_utf16(&self.range_to_utf16(&range.into()));
-        &self.text[range]
+    /// Return true if the text need to be validated or masked before changed.
+    fn need_validate(&self) -> bool {
+        self.validate.is_some() || self.pattern.is_some() || !self.mask_pattern.is_none()
     }
 }
 
@@ -2080,7 +2953,7 @@ impl EntityInputHandler for InputState {
     ) -> Option<String> {
         let range = self.range_from_utf16(&range_utf16);
         adjusted_range.replace(self.range_to_utf16(&range));
-        Some(self.text[range].to_string())
+        Some(self.text.text_for_range(range))
     }
 
     fn selected_text_range(
@@ -2124,37 +2997,72 @@ impl EntityInputHandler for InputState {
             return;
         }
 
+        // Typing the bracket pairs, or the closing bracket to dedent the line.
+        if range_utf16.is_none()
+            && self.marked_range.is_none()
+            && (self.handle_bracket_input(new_text, window, cx)
+                || self.handle_closing_bracket_indent(new_text, window, cx))
+        {
+            return;
+        }
+
+        // Typing with multiple cursors, or committing the IME input at multiple cursors.
+        if range_utf16.is_none() && !self.extra_selections.is_empty() {
+            let selections = if self.marked_range.is_some() {
+                self.marked_ranges()
+            } else {
+                self.all_selections()
+            };
+            return self.replace_text_at_selections(selections, new_text, window, cx);
+        }
+
         let range = range_utf16
             .as_ref()
             .map(|range_utf16| self.range_from_utf16(range_utf16))
             .or(self.marked_range.clone())
             .unwrap_or(self.selected_range.clone());
 
-        let pending_text: SharedString = (self.text_for_range_utf8(0..range.start).to_owned()
-            + new_text
-            + self.text_for_range_utf8(range.end..self.text.len()))
-        .into();
-        // Check if the new text is valid
-        if !self.is_valid_input(&pending_text) {
-            return;
-        }
+        let new_offset = if self.need_validate() {
+            let pending_text = self.text_for_range_utf8(0..range.start)
+                + new_text
+                + &self.text_for_range_utf8(range.end..self.text.len_bytes());
+            // Check if the new text is valid
+            if !self.is_valid_input(&pending_text) {
+                return;
+            }
+
+            let mask_text = self.mask_pattern.mask(&pending_text);
+            let new_text_len =
+                (new_text.len() + mask_text.len()).saturating_sub(pending_text.len());
+            let new_offset = (range.start + new_text_len).min(mask_text.len());
 
-        let mask_text = self.mask_pattern.mask(&pending_text);
-        let new_text_len = (new_text.len() + mask_text.len()).saturating_sub(pending_text.len());
-        let new_offset = (range.start + new_text_len).min(mask_text.len());
+            // Only replace the changed part of the masked text.
+            let (changed_range, changed_text) = changed_range(&self.text.to_string(), &mask_text);
+            self.push_history(&changed_range, changed_text, window, cx);
+            self.edit_text(&changed_range, changed_text, cx);
+            new_offset
+        } else {
+            self.push_history(&range, &new_text, window, cx);
+            self.edit_text(&range, new_text, cx);
+            range.start + new_text.len()
+        };
 
-        self.push_history(&range, &new_text, window, cx);
-        self.text = mask_text.clone();
-        self.mode
-            .update_highlighter(&range, self.text.clone(), &new_text, cx);
         self.mode.clear_markers();
-        self.text_wrapper.update(self.text.clone(), false, cx);
         self.selected_range = new_offset..new_offset;
+        self.extra_selections.clear();
         self.marked_range.take();
+        self.emacs_mark = false;
+        self.selection_stack.clear();
         self.update_preferred_x_offset(cx);
         self.update_scroll_offset(None, cx);
         self.mode.update_auto_grow(&self.text_wrapper);
-        cx.emit(InputEvent::Change(self.unmask_value()));
+        cx.emit(InputEvent::Change);
+        self.refresh_diagnostics(window, cx);
+        if range_utf16.is_none() {
+            self.handle_completion_input(new_text, window, cx);
+        } else {
+            self.hide_completion_menu(cx);
+        }
         cx.notify();
     }
 
@@ -2176,34 +3084,74 @@ impl EntityInputHandler for InputState {
             .map(|range_utf16| self.range_from_utf16(range_utf16))
             .or(self.marked_range.clone())
             .unwrap_or(self.selected_range.clone());
-        let pending_text: SharedString = (self.text_for_range_utf8(0..range.start).to_owned()
-            + new_text
-            + self.text_for_range_utf8(range.end..self.text.len()))
-        .into();
-        if !self.is_valid_input(&pending_text) {
-            return;
+        if self.need_validate() {
+            let pending_text = self.text_for_range_utf8(0..range.start)
+                + new_text
+                + &self.text_for_range_utf8(range.end..self.text.len_bytes());
+            if !self.is_valid_input(&pending_text) {
+                return;
+            }
         }
 
-        self.push_history(&range, new_text, window, cx);
-        self.text = pending_text;
-        self.mode
-            .update_highlighter(&range, self.text.clone(), &new_text, cx);
+        // The marked text is also replaced at the additional cursors, e.g.: the mirrors of a snippet tab stop.
+        let ranges = if self.marked_range.as_ref() == Some(&range) {
+            self.marked_ranges()
+        } else if self.marked_range.is_none() && range == self.selected_range {
+            self.all_selections()
+        } else {
+            vec![range.clone()]
+        };
+
+        let new_len = new_text.len();
+        let selection = new_selected_range_utf16
+            .as_ref()
+            .filter(|_| !new_text.is_empty())
+            .map(|range_utf16| {
+                // The selected range is relative to the new text.
+                let new_text = Rope::from_str(new_text);
+                new_text.offset_utf16_to_offset(range_utf16.start)
+                    ..new_text.offset_utf16_to_offset(range_utf16.end)
+            })
+            .unwrap_or(new_len..new_len);
+        let primary_ix = ranges
+            .iter()
+            .position(|edit_range| *edit_range == range)
+            .unwrap_or_default();
+        let edits = ranges
+            .into_iter()
+            .enumerate()
+            .map(|(ix, range)| {
+                let selection = if ix == primary_ix {
+                    selection.clone()
+                } else {
+                    // Keep the additional cursors at the end of the marked text.
+                    new_len..new_len
+                };
+                (range, new_text.to_string(), selection)
+            })
+            .collect::<Vec<_>>();
+
+        // Replace from the last one, so the offsets of the previous edits are not changed.
+        for (range, new_text, _) in edits.iter().rev() {
+            self.push_history(range, new_text, window, cx);
+            self.edit_text(range, new_text, cx);
+        }
         self.mode.clear_markers();
-        self.text_wrapper.update(self.text.clone(), false, cx);
+
+        let mut selections = selections_after_replace(&edits);
+        let primary = selections.remove(primary_ix);
+        let start = primary.start - edits[primary_ix].2.start;
+        self.extra_selections = selections;
+        self.selected_range = primary;
         if new_text.is_empty() {
             // Cancel selection, when cancel IME input.
-            self.selected_range = range.start..range.start;
             self.marked_range = None;
         } else {
-            self.marked_range = Some(range.start..range.start + new_text.len());
-            self.selected_range = new_selected_range_utf16
-                .as_ref()
-                .map(|range_utf16| self.range_from_utf16(range_utf16))
-                .map(|new_range| new_range.start + range.start..new_range.end + range.end)
-                .unwrap_or_else(|| range.start + new_text.len()..range.start + new_text.len());
+            self.marked_range = Some(start..start + new_len);
         }
         self.mode.update_auto_grow(&self.text_wrapper);
-        cx.emit(InputEvent::Change(self.unmask_value()));
+        cx.emit(InputEvent::Change);
+        self.refresh_diagnostics(window, cx);
         cx.notify();
     }
 
@@ -2223,31 +3171,33 @@ impl EntityInputHandler for InputState {
         let mut start_origin = None;
         let mut end_origin = None;
         let line_number_origin = point(self.line_number_width, px(0.));
-        let mut y_offset = px(0.);
-        let mut index_offset = 0;
+        let mut y_offset = last_layout.visible_top;
 
-        for line in last_layout.lines.iter() {
+        for (ix, line) in last_layout.lines.iter().enumerate() {
+            let index_offset = last_layout.line_offsets[ix];
             if start_origin.is_some() && end_origin.is_some() {
                 break;
             }
+            if range.end < index_offset {
+                break;
+            }
 
-            if start_origin.is_none() {
-                if let Some(p) =
-                    line.position_for_index(range.start.saturating_sub(index_offset), line_height)
-                {
+            if start_origin.is_none() && range.start >= index_offset {
+                let index =
+                    last_layout.index_for_offset(ix, range.start.saturating_sub(index_offset));
+                if let Some(p) = line.position_for_index(index, line_height) {
                     start_origin = Some(p + point(px(0.), y_offset));
                 }
             }
 
-            if end_origin.is_none() {
-                if let Some(p) =
-                    line.position_for_index(range.end.saturating_sub(index_offset), line_height)
-                {
+            if end_origin.is_none() && range.end >= index_offset {
+                let index =
+                    last_layout.index_for_offset(ix, range.end.saturating_sub(index_offset));
+                if let Some(p) = line.position_for_index(index, line_height) {
                     end_origin = Some(p + point(px(0.), y_offset));
                 }
             }
 
-            index_offset += line.len() + 1;
             y_offset += line.size(line_height).height;
         }
 
@@ -2266,20 +3216,14 @@ impl EntityInputHandler for InputState {
     fn character_index_for_point(
         &mut self,
         point: gpui::Point<Pixels>,
-        _window: &mut Window,
-        _cx: &mut Context<Self>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
     ) -> Option<usize> {
-        let last_layout = self.last_layout.as_ref()?;
-        let line_height = last_layout.line_height;
-        let line_point = self.last_bounds?.localize(&point)?;
-
-        for line in last_layout.lines.iter() {
-            if let Ok(utf8_index) = line.index_for_position(line_point, line_height) {
-                return Some(self.offset_to_utf16(utf8_index));
-            }
-        }
+        self.last_layout.as_ref()?;
+        self.last_bounds?.localize(&point)?;
 
-        None
+        let offset = self.index_for_mouse_position(point, window, cx);
+        Some(self.offset_to_utf16(offset))
     }
 }
 
@@ -2290,10 +3234,15 @@ impl Focusable for InputState {
 }
 
 impl Render for InputState {
-    fn render(&mut self, _: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        self.text_wrapper.update(self.text.clone(), false, cx);
-        self.mode
-            .update_highlighter(&(0..0), self.text.clone(), "", cx);
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        if self.text_wrapper.is_empty() {
+            self.text_wrapper.update_all(&self.text, cx);
+        }
+        self.parse_syntax(cx);
+        // Request the diagnostics for the initial text.
+        if self.diagnostics_provider.is_some() && self.diagnostics_task.is_none() {
+            self.refresh_diagnostics(window, cx);
+        }
 
         div()
             .id("text-element")
@@ -2302,6 +3251,185 @@ impl Render for InputState {
             .flex_grow()
             .overflow_x_hidden()
             .child(TextElement::new(cx.entity().clone()).placeholder(self.placeholder.clone()))
-            .children(self.diagnostic_popover.clone())
+            .children(self.hover_popover.clone())
+            .children(self.completion_menu.clone())
+            .children(
+                self.search_panel
+                    .clone()
+                    .filter(|search_panel| search_panel.read(cx).open),
+            )
+    }
+}
+
+/// Return the changed range in the `old` text and the text that replaces it to get the `new` text,
+/// by skipping the common prefix and suffix of the texts.
+fn changed_range<'a>(old: &str, new: &'a str) -> (Range<usize>, &'a str) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((ix, _), _)| ix);
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+
+    (prefix..old.len() - suffix, &new[prefix..new.len() - suffix])
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::{EntityInputHandler as _, TestAppContext};
+
+    use super::{changed_range, Redo, Undo};
+    use crate::input::{new_state, GutterDecoration, InlayHint};
+
+    #[test]
+    fn test_changed_range() {
+        assert_eq!(changed_range("123-456", "123-4567"), (7..7, "7"));
+        assert_eq!(changed_range("1234567", "123-4567"), (3..3, "-"));
+        assert_eq!(changed_range("123-4567", "123-456"), (7..8, ""));
+        assert_eq!(changed_range("你好", "你们好"), (3..3, "们"));
+        assert_eq!(changed_range("abc", "abc"), (3..3, ""));
+        assert_eq!(changed_range("", "abc"), (0..0, "abc"));
+    }
+
+    #[gpui::test]
+    fn test_ime_input_moves_decorations_and_inlays(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state.code_editor("text").default_value("a\nb\nc")
+        });
+        state.update_in(cx, |state, window, cx| {
+            state.set_gutter_decorations(vec![GutterDecoration::new(2)], window, cx);
+            state.set_inlay_hints(vec![InlayHint::new(4, ": i32")], window, cx);
+
+            // Delete the first line by the IME.
+            state.replace_and_mark_text_in_range(Some(0..2), "", None, window, cx);
+            assert_eq!(state.value(), "b\nc");
+            assert_eq!(state.gutter_decorations()[0].line, 1);
+            assert_eq!(state.inlay_hints()[0].offset, 2);
+
+            // Compose the text at the start.
+            state.selected_range = 0..0;
+            state.replace_and_mark_text_in_range(None, "に", None, window, cx);
+            state.replace_and_mark_text_in_range(None, "日本", None, window, cx);
+            assert_eq!(state.value(), "日本b\nc");
+            assert_eq!(state.marked_range, Some(0..6));
+            assert_eq!(state.selected_range, 6..6);
+            assert_eq!(state.gutter_decorations()[0].line, 1);
+            assert_eq!(state.inlay_hints()[0].offset, 8);
+
+            state.replace_text_in_range(None, "日本語", window, cx);
+            assert_eq!(state.value(), "日本語b\nc");
+            assert_eq!(state.marked_range, None);
+            assert_eq!(state.inlay_hints()[0].offset, 11);
+        });
+    }
+
+    #[gpui::test]
+    fn test_inlay_hints_in_one_line(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .code_editor("text")
+                .default_value("let a = 1;\nlet b = 2;")
+        });
+        state.update_in(cx, |state, window, cx| {
+            state.set_inlay_hints(
+                vec![
+                    InlayHint::new(100, ": i32"),
+                    InlayHint::new(5, ": Foo<\n  T>"),
+                ],
+                window,
+                cx,
+            );
+            assert_eq!(state.inlay_hints()[0].offset, 5);
+            assert_eq!(state.inlay_hints()[0].label, ": Foo<   T>");
+            assert_eq!(state.inlay_hints()[1].offset, 21);
+            assert_eq!(state.text_wrapper.lines.len(), 2);
+        });
+    }
+
+    #[gpui::test]
+    fn test_ime_input_in_snippet(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| state.code_editor("text").default_value(""));
+        state.update_in(cx, |state, window, cx| {
+            state.insert_snippet("${1:foo} ${1:foo}$0", window, cx);
+            assert_eq!(state.selected_range, 0..3);
+            assert_eq!(state.extra_selections, vec![4..7]);
+
+            // The marked text is typed at the mirror of the tab stop.
+            state.replace_and_mark_text_in_range(None, "n", None, window, cx);
+            state.replace_and_mark_text_in_range(None, "ni", None, window, cx);
+            assert_eq!(state.value(), "ni ni");
+            assert_eq!(state.extra_selections, vec![5..5]);
+
+            state.replace_text_in_range(None, "に", window, cx);
+            assert_eq!(state.value(), "に に");
+            assert_eq!(state.selected_range, 3..3);
+            assert_eq!(state.extra_selections, vec![7..7]);
+            assert!(state.snippet.is_some());
+        });
+    }
+
+    #[gpui::test]
+    fn test_set_value_keeps_decorations(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state.code_editor("text").default_value("a\nb\nc")
+        });
+        state.update_in(cx, |state, window, cx| {
+            state.set_gutter_decorations(
+                vec![GutterDecoration::new(1), GutterDecoration::new(2)],
+                window,
+                cx,
+            );
+            state.set_value("foo\nbar", window, cx);
+            assert_eq!(state.gutter_decorations().len(), 1);
+            assert_eq!(state.gutter_decorations()[0].line, 1);
+        });
+    }
+
+    #[gpui::test]
+    fn test_undo_multiple_cursors_edit_in_one_step(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state.code_editor("text").default_value("a\nb\nc")
+        });
+        state.update_in(cx, |state, window, cx| {
+            state.replace_text_in_range(None, "x", window, cx);
+            assert_eq!(state.value(), "xa\nb\nc");
+
+            // Type at 3 cursors right after the previous change.
+            state.selected_range = 2..2;
+            state.extra_selections = vec![4..4, 6..6];
+            state.replace_text_in_range(None, "y", window, cx);
+            assert_eq!(state.value(), "xay\nby\ncy");
+
+            state.undo(&Undo, window, cx);
+            assert_eq!(state.value(), "xa\nb\nc");
+            state.undo(&Undo, window, cx);
+            assert_eq!(state.value(), "a\nb\nc");
+        });
+    }
+
+    #[gpui::test]
+    fn test_undo_replace_all_more_than_max_undo(cx: &mut TestAppContext) {
+        let text = "a ".repeat(1500);
+        let (state, cx) = new_state(cx, |state| {
+            state.code_editor("text").default_value(text.clone())
+        });
+        state.update_in(cx, |state, window, cx| {
+            let edits = (0..1500)
+                .map(|ix| (ix * 2..ix * 2 + 1, "bb".to_string()))
+                .collect();
+            state.replace_text_in_ranges(edits, window, cx);
+            assert_eq!(state.value().as_ref(), "bb ".repeat(1500));
+
+            state.undo(&Undo, window, cx);
+            assert_eq!(state.value().as_ref(), text);
+            state.redo(&Redo, window, cx);
+            assert_eq!(state.value().as_ref(), "bb ".repeat(1500));
+        });
     }
 }
@@ -0,0 +1,103 @@
+//! The sticky scroll of the code editor, the headers of the enclosing scopes of the first
+//! visible line are pinned at the top of the viewport.
+//!
+//! The scopes are the foldable ranges of the syntax tree, see [`super::fold_map::FoldMap`].
+use std::ops::Range;
+
+use gpui::{Context, Pixels, Point, Window};
+
+use super::{InputState, RopeExt as _};
+
+/// The max number of the sticky lines.
+pub(super) const MAX_STICKY_ROWS: usize = 5;
+
+/// Return the header rows of the `ranges` to pin at the top, outer first.
+///
+/// - `ranges` are the foldable row ranges sorted by the start, outer first for the same start.
+/// - `row_at` returns the row that is displayed at the `ix` line of the viewport.
+///
+/// Each sticky line covers a line of the viewport, so the scope must contain the row
+/// under the sticky lines to be pinned.
+pub(super) fn sticky_rows(
+    ranges: &[Range<usize>],
+    max_rows: usize,
+    row_at: impl Fn(usize) -> usize,
+) -> Vec<usize> {
+    let mut rows: Vec<usize> = vec![];
+    while rows.len() < max_rows {
+        let top = row_at(rows.len());
+        let Some(range) = ranges.iter().find(|range| {
+            range.start < top
+                && top < range.end
+                && rows.last().map_or(true, |last| range.start > *last)
+        }) else {
+            break;
+        };
+        rows.push(range.start);
+    }
+
+    rows
+}
+
+impl InputState {
+    /// Return the sticky row at the mouse `position`.
+    pub(super) fn sticky_row_for_position(&self, position: Point<Pixels>) -> Option<usize> {
+        let last_layout = self.last_layout.as_ref()?;
+        if last_layout.sticky_rows.is_empty() || !self.input_bounds.contains(&position) {
+            return None;
+        }
+
+        let ix = ((position.y - self.input_bounds.top()) / last_layout.line_height).floor();
+        last_layout.sticky_rows.get(ix as usize).copied()
+    }
+
+    /// Click the sticky line to move the cursor to the start of the row.
+    ///
+    /// Returns true if a sticky line is clicked.
+    pub(super) fn click_sticky_row(
+        &mut self,
+        position: Point<Pixels>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(row) = self.sticky_row_for_position(position) else {
+            return false;
+        };
+
+        let offset = self.text.line_start_offset(row);
+        self.move_to(offset, window, cx);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sticky_rows;
+
+    #[test]
+    fn test_sticky_rows() {
+        // 0 impl Foo {
+        // 1     fn foo() {
+        // 2         if true {
+        // 3             foo();
+        // 4         }
+        // 5     }
+        // 6
+        // 7     fn bar() {
+        // 8         bar();
+        // 9     }
+        // 10 }
+        let ranges = vec![0..10, 1..5, 2..4, 7..9];
+
+        assert_eq!(sticky_rows(&ranges, 5, |ix| ix), Vec::<usize>::new());
+        assert_eq!(sticky_rows(&ranges, 5, |ix| 3 + ix), vec![0, 1]);
+        // The rows 1, 2 are covered by the sticky lines, so their scopes are pinned too.
+        assert_eq!(sticky_rows(&ranges, 5, |ix| 1 + ix), vec![0, 1, 2]);
+        assert_eq!(sticky_rows(&ranges, 2, |ix| 1 + ix), vec![0, 1]);
+        // The scope is ended.
+        assert_eq!(sticky_rows(&ranges, 5, |ix| 5 + ix), vec![0]);
+        assert_eq!(sticky_rows(&ranges, 5, |ix| 7 + ix), vec![0, 7]);
+        assert_eq!(sticky_rows(&ranges, 5, |ix| 10 + ix), Vec::<usize>::new());
+        assert_eq!(sticky_rows(&[], 5, |ix| 3 + ix), Vec::<usize>::new());
+    }
+}
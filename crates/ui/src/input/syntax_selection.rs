@@ -0,0 +1,150 @@
+//! Expand the selection to the enclosing syntax node, and shrink it back.
+//!
+//! Without a grammar (e.g.: the plain text), the selection is expanded to the word,
+//! the lines, the paragraph, and then the entire text.
+use std::ops::Range;
+
+use gpui::{Context, Window};
+use ropey::Rope;
+
+use super::{InputState, RopeExt as _, SelectLargerSyntaxNode, SelectSmallerSyntaxNode};
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_blank_line(text: &Rope, row: usize) -> bool {
+    text.slice_line(row).chars().all(char::is_whitespace)
+}
+
+/// Return the `range` extended to the word boundaries at the both ends.
+fn word_range(text: &Rope, range: &Range<usize>) -> Range<usize> {
+    let before = text
+        .chars_at(text.byte_to_char(range.start))
+        .reversed()
+        .take_while(|c| is_word_char(*c))
+        .map(char::len_utf8)
+        .sum::<usize>();
+    let after = text
+        .chars_at(text.byte_to_char(range.end))
+        .take_while(|c| is_word_char(*c))
+        .map(char::len_utf8)
+        .sum::<usize>();
+
+    range.start - before..range.end + after
+}
+
+/// Return the range of the paragraph (separated by the blank lines) from `first_row` to `last_row`.
+fn paragraph_range(text: &Rope, first_row: usize, last_row: usize) -> Range<usize> {
+    let mut start_row = first_row;
+    while start_row > 0 && !is_blank_line(text, start_row - 1) {
+        start_row -= 1;
+    }
+    let mut end_row = last_row;
+    while end_row + 1 < text.lines_len() && !is_blank_line(text, end_row + 1) {
+        end_row += 1;
+    }
+
+    text.line_start_offset(start_row)..text.line_end_offset(end_row)
+}
+
+/// Return the smallest range that contains the `range` and is larger than it, without the syntax tree.
+///
+/// In order: the word, the lines, the paragraph, and the entire text.
+pub(super) fn larger_text_range(text: &Rope, range: &Range<usize>) -> Option<Range<usize>> {
+    let first_row = text.offset_to_row(range.start);
+    let last_row = text.offset_to_row(range.end);
+
+    [
+        word_range(text, range),
+        text.line_start_offset(first_row)..text.line_end_offset(last_row),
+        paragraph_range(text, first_row, last_row),
+        0..text.len_bytes(),
+    ]
+    .into_iter()
+    .find(|larger| contains(larger, range) && larger.len() > range.len())
+}
+
+fn contains(range: &Range<usize>, inner: &Range<usize>) -> bool {
+    range.start <= inner.start && range.end >= inner.end
+}
+
+impl InputState {
+    /// Expand the selection to the enclosing syntax node, e.g.: word → expression → statement → block.
+    pub(super) fn select_larger_syntax_node(
+        &mut self,
+        _: &SelectLargerSyntaxNode,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let range = self.selected_range.clone();
+        // The selection has been changed since the last expand.
+        if self
+            .selection_stack
+            .last()
+            .is_some_and(|last| !contains(&range, last))
+        {
+            self.selection_stack.clear();
+        }
+
+        let Some(larger) = self
+            .mode
+            .larger_node_range(&range)
+            .or_else(|| larger_text_range(&self.text, &range))
+        else {
+            return;
+        };
+
+        self.selection_stack.push(range);
+        self.select_range(larger, cx);
+    }
+
+    /// Shrink the selection back to the one before the last expand.
+    pub(super) fn select_smaller_syntax_node(
+        &mut self,
+        _: &SelectSmallerSyntaxNode,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(smaller) = self.selection_stack.pop() else {
+            return;
+        };
+        if !contains(&self.selected_range, &smaller) {
+            self.selection_stack.clear();
+            return;
+        }
+
+        self.select_range(smaller, cx);
+    }
+
+    fn select_range(&mut self, range: Range<usize>, cx: &mut Context<Self>) {
+        self.selected_range = range;
+        self.selection_reversed = false;
+        self.extra_selections.clear();
+        self.update_scroll_offset(None, cx);
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_larger_text_range() {
+        let text = Rope::from_str("foo bar_baz qux\nline 2\n\nline 4");
+        let expand = |range: Range<usize>| larger_text_range(&text, &range);
+
+        assert_eq!(expand(5..5), Some(4..11));
+        assert_eq!(expand(4..11), Some(0..15));
+        assert_eq!(expand(0..15), Some(0..22));
+        assert_eq!(expand(0..22), Some(0..30));
+        assert_eq!(expand(0..30), None);
+
+        assert_eq!(expand(3..3), Some(0..3));
+        assert_eq!(expand(3..5), Some(0..11));
+        assert_eq!(expand(28..28), Some(24..28));
+        assert_eq!(expand(24..28), Some(24..30));
+        assert_eq!(expand(24..30), Some(0..30));
+    }
+}
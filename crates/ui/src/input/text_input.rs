@@ -174,14 +174,16 @@ impl RenderOnce for TextInput {
 
         let prefix = self.prefix;
         let suffix = self.suffix;
-        let show_clear_button =
-            self.cleanable && !state.loading && !state.text.is_empty() && state.is_single_line();
+        let show_clear_button = self.cleanable
+            && !state.loading
+            && state.text.len_bytes() > 0
+            && state.is_single_line();
         let has_suffix = suffix.is_some() || state.loading || self.mask_toggle || show_clear_button;
 
         div()
             .id(("input", self.state.entity_id()))
             .flex()
-            .key_context(crate::input::CONTEXT)
+            .key_context(state.key_context())
             .track_focus(&state.focus_handle)
             .when(!state.disabled, |this| {
                 this.on_action(window.listener_for(&self.state, InputState::backspace))
@@ -198,12 +200,24 @@ impl RenderOnce for TextInput {
                     .on_action(window.listener_for(&self.state, InputState::cut))
                     .on_action(window.listener_for(&self.state, InputState::undo))
                     .on_action(window.listener_for(&self.state, InputState::redo))
+                    .on_action(window.listener_for(&self.state, InputState::show_completions))
+                    .on_action(window.listener_for(&self.state, InputState::set_mark))
                     .when(state.is_multi_line(), |this| {
                         this.on_action(window.listener_for(&self.state, InputState::indent_inline))
                             .on_action(window.listener_for(&self.state, InputState::outdent_inline))
                             .on_action(window.listener_for(&self.state, InputState::indent_block))
                             .on_action(window.listener_for(&self.state, InputState::outdent_block))
                     })
+                    .when(state.mode.is_code_editor(), |this| {
+                        this.on_action(
+                            window.listener_for(&self.state, InputState::find_and_replace),
+                        )
+                        .on_action(
+                            window.listener_for(&self.state, InputState::move_to_matching_bracket),
+                        )
+                        .on_action(window.listener_for(&self.state, InputState::toggle_comment))
+                        .on_action(window.listener_for(&self.state, InputState::accept_ghost_text))
+                    })
             })
             .on_action(window.listener_for(&self.state, InputState::left))
             .on_action(window.listener_for(&self.state, InputState::right))
@@ -216,8 +230,11 @@ impl RenderOnce for TextInput {
                     .on_action(window.listener_for(&self.state, InputState::select_down))
                     .on_action(window.listener_for(&self.state, InputState::page_up))
                     .on_action(window.listener_for(&self.state, InputState::page_down))
+                    .on_action(window.listener_for(&self.state, InputState::select_next_occurrence))
             })
             .on_action(window.listener_for(&self.state, InputState::select_all))
+            .on_action(window.listener_for(&self.state, InputState::select_larger_syntax_node))
+            .on_action(window.listener_for(&self.state, InputState::select_smaller_syntax_node))
             .on_action(window.listener_for(&self.state, InputState::select_to_start_of_line))
             .on_action(window.listener_for(&self.state, InputState::select_to_end_of_line))
             .on_action(window.listener_for(&self.state, InputState::select_to_previous_word))
@@ -232,6 +249,16 @@ impl RenderOnce for TextInput {
             .on_action(window.listener_for(&self.state, InputState::select_to_end))
             .on_action(window.listener_for(&self.state, InputState::show_character_palette))
             .on_action(window.listener_for(&self.state, InputState::copy))
+            .when(state.mode.is_code_editor(), |this| {
+                this.on_action(window.listener_for(&self.state, InputState::find))
+                    .on_action(window.listener_for(&self.state, InputState::copy_as_rich_text))
+                    .on_action(window.listener_for(&self.state, InputState::find_next))
+                    .on_action(window.listener_for(&self.state, InputState::find_previous))
+                    .on_action(window.listener_for(&self.state, InputState::fold))
+                    .on_action(window.listener_for(&self.state, InputState::unfold))
+                    .on_action(window.listener_for(&self.state, InputState::fold_all))
+                    .on_action(window.listener_for(&self.state, InputState::unfold_all))
+            })
             .on_key_down(window.listener_for(&self.state, InputState::on_key_down))
             .on_mouse_down(
                 MouseButton::Left,
@@ -315,5 +342,16 @@ impl RenderOnce for TextInput {
                     this
                 }
             })
+            .when_some(state.vim_status(), |this, status| {
+                this.relative().child(
+                    div()
+                        .absolute()
+                        .right_3()
+                        .bottom_1()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(status),
+                )
+            })
     }
 }
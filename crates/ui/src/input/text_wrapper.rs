@@ -1,102 +1,741 @@
-use std::ops::Range;
+use std::{collections::HashSet, ops::Range};
 
-use gpui::{App, Font, LineFragment, Pixels, SharedString};
+use gpui::{App, Font, LineFragment, LineWrapper, Pixels, SharedString};
+use ropey::Rope;
 
-#[allow(unused)]
-pub(super) struct LineWrap {
-    /// The number of soft wrapped lines of this line (Not include first line.)
-    pub(super) wrap_lines: usize,
-    /// The range of the line text in the entire text.
-    pub(super) range: Range<usize>,
+use super::{
+    fold_map::FoldMap,
+    inlay::{offset_after_edit, offset_for_index},
+    RopeExt as _,
+};
+
+/// The soft wrapped info of a line (split by `\n`).
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LineItem {
+    /// The soft wrapped lines, the range is relative to the start of the line.
+    ///
+    /// There is at least one item, if the line is not wrapped.
+    pub(super) wrapped_lines: Vec<Range<usize>>,
+    /// The line is hidden by a folded region.
+    pub(super) hidden: bool,
 }
 
-impl LineWrap {
-    pub(super) fn height(&self, line_height: Pixels) -> Pixels {
-        line_height * (self.wrap_lines + 1)
+impl LineItem {
+    /// The number of the soft wrapped lines, at least 1 if the line is not hidden.
+    pub(super) fn lines_len(&self) -> usize {
+        if self.hidden {
+            return 0;
+        }
+
+        self.wrapped_lines.len().max(1)
     }
 }
 
+/// A display position of soft wrapped text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct DisplayPoint {
+    /// The line index (0-based, split by `\n`).
+    pub(super) row: usize,
+    /// The soft wrapped line index in the line (0-based).
+    pub(super) wrap_row: usize,
+    /// The index of the soft wrapped line in the entire text (0-based).
+    pub(super) display_row: usize,
+}
+
 /// Used to prepare the text with soft_wrap to be get lines to displayed in the TextArea
 ///
 /// After use lines to calculate the scroll size of the TextArea
+///
+/// The lines are updated incrementally by [`TextWrapper::update`] when the text is changed,
+/// only the changed lines will be re-wrapped.
 pub(super) struct TextWrapper {
-    pub(super) text: SharedString,
-    /// The wrapped lines, value is start and end index of the line.
-    pub(super) wrapped_lines: Vec<Range<usize>>,
+    pub(super) text: Rope,
     /// The lines by split \n
-    pub(super) lines: Vec<LineWrap>,
+    pub(super) lines: Vec<LineItem>,
     pub(super) font: Font,
     pub(super) font_size: Pixels,
     /// If is none, it means the text is not wrapped
     pub(super) wrap_width: Option<Pixels>,
+    /// The code folding state, the hidden lines are not displayed.
+    pub(super) folds: FoldMap,
+    /// The inlays (offset, text) displayed in the text sorted by the offset, they are wrapped with the text.
+    inlays: Vec<(usize, SharedString)>,
+    /// The soft wrapped lines count of the `lines`.
+    index: LineIndex,
+    /// The hidden rows of the `lines`, sorted by the start row.
+    hidden: Vec<Range<usize>>,
 }
 
-#[allow(unused)]
 impl TextWrapper {
     pub(super) fn new(font: Font, font_size: Pixels, wrap_width: Option<Pixels>) -> Self {
         Self {
-            text: SharedString::default(),
+            text: Rope::new(),
             font,
             font_size,
             wrap_width,
-            wrapped_lines: Vec::new(),
             lines: Vec::new(),
+            folds: FoldMap::default(),
+            inlays: vec![],
+            index: LineIndex::default(),
+            hidden: vec![],
         }
     }
 
+    /// Return true if the text has not been wrapped yet.
+    pub(super) fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Return the total number of the soft wrapped lines.
+    pub(super) fn len(&self) -> usize {
+        self.index.total()
+    }
+
     pub(super) fn set_wrap_width(&mut self, wrap_width: Option<Pixels>, cx: &mut App) {
+        if self.wrap_width == wrap_width {
+            return;
+        }
+
         self.wrap_width = wrap_width;
-        self.update(self.text.clone(), true, cx);
+        self.update_all(&self.text.clone(), cx);
     }
 
     pub(super) fn set_font(&mut self, font: Font, font_size: Pixels, cx: &mut App) {
+        if self.font == font && self.font_size == font_size {
+            return;
+        }
+
         self.font = font;
         self.font_size = font_size;
-        self.update(self.text.clone(), true, cx);
+        self.update_all(&self.text.clone(), cx);
+    }
+
+    /// Re-wrap all lines of the `text`.
+    pub(super) fn update_all(&mut self, text: &Rope, cx: &mut App) {
+        let wrap_width = self.wrap_width.unwrap_or(Pixels::MAX);
+        let mut line_wrapper = cx
+            .text_system()
+            .line_wrapper(self.font.clone(), self.font_size);
+
+        self.text = text.clone();
+        let mut lines = Vec::with_capacity(text.lines_len());
+        for row in 0..text.lines_len() {
+            let line = text.slice_line(row).to_string();
+            let inlays = self.line_inlays(row);
+            lines.push(Self::wrap_line(
+                &mut line_wrapper,
+                &line,
+                &inlays,
+                wrap_width,
+            ));
+        }
+
+        self.index = LineIndex::new(&lines);
+        self.lines = lines;
+        self.hidden.clear();
+        self.apply_folds();
+    }
+
+    /// Set the inlays displayed in the text, only the lines that the inlays changed are re-wrapped.
+    pub(super) fn set_inlays(&mut self, inlays: Vec<(usize, SharedString)>, cx: &mut App) {
+        if self.inlays == inlays {
+            return;
+        }
+
+        let mut rows = {
+            let old: HashSet<_> = self.inlays.iter().collect();
+            let new: HashSet<_> = inlays.iter().collect();
+            old.symmetric_difference(&new)
+                .map(|(offset, _)| self.text.offset_to_row(*offset))
+                .collect::<Vec<_>>()
+        };
+        rows.sort();
+        rows.dedup();
+        self.inlays = inlays;
+        if self.lines.is_empty() {
+            return;
+        }
+
+        let wrap_width = self.wrap_width.unwrap_or(Pixels::MAX);
+        let mut line_wrapper = cx
+            .text_system()
+            .line_wrapper(self.font.clone(), self.font_size);
+        for row in rows.into_iter().filter(|row| *row < self.lines.len()) {
+            let line = self.text.slice_line(row).to_string();
+            let inlays = self.line_inlays(row);
+            let mut item = Self::wrap_line(&mut line_wrapper, &line, &inlays, wrap_width);
+            item.hidden = self.lines[row].hidden;
+            self.index.set(row, item.lines_len());
+            self.lines[row] = item;
+        }
+    }
+
+    /// Return the inlays (offset relative to the line start, text) of the line at the `row`.
+    pub(super) fn line_inlays(&self, row: usize) -> Vec<(usize, SharedString)> {
+        if self.inlays.is_empty() {
+            return vec![];
+        }
+
+        let start = self.text.line_start_offset(row);
+        let end = self.text.line_end_offset(row);
+        let ix = self.inlays.partition_point(|(offset, _)| *offset < start);
+        self.inlays[ix..]
+            .iter()
+            .take_while(|(offset, _)| *offset <= end)
+            .map(|(offset, text)| (offset - start, text.clone()))
+            .collect()
     }
 
-    /// Update the text wrapper and recalculate the wrapped lines.
+    /// Update the wrapped lines after the `range` (in the old text) was replaced by `new_text`.
     ///
-    /// If the `text` is the same as the current text, do nothing.
-    pub(super) fn update(&mut self, text: SharedString, force: bool, cx: &mut App) {
-        if self.text == text && !force {
+    /// The `text` is the new text after the replacement.
+    pub(super) fn update(
+        &mut self,
+        text: &Rope,
+        range: &Range<usize>,
+        new_text: &str,
+        cx: &mut App,
+    ) {
+        self.inlays.retain_mut(|(offset, _)| {
+            offset_after_edit(*offset, range, new_text.len())
+                .map(|new_offset| *offset = new_offset)
+                .is_some()
+        });
+
+        if self.lines.is_empty() || range.end > self.text.len_bytes() {
+            self.folds.unfold_all();
+            self.update_all(text, cx);
             return;
         }
 
-        let mut wrapped_lines = vec![];
-        let mut lines = vec![];
+        let start_row = self.text.offset_to_row(range.start);
+        let old_end_row = self.text.offset_to_row(range.end);
+        let new_end_row = text.offset_to_row(range.start + new_text.len());
+
         let wrap_width = self.wrap_width.unwrap_or(Pixels::MAX);
         let mut line_wrapper = cx
             .text_system()
             .line_wrapper(self.font.clone(), self.font_size);
 
-        let mut prev_line_ix = 0;
-        for line in text.split('\n') {
-            let mut line_wraps = vec![];
-            let mut prev_boundary_ix = 0;
+        self.text = text.clone();
+        let mut new_lines = Vec::with_capacity(new_end_row + 1 - start_row);
+        for row in start_row..=new_end_row {
+            let line = text.slice_line(row).to_string();
+            let inlays = self.line_inlays(row);
+            new_lines.push(Self::wrap_line(
+                &mut line_wrapper,
+                &line,
+                &inlays,
+                wrap_width,
+            ));
+        }
 
-            // Here only have wrapped line, if there is no wrap meet, the `line_wraps` result will empty.
-            for boundary in line_wrapper.wrap_line(&[LineFragment::text(line)], wrap_width) {
-                line_wraps.push(prev_boundary_ix..boundary.ix);
-                prev_boundary_ix = boundary.ix;
+        let old_end_row = old_end_row.min(self.lines.len().saturating_sub(1));
+        let lines_lens = new_lines.iter().map(|line| line.lines_len()).collect();
+        self.lines.splice(start_row..=old_end_row, new_lines);
+        self.index.splice(start_row..old_end_row + 1, lines_lens);
+
+        // The hidden rows are moved with the lines, and the replaced lines are not hidden.
+        let mut hidden = Vec::with_capacity(self.hidden.len());
+        for rows in self.hidden.drain(..) {
+            if rows.start < start_row {
+                hidden.push(rows.start..rows.end.min(start_row));
+            }
+            if rows.end > old_end_row + 1 {
+                let start = rows.start.max(old_end_row + 1);
+                hidden
+                    .push(start + new_end_row - old_end_row..rows.end + new_end_row - old_end_row);
             }
+        }
+        self.hidden = hidden;
+        self.folds.edit(start_row, old_end_row, new_end_row);
+        self.apply_folds();
+    }
+
+    /// Set the foldable row ranges, the folded ranges that no longer foldable are unfolded.
+    pub(super) fn set_fold_ranges(&mut self, ranges: Vec<Range<usize>>) {
+        let folded = self.folds.folded().to_vec();
+        self.folds.set_ranges(ranges);
+        if self.folds.folded() != folded {
+            self.apply_folds();
+        }
+    }
+
+    /// Update the hidden state of the lines by the folded ranges, only the rows that
+    /// the hidden state changed are updated.
+    ///
+    /// This must be called after the [`FoldMap`] is changed.
+    pub(super) fn apply_folds(&mut self) {
+        let hidden = self.folds.hidden_rows();
+        for rows in subtract_rows(&self.hidden, &hidden) {
+            self.set_hidden(rows, false);
+        }
+        for rows in subtract_rows(&hidden, &self.hidden) {
+            self.set_hidden(rows, true);
+        }
+        self.hidden = hidden;
+    }
+
+    fn set_hidden(&mut self, rows: Range<usize>, hidden: bool) {
+        for row in rows {
+            let Some(line) = self.lines.get_mut(row) else {
+                break;
+            };
+            line.hidden = hidden;
+            self.index.set(row, line.lines_len());
+        }
+    }
+
+    /// Return true if the line at the `row` is hidden by a folded region.
+    pub(super) fn is_hidden(&self, row: usize) -> bool {
+        self.lines.get(row).map_or(false, |line| line.hidden)
+    }
+
+    /// Wrap the `line` with the `inlays` (offset relative to the line start, text) displayed in it.
+    fn wrap_line(
+        line_wrapper: &mut LineWrapper,
+        line: &str,
+        inlays: &[(usize, SharedString)],
+        wrap_width: Pixels,
+    ) -> LineItem {
+        let mut wrapped_lines = vec![];
+        let mut prev_boundary_ix = 0;
 
-            lines.push(LineWrap {
-                wrap_lines: line_wraps.len(),
-                range: prev_line_ix..prev_line_ix + line.len(),
-            });
+        let mut fragments = Vec::with_capacity(inlays.len() * 2 + 1);
+        let mut start = 0;
+        for (offset, text) in inlays {
+            fragments.push(LineFragment::text(&line[start..*offset]));
+            fragments.push(LineFragment::text(text));
+            start = *offset;
+        }
+        fragments.push(LineFragment::text(&line[start..]));
+        let inlay_lens = inlays
+            .iter()
+            .map(|(offset, text)| (*offset, text.len()))
+            .collect::<Vec<_>>();
 
-            wrapped_lines.extend(line_wraps);
-            // Reset of the line
-            if !line[prev_boundary_ix..].is_empty() || prev_boundary_ix == 0 {
-                wrapped_lines.push(prev_line_ix + prev_boundary_ix..prev_line_ix + line.len());
+        // Here only have wrapped line, if there is no wrap meet, the `wrapped_lines` result will empty.
+        for boundary in line_wrapper.wrap_line(&fragments, wrap_width) {
+            // The boundary in an inlay is moved to the offset of the inlay.
+            let ix = offset_for_index(&inlay_lens, boundary.ix);
+            if ix > prev_boundary_ix {
+                wrapped_lines.push(prev_boundary_ix..ix);
+                prev_boundary_ix = ix;
             }
+        }
+        // Reset of the line
+        wrapped_lines.push(prev_boundary_ix..line.len());
 
-            prev_line_ix += line.len() + 1;
+        LineItem {
+            wrapped_lines,
+            hidden: false,
         }
+    }
 
-        self.text = text;
-        self.wrapped_lines = wrapped_lines;
-        self.lines = lines;
+    /// Return the [`DisplayPoint`] of the given offset.
+    pub(super) fn display_point_for_offset(&self, offset: usize) -> DisplayPoint {
+        let row = self.text.offset_to_row(offset);
+        let row = row.min(self.lines.len().saturating_sub(1));
+        let local_offset = offset.saturating_sub(self.text.line_start_offset(row));
+
+        let display_row = self.index.prefix(row);
+
+        let wrap_row = self
+            .lines
+            .get(row)
+            .and_then(|line| {
+                line.wrapped_lines
+                    .iter()
+                    .position(|r| local_offset < r.end)
+                    .or(Some(line.lines_len().saturating_sub(1)))
+            })
+            .unwrap_or(0);
+
+        DisplayPoint {
+            row,
+            wrap_row,
+            display_row: display_row + wrap_row,
+        }
+    }
+
+    /// Return the [`DisplayPoint`] of the display row (the index of soft wrapped lines).
+    ///
+    /// The `display_row` will be clamped to the last line.
+    pub(super) fn display_point_for_display_row(&self, display_row: usize) -> DisplayPoint {
+        let total = self.index.total();
+        if display_row < total {
+            let (row, start) = self.index.find(display_row);
+            return DisplayPoint {
+                row,
+                wrap_row: display_row - start,
+                display_row,
+            };
+        }
+
+        let row = self.lines.len().saturating_sub(1);
+        let wrap_row = self
+            .lines
+            .last()
+            .map(|line| line.lines_len().saturating_sub(1))
+            .unwrap_or(0);
+        DisplayPoint {
+            row,
+            wrap_row,
+            display_row: total.saturating_sub(1),
+        }
+    }
+}
+
+/// Return the rows of the `a` that are not in the `b`, both of them are sorted and not overlapped.
+fn subtract_rows(a: &[Range<usize>], b: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut rows = vec![];
+    let mut ix = 0;
+    for range in a {
+        let mut start = range.start;
+        while ix < b.len() && b[ix].end <= start {
+            ix += 1;
+        }
+
+        for other in b[ix..].iter() {
+            if other.start >= range.end {
+                break;
+            }
+            if other.start > start {
+                rows.push(start..other.start);
+            }
+            start = start.max(other.end);
+        }
+        if start < range.end {
+            rows.push(start..range.end);
+        }
+    }
+    rows
+}
+
+/// The soft wrapped lines count of each line, used to find the display rows in `O(log n)`.
+///
+/// The counts are stored in chunks, with Fenwick trees of the rows and the counts of the
+/// chunks, so replacing some lines only updates the chunks of them.
+#[derive(Debug, Default, Clone)]
+struct LineIndex {
+    chunks: Vec<Vec<usize>>,
+    /// The number of the rows of each chunk.
+    rows: Fenwick,
+    /// The soft wrapped lines count of each chunk.
+    counts: Fenwick,
+}
+
+/// The number of the rows in a chunk of the [`LineIndex`], the chunk is split when it has
+/// more than twice of the rows.
+const CHUNK_SIZE: usize = 256;
+
+impl LineIndex {
+    fn new(lines: &[LineItem]) -> Self {
+        let mut this = Self::default();
+        this.splice(0..0, lines.iter().map(|line| line.lines_len()).collect());
+        this
+    }
+
+    /// Return the number of the lines.
+    fn len(&self) -> usize {
+        self.rows.total()
+    }
+
+    /// Return the total number of the soft wrapped lines.
+    fn total(&self) -> usize {
+        self.counts.total()
+    }
+
+    /// Return the chunk index and the row index in the chunk of the `row`.
+    ///
+    /// The `row` must be less than the [`Self::len`].
+    fn locate(&self, row: usize) -> (usize, usize) {
+        let (chunk_ix, start) = self.rows.find(row);
+        (chunk_ix, row - start)
+    }
+
+    /// Return the number of the soft wrapped lines before the `row`.
+    fn prefix(&self, row: usize) -> usize {
+        if row >= self.len() {
+            return self.total();
+        }
+
+        let (chunk_ix, ix) = self.locate(row);
+        self.counts.prefix(chunk_ix) + self.chunks[chunk_ix][..ix].iter().sum::<usize>()
+    }
+
+    /// Set the soft wrapped lines count of the `row`.
+    fn set(&mut self, row: usize, lines_len: usize) {
+        if row >= self.len() {
+            return;
+        }
+
+        let (chunk_ix, ix) = self.locate(row);
+        let old = std::mem::replace(&mut self.chunks[chunk_ix][ix], lines_len);
+        self.counts.update(chunk_ix, old, lines_len);
+    }
+
+    /// Replace the soft wrapped lines count of the `rows` with the `lines_lens`.
+    fn splice(&mut self, rows: Range<usize>, lines_lens: Vec<usize>) {
+        let len = self.len();
+        let rows = rows.start.min(len)..rows.end.min(len);
+
+        // The chunks that contain the `rows`, or the chunk to insert into.
+        let (first, start) = if rows.start < len {
+            self.locate(rows.start)
+        } else if let Some(last) = self.chunks.last() {
+            (self.chunks.len() - 1, last.len())
+        } else {
+            (0, 0)
+        };
+        let last = if rows.end > rows.start {
+            self.locate(rows.end - 1).0
+        } else {
+            first
+        };
+        let end = start + rows.len();
+
+        let mut items = Vec::with_capacity(CHUNK_SIZE + lines_lens.len());
+        let mut old_chunks = self
+            .chunks
+            .get(first..=last)
+            .unwrap_or(&[])
+            .iter()
+            .flatten();
+        items.extend(old_chunks.by_ref().take(start).copied());
+        items.extend(lines_lens);
+        items.extend(old_chunks.skip(end - start).copied());
+
+        // Keep the changed rows in one chunk if it is not too large, so the chunks are not changed.
+        let new_chunks: Vec<Vec<usize>> = if items.is_empty() {
+            vec![]
+        } else if items.len() <= CHUNK_SIZE * 2 {
+            vec![items]
+        } else {
+            items
+                .chunks(CHUNK_SIZE)
+                .map(|chunk| chunk.to_vec())
+                .collect()
+        };
+
+        let old_len = if self.chunks.is_empty() {
+            0
+        } else {
+            last + 1 - first
+        };
+        if new_chunks.len() == old_len {
+            for (ix, chunk) in new_chunks.into_iter().enumerate() {
+                let chunk_ix = first + ix;
+                let old = std::mem::replace(&mut self.chunks[chunk_ix], chunk);
+                let chunk = &self.chunks[chunk_ix];
+                self.rows.update(chunk_ix, old.len(), chunk.len());
+                self.counts
+                    .update(chunk_ix, old.iter().sum(), chunk.iter().sum());
+            }
+        } else {
+            self.chunks.splice(first..first + old_len, new_chunks);
+            self.rows = Fenwick::new(self.chunks.iter().map(|chunk| chunk.len()));
+            self.counts = Fenwick::new(self.chunks.iter().map(|chunk| chunk.iter().sum()));
+        }
+    }
+
+    /// Return the row that contains the `display_row` and the display row of the row start.
+    ///
+    /// The `display_row` must be less than the [`Self::total`].
+    fn find(&self, display_row: usize) -> (usize, usize) {
+        let (chunk_ix, mut start) = self.counts.find(display_row);
+        let mut row = self.rows.prefix(chunk_ix);
+        for lines_len in self.chunks[chunk_ix].iter() {
+            if start + lines_len > display_row {
+                break;
+            }
+            start += lines_len;
+            row += 1;
+        }
+
+        (row, start)
+    }
+}
+
+/// A Fenwick tree of the values, to get the prefix sums in `O(log n)`.
+#[derive(Debug, Default, Clone)]
+struct Fenwick {
+    tree: Vec<usize>,
+    total: usize,
+}
+
+impl Fenwick {
+    fn new(values: impl Iterator<Item = usize>) -> Self {
+        let mut tree = vec![0];
+        tree.extend(values);
+        let total = tree.iter().sum();
+        for ix in 1..tree.len() {
+            let parent = ix + (ix & ix.wrapping_neg());
+            if parent < tree.len() {
+                tree[parent] += tree[ix];
+            }
+        }
+
+        Self { tree, total }
+    }
+
+    fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Return the sum of the values before the `ix`.
+    fn prefix(&self, ix: usize) -> usize {
+        let mut ix = ix.min(self.tree.len().saturating_sub(1));
+        let mut sum = 0;
+        while ix > 0 {
+            sum += self.tree[ix];
+            ix &= ix - 1;
+        }
+        sum
+    }
+
+    /// Update the value at the `ix` from the `old` to the `new`.
+    fn update(&mut self, ix: usize, old: usize, new: usize) {
+        if old == new {
+            return;
+        }
+
+        let mut ix = ix + 1;
+        while ix < self.tree.len() {
+            self.tree[ix] = self.tree[ix] + new - old;
+            ix += ix & ix.wrapping_neg();
+        }
+        self.total = self.total + new - old;
+    }
+
+    /// Return the index of the value that contains the `target` and the sum of the values before it.
+    ///
+    /// The `target` must be less than the [`Self::total`].
+    fn find(&self, target: usize) -> (usize, usize) {
+        let mut pos = 0;
+        let mut sum = 0;
+        let mut step = self
+            .tree
+            .len()
+            .saturating_sub(1)
+            .checked_next_power_of_two()
+            .unwrap_or(0);
+        while step > 0 {
+            let next = pos + step;
+            if next < self.tree.len() && sum + self.tree[next] <= target {
+                pos = next;
+                sum += self.tree[next];
+            }
+            step >>= 1;
+        }
+
+        (pos, sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{subtract_rows, LineIndex, LineItem};
+
+    fn line(wrapped: usize, hidden: bool) -> LineItem {
+        LineItem {
+            wrapped_lines: (0..wrapped).map(|ix| ix..ix + 1).collect(),
+            hidden,
+        }
+    }
+
+    #[test]
+    fn test_line_index() {
+        let mut lines = vec![
+            line(1, false),
+            line(3, false),
+            line(2, true),
+            line(1, false),
+            line(2, false),
+        ];
+        let mut index = LineIndex::new(&lines);
+        assert_eq!(index.total(), 7);
+        assert_eq!(index.prefix(0), 0);
+        assert_eq!(index.prefix(2), 4);
+        assert_eq!(index.prefix(3), 4);
+        assert_eq!(index.prefix(5), 7);
+
+        assert_eq!(index.find(0), (0, 0));
+        assert_eq!(index.find(1), (1, 1));
+        assert_eq!(index.find(3), (1, 1));
+        // The hidden line is skipped.
+        assert_eq!(index.find(4), (3, 4));
+        assert_eq!(index.find(6), (4, 5));
+
+        lines[1] = line(1, false);
+        index.set(1, lines[1].lines_len());
+        assert_eq!(index.total(), 5);
+        assert_eq!(index.prefix(3), 2);
+        assert_eq!(index.find(2), (3, 2));
+        assert_eq!(index.find(4), (4, 3));
+    }
+
+    fn assert_line_index(index: &LineIndex, lines: &[LineItem]) {
+        let expected = LineIndex::new(lines);
+        assert_eq!(index.len(), lines.len());
+        assert_eq!(index.total(), expected.total());
+        for row in (0..=lines.len()).step_by(7) {
+            assert_eq!(index.prefix(row), expected.prefix(row), "prefix of {}", row);
+        }
+        for display_row in (0..expected.total()).step_by(5) {
+            assert_eq!(index.find(display_row), expected.find(display_row));
+        }
+    }
+
+    #[test]
+    fn test_line_index_splice() {
+        let mut lines = (0..1000)
+            .map(|ix| line(ix % 3 + 1, false))
+            .collect::<Vec<_>>();
+        let mut index = LineIndex::new(&lines);
+
+        // Insert a line.
+        lines.insert(10, line(2, false));
+        index.splice(10..10, vec![2]);
+        assert_line_index(&index, &lines);
+
+        // Insert lines that more than a chunk.
+        let new_lines = (0..600).map(|_| line(1, false)).collect::<Vec<_>>();
+        lines.splice(500..501, new_lines.clone());
+        index.splice(500..501, new_lines.iter().map(|l| l.lines_len()).collect());
+        assert_line_index(&index, &lines);
+
+        // Remove the lines across the chunks.
+        lines.drain(100..900);
+        index.splice(100..900, vec![]);
+        assert_line_index(&index, &lines);
+
+        // Append to the end.
+        lines.push(line(3, true));
+        index.splice(lines.len() - 1..lines.len() - 1, vec![0]);
+        assert_line_index(&index, &lines);
+
+        // Remove all lines.
+        let len = lines.len();
+        lines.clear();
+        index.splice(0..len, vec![]);
+        assert_eq!(index.len(), 0);
+        assert_eq!(index.total(), 0);
+        assert_eq!(index.prefix(0), 0);
+    }
+
+    #[test]
+    fn test_subtract_rows() {
+        assert_eq!(subtract_rows(&[1..5, 7..9], &[]), vec![1..5, 7..9]);
+        assert_eq!(subtract_rows(&[1..5, 7..9], &[1..5, 7..9]), vec![]);
+        assert_eq!(
+            subtract_rows(&[1..5, 7..9], &[2..3]),
+            vec![1..2, 3..5, 7..9]
+        );
+        assert_eq!(
+            subtract_rows(&[1..10], &[0..2, 4..5, 8..12]),
+            vec![2..4, 5..8]
+        );
+        assert_eq!(subtract_rows(&[2..3], &[0..1, 5..6]), vec![2..3]);
     }
 }
@@ -0,0 +1,1963 @@
+//! The Vim mode of the [`InputState`], enabled by [`super::KeymapMode::Vim`].
+//!
+//! The keys typed in the normal and visual mode are parsed as `["x][count]command`:
+//!
+//! - Motions: `h j k l w b e W B E 0 ^ $ gg G f F t T ; , % { }`
+//! - Operators: `d c y > <` followed by a motion, a text object (`iw aw i( a" ...`) or itself for the lines.
+//! - Commands: `x X D C s S Y ~ i a I A o O p P r J u . v V`
+//! - Registers: `"a` - `"z` (`"A` - `"Z` to append), `"0` the last yank, `"+` the clipboard, `"_` to discard.
+use std::{collections::HashMap, ops::Range};
+
+use gpui::{ClipboardItem, Context, EntityInputHandler as _, KeyDownEvent, SharedString, Window};
+use ropey::Rope;
+
+use super::{InputState, KeymapMode, RopeExt as _, Undo};
+
+/// The mode of the Vim keymap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VimMode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+impl VimMode {
+    /// The label to display in the mode indicator.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
+            Self::VisualLine => "VISUAL LINE",
+        }
+    }
+
+    fn is_visual(&self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    NextWordStart { big: bool },
+    NextWordEnd { big: bool },
+    PrevWordStart { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    FindChar { c: char, forward: bool, till: bool },
+    RepeatFind { reverse: bool },
+    MatchingBracket,
+    NextParagraph,
+    PrevParagraph,
+}
+
+/// How the range between the cursor and the motion target is operated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MotionKind {
+    /// The target char is not included.
+    Exclusive,
+    /// The target char is included.
+    Inclusive,
+    /// The entire lines are operated.
+    Linewise,
+}
+
+impl Motion {
+    fn kind(&self) -> MotionKind {
+        match self {
+            Self::Up | Self::Down | Self::FirstLine | Self::LastLine => MotionKind::Linewise,
+            Self::NextWordEnd { .. }
+            | Self::LineEnd
+            | Self::FindChar { forward: true, .. }
+            | Self::MatchingBracket => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ObjectKind {
+    Word { big: bool },
+    Pair(char, char),
+    Quote(char),
+}
+
+/// The text object, `around` is true for `a` (e.g.: `aw`), false for `i` (e.g.: `iw`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct TextObject {
+    kind: ObjectKind,
+    around: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    ToggleCase,
+}
+
+/// The target of the operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Target {
+    Motion(Motion),
+    Object(TextObject),
+    /// The lines by repeating the operator, e.g.: `dd`.
+    Line,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum InsertAt {
+    Cursor,
+    After,
+    LineStart,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Command {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// Operate the selection of the visual mode.
+    OperateSelection(Operator),
+    /// Select the text object in the visual mode.
+    SelectObject(TextObject),
+    Insert(InsertAt),
+    Paste {
+        before: bool,
+    },
+    ReplaceChar(char),
+    JoinLines,
+    Undo,
+    Repeat,
+    Visual {
+        line: bool,
+    },
+    /// Swap the cursor and the anchor of the visual selection.
+    SwapAnchor,
+}
+
+impl Command {
+    /// Return true if the command changes the text, can be repeated by `.`.
+    fn is_change(&self) -> bool {
+        match self {
+            Self::Operate(op, _) => *op != Operator::Yank,
+            Self::Insert(_) | Self::Paste { .. } | Self::ReplaceChar(_) | Self::JoinLines => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct VimCommand {
+    pub(super) register: Option<char>,
+    pub(super) count: Option<usize>,
+    pub(super) command: Command,
+}
+
+/// The result of parsing the typed keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Parsed<T> {
+    /// Need more keys.
+    Pending,
+    Invalid,
+    Done(T),
+}
+
+/// The max count of a command, the larger count is clamped to it.
+const MAX_COUNT: usize = 9_999;
+/// The max length of the text repeated by the count, e.g.: `3p`.
+const MAX_REPEAT_LEN: usize = 1024 * 1024;
+
+fn is_register(c: char) -> bool {
+    c.is_ascii_alphabetic() || matches!(c, '"' | '0' | '+' | '*' | '_')
+}
+
+fn parse_count(keys: &[char], ix: &mut usize) -> Option<usize> {
+    if !keys.get(*ix).is_some_and(|c| matches!(c, '1'..='9')) {
+        return None;
+    }
+
+    let start = *ix;
+    while keys.get(*ix).is_some_and(char::is_ascii_digit) {
+        *ix += 1;
+    }
+    let count = keys[start..*ix]
+        .iter()
+        .collect::<String>()
+        .parse::<usize>()
+        .map_or(MAX_COUNT, |count| count.min(MAX_COUNT));
+    Some(count)
+}
+
+/// Parse the `keys` (all the keys must be used) as a motion.
+fn parse_motion(keys: &[char]) -> Parsed<Motion> {
+    let (motion, len) = match keys[0] {
+        'h' => (Motion::Left, 1),
+        'l' | ' ' => (Motion::Right, 1),
+        'j' => (Motion::Down, 1),
+        'k' => (Motion::Up, 1),
+        'w' => (Motion::NextWordStart { big: false }, 1),
+        'W' => (Motion::NextWordStart { big: true }, 1),
+        'e' => (Motion::NextWordEnd { big: false }, 1),
+        'E' => (Motion::NextWordEnd { big: true }, 1),
+        'b' => (Motion::PrevWordStart { big: false }, 1),
+        'B' => (Motion::PrevWordStart { big: true }, 1),
+        '0' => (Motion::LineStart, 1),
+        '^' => (Motion::FirstNonBlank, 1),
+        '$' => (Motion::LineEnd, 1),
+        'G' => (Motion::LastLine, 1),
+        '%' => (Motion::MatchingBracket, 1),
+        '{' => (Motion::PrevParagraph, 1),
+        '}' => (Motion::NextParagraph, 1),
+        ';' => (Motion::RepeatFind { reverse: false }, 1),
+        ',' => (Motion::RepeatFind { reverse: true }, 1),
+        'g' => match keys.get(1) {
+            None => return Parsed::Pending,
+            Some('g') => (Motion::FirstLine, 2),
+            Some(_) => return Parsed::Invalid,
+        },
+        key @ ('f' | 'F' | 't' | 'T') => match keys.get(1) {
+            None => return Parsed::Pending,
+            Some(&c) => (
+                Motion::FindChar {
+                    c,
+                    forward: matches!(key, 'f' | 't'),
+                    till: matches!(key, 't' | 'T'),
+                },
+                2,
+            ),
+        },
+        _ => return Parsed::Invalid,
+    };
+
+    if keys.len() == len {
+        Parsed::Done(motion)
+    } else {
+        Parsed::Invalid
+    }
+}
+
+/// Parse the `keys` (start with `i` or `a`) as a text object.
+fn parse_object(keys: &[char]) -> Parsed<TextObject> {
+    let around = keys[0] == 'a';
+    let Some(&c) = keys.get(1) else {
+        return Parsed::Pending;
+    };
+
+    let kind = match c {
+        'w' => ObjectKind::Word { big: false },
+        'W' => ObjectKind::Word { big: true },
+        '(' | ')' | 'b' => ObjectKind::Pair('(', ')'),
+        '[' | ']' => ObjectKind::Pair('[', ']'),
+        '{' | '}' | 'B' => ObjectKind::Pair('{', '}'),
+        '<' | '>' => ObjectKind::Pair('<', '>'),
+        '"' | '\'' | '`' => ObjectKind::Quote(c),
+        _ => return Parsed::Invalid,
+    };
+
+    if keys.len() == 2 {
+        Parsed::Done(TextObject { kind, around })
+    } else {
+        Parsed::Invalid
+    }
+}
+
+/// Parse the typed `keys` as a command, `visual` is true in the visual mode.
+pub(super) fn parse_command(keys: &[char], visual: bool) -> Parsed<VimCommand> {
+    let mut ix = 0;
+    let mut register = None;
+    if keys.first() == Some(&'"') {
+        match keys.get(1) {
+            None => return Parsed::Pending,
+            Some(&c) if is_register(c) => register = Some(c),
+            Some(_) => return Parsed::Invalid,
+        }
+        ix = 2;
+    }
+
+    let mut count = parse_count(keys, &mut ix);
+    let Some(&key) = keys.get(ix) else {
+        return Parsed::Pending;
+    };
+
+    let operator = match key {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        '>' => Some(Operator::Indent),
+        '<' => Some(Operator::Outdent),
+        _ => None,
+    };
+
+    let command = match (key, operator) {
+        (_, Some(op)) if visual => Command::OperateSelection(op),
+        (_, Some(op)) => {
+            let mut ix = ix + 1;
+            let count2 = parse_count(keys, &mut ix);
+            let Some(&next) = keys.get(ix) else {
+                return Parsed::Pending;
+            };
+
+            let target = if next == key {
+                if keys.len() != ix + 1 {
+                    return Parsed::Invalid;
+                }
+                Target::Line
+            } else if matches!(next, 'i' | 'a') {
+                match parse_object(&keys[ix..]) {
+                    Parsed::Done(object) => Target::Object(object),
+                    Parsed::Pending => return Parsed::Pending,
+                    Parsed::Invalid => return Parsed::Invalid,
+                }
+            } else {
+                match parse_motion(&keys[ix..]) {
+                    Parsed::Done(motion) => Target::Motion(motion),
+                    Parsed::Pending => return Parsed::Pending,
+                    Parsed::Invalid => return Parsed::Invalid,
+                }
+            };
+
+            if let Some(count2) = count2 {
+                count = Some(count.unwrap_or(1).saturating_mul(count2).min(MAX_COUNT));
+            }
+            Command::Operate(op, target)
+        }
+        ('x', _) if visual => Command::OperateSelection(Operator::Delete),
+        ('s', _) if visual => Command::OperateSelection(Operator::Change),
+        ('~', _) if visual => Command::OperateSelection(Operator::ToggleCase),
+        ('o', _) if visual => Command::SwapAnchor,
+        ('i' | 'a', _) if visual => match parse_object(&keys[ix..]) {
+            Parsed::Done(object) => Command::SelectObject(object),
+            Parsed::Pending => return Parsed::Pending,
+            Parsed::Invalid => return Parsed::Invalid,
+        },
+        ('x', _) => Command::Operate(Operator::Delete, Target::Motion(Motion::Right)),
+        ('X', _) => Command::Operate(Operator::Delete, Target::Motion(Motion::Left)),
+        ('D', _) => Command::Operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+        ('C', _) => Command::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+        ('s', _) => Command::Operate(Operator::Change, Target::Motion(Motion::Right)),
+        ('S', _) => Command::Operate(Operator::Change, Target::Line),
+        ('Y', _) => Command::Operate(Operator::Yank, Target::Line),
+        ('~', _) => Command::Operate(Operator::ToggleCase, Target::Motion(Motion::Right)),
+        ('i', _) => Command::Insert(InsertAt::Cursor),
+        ('a', _) => Command::Insert(InsertAt::After),
+        ('I', _) => Command::Insert(InsertAt::LineStart),
+        ('A', _) => Command::Insert(InsertAt::LineEnd),
+        ('o', _) => Command::Insert(InsertAt::LineBelow),
+        ('O', _) => Command::Insert(InsertAt::LineAbove),
+        ('u', _) => Command::Undo,
+        ('.', _) => Command::Repeat,
+        ('r', _) => match keys.get(ix + 1) {
+            None => return Parsed::Pending,
+            Some(&c) => Command::ReplaceChar(c),
+        },
+        ('p', _) => Command::Paste { before: false },
+        ('P', _) => Command::Paste { before: true },
+        ('J', _) => Command::JoinLines,
+        ('v', _) => Command::Visual { line: false },
+        ('V', _) => Command::Visual { line: true },
+        _ => match parse_motion(&keys[ix..]) {
+            Parsed::Done(motion) => Command::Move(motion),
+            Parsed::Pending => return Parsed::Pending,
+            Parsed::Invalid => return Parsed::Invalid,
+        },
+    };
+
+    Parsed::Done(VimCommand {
+        register,
+        count,
+        command,
+    })
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The class of the char for the word motions: whitespace, word, or punctuation.
+fn char_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || is_word_char(c) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Iterate the (offset, char) from the `offset`.
+fn chars_after(text: &Rope, offset: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut offset = offset;
+    text.chars_at(text.byte_to_char(offset)).map(move |c| {
+        let current = offset;
+        offset += c.len_utf8();
+        (current, c)
+    })
+}
+
+/// Iterate the (offset, char) before the `offset` in reverse order.
+fn chars_before(text: &Rope, offset: usize) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut offset = offset;
+    let mut chars = text.chars_at(text.byte_to_char(offset));
+    std::iter::from_fn(move || {
+        let c = chars.prev()?;
+        offset -= c.len_utf8();
+        Some((offset, c))
+    })
+}
+
+fn next_char_offset(text: &Rope, offset: usize) -> usize {
+    text.char_at(offset)
+        .map_or(offset, |c| offset + c.len_utf8())
+}
+
+fn prev_char_offset(text: &Rope, offset: usize) -> usize {
+    chars_before(text, offset)
+        .next()
+        .map_or(0, |(offset, _)| offset)
+}
+
+fn is_blank_line(text: &Rope, row: usize) -> bool {
+    text.slice_line(row).chars().all(char::is_whitespace)
+}
+
+/// Return the offset of the first non-blank char of the `row`.
+pub(super) fn first_non_blank(text: &Rope, row: usize) -> usize {
+    let start = text.line_start_offset(row);
+    let end = text.line_end_offset(row);
+    chars_after(text, start)
+        .take_while(|(offset, _)| *offset < end)
+        .find(|(_, c)| !c.is_whitespace())
+        .map_or(end, |(offset, _)| offset)
+}
+
+/// Return the offset of the last char of the `row`, or the line start if the line is empty.
+fn last_char(text: &Rope, row: usize) -> usize {
+    let start = text.line_start_offset(row);
+    let end = text.line_end_offset(row);
+    if end > start {
+        prev_char_offset(text, end)
+    } else {
+        start
+    }
+}
+
+/// Return the column (in chars) of the `offset` in its line.
+fn column(text: &Rope, offset: usize) -> usize {
+    let start = text.line_start_offset(text.offset_to_row(offset));
+    text.byte_to_char(offset) - text.byte_to_char(start)
+}
+
+fn next_word_start(text: &Rope, offset: usize, big: bool) -> usize {
+    let mut chars = chars_after(text, offset);
+    let Some((_, c)) = chars.next() else {
+        return offset;
+    };
+
+    let class = char_class(c, big);
+    chars
+        .skip_while(|(_, c)| class != 0 && char_class(*c, big) == class)
+        .find(|(_, c)| !c.is_whitespace())
+        .map_or(text.len_bytes(), |(offset, _)| offset)
+}
+
+fn next_word_end(text: &Rope, offset: usize, big: bool) -> usize {
+    let mut chars = chars_after(text, offset)
+        .skip(1)
+        .skip_while(|(_, c)| c.is_whitespace())
+        .peekable();
+    let Some((mut end, c)) = chars.next() else {
+        return offset;
+    };
+
+    let class = char_class(c, big);
+    while let Some((offset, _)) = chars.next_if(|(_, c)| char_class(*c, big) == class) {
+        end = offset;
+    }
+    end
+}
+
+fn prev_word_start(text: &Rope, offset: usize, big: bool) -> usize {
+    let mut chars = chars_before(text, offset)
+        .skip_while(|(_, c)| c.is_whitespace())
+        .peekable();
+    let Some((mut start, c)) = chars.next() else {
+        return 0;
+    };
+
+    let class = char_class(c, big);
+    while let Some((offset, _)) = chars.next_if(|(_, c)| char_class(*c, big) == class) {
+        start = offset;
+    }
+    start
+}
+
+/// Find the `count`th `c` in the line of the `offset`.
+fn find_char(
+    text: &Rope,
+    offset: usize,
+    c: char,
+    forward: bool,
+    till: bool,
+    count: usize,
+) -> Option<usize> {
+    let row = text.offset_to_row(offset);
+    let line_start = text.line_start_offset(row);
+    let line_end = text.line_end_offset(row);
+
+    if forward {
+        let mut prev = offset;
+        let mut found = 0;
+        for (ix, ch) in chars_after(text, offset).skip(1) {
+            if ix >= line_end {
+                break;
+            }
+            if ch == c {
+                found += 1;
+                if found == count {
+                    return Some(if till { prev } else { ix });
+                }
+            }
+            prev = ix;
+        }
+    } else {
+        let mut found = 0;
+        for (ix, ch) in chars_before(text, offset) {
+            if ix < line_start {
+                break;
+            }
+            if ch == c {
+                found += 1;
+                if found == count {
+                    return Some(if till { ix + ch.len_utf8() } else { ix });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Return the offset of the bracket matching the first bracket at or after the `offset` in the line.
+fn matching_bracket(text: &Rope, offset: usize) -> Option<usize> {
+    let line_end = text.line_end_offset(text.offset_to_row(offset));
+    let (start, c) = chars_after(text, offset)
+        .take_while(|(ix, _)| *ix < line_end)
+        .find(|(_, c)| matches!(c, '(' | ')' | '[' | ']' | '{' | '}'))?;
+
+    let (open, close, forward) = match c {
+        '(' => ('(', ')', true),
+        ')' => ('(', ')', false),
+        '[' => ('[', ']', true),
+        ']' => ('[', ']', false),
+        '{' => ('{', '}', true),
+        _ => ('{', '}', false),
+    };
+
+    let mut depth = 0;
+    if forward {
+        for (ix, c) in chars_after(text, start) {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(ix);
+                }
+            }
+        }
+    } else {
+        for (ix, c) in chars_before(text, start + c.len_utf8()) {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(ix);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn next_paragraph(text: &Rope, offset: usize) -> usize {
+    let mut row = text.offset_to_row(offset);
+    while row < text.lines_len() && is_blank_line(text, row) {
+        row += 1;
+    }
+    while row < text.lines_len() && !is_blank_line(text, row) {
+        row += 1;
+    }
+
+    if row < text.lines_len() {
+        text.line_start_offset(row)
+    } else {
+        text.len_bytes()
+    }
+}
+
+fn prev_paragraph(text: &Rope, offset: usize) -> usize {
+    let mut row = text.offset_to_row(offset) as isize;
+    while row >= 0 && is_blank_line(text, row as usize) {
+        row -= 1;
+    }
+    while row >= 0 && !is_blank_line(text, row as usize) {
+        row -= 1;
+    }
+
+    if row >= 0 {
+        text.line_start_offset(row as usize)
+    } else {
+        0
+    }
+}
+
+/// Return the target offset of the `motion` from the `offset`.
+///
+/// - `count` is the count typed before the motion.
+/// - `col` is the column (in chars) to keep for the vertical motions.
+///
+/// Returns `None` if the motion fails, e.g.: moving up at the first line.
+pub(super) fn motion_target(
+    text: &Rope,
+    offset: usize,
+    motion: Motion,
+    count: Option<usize>,
+    col: usize,
+) -> Option<usize> {
+    let n = count.unwrap_or(1).max(1);
+    let row = text.offset_to_row(offset);
+    let last_row = text.lines_len().saturating_sub(1);
+    let line_start = text.line_start_offset(row);
+    let line_end = text.line_end_offset(row);
+
+    let offset_for_col = |row: usize| {
+        let start = text.line_start_offset(row);
+        let end = text.line_end_offset(row);
+        chars_after(text, start)
+            .take_while(|(ix, _)| *ix < end)
+            .take(col.saturating_add(1))
+            .last()
+            .map_or(start, |(ix, _)| ix)
+    };
+
+    let target = match motion {
+        Motion::Left => {
+            let mut target = offset;
+            for _ in 0..n {
+                if target <= line_start {
+                    break;
+                }
+                target = prev_char_offset(text, target);
+            }
+            target
+        }
+        Motion::Right => {
+            let mut target = offset;
+            for _ in 0..n {
+                if target >= line_end {
+                    break;
+                }
+                target = next_char_offset(text, target);
+            }
+            target
+        }
+        Motion::Up => {
+            if row == 0 {
+                return None;
+            }
+            offset_for_col(row.saturating_sub(n))
+        }
+        Motion::Down => {
+            if row >= last_row {
+                return None;
+            }
+            offset_for_col(row.saturating_add(n).min(last_row))
+        }
+        Motion::NextWordStart { big } => {
+            (0..n).fold(offset, |offset, _| next_word_start(text, offset, big))
+        }
+        Motion::NextWordEnd { big } => {
+            (0..n).fold(offset, |offset, _| next_word_end(text, offset, big))
+        }
+        Motion::PrevWordStart { big } => {
+            (0..n).fold(offset, |offset, _| prev_word_start(text, offset, big))
+        }
+        Motion::LineStart => line_start,
+        Motion::FirstNonBlank => first_non_blank(text, row),
+        Motion::LineEnd => last_char(text, row.saturating_add(n - 1).min(last_row)),
+        Motion::FirstLine => first_non_blank(text, count.map_or(0, |n| n - 1).min(last_row)),
+        Motion::LastLine => first_non_blank(text, count.map_or(last_row, |n| n - 1).min(last_row)),
+        Motion::FindChar { c, forward, till } => find_char(text, offset, c, forward, till, n)?,
+        Motion::MatchingBracket => matching_bracket(text, offset)?,
+        Motion::NextParagraph => (0..n).fold(offset, |offset, _| next_paragraph(text, offset)),
+        Motion::PrevParagraph => (0..n).fold(offset, |offset, _| prev_paragraph(text, offset)),
+        Motion::RepeatFind { .. } => return None,
+    };
+
+    Some(target)
+}
+
+/// Return the range operated by the motion from `offset` to `target`, and true if it is linewise.
+///
+/// The linewise range is from the start of the first line to the end of the last line (without the `\n`).
+pub(super) fn motion_range(
+    text: &Rope,
+    offset: usize,
+    target: usize,
+    kind: MotionKind,
+) -> (Range<usize>, bool) {
+    let (start, end) = (offset.min(target), offset.max(target));
+    match kind {
+        MotionKind::Exclusive => (start..end, false),
+        MotionKind::Inclusive => (start..next_char_offset(text, end), false),
+        MotionKind::Linewise => {
+            let start = text.line_start_offset(text.offset_to_row(start));
+            let end = text.line_end_offset(text.offset_to_row(end));
+            (start..end, true)
+        }
+    }
+}
+
+/// Return the range to delete the lines of the linewise `range`, include a `\n`.
+pub(super) fn lines_range_with_newline(text: &Rope, range: &Range<usize>) -> Range<usize> {
+    let first_row = text.offset_to_row(range.start);
+    let last_row = text.offset_to_row(range.end);
+    if last_row + 1 < text.lines_len() {
+        text.line_start_offset(first_row)..text.line_start_offset(last_row + 1)
+    } else if first_row > 0 {
+        // Delete the `\n` before the last line.
+        text.line_end_offset(first_row - 1)..text.len_bytes()
+    } else {
+        0..text.len_bytes()
+    }
+}
+
+/// Return the range of the text `object` at the `offset`.
+pub(super) fn text_object_range(
+    text: &Rope,
+    offset: usize,
+    object: TextObject,
+) -> Option<Range<usize>> {
+    let row = text.offset_to_row(offset);
+    let line_start = text.line_start_offset(row);
+    let line_end = text.line_end_offset(row);
+
+    match object.kind {
+        ObjectKind::Word { big } => {
+            let c = text.char_at(offset).filter(|_| offset < line_end)?;
+            let class = char_class(c, big);
+            let same_class = |c: char| char_class(c, big) == class;
+
+            let mut start = chars_before(text, offset)
+                .take_while(|(ix, c)| *ix >= line_start && same_class(*c))
+                .last()
+                .map_or(offset, |(ix, _)| ix);
+            let mut end = chars_after(text, offset)
+                .take_while(|(ix, c)| *ix < line_end && same_class(*c))
+                .last()
+                .map_or(offset, |(ix, c)| ix + c.len_utf8());
+
+            if object.around {
+                let trailing_end = chars_after(text, end)
+                    .take_while(|(ix, c)| *ix < line_end && (class == 0) != c.is_whitespace())
+                    .last()
+                    .map(|(ix, c)| ix + c.len_utf8());
+                match trailing_end {
+                    Some(trailing_end) => end = trailing_end,
+                    None if class != 0 => {
+                        start = chars_before(text, start)
+                            .take_while(|(ix, c)| *ix >= line_start && c.is_whitespace())
+                            .last()
+                            .map_or(start, |(ix, _)| ix);
+                    }
+                    None => {}
+                }
+            }
+
+            Some(start..end)
+        }
+        ObjectKind::Pair(open, close) => {
+            let start = if text.char_at(offset) == Some(open) {
+                offset
+            } else {
+                let mut depth = 0;
+                chars_before(text, offset).find_map(|(ix, c)| {
+                    if c == close {
+                        depth += 1;
+                    } else if c == open {
+                        if depth == 0 {
+                            return Some(ix);
+                        }
+                        depth -= 1;
+                    }
+                    None
+                })?
+            };
+
+            let mut depth = 0;
+            let end = chars_after(text, start + open.len_utf8()).find_map(|(ix, c)| {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return Some(ix);
+                    }
+                    depth -= 1;
+                }
+                None
+            })?;
+
+            if object.around {
+                Some(start..end + close.len_utf8())
+            } else {
+                Some(start + open.len_utf8()..end)
+            }
+        }
+        ObjectKind::Quote(quote) => {
+            let quotes = chars_after(text, line_start)
+                .take_while(|(ix, _)| *ix < line_end)
+                .filter(|(_, c)| *c == quote)
+                .map(|(ix, _)| ix)
+                .collect::<Vec<_>>();
+            let (start, end) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|(_, end)| offset <= *end)?;
+
+            if object.around {
+                Some(start..end + quote.len_utf8())
+            } else {
+                Some(start + quote.len_utf8()..end)
+            }
+        }
+    }
+}
+
+fn toggle_case(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| {
+            let toggled: Vec<char> = if c.is_uppercase() {
+                c.to_lowercase().collect()
+            } else {
+                c.to_uppercase().collect()
+            };
+            toggled
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+/// The state of the Vim keymap.
+#[derive(Default)]
+pub(super) struct VimState {
+    pub(super) mode: VimMode,
+    /// The keys typed for the pending command.
+    pending: Vec<char>,
+    registers: HashMap<char, Register>,
+    /// The last change to repeat by `.`, with the text inserted by it.
+    last_change: Option<(VimCommand, Option<String>)>,
+    /// The change that entered the insert mode, and the offset of the insert mode started.
+    insert_change: Option<(VimCommand, usize)>,
+    /// The last `f`, `F`, `t` or `T` motion, to repeat by `;` and `,`.
+    last_find: Option<Motion>,
+    /// The column to keep for the vertical motions.
+    col: Option<usize>,
+    /// The anchor and the cursor of the visual selection.
+    visual_anchor: usize,
+    visual_head: usize,
+}
+
+impl InputState {
+    /// Return the current [`VimMode`], `None` if the keymap is not [`KeymapMode::Vim`].
+    pub fn vim_mode(&self) -> Option<VimMode> {
+        (self.keymap == KeymapMode::Vim).then_some(self.vim.mode)
+    }
+
+    /// Return the text of the mode indicator, e.g.: `-- INSERT --`, with the pending keys.
+    pub(super) fn vim_status(&self) -> Option<SharedString> {
+        let mode = self.vim_mode()?;
+        let pending = self.vim.pending.iter().collect::<String>();
+        Some(
+            format!("-- {} --  {}", mode.label(), pending)
+                .trim_end()
+                .to_string()
+                .into(),
+        )
+    }
+
+    /// Return true to display the block cursor, in the normal mode.
+    pub(super) fn is_block_cursor(&self) -> bool {
+        self.vim_mode() == Some(VimMode::Normal)
+    }
+
+    /// Reset the Vim state to the normal mode.
+    pub(super) fn reset_vim(&mut self) {
+        self.vim.mode = VimMode::Normal;
+        self.vim.pending.clear();
+        self.vim.insert_change = None;
+        self.clamp_vim_cursor();
+    }
+
+    /// Handle the typed key in the normal and visual mode.
+    ///
+    /// Returns true if the key is handled, so it will not be inserted as text.
+    pub(super) fn handle_vim_key(
+        &mut self,
+        event: &KeyDownEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        if self.vim_mode().map_or(true, |mode| mode == VimMode::Insert) {
+            return false;
+        }
+
+        let keystroke = &event.keystroke;
+        if keystroke.modifiers.control || keystroke.modifiers.platform {
+            return false;
+        }
+        let mut chars = keystroke.key_char.as_deref().unwrap_or_default().chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) if !c.is_control() => c,
+            // The `delete` is the same as `x`.
+            _ if keystroke.key == "delete" && !keystroke.modifiers.modified() => 'x',
+            _ => return false,
+        };
+
+        self.vim.pending.push(key);
+        match parse_command(&self.vim.pending, self.vim.mode.is_visual()) {
+            Parsed::Pending => {}
+            Parsed::Invalid => self.vim.pending.clear(),
+            Parsed::Done(command) => {
+                self.vim.pending.clear();
+                // Each command is undone in one step, and not grouped with the other changes.
+                self.history.start_group();
+                self.execute_vim_command(command, window, cx);
+                self.history.end_group();
+            }
+        }
+        cx.notify();
+        true
+    }
+
+    /// Handle the escape in the Vim mode, returns false if there is nothing to escape.
+    pub(super) fn vim_escape(&mut self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(mode) = self.vim_mode() else {
+            return false;
+        };
+
+        match mode {
+            VimMode::Insert => {
+                let cursor = self.cursor_offset();
+                if let Some((command, start)) = self.vim.insert_change.take() {
+                    let inserted =
+                        (cursor >= start).then(|| self.text.text_for_range(start..cursor));
+                    self.vim.last_change = Some((command, inserted));
+                }
+
+                self.vim.mode = VimMode::Normal;
+                let line_start = self.text.line_start_offset(self.text.offset_to_row(cursor));
+                if cursor > line_start {
+                    self.move_to(prev_char_offset(&self.text, cursor), window, cx);
+                } else {
+                    self.move_to(cursor, window, cx);
+                }
+            }
+            VimMode::Visual | VimMode::VisualLine => {
+                self.vim.mode = VimMode::Normal;
+                self.move_to(self.vim.visual_head, window, cx);
+            }
+            VimMode::Normal => {
+                if self.vim.pending.is_empty() {
+                    return false;
+                }
+                self.vim.pending.clear();
+            }
+        }
+
+        self.clamp_vim_cursor();
+        cx.notify();
+        true
+    }
+
+    fn execute_vim_command(
+        &mut self,
+        command: VimCommand,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let count = command.count.unwrap_or(1);
+        let is_vertical = matches!(
+            command.command,
+            Command::Move(Motion::Up | Motion::Down | Motion::LineEnd)
+        );
+
+        match command.command {
+            Command::Move(motion) => {
+                let from = if self.vim.mode.is_visual() {
+                    self.vim.visual_head
+                } else {
+                    self.cursor_offset()
+                };
+                if let Some((target, _)) = self.vim_motion_target(from, motion, command.count) {
+                    if self.vim.mode.is_visual() {
+                        self.vim.visual_head = target;
+                        self.update_visual_selection(cx);
+                    } else {
+                        self.move_to(target, window, cx);
+                    }
+                }
+            }
+            Command::Operate(op, target) => {
+                if let Some((range, linewise)) = self.vim_operator_range(op, target, command.count)
+                {
+                    // The `~` moves the cursor after the toggled chars.
+                    let after = matches!(
+                        (op, target),
+                        (Operator::ToggleCase, Target::Motion(Motion::Right))
+                    )
+                    .then_some(range.end);
+                    self.vim_operate(op, range, linewise, command.register, window, cx);
+                    if let Some(offset) = after {
+                        self.move_to(offset, window, cx);
+                    }
+                }
+            }
+            Command::OperateSelection(op) => {
+                let linewise = self.vim.mode == VimMode::VisualLine;
+                let range = self.selected_range.clone();
+                self.vim.mode = VimMode::Normal;
+                self.vim_operate(op, range, linewise, command.register, window, cx);
+            }
+            Command::SelectObject(object) => {
+                if let Some(range) = text_object_range(&self.text, self.vim.visual_head, object)
+                    .filter(|range| !range.is_empty())
+                {
+                    self.vim.mode = VimMode::Visual;
+                    self.vim.visual_anchor = range.start;
+                    self.vim.visual_head = prev_char_offset(&self.text, range.end);
+                    self.update_visual_selection(cx);
+                }
+            }
+            Command::Insert(at) => self.vim_insert(at, window, cx),
+            Command::Paste { before } => {
+                self.vim_paste(before, count, command.register, window, cx)
+            }
+            Command::ReplaceChar(c) => self.vim_replace_char(c, count, window, cx),
+            Command::JoinLines => {
+                let count = if self.vim.mode.is_visual() {
+                    let rows = self.text.offset_to_row(self.selected_range.start)
+                        ..=self.text.offset_to_row(self.selected_range.end);
+                    self.vim.mode = VimMode::Normal;
+                    rows.count()
+                } else {
+                    count
+                };
+                self.vim_join_lines(count, window, cx);
+            }
+            Command::Undo => {
+                for _ in 0..count {
+                    self.undo(&Undo, window, cx);
+                }
+                self.move_to(self.selected_range.start, window, cx);
+            }
+            Command::Repeat => self.vim_repeat(command.count, window, cx),
+            Command::Visual { line } => {
+                let mode = if line {
+                    VimMode::VisualLine
+                } else {
+                    VimMode::Visual
+                };
+                if self.vim.mode == mode {
+                    self.vim.mode = VimMode::Normal;
+                    self.move_to(self.vim.visual_head, window, cx);
+                } else {
+                    if !self.vim.mode.is_visual() {
+                        let cursor = self.cursor_offset();
+                        self.vim.visual_anchor = cursor;
+                        self.vim.visual_head = cursor;
+                    }
+                    self.vim.mode = mode;
+                    self.update_visual_selection(cx);
+                }
+            }
+            Command::SwapAnchor => {
+                std::mem::swap(&mut self.vim.visual_anchor, &mut self.vim.visual_head);
+                self.update_visual_selection(cx);
+            }
+        }
+
+        if command.command.is_change() {
+            if self.vim.mode == VimMode::Insert {
+                if let Some((change, _)) = self.vim.insert_change.as_mut() {
+                    *change = command;
+                }
+            } else {
+                self.vim.last_change = Some((command, None));
+            }
+        }
+        if !is_vertical {
+            self.vim.col = None;
+        }
+        self.clamp_vim_cursor();
+    }
+
+    /// Return the target offset and the kind of the `motion` from the `offset`.
+    fn vim_motion_target(
+        &mut self,
+        offset: usize,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> Option<(usize, MotionKind)> {
+        let motion = match motion {
+            Motion::RepeatFind { reverse } => match self.vim.last_find? {
+                Motion::FindChar { c, forward, till } => Motion::FindChar {
+                    c,
+                    forward: forward != reverse,
+                    till,
+                },
+                _ => return None,
+            },
+            Motion::FindChar { .. } => {
+                self.vim.last_find = Some(motion);
+                motion
+            }
+            motion => motion,
+        };
+
+        let col = match motion {
+            Motion::Up | Motion::Down => *self
+                .vim
+                .col
+                .get_or_insert_with(|| column(&self.text, offset)),
+            _ => 0,
+        };
+        let target = motion_target(&self.text, offset, motion, count, col)?;
+        if motion == Motion::LineEnd {
+            self.vim.col = Some(usize::MAX);
+        }
+
+        Some((target, motion.kind()))
+    }
+
+    /// Return the range and whether it is linewise to operate by the `op` on the `target`.
+    fn vim_operator_range(
+        &mut self,
+        op: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<(Range<usize>, bool)> {
+        let cursor = self.cursor_offset();
+        match target {
+            Target::Line => {
+                let row = self.text.offset_to_row(cursor);
+                let last_row = row
+                    .saturating_add(count.unwrap_or(1).max(1) - 1)
+                    .min(self.text.lines_len() - 1);
+                Some((
+                    self.text.line_start_offset(row)..self.text.line_end_offset(last_row),
+                    true,
+                ))
+            }
+            Target::Object(object) => {
+                text_object_range(&self.text, cursor, object).map(|range| (range, false))
+            }
+            Target::Motion(motion) => {
+                let on_word = self
+                    .text
+                    .char_at(cursor)
+                    .is_some_and(|c| !c.is_whitespace());
+                let motion = match motion {
+                    // The `cw` is `ce` on the word.
+                    Motion::NextWordStart { big } if op == Operator::Change && on_word => {
+                        Motion::NextWordEnd { big }
+                    }
+                    motion => motion,
+                };
+
+                let (mut target, kind) = self.vim_motion_target(cursor, motion, count)?;
+                // The `dw` on the last word of the line doesn't join the next line.
+                if matches!(motion, Motion::NextWordStart { .. }) {
+                    let row = self.text.offset_to_row(cursor);
+                    if self.text.offset_to_row(target) > row {
+                        target = self.text.line_end_offset(row).max(cursor);
+                    }
+                }
+
+                Some(motion_range(&self.text, cursor, target, kind))
+            }
+        }
+    }
+
+    /// Replace the text in the `range` (not the selection) with `new_text`.
+    fn vim_replace(
+        &mut self,
+        range: Range<usize>,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let range_utf16 = self.range_to_utf16(&range);
+        self.replace_text_in_range(Some(range_utf16), new_text, window, cx);
+    }
+
+    fn vim_operate(
+        &mut self,
+        op: Operator,
+        range: Range<usize>,
+        linewise: bool,
+        register: Option<char>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let first_row = self.text.offset_to_row(range.start);
+        let mut text = self.text.text_for_range(range.clone());
+        if linewise {
+            text.push('\n');
+        }
+
+        match op {
+            Operator::Yank => {
+                self.write_register(register, text, linewise, true, cx);
+                let cursor = if linewise {
+                    self.cursor_offset().min(range.end)
+                } else {
+                    range.start
+                };
+                self.move_to(cursor, window, cx);
+            }
+            Operator::Delete => {
+                self.write_register(register, text, linewise, false, cx);
+                if linewise {
+                    let range = lines_range_with_newline(&self.text, &range);
+                    self.vim_replace(range, "", window, cx);
+                    let row = first_row.min(self.text.lines_len().saturating_sub(1));
+                    self.move_to(first_non_blank(&self.text, row), window, cx);
+                } else {
+                    self.vim_replace(range.clone(), "", window, cx);
+                    self.move_to(range.start, window, cx);
+                }
+            }
+            Operator::Change => {
+                self.write_register(register, text, linewise, false, cx);
+                let range = if linewise {
+                    first_non_blank(&self.text, first_row)..range.end
+                } else {
+                    range
+                };
+                self.vim_replace(range.clone(), "", window, cx);
+                self.move_to(range.start, window, cx);
+                self.enter_vim_insert(cx);
+            }
+            Operator::Indent | Operator::Outdent => {
+                let start = self.text.line_start_offset(first_row);
+                self.selected_range = start..range.end.max(start);
+                self.selection_reversed = false;
+                if op == Operator::Indent {
+                    self.indent(true, window, cx);
+                } else {
+                    self.outdent(true, window, cx);
+                }
+                self.move_to(first_non_blank(&self.text, first_row), window, cx);
+            }
+            Operator::ToggleCase => {
+                let toggled = toggle_case(&self.text.text_for_range(range.clone()));
+                self.vim_replace(range.clone(), &toggled, window, cx);
+                self.move_to(range.start, window, cx);
+            }
+        }
+    }
+
+    fn enter_vim_insert(&mut self, cx: &mut Context<Self>) {
+        self.vim.mode = VimMode::Insert;
+        self.vim.insert_change = Some((
+            VimCommand {
+                register: None,
+                count: None,
+                command: Command::Insert(InsertAt::Cursor),
+            },
+            self.cursor_offset(),
+        ));
+        cx.notify();
+    }
+
+    fn vim_insert(&mut self, at: InsertAt, window: &mut Window, cx: &mut Context<Self>) {
+        let cursor = self.cursor_offset();
+        let row = self.text.offset_to_row(cursor);
+        let line_start = self.text.line_start_offset(row);
+        let line_end = self.text.line_end_offset(row);
+        let indent = self
+            .text
+            .text_for_range(line_start..first_non_blank(&self.text, row));
+
+        match at {
+            InsertAt::Cursor => {}
+            InsertAt::After => {
+                if cursor < line_end {
+                    self.move_to(next_char_offset(&self.text, cursor), window, cx);
+                }
+            }
+            InsertAt::LineStart => self.move_to(first_non_blank(&self.text, row), window, cx),
+            InsertAt::LineEnd => self.move_to(line_end, window, cx),
+            InsertAt::LineBelow => {
+                self.vim_replace(line_end..line_end, &format!("\n{}", indent), window, cx);
+                self.move_to(line_end + 1 + indent.len(), window, cx);
+            }
+            InsertAt::LineAbove => {
+                self.vim_replace(line_start..line_start, &format!("{}\n", indent), window, cx);
+                self.move_to(line_start + indent.len(), window, cx);
+            }
+        }
+
+        self.enter_vim_insert(cx);
+    }
+
+    fn vim_paste(
+        &mut self,
+        before: bool,
+        count: usize,
+        register: Option<char>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(register) = self.read_register(register, cx) else {
+            return;
+        };
+        // Avoid the huge text by a large count.
+        let count = count
+            .min(MAX_REPEAT_LEN / register.text.len().max(1))
+            .max(1);
+        let text = register.text.repeat(count);
+
+        // Replace the selection in the visual mode.
+        if self.vim.mode.is_visual() {
+            let range = self.selected_range.clone();
+            let text = if register.linewise && self.vim.mode == VimMode::Visual {
+                format!("\n{}", text)
+            } else if register.linewise {
+                text.trim_end_matches('\n').to_string()
+            } else {
+                text
+            };
+            self.vim.mode = VimMode::Normal;
+            self.vim_replace(range.clone(), &text, window, cx);
+            self.move_to(range.start, window, cx);
+            return;
+        }
+
+        let cursor = self.cursor_offset();
+        let row = self.text.offset_to_row(cursor);
+        if register.linewise {
+            let row = if before {
+                let start = self.text.line_start_offset(row);
+                self.vim_replace(start..start, &text, window, cx);
+                row
+            } else if row + 1 < self.text.lines_len() {
+                let start = self.text.line_start_offset(row + 1);
+                self.vim_replace(start..start, &text, window, cx);
+                row + 1
+            } else {
+                let end = self.text.len_bytes();
+                let text = format!("\n{}", text.trim_end_matches('\n'));
+                self.vim_replace(end..end, &text, window, cx);
+                row + 1
+            };
+            self.move_to(first_non_blank(&self.text, row), window, cx);
+        } else {
+            let offset = if before || cursor >= self.text.line_end_offset(row) {
+                cursor
+            } else {
+                next_char_offset(&self.text, cursor)
+            };
+            self.vim_replace(offset..offset, &text, window, cx);
+            let end = offset + text.len();
+            self.move_to(prev_char_offset(&self.text, end).max(offset), window, cx);
+        }
+    }
+
+    fn vim_replace_char(
+        &mut self,
+        c: char,
+        count: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let cursor = self.cursor_offset();
+        let line_end = self.text.line_end_offset(self.text.offset_to_row(cursor));
+        let chars = chars_after(&self.text, cursor)
+            .take_while(|(ix, _)| *ix < line_end)
+            .take(count)
+            .collect::<Vec<_>>();
+        let Some((last, last_c)) = chars.last().copied() else {
+            return;
+        };
+        if chars.len() < count {
+            return;
+        }
+
+        let new_text = c.to_string().repeat(count);
+        self.vim_replace(cursor..last + last_c.len_utf8(), &new_text, window, cx);
+        self.move_to(cursor + new_text.len() - c.len_utf8(), window, cx);
+    }
+
+    fn vim_join_lines(&mut self, count: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let row = self.text.offset_to_row(self.cursor_offset());
+        let mut cursor = self.cursor_offset();
+        for _ in 0..count.max(2) - 1 {
+            if row + 1 >= self.text.lines_len() {
+                break;
+            }
+
+            let end = self.text.line_end_offset(row);
+            let next_start = self.text.line_start_offset(row + 1);
+            let next_end = self.text.line_end_offset(row + 1);
+            let content_start = first_non_blank(&self.text, row + 1);
+            let separator = if content_start == next_end
+                || end == self.text.line_start_offset(row)
+                || self.text.char_at(content_start) == Some(')')
+            {
+                ""
+            } else {
+                " "
+            };
+
+            self.vim_replace(end..content_start.max(next_start), separator, window, cx);
+            cursor = end;
+        }
+        self.move_to(cursor, window, cx);
+    }
+
+    fn vim_repeat(&mut self, count: Option<usize>, window: &mut Window, cx: &mut Context<Self>) {
+        let Some((mut command, inserted)) = self.vim.last_change.clone() else {
+            return;
+        };
+        if count.is_some() {
+            command.count = count;
+        }
+
+        self.execute_vim_command(command, window, cx);
+        if self.vim.mode == VimMode::Insert {
+            if let Some(inserted) = inserted.as_deref() {
+                let cursor = self.cursor_offset();
+                self.vim_replace(cursor..cursor, inserted, window, cx);
+            }
+            self.vim_escape(window, cx);
+        }
+    }
+
+    /// Update the selection by the anchor and the cursor of the visual mode.
+    fn update_visual_selection(&mut self, cx: &mut Context<Self>) {
+        let anchor = self.vim.visual_anchor.min(self.text.len_bytes());
+        let head = self.vim.visual_head.min(self.text.len_bytes());
+        let (start, end) = (anchor.min(head), anchor.max(head));
+
+        self.selected_range = if self.vim.mode == VimMode::VisualLine {
+            self.text.line_start_offset(self.text.offset_to_row(start))
+                ..self.text.line_end_offset(self.text.offset_to_row(end))
+        } else {
+            start..next_char_offset(&self.text, end)
+        };
+        self.selection_reversed = head < anchor;
+        self.extra_selections.clear();
+        cx.notify();
+    }
+
+    /// Keep the cursor on a char in the normal mode, it can't be after the end of a non-empty line.
+    fn clamp_vim_cursor(&mut self) {
+        if self.vim_mode() != Some(VimMode::Normal) {
+            return;
+        }
+
+        let offset = self.cursor_offset();
+        let row = self.text.offset_to_row(offset);
+        let line_start = self.text.line_start_offset(row);
+        let line_end = self.text.line_end_offset(row);
+        if offset >= line_end && line_end > line_start {
+            let offset = prev_char_offset(&self.text, line_end);
+            self.selected_range = offset..offset;
+        } else if !self.selected_range.is_empty() {
+            self.selected_range = offset..offset;
+        }
+    }
+
+    fn write_register(
+        &mut self,
+        register: Option<char>,
+        text: String,
+        linewise: bool,
+        yank: bool,
+        cx: &mut Context<Self>,
+    ) {
+        let value = Register { text, linewise };
+        match register {
+            Some('_') => return,
+            Some('+' | '*') => {
+                cx.write_to_clipboard(ClipboardItem::new_string(value.text.clone()));
+            }
+            Some(c) if c.is_ascii_uppercase() => {
+                let register = self
+                    .vim
+                    .registers
+                    .entry(c.to_ascii_lowercase())
+                    .or_insert_with(|| Register {
+                        text: String::new(),
+                        linewise,
+                    });
+                register.text.push_str(&value.text);
+                // The unnamed register has the entire content of the appended register.
+                let value = register.clone();
+                self.vim.registers.insert('"', value);
+                return;
+            }
+            Some(c) if c != '"' => {
+                self.vim.registers.insert(c, value.clone());
+            }
+            _ => {
+                if yank {
+                    self.vim.registers.insert('0', value.clone());
+                }
+            }
+        }
+        self.vim.registers.insert('"', value);
+    }
+
+    fn read_register(&self, register: Option<char>, cx: &mut Context<Self>) -> Option<Register> {
+        match register.unwrap_or('"') {
+            '+' | '*' => {
+                let text = cx.read_from_clipboard()?.text()?;
+                Some(Register {
+                    linewise: text.ends_with('\n'),
+                    text,
+                })
+            }
+            c => self.vim.registers.get(&c.to_ascii_lowercase()).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gpui::{Keystroke, TestAppContext};
+
+    use super::*;
+    use crate::input::new_state;
+
+    /// Type the keys in the normal or visual mode.
+    fn type_keys(
+        state: &mut InputState,
+        keys: &str,
+        window: &mut Window,
+        cx: &mut Context<InputState>,
+    ) {
+        for c in keys.chars() {
+            let event = KeyDownEvent {
+                keystroke: Keystroke {
+                    key: c.to_string(),
+                    key_char: Some(c.to_string()),
+                    ..Default::default()
+                },
+                is_held: false,
+            };
+            assert!(state.handle_vim_key(&event, window, cx), "key: {c}");
+        }
+    }
+
+    fn parse(keys: &str, visual: bool) -> Parsed<VimCommand> {
+        parse_command(&keys.chars().collect::<Vec<_>>(), visual)
+    }
+
+    fn command(
+        register: Option<char>,
+        count: Option<usize>,
+        command: Command,
+    ) -> Parsed<VimCommand> {
+        Parsed::Done(VimCommand {
+            register,
+            count,
+            command,
+        })
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse("j", false),
+            command(None, None, Command::Move(Motion::Down))
+        );
+        assert_eq!(
+            parse("10j", false),
+            command(None, Some(10), Command::Move(Motion::Down))
+        );
+        assert_eq!(
+            parse("0", false),
+            command(None, None, Command::Move(Motion::LineStart))
+        );
+        assert_eq!(parse("g", false), Parsed::Pending);
+        assert_eq!(
+            parse("gg", false),
+            command(None, None, Command::Move(Motion::FirstLine))
+        );
+        assert_eq!(parse("gx", false), Parsed::Invalid);
+        assert_eq!(parse("f", false), Parsed::Pending);
+        assert_eq!(
+            parse("2t)", false),
+            command(
+                None,
+                Some(2),
+                Command::Move(Motion::FindChar {
+                    c: ')',
+                    forward: true,
+                    till: true
+                })
+            )
+        );
+
+        assert_eq!(parse("d", false), Parsed::Pending);
+        assert_eq!(parse("d2", false), Parsed::Pending);
+        assert_eq!(
+            parse("2d3w", false),
+            command(
+                None,
+                Some(6),
+                Command::Operate(
+                    Operator::Delete,
+                    Target::Motion(Motion::NextWordStart { big: false })
+                )
+            )
+        );
+        assert_eq!(
+            parse("\"ayy", false),
+            command(
+                Some('a'),
+                None,
+                Command::Operate(Operator::Yank, Target::Line)
+            )
+        );
+        assert_eq!(
+            parse("ci\"", false),
+            command(
+                None,
+                None,
+                Command::Operate(
+                    Operator::Change,
+                    Target::Object(TextObject {
+                        kind: ObjectKind::Quote('"'),
+                        around: false
+                    })
+                )
+            )
+        );
+        assert_eq!(parse("dy", false), Parsed::Invalid);
+        assert_eq!(parse("\"", false), Parsed::Pending);
+        assert_eq!(parse("\"!", false), Parsed::Invalid);
+        assert_eq!(parse("r", false), Parsed::Pending);
+        assert_eq!(
+            parse("rx", false),
+            command(None, None, Command::ReplaceChar('x'))
+        );
+        assert_eq!(parse("3.", false), command(None, Some(3), Command::Repeat));
+        assert_eq!(parse("z", false), Parsed::Invalid);
+
+        // The visual mode.
+        assert_eq!(
+            parse("d", true),
+            command(None, None, Command::OperateSelection(Operator::Delete))
+        );
+        assert_eq!(parse("o", true), command(None, None, Command::SwapAnchor));
+        assert_eq!(
+            parse("aw", true),
+            command(
+                None,
+                None,
+                Command::SelectObject(TextObject {
+                    kind: ObjectKind::Word { big: false },
+                    around: true
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let text = Rope::from_str("foo.bar  baz\n  qux");
+        let target = |offset, motion, count| motion_target(&text, offset, motion, count, 0);
+
+        assert_eq!(
+            target(0, Motion::NextWordStart { big: false }, None),
+            Some(3)
+        );
+        assert_eq!(
+            target(3, Motion::NextWordStart { big: false }, None),
+            Some(4)
+        );
+        assert_eq!(
+            target(4, Motion::NextWordStart { big: false }, None),
+            Some(9)
+        );
+        assert_eq!(
+            target(0, Motion::NextWordStart { big: true }, None),
+            Some(9)
+        );
+        assert_eq!(
+            target(9, Motion::NextWordStart { big: false }, None),
+            Some(15)
+        );
+        assert_eq!(
+            target(15, Motion::NextWordStart { big: false }, None),
+            Some(18)
+        );
+        assert_eq!(
+            target(0, Motion::NextWordStart { big: false }, Some(3)),
+            Some(9)
+        );
+
+        assert_eq!(target(0, Motion::NextWordEnd { big: false }, None), Some(2));
+        assert_eq!(target(2, Motion::NextWordEnd { big: false }, None), Some(3));
+        assert_eq!(target(0, Motion::NextWordEnd { big: true }, None), Some(6));
+        assert_eq!(
+            target(11, Motion::NextWordEnd { big: false }, None),
+            Some(17)
+        );
+
+        assert_eq!(
+            target(15, Motion::PrevWordStart { big: false }, None),
+            Some(9)
+        );
+        assert_eq!(
+            target(9, Motion::PrevWordStart { big: false }, None),
+            Some(4)
+        );
+        assert_eq!(
+            target(9, Motion::PrevWordStart { big: true }, None),
+            Some(0)
+        );
+        assert_eq!(
+            target(0, Motion::PrevWordStart { big: false }, None),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_line_motions() {
+        let text = Rope::from_str("  foo(a, (b))\nbar\n\nbaz qux");
+        let target = |offset, motion, count| motion_target(&text, offset, motion, count, 0);
+
+        assert_eq!(target(5, Motion::LineStart, None), Some(0));
+        assert_eq!(target(5, Motion::FirstNonBlank, None), Some(2));
+        assert_eq!(target(5, Motion::LineEnd, None), Some(12));
+        assert_eq!(target(5, Motion::LineEnd, Some(2)), Some(16));
+        assert_eq!(target(5, Motion::FirstLine, Some(2)), Some(14));
+        assert_eq!(target(5, Motion::LastLine, None), Some(19));
+        assert_eq!(target(2, Motion::Left, Some(5)), Some(0));
+        assert_eq!(target(10, Motion::Right, Some(5)), Some(13));
+        assert_eq!(target(0, Motion::Up, None), None);
+
+        // Keep the column, but not after the line end.
+        assert_eq!(motion_target(&text, 5, Motion::Down, None, 5), Some(16));
+        assert_eq!(motion_target(&text, 5, Motion::Down, Some(2), 5), Some(18));
+        assert_eq!(motion_target(&text, 16, Motion::Up, None, 5), Some(5));
+
+        let find = |c, forward, till| Motion::FindChar { c, forward, till };
+        assert_eq!(target(0, find('(', true, false), None), Some(5));
+        assert_eq!(target(0, find('(', true, false), Some(2)), Some(9));
+        assert_eq!(target(0, find('(', true, true), None), Some(4));
+        assert_eq!(target(12, find('(', false, false), None), Some(9));
+        assert_eq!(target(12, find('(', false, true), None), Some(10));
+        assert_eq!(target(0, find('z', true, false), None), None);
+
+        assert_eq!(target(0, Motion::MatchingBracket, None), Some(12));
+        assert_eq!(target(12, Motion::MatchingBracket, None), Some(5));
+        assert_eq!(target(9, Motion::MatchingBracket, None), Some(11));
+
+        assert_eq!(target(0, Motion::NextParagraph, None), Some(18));
+        assert_eq!(target(18, Motion::NextParagraph, None), Some(26));
+        assert_eq!(target(22, Motion::PrevParagraph, None), Some(18));
+        assert_eq!(target(18, Motion::PrevParagraph, None), Some(0));
+    }
+
+    #[test]
+    fn test_motion_range() {
+        let text = Rope::from_str("foo bar\nbaz\nqux");
+        assert_eq!(
+            motion_range(&text, 4, 0, MotionKind::Exclusive),
+            (0..4, false)
+        );
+        assert_eq!(
+            motion_range(&text, 0, 2, MotionKind::Inclusive),
+            (0..3, false)
+        );
+        assert_eq!(
+            motion_range(&text, 9, 2, MotionKind::Linewise),
+            (0..11, true)
+        );
+
+        assert_eq!(lines_range_with_newline(&text, &(0..7)), 0..8);
+        assert_eq!(lines_range_with_newline(&text, &(8..15)), 7..15);
+        assert_eq!(lines_range_with_newline(&text, &(0..15)), 0..15);
+    }
+
+    #[test]
+    fn test_text_object_range() {
+        let text = Rope::from_str("let foo = (a, [b]);  \"x y\"");
+        let object = |kind, around| TextObject { kind, around };
+        let word = ObjectKind::Word { big: false };
+
+        assert_eq!(text_object_range(&text, 5, object(word, false)), Some(4..7));
+        assert_eq!(text_object_range(&text, 5, object(word, true)), Some(4..8));
+        assert_eq!(text_object_range(&text, 3, object(word, false)), Some(3..4));
+        assert_eq!(text_object_range(&text, 3, object(word, true)), Some(3..7));
+
+        let parens = ObjectKind::Pair('(', ')');
+        assert_eq!(
+            text_object_range(&text, 11, object(parens, false)),
+            Some(11..17)
+        );
+        assert_eq!(
+            text_object_range(&text, 15, object(parens, true)),
+            Some(10..18)
+        );
+        assert_eq!(
+            text_object_range(&text, 10, object(parens, false)),
+            Some(11..17)
+        );
+        assert_eq!(text_object_range(&text, 2, object(parens, false)), None);
+
+        let quote = ObjectKind::Quote('"');
+        assert_eq!(
+            text_object_range(&text, 23, object(quote, false)),
+            Some(22..25)
+        );
+        assert_eq!(
+            text_object_range(&text, 0, object(quote, true)),
+            Some(21..26)
+        );
+    }
+
+    #[test]
+    fn test_toggle_case() {
+        assert_eq!(toggle_case("Hello, World"), "hELLO, wORLD");
+    }
+
+    #[gpui::test]
+    fn test_vim_repeat(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .multi_line()
+                .keymap_mode(KeymapMode::Vim)
+                .default_value("a b c d e f")
+        });
+        state.update_in(cx, |state, window, cx| {
+            type_keys(state, "dw", window, cx);
+            assert_eq!(state.value(), "b c d e f");
+            type_keys(state, ".", window, cx);
+            assert_eq!(state.value(), "c d e f");
+            // The count replaces the count of the last change.
+            type_keys(state, "2.", window, cx);
+            assert_eq!(state.value(), "e f");
+
+            // Repeat the change with the inserted text.
+            type_keys(state, "cw", window, cx);
+            assert_eq!(state.vim_mode(), Some(VimMode::Insert));
+            state.replace_text_in_range(None, "x", window, cx);
+            assert!(state.vim_escape(window, cx));
+            assert_eq!(state.value(), "x f");
+            type_keys(state, "w.", window, cx);
+            assert_eq!(state.value(), "x x");
+        });
+    }
+
+    #[gpui::test]
+    fn test_vim_undo_each_command(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .multi_line()
+                .keymap_mode(KeymapMode::Vim)
+                .default_value("a b c d e f")
+        });
+        state.update_in(cx, |state, window, cx| {
+            type_keys(state, "dwx.", window, cx);
+            assert_eq!(state.value(), "c d e f");
+
+            type_keys(state, "u", window, cx);
+            assert_eq!(state.value(), " c d e f");
+            type_keys(state, "u", window, cx);
+            assert_eq!(state.value(), "b c d e f");
+            type_keys(state, "u", window, cx);
+            assert_eq!(state.value(), "a b c d e f");
+        });
+    }
+
+    #[gpui::test]
+    fn test_vim_count(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .multi_line()
+                .keymap_mode(KeymapMode::Vim)
+                .default_value("abcdef\nline 2\nline 3")
+        });
+        state.update_in(cx, |state, window, cx| {
+            type_keys(state, "3x", window, cx);
+            assert_eq!(state.value(), "def\nline 2\nline 3");
+            type_keys(state, "2dd", window, cx);
+            assert_eq!(state.value(), "line 3");
+
+            // The huge count is clamped.
+            type_keys(state, "99999999999999999999999x", window, cx);
+            assert_eq!(state.value(), "");
+            state.set_value("ab", window, cx);
+            type_keys(state, "yl99999999p", window, cx);
+            assert_eq!(state.value().len(), 2 + MAX_COUNT);
+        });
+    }
+
+    #[gpui::test]
+    fn test_vim_register(cx: &mut TestAppContext) {
+        let (state, cx) = new_state(cx, |state| {
+            state
+                .multi_line()
+                .keymap_mode(KeymapMode::Vim)
+                .default_value("foo bar")
+        });
+        state.update_in(cx, |state, window, cx| {
+            type_keys(state, "\"ayw", window, cx);
+            assert_eq!(state.vim.registers[&'a'].text, "foo ");
+            assert_eq!(state.vim.registers[&'"'].text, "foo ");
+
+            // Append to the register.
+            type_keys(state, "w\"Ayw", window, cx);
+            assert_eq!(state.vim.registers[&'a'].text, "foo bar");
+            assert_eq!(state.vim.registers[&'"'].text, "foo bar");
+
+            type_keys(state, "$p", window, cx);
+            assert_eq!(state.value(), "foo barfoo bar");
+
+            // The black hole register keeps the other registers.
+            type_keys(state, "0\"_dw", window, cx);
+            assert_eq!(state.value(), "barfoo bar");
+            assert_eq!(state.vim.registers[&'"'].text, "foo bar");
+            type_keys(state, "\"aP", window, cx);
+            assert_eq!(state.value(), "foo barbarfoo bar");
+        });
+    }
+}
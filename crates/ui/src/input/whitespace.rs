@@ -0,0 +1,108 @@
+//! The visible markers of the whitespaces, `·` for the space and `→` for the tab.
+use std::ops::Range;
+
+use gpui::{Context, Window};
+
+use super::InputState;
+
+/// Where to display the whitespace markers, default is [`ShowWhitespace::None`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShowWhitespace {
+    /// Do not display the whitespaces.
+    #[default]
+    None,
+    /// Display all the whitespaces.
+    All,
+    /// Only display the whitespaces in the selections.
+    Selection,
+    /// Only display the whitespaces at the end of the lines.
+    Trailing,
+}
+
+/// Return the offsets (in the entire text) and chars of the whitespaces to display in the `line`.
+///
+/// - `line_start` is the offset of the line in the entire text.
+/// - `selections` are the selected ranges in the entire text, sorted by the start.
+pub(super) fn whitespace_markers(
+    line: &str,
+    line_start: usize,
+    show: ShowWhitespace,
+    selections: &[Range<usize>],
+) -> Vec<(usize, char)> {
+    if show == ShowWhitespace::None {
+        return vec![];
+    }
+
+    let trailing_start = line.trim_end_matches([' ', '\t']).len();
+    line.char_indices()
+        .filter(|(_, c)| *c == ' ' || *c == '\t')
+        .map(|(ix, c)| (line_start + ix, c))
+        .filter(|(offset, _)| match show {
+            ShowWhitespace::Selection => selections
+                .iter()
+                .any(|range| range.start <= *offset && *offset < range.end),
+            ShowWhitespace::Trailing => *offset >= line_start + trailing_start,
+            _ => true,
+        })
+        .collect()
+}
+
+impl InputState {
+    /// Set where to display the whitespace markers, only for the multi-line input.
+    ///
+    /// Default: [`ShowWhitespace::None`]
+    pub fn show_whitespace(mut self, show: ShowWhitespace) -> Self {
+        self.show_whitespace = show;
+        self
+    }
+
+    /// Set where to display the whitespace markers, only for the multi-line input.
+    pub fn set_show_whitespace(
+        &mut self,
+        show: ShowWhitespace,
+        _: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.show_whitespace = show;
+        cx.notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace_markers() {
+        let line = "\tlet a = 1;  ";
+        assert_eq!(
+            whitespace_markers(line, 10, ShowWhitespace::None, &[]),
+            vec![]
+        );
+        assert_eq!(
+            whitespace_markers(line, 10, ShowWhitespace::All, &[]),
+            vec![
+                (10, '\t'),
+                (14, ' '),
+                (16, ' '),
+                (18, ' '),
+                (21, ' '),
+                (22, ' ')
+            ]
+        );
+        assert_eq!(
+            whitespace_markers(line, 10, ShowWhitespace::Trailing, &[]),
+            vec![(21, ' '), (22, ' ')]
+        );
+        assert_eq!(
+            whitespace_markers(line, 10, ShowWhitespace::Selection, &[0..11, 16..19]),
+            vec![(10, '\t'), (16, ' '), (18, ' ')]
+        );
+
+        // The blank line is trailing whitespaces.
+        assert_eq!(
+            whitespace_markers("  ", 0, ShowWhitespace::Trailing, &[]),
+            vec![(0, ' '), (1, ' ')]
+        );
+    }
+}
@@ -27,6 +27,7 @@ pub mod checkbox;
 pub mod clipboard;
 pub mod color_picker;
 pub mod description_list;
+pub mod diff;
 pub mod divider;
 pub mod dock;
 pub mod drawer;
@@ -98,6 +99,7 @@ pub fn init(cx: &mut App) {
     #[cfg(any(feature = "inspector", debug_assertions))]
     inspector::init(cx);
     highlighter::init(cx);
+    diff::init(cx);
     date_picker::init(cx);
     dock::init(cx);
     drawer::init(cx);
@@ -108,6 +110,7 @@ pub fn init(cx: &mut App) {
     popover::init(cx);
     menu::init(cx);
     table::init(cx);
+    text::init(cx);
 }
 
 #[inline]
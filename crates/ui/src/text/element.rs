@@ -3,10 +3,11 @@ use std::ops::Range;
 use gpui::{
     div, img, prelude::FluentBuilder as _, px, relative, rems, AnyElement, App, DefiniteLength,
     ElementId, FontStyle, FontWeight, Half, HighlightStyle, InteractiveElement as _,
-    InteractiveText, IntoElement, Length, ObjectFit, ParentElement, Rems, RenderOnce, SharedString,
-    SharedUri, Styled, StyledImage as _, StyledText, Window,
+    InteractiveText, IntoElement, Length, ObjectFit, ParentElement, Rems, SharedString, SharedUri,
+    Styled, StyledImage as _, StyledText, Window,
 };
 use markdown::mdast;
+use ropey::Rope;
 
 use crate::{
     h_flex,
@@ -14,7 +15,11 @@ use crate::{
     v_flex, ActiveTheme as _, Icon, IconName,
 };
 
-use super::{utils::list_item_prefix, TextViewStyle};
+use super::{
+    selection::{BlockMarkdown, BlockSeparator, TextSelection},
+    utils::list_item_prefix,
+    TextViewStyle,
+};
 
 #[allow(unused)]
 #[derive(Debug, Default, Clone, PartialEq)]
@@ -68,7 +73,7 @@ pub struct TextNode {
     pub marks: Vec<(Range<usize>, InlineTextStyle)>,
 }
 
-#[derive(Debug, Clone, PartialEq, IntoElement)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Paragraph {
     Texts {
         span: Option<Span>,
@@ -177,6 +182,15 @@ impl Paragraph {
         }
     }
 
+    /// Move the span by the `offset`, e.g.: the paragraph is parsed from a part of the source.
+    pub(crate) fn shift_span(&mut self, offset: usize) {
+        let (Self::Texts { span, .. } | Self::Image { span, .. }) = self;
+        if let Some(span) = span {
+            span.start += offset;
+            span.end += offset;
+        }
+    }
+
     pub fn set_image(&mut self, image: ImageNode) {
         *self = Self::Image { span: None, image };
     }
@@ -223,7 +237,7 @@ impl CodeBlock {
         let mut styles = vec![];
         if let Some(lang) = &lang {
             let mut highlighter = SyntaxHighlighter::new(&lang, cx);
-            highlighter.update(&(0..0), code.clone(), "", cx);
+            highlighter.update(&(0..0), &Rope::from_str(&code), "", cx);
             styles = highlighter.styles(&(0..code.len()), &theme);
         };
 
@@ -279,6 +293,33 @@ impl Node {
         matches!(self, Self::Break { .. })
     }
 
+    /// Move the spans of the paragraphs by the `offset`, e.g.: the node is parsed from a part of the source.
+    pub(super) fn shift_spans(&mut self, offset: usize) {
+        match self {
+            Self::Root { children }
+            | Self::List { children, .. }
+            | Self::ListItem { children, .. } => {
+                children
+                    .iter_mut()
+                    .for_each(|child| child.shift_spans(offset));
+            }
+            Self::Paragraph(paragraph)
+            | Self::Heading {
+                children: paragraph,
+                ..
+            }
+            | Self::Blockquote(paragraph) => paragraph.shift_span(offset),
+            Self::Table(table) => {
+                for row in table.children.iter_mut() {
+                    for cell in row.children.iter_mut() {
+                        cell.children.shift_span(offset);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
     /// Combine all children, omitting the empt parent nodes.
     pub(super) fn compact(&self) -> Node {
         match self {
@@ -299,8 +340,16 @@ impl Node {
     }
 }
 
-impl RenderOnce for Paragraph {
-    fn render(self, window: &mut Window, cx: &mut App) -> impl IntoElement {
+impl Paragraph {
+    /// Render the paragraph, the text is registered to the `selection` to be selectable.
+    pub(crate) fn render(
+        self,
+        markdown: BlockMarkdown,
+        selection: &TextSelection,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> AnyElement {
+        let paragraph = selection.is_selectable().then(|| self.clone());
         match self {
             Self::Texts { span, children } => {
                 let mut text = String::new();
@@ -360,8 +409,10 @@ impl RenderOnce for Paragraph {
 
                 let text_style = window.text_style();
                 let element_id: ElementId = span.unwrap_or_default().into();
+                let text = SharedString::from(text);
                 let styled_text =
-                    StyledText::new(text).with_default_highlights(&text_style, highlights);
+                    StyledText::new(text.clone()).with_default_highlights(&text_style, highlights);
+                selection.push_block(text, paragraph, markdown, styled_text.layout().clone());
                 let link_ranges = links
                     .iter()
                     .map(|(range, _)| range.clone())
@@ -396,6 +447,27 @@ pub(crate) struct ListState {
     todo: bool,
     ordered: bool,
     depth: usize,
+    /// The index of the list item, and the checked state of the todo item.
+    ix: usize,
+    checked: Option<bool>,
+}
+
+impl ListState {
+    /// The Markdown prefix of the paragraph in the list item, e.g.: `- `, `1. `, `- [x] `.
+    fn markdown_prefix(&self) -> String {
+        let indent = "  ".repeat(self.depth.saturating_sub(1));
+        let marker = if self.ordered {
+            format!("{}. ", self.ix + 1)
+        } else {
+            "- ".to_string()
+        };
+        let checkbox = match self.checked {
+            Some(true) => "[x] ",
+            Some(false) => "[ ] ",
+            None => "",
+        };
+        format!("{}{}{}", indent, marker, checkbox)
+    }
 }
 
 impl Node {
@@ -404,6 +476,7 @@ impl Node {
         ix: usize,
         state: ListState,
         text_view_style: &TextViewStyle,
+        selection: &TextSelection,
         window: &mut Window,
         cx: &mut App,
     ) -> impl IntoElement {
@@ -461,9 +534,12 @@ impl Node {
                                                     depth: state.depth + 1,
                                                     ordered: state.ordered,
                                                     todo: checked.is_some(),
+                                                    ix,
+                                                    checked,
                                                 }),
                                                 true,
                                                 text_view_style,
+                                                selection,
                                                 window,
                                                 cx,
                                             ),
@@ -476,9 +552,11 @@ impl Node {
                                         depth: state.depth + 1,
                                         ordered: state.ordered,
                                         todo: checked.is_some(),
+                                        ..Default::default()
                                     }),
                                     true,
                                     text_view_style,
+                                    selection,
                                     window,
                                     cx,
                                 )))
@@ -493,7 +571,12 @@ impl Node {
         }
     }
 
-    fn render_table(item: &Node, _: &mut Window, cx: &mut App) -> impl IntoElement {
+    fn render_table(
+        item: &Node,
+        selection: &TextSelection,
+        window: &mut Window,
+        cx: &mut App,
+    ) -> impl IntoElement {
         const DEFAULT_LENGTH: usize = 5;
         const MAX_LENGTH: usize = 150;
         let col_lens = match item {
@@ -564,7 +647,19 @@ impl Node {
                                                         .border_color(cx.theme().border)
                                                 })
                                                 .truncate()
-                                                .child(cell.children.clone()),
+                                                .child(cell.children.clone().render(
+                                                    BlockMarkdown {
+                                                        separator: match (row_ix, ix) {
+                                                            (0, 0) => BlockSeparator::Block,
+                                                            (_, 0) => BlockSeparator::Line,
+                                                            _ => BlockSeparator::Cell,
+                                                        },
+                                                        ..Default::default()
+                                                    },
+                                                    selection,
+                                                    window,
+                                                    cx,
+                                                )),
                                         )
                                     }
                                     cells
@@ -581,9 +676,23 @@ impl Node {
     fn render_codeblock(
         code_block: CodeBlock,
         mb: Rems,
+        selection: &TextSelection,
         _: &mut Window,
         cx: &mut App,
     ) -> AnyElement {
+        let styled_text =
+            StyledText::new(code_block.code.clone()).with_highlights(code_block.styles);
+        selection.push_block(
+            code_block.code.clone(),
+            None,
+            BlockMarkdown {
+                prefix: format!("```{}\n", code_block.lang.unwrap_or_default()),
+                suffix: "\n```".to_string(),
+                ..Default::default()
+            },
+            styled_text.layout().clone(),
+        );
+
         div()
             .mb(mb)
             .p_3()
@@ -592,7 +701,7 @@ impl Node {
             .font_family("Menlo, Monaco, Consolas, monospace")
             .text_size(rems(0.875))
             .relative()
-            .child(StyledText::new(code_block.code.clone()).with_highlights(code_block.styles))
+            .child(styled_text)
             .into_any_element()
     }
 
@@ -601,6 +710,7 @@ impl Node {
         list_state: Option<ListState>,
         is_last_child: bool,
         text_view_style: &TextViewStyle,
+        selection: &TextSelection,
         window: &mut Window,
         cx: &mut App,
     ) -> impl IntoElement {
@@ -617,11 +727,24 @@ impl Node {
                     let children_len = children.len();
                     children.into_iter().enumerate().map(move |(index, c)| {
                         let is_last_child = index == children_len - 1;
-                        c.render(None, is_last_child, text_view_style, window, cx)
+                        c.render(None, is_last_child, text_view_style, selection, window, cx)
                     })
                 })
                 .into_any_element(),
-            Node::Paragraph(paragraph) => div().mb(mb).child(paragraph).into_any_element(),
+            Node::Paragraph(paragraph) => {
+                let markdown = match &list_state {
+                    Some(list_state) => BlockMarkdown {
+                        prefix: list_state.markdown_prefix(),
+                        separator: BlockSeparator::Line,
+                        ..Default::default()
+                    },
+                    None => BlockMarkdown::default(),
+                };
+                div()
+                    .mb(mb)
+                    .child(paragraph.render(markdown, selection, window, cx))
+                    .into_any_element()
+            }
             Node::Heading { level, children } => {
                 let (text_size, font_weight) = match level {
                     1 => (rems(2.), FontWeight::BOLD),
@@ -640,7 +763,15 @@ impl Node {
                     .whitespace_normal()
                     .text_size(text_size)
                     .font_weight(font_weight)
-                    .child(children)
+                    .child(children.render(
+                        BlockMarkdown {
+                            prefix: format!("{} ", "#".repeat(level as usize)),
+                            ..Default::default()
+                        },
+                        selection,
+                        window,
+                        cx,
+                    ))
                     .into_any_element()
             }
             Node::Blockquote(children) => div()
@@ -650,7 +781,15 @@ impl Node {
                 .border_l_3()
                 .border_color(cx.theme().secondary_active)
                 .px_4()
-                .child(children)
+                .child(children.render(
+                    BlockMarkdown {
+                        prefix: "> ".to_string(),
+                        ..Default::default()
+                    },
+                    selection,
+                    window,
+                    cx,
+                ))
                 .into_any_element(),
             Node::List { children, ordered } => v_flex()
                 .mb(mb)
@@ -668,8 +807,11 @@ impl Node {
                                 ordered,
                                 todo: list_state.todo,
                                 depth: list_state.depth,
+                                ix,
+                                checked: None,
                             },
                             text_view_style,
+                            selection,
                             window,
                             cx,
                         ));
@@ -681,8 +823,12 @@ impl Node {
                     items
                 })
                 .into_any_element(),
-            Node::CodeBlock(code_block) => Self::render_codeblock(code_block, mb, window, cx),
-            Node::Table { .. } => Self::render_table(&self, window, cx).into_any_element(),
+            Node::CodeBlock(code_block) => {
+                Self::render_codeblock(code_block, mb, selection, window, cx)
+            }
+            Node::Table { .. } => {
+                Self::render_table(&self, selection, window, cx).into_any_element()
+            }
             Node::Divider => div()
                 .bg(cx.theme().border)
                 .h(px(2.))
@@ -702,7 +848,63 @@ impl Node {
 }
 
 impl Paragraph {
-    fn to_markdown(&self) -> String {
+    /// Return the part of the paragraph in the `range` of the rendered text, with the marks in the range.
+    pub(crate) fn slice(&self, range: Range<usize>) -> Paragraph {
+        let Self::Texts { span, children } = self else {
+            return self.clone();
+        };
+
+        let mut rendered_len = 0;
+        let mut nodes = vec![];
+        for text_node in children {
+            // Same as the rendering, the leading spaces of the first text are trimmed.
+            let text = if rendered_len == 0 {
+                text_node.text.trim_start()
+            } else {
+                text_node.text.as_str()
+            };
+            let trimmed = text_node.text.len() - text.len();
+            let node_start = rendered_len;
+            rendered_len += text.len();
+
+            let start = range.start.max(node_start);
+            let end = range.end.min(rendered_len);
+            if start >= end {
+                continue;
+            }
+
+            let local_range = (start - node_start + trimmed)..(end - node_start + trimmed);
+            let Some(text) = text_node.text.get(local_range.clone()) else {
+                continue;
+            };
+            let marks = text_node
+                .marks
+                .iter()
+                .filter_map(|(range, style)| {
+                    let start = range.start.max(local_range.start);
+                    let end = range.end.min(local_range.end);
+                    (start < end).then(|| {
+                        (
+                            (start - local_range.start)..(end - local_range.start),
+                            style.clone(),
+                        )
+                    })
+                })
+                .collect();
+
+            nodes.push(TextNode {
+                text: text.to_string(),
+                marks,
+            });
+        }
+
+        Self::Texts {
+            span: *span,
+            children: nodes,
+        }
+    }
+
+    pub(crate) fn to_markdown(&self) -> String {
         let mut text = match self {
             Paragraph::Texts { children, .. } => children
                 .iter()
@@ -7,8 +7,8 @@ use std::rc::Rc;
 
 use gpui::prelude::FluentBuilder as _;
 use gpui::{
-    div, px, relative, AnyElement, DefiniteLength, Element, ElementId, IntoElement,
-    ParentElement as _, SharedString, Styled as _, Window,
+    div, px, relative, AnyElement, DefiniteLength, Element, ElementId, Hitbox, HitboxBehavior,
+    IntoElement, ParentElement as _, SharedString, Styled as _, Window,
 };
 use html5ever::tendril::TendrilSink;
 use html5ever::{local_name, parse_document, LocalName, ParseOpts};
@@ -19,6 +19,7 @@ use crate::v_flex;
 use super::element::{
     self, ImageNode, InlineTextStyle, LinkMark, Paragraph, Table, TableRow, TextNode,
 };
+use super::selection::TextSelection;
 use super::TextViewStyle;
 
 const BLOCK_ELEMENTS: [&str; 35] = [
@@ -92,6 +93,7 @@ pub(super) struct HtmlElement {
     id: ElementId,
     pub(super) text: SharedString,
     style: TextViewStyle,
+    selectable: bool,
 }
 
 impl HtmlElement {
@@ -100,6 +102,7 @@ impl HtmlElement {
             id: id.into(),
             text: raw.into(),
             style: TextViewStyle::default(),
+            selectable: false,
         }
     }
 
@@ -114,12 +117,19 @@ impl HtmlElement {
         self.style = style.into();
         self
     }
+
+    /// Set the text to be selectable, default is false.
+    pub(crate) fn selectable(mut self, selectable: bool) -> Self {
+        self.selectable = selectable;
+        self
+    }
 }
 
 #[derive(Default)]
 pub struct HtmlState {
     raw: SharedString,
     root: Option<Result<element::Node, SharedString>>,
+    selection: Option<TextSelection>,
 }
 
 impl HtmlState {
@@ -144,8 +154,8 @@ impl IntoElement for HtmlElement {
 }
 
 impl Element for HtmlElement {
-    type RequestLayoutState = AnyElement;
-    type PrepaintState = ();
+    type RequestLayoutState = (AnyElement, TextSelection);
+    type PrepaintState = Hitbox;
 
     fn id(&self) -> Option<gpui::ElementId> {
         Some(self.id.clone())
@@ -165,27 +175,33 @@ impl Element for HtmlElement {
         window.with_element_state(id.unwrap(), |state, window| {
             let mut state: HtmlState = state.unwrap_or_default();
             state.parse_if_needed(self.text.clone());
+            let selection = state
+                .selection
+                .get_or_insert_with(|| TextSelection::new(cx))
+                .clone();
+            selection.reset(self.selectable);
 
             let root = state
                 .root
                 .clone()
                 .expect("BUG: root should not None, maybe parse_if_needed issue.");
 
-            let mut el = div()
-                .map(|this| match root {
-                    Ok(node) => this.child(node.render(None, true, &self.style, window, cx)),
-                    Err(err) => this.child(
-                        v_flex()
-                            .gap_1()
-                            .child("Error parsing HTML")
-                            .child(err.to_string()),
-                    ),
-                })
-                .into_any_element();
+            let el = div().map(|this| match root {
+                Ok(node) => {
+                    this.child(node.render(None, true, &self.style, &selection, window, cx))
+                }
+                Err(err) => this.child(
+                    v_flex()
+                        .gap_1()
+                        .child("Error parsing HTML")
+                        .child(err.to_string()),
+                ),
+            });
+            let mut el = selection.wrap(el);
 
             let layout_id = el.request_layout(window, cx);
 
-            ((layout_id, el), state)
+            ((layout_id, (el, selection)), state)
         })
     }
 
@@ -193,12 +209,13 @@ impl Element for HtmlElement {
         &mut self,
         _: Option<&gpui::GlobalElementId>,
         _: Option<&gpui::InspectorElementId>,
-        _: gpui::Bounds<gpui::Pixels>,
+        bounds: gpui::Bounds<gpui::Pixels>,
         request_layout: &mut Self::RequestLayoutState,
         window: &mut Window,
         cx: &mut gpui::App,
     ) -> Self::PrepaintState {
-        request_layout.prepaint(window, cx);
+        request_layout.0.prepaint(window, cx);
+        window.insert_hitbox(bounds, HitboxBehavior::Normal)
     }
 
     fn paint(
@@ -207,11 +224,13 @@ impl Element for HtmlElement {
         _: Option<&gpui::InspectorElementId>,
         _: gpui::Bounds<gpui::Pixels>,
         request_layout: &mut Self::RequestLayoutState,
-        _: &mut Self::PrepaintState,
+        hitbox: &mut Self::PrepaintState,
         window: &mut Window,
         cx: &mut gpui::App,
     ) {
-        request_layout.paint(window, cx);
+        let (el, selection) = request_layout;
+        el.paint(window, cx);
+        selection.paint(hitbox, window, cx);
     }
 }
 
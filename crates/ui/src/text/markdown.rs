@@ -1,21 +1,22 @@
 use std::time::Instant;
 
 use gpui::{
-    div, prelude::FluentBuilder as _, AnyElement, App, Element, ElementId, IntoElement,
-    ParentElement, SharedString, Styled, Window,
+    div, prelude::FluentBuilder as _, AnyElement, App, Element, ElementId, Hitbox, HitboxBehavior,
+    IntoElement, ParentElement, SharedString, Styled, Window,
 };
 use markdown::{
     mdast::{self, Node},
     ParseOptions,
 };
 
-use crate::v_flex;
+use crate::{highlighter::Language, v_flex};
 
 use super::{
     element::{
         self, CodeBlock, ImageNode, InlineTextStyle, LinkMark, Paragraph, Span, Table, TableRow,
     },
     html::parse_html,
+    selection::TextSelection,
     TextViewStyle,
 };
 
@@ -30,6 +31,8 @@ pub(super) struct MarkdownElement {
     id: ElementId,
     pub(super) text: SharedString,
     style: TextViewStyle,
+    selectable: bool,
+    streaming: bool,
 }
 
 impl MarkdownElement {
@@ -38,6 +41,8 @@ impl MarkdownElement {
             id: id.into(),
             text: raw.into(),
             style: TextViewStyle::default(),
+            selectable: false,
+            streaming: false,
         }
     }
 
@@ -52,6 +57,18 @@ impl MarkdownElement {
         self.style = style.into();
         self
     }
+
+    /// Set the text to be selectable, default is false.
+    pub(crate) fn selectable(mut self, selectable: bool) -> Self {
+        self.selectable = selectable;
+        self
+    }
+
+    /// Set to parse the appended text incrementally, default is false.
+    pub(crate) fn streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
 }
 
 #[derive(Default)]
@@ -59,33 +76,229 @@ pub struct MarkdownState {
     raw: SharedString,
     root: Option<Result<element::Node, SharedString>>,
     style: TextViewStyle,
+    selection: Option<TextSelection>,
+    stream: StreamState,
     _last_parsed: Option<Instant>,
 }
 
 impl MarkdownState {
-    fn parse_if_needed(&mut self, new_text: SharedString, style: &TextViewStyle, cx: &mut App) {
+    fn parse_if_needed(
+        &mut self,
+        new_text: SharedString,
+        style: &TextViewStyle,
+        streaming: bool,
+        cx: &mut App,
+    ) {
         let is_changed = self.raw != new_text || self.style != *style;
 
         if self.root.is_some() && !is_changed {
             return;
         }
 
-        if let Some(last_parsed) = self._last_parsed {
-            if last_parsed.elapsed().as_millis() < 500 {
-                return;
+        // The appended text is cheap to parse in streaming, so it is not throttled.
+        let is_appended = streaming
+            && self.root.is_some()
+            && self.style == *style
+            && new_text.starts_with(self.raw.as_str());
+        if !is_appended {
+            if let Some(last_parsed) = self._last_parsed {
+                if last_parsed.elapsed().as_millis() < 500 {
+                    return;
+                }
             }
+
+            self.stream = StreamState::default();
         }
 
         self.raw = new_text;
         // NOTE: About 100ms
         // let measure = crate::Measure::new("parse_markdown");
-        self.root = Some(parse_markdown(&self.raw, &style, cx));
+        self.root = Some(if streaming {
+            self.stream.parse(&self.raw, style, cx)
+        } else {
+            parse_markdown(&self.raw, &style, cx)
+        });
         // measure.end();
         self._last_parsed = Some(Instant::now());
         self.style = style.clone();
     }
 }
 
+/// The state to parse the streaming Markdown incrementally.
+///
+/// A block is completed once the next block is started, so the completed blocks are cached,
+/// and only the last block is parsed again when the text is appended.
+#[derive(Default)]
+struct StreamState {
+    /// The completed blocks before the `offset`.
+    blocks: Vec<element::Node>,
+    /// The byte offset of the last block, that may be changed by the appended text.
+    offset: usize,
+}
+
+impl StreamState {
+    fn parse(
+        &mut self,
+        raw: &str,
+        style: &TextViewStyle,
+        cx: &mut App,
+    ) -> Result<element::Node, SharedString> {
+        let root = markdown::to_mdast(&raw[self.offset..], &ParseOptions::gfm())
+            .map_err(|e| SharedString::from(e.to_string()))?;
+        let mut children = match root {
+            Node::Root(root) => root.children,
+            _ => vec![],
+        };
+
+        let last_start = children
+            .last()
+            .and_then(|node| node.position())
+            .map(|pos| pos.start.offset);
+        if let Some(last_start) = last_start.filter(|_| children.len() > 1) {
+            children.pop();
+            for child in children {
+                let mut node = ast_to_node(child, style, cx);
+                node.shift_spans(self.offset);
+                self.blocks.push(node);
+            }
+            self.offset += last_start;
+        }
+
+        let mut pending = parse_markdown(&complete_pending(&raw[self.offset..]), style, cx)?;
+        pending.shift_spans(self.offset);
+        let mut children = self.blocks.clone();
+        if let element::Node::Root { children: pending } = pending {
+            children.extend(pending);
+        }
+
+        Ok(element::Node::Root { children })
+    }
+}
+
+/// Complete the unfinished Markdown at the end of the streaming text, to render it gracefully
+/// before the rest is received, e.g.: the partial closing fence of the code block is removed,
+/// the partial delimiter row of the table is completed, and the emphasis is closed.
+fn complete_pending(text: &str) -> String {
+    let mut fence: Option<(char, usize)> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        let Some(c) = line.chars().next().filter(|c| matches!(c, '`' | '~')) else {
+            continue;
+        };
+        let len = line.chars().take_while(|ch| *ch == c).count();
+        match fence {
+            Some((fence_char, fence_len)) => {
+                if c == fence_char && len >= fence_len && line.len() == len {
+                    fence = None;
+                }
+            }
+            None => {
+                if len >= 3 {
+                    fence = Some((c, len));
+                }
+            }
+        }
+    }
+
+    let (head, last_line) = match text.rfind('\n') {
+        Some(ix) => text.split_at(ix + 1),
+        None => ("", text),
+    };
+    let trimmed = last_line.trim();
+
+    // The fence is being typed, e.g.: the partial closing fence of the code block.
+    if !trimmed.is_empty() && trimmed.chars().all(|c| matches!(c, '`' | '~')) {
+        return head.to_string();
+    }
+    if fence.is_some() {
+        return text.to_string();
+    }
+
+    // The delimiter row of the table is being typed.
+    let prev_line = head
+        .trim_end_matches('\n')
+        .lines()
+        .last()
+        .unwrap_or_default();
+    if prev_line.trim_start().starts_with('|')
+        && trimmed.starts_with('|')
+        && trimmed.contains('-')
+        && trimmed.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+    {
+        let cols = prev_line
+            .trim()
+            .trim_start_matches('|')
+            .trim_end_matches('|')
+            .split('|')
+            .count();
+        return format!("{}|{}", head, " --- |".repeat(cols));
+    }
+
+    format!("{}{}", head, close_inline_marks(last_line))
+}
+
+/// Close the unclosed emphasis, strikethrough and inline code of the line,
+/// and remove the trailing marks that are not followed by any text.
+fn close_inline_marks(line: &str) -> String {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut marks: Vec<(char, usize)> = vec![];
+    let mut code: Option<usize> = None;
+    let mut trailing = None;
+
+    let mut ix = 0;
+    while ix < chars.len() {
+        let (offset, c) = chars[ix];
+        if c == '\\' {
+            ix += 2;
+            continue;
+        }
+        if !matches!(c, '`' | '*' | '~') {
+            ix += 1;
+            continue;
+        }
+
+        let len = chars[ix..].iter().take_while(|(_, ch)| *ch == c).count();
+        let prev = ix.checked_sub(1).map(|ix| chars[ix].1);
+        let next = chars.get(ix + len).map(|(_, c)| *c);
+        ix += len;
+
+        if c == '`' {
+            match code {
+                Some(code_len) if code_len == len => code = None,
+                None if next.is_none() => trailing = Some(offset),
+                None => code = Some(len),
+                _ => {}
+            }
+            continue;
+        }
+        if code.is_some() || (c == '~' && len != 2) {
+            continue;
+        }
+
+        let can_open = next.is_some_and(|c| !c.is_whitespace());
+        let can_close = prev.is_some_and(|c| !c.is_whitespace());
+        if can_close && marks.last() == Some(&(c, len)) {
+            marks.pop();
+        } else if can_open {
+            marks.push((c, len));
+        } else if next.is_none() {
+            trailing = Some(offset);
+        }
+    }
+
+    let mut line = match trailing {
+        Some(offset) => line[..offset].to_string(),
+        None => line.to_string(),
+    };
+    if let Some(len) = code {
+        line.push_str(&"`".repeat(len));
+    }
+    for (c, len) in marks.into_iter().rev() {
+        line.push_str(&c.to_string().repeat(len));
+    }
+    line
+}
+
 impl IntoElement for MarkdownElement {
     type Element = Self;
 
@@ -95,8 +308,8 @@ impl IntoElement for MarkdownElement {
 }
 
 impl Element for MarkdownElement {
-    type RequestLayoutState = AnyElement;
-    type PrepaintState = ();
+    type RequestLayoutState = (AnyElement, TextSelection);
+    type PrepaintState = Hitbox;
 
     fn id(&self) -> Option<gpui::ElementId> {
         Some(self.id.clone())
@@ -115,28 +328,34 @@ impl Element for MarkdownElement {
     ) -> (gpui::LayoutId, Self::RequestLayoutState) {
         window.with_element_state(id.unwrap(), |state, window| {
             let mut state: MarkdownState = state.unwrap_or_default();
-            state.parse_if_needed(self.text.clone(), &self.style, cx);
+            state.parse_if_needed(self.text.clone(), &self.style, self.streaming, cx);
+            let selection = state
+                .selection
+                .get_or_insert_with(|| TextSelection::new(cx))
+                .clone();
+            selection.reset(self.selectable);
 
             let root = state
                 .root
                 .clone()
                 .expect("BUG: root should not None, maybe parse_if_needed issue.");
 
-            let mut el = div()
-                .map(|this| match root {
-                    Ok(node) => this.child(node.render(None, true, &self.style, window, cx)),
-                    Err(err) => this.child(
-                        v_flex()
-                            .gap_1()
-                            .child("Error parsing Markdown")
-                            .child(err.to_string()),
-                    ),
-                })
-                .into_any_element();
+            let el = div().map(|this| match root {
+                Ok(node) => {
+                    this.child(node.render(None, true, &self.style, &selection, window, cx))
+                }
+                Err(err) => this.child(
+                    v_flex()
+                        .gap_1()
+                        .child("Error parsing Markdown")
+                        .child(err.to_string()),
+                ),
+            });
+            let mut el = selection.wrap(el);
 
             let layout_id = el.request_layout(window, cx);
 
-            ((layout_id, el), state)
+            ((layout_id, (el, selection)), state)
         })
     }
 
@@ -144,12 +363,13 @@ impl Element for MarkdownElement {
         &mut self,
         _: Option<&gpui::GlobalElementId>,
         _: Option<&gpui::InspectorElementId>,
-        _: gpui::Bounds<gpui::Pixels>,
+        bounds: gpui::Bounds<gpui::Pixels>,
         request_layout: &mut Self::RequestLayoutState,
         window: &mut Window,
         cx: &mut gpui::App,
     ) -> Self::PrepaintState {
-        request_layout.prepaint(window, cx);
+        request_layout.0.prepaint(window, cx);
+        window.insert_hitbox(bounds, HitboxBehavior::Normal)
     }
 
     fn paint(
@@ -158,11 +378,13 @@ impl Element for MarkdownElement {
         _: Option<&gpui::InspectorElementId>,
         _: gpui::Bounds<gpui::Pixels>,
         request_layout: &mut Self::RequestLayoutState,
-        _: &mut Self::PrepaintState,
+        hitbox: &mut Self::PrepaintState,
         window: &mut Window,
         cx: &mut gpui::App,
     ) {
-        request_layout.paint(window, cx);
+        let (el, selection) = request_layout;
+        el.paint(window, cx);
+        selection.paint(hitbox, window, cx);
     }
 }
 
@@ -413,12 +635,16 @@ fn ast_to_node(value: mdast::Node, style: &TextViewStyle, cx: &mut App) -> eleme
             }
         }
         Node::Break(_) => element::Node::Break { html: false },
-        Node::Code(raw) => element::Node::CodeBlock(CodeBlock::new(
-            raw.value.into(),
-            raw.lang.map(|s| s.into()),
-            style,
-            cx,
-        )),
+        Node::Code(raw) => {
+            let lang = raw.lang.map(SharedString::from).or_else(|| {
+                style
+                    .detect_language
+                    .then(|| Language::detect(None, &raw.value))
+                    .filter(|language| *language != Language::Plain)
+                    .map(|language| language.name().into())
+            });
+            element::Node::CodeBlock(CodeBlock::new(raw.value.into(), lang, style, cx))
+        }
         Node::Heading(val) => {
             let mut paragraph = Paragraph::default();
             val.children.iter().for_each(|c| {
@@ -500,3 +726,53 @@ fn ast_to_node(value: mdast::Node, style: &TextViewStyle, cx: &mut App) -> eleme
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{close_inline_marks, complete_pending};
+
+    #[test]
+    fn test_close_inline_marks() {
+        assert_eq!(close_inline_marks("Hello **world"), "Hello **world**");
+        assert_eq!(
+            close_inline_marks("Hello **world** and *it"),
+            "Hello **world** and *it*"
+        );
+        assert_eq!(close_inline_marks("~~del and `code"), "~~del and `code`~~");
+        assert_eq!(close_inline_marks("`a * b"), "`a * b`");
+        assert_eq!(close_inline_marks("Hello **"), "Hello ");
+        assert_eq!(close_inline_marks("Hello `"), "Hello ");
+        assert_eq!(close_inline_marks("* item"), "* item");
+        assert_eq!(close_inline_marks("2 * 3 = 6"), "2 * 3 = 6");
+        assert_eq!(close_inline_marks("\\*not"), "\\*not");
+        assert_eq!(close_inline_marks("~5 min"), "~5 min");
+    }
+
+    #[test]
+    fn test_complete_pending() {
+        assert_eq!(complete_pending("Hello\n**wor"), "Hello\n**wor**");
+        assert_eq!(complete_pending("**done**\n"), "**done**\n");
+
+        // Code fence
+        assert_eq!(
+            complete_pending("```rust\nlet a = **b;\n``"),
+            "```rust\nlet a = **b;\n"
+        );
+        assert_eq!(
+            complete_pending("```rust\nlet a = **b"),
+            "```rust\nlet a = **b"
+        );
+        assert_eq!(complete_pending("Text\n``"), "Text\n");
+
+        // Table
+        assert_eq!(
+            complete_pending("| a | b |\n| --"),
+            "| a | b |\n| --- | --- |"
+        );
+        assert_eq!(
+            complete_pending("| a | b |\n| - | - |\n| **1"),
+            "| a | b |\n| - | - |\n| **1**"
+        );
+        assert_eq!(complete_pending("a | b\n-"), "a | b\n-");
+    }
+}
@@ -1,7 +1,13 @@
 mod element;
 mod html;
 mod markdown;
+mod selection;
 mod text_view;
 mod utils;
 
+pub use selection::{Copy, CopyAsMarkdown};
 pub use text_view::*;
+
+pub(crate) fn init(cx: &mut gpui::App) {
+    selection::init(cx);
+}
@@ -0,0 +1,676 @@
+//! Select and copy the text of the [`TextView`](super::TextView).
+use std::{cell::RefCell, ops::Range, rc::Rc};
+
+use gpui::{
+    actions, div, point, AnyElement, App, Bounds, ClipboardItem, CursorStyle, DispatchPhase,
+    FocusHandle, Hitbox, InteractiveElement as _, IntoElement, KeyBinding, MouseButton,
+    MouseDownEvent, MouseMoveEvent, MouseUpEvent, ParentElement as _, Pixels, Point, SharedString,
+    TextLayout, Window,
+};
+
+use crate::ActiveTheme as _;
+
+use super::element::Paragraph;
+
+actions!(text_view, [Copy, CopyAsMarkdown]);
+
+const CONTEXT: &str = "TextView";
+
+pub(crate) fn init(cx: &mut App) {
+    cx.bind_keys([
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-c", Copy, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-c", Copy, Some(CONTEXT)),
+        #[cfg(target_os = "macos")]
+        KeyBinding::new("cmd-shift-c", CopyAsMarkdown, Some(CONTEXT)),
+        #[cfg(not(target_os = "macos"))]
+        KeyBinding::new("ctrl-shift-c", CopyAsMarkdown, Some(CONTEXT)),
+    ]);
+}
+
+/// How a text block is joined with the previous block when copying.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) enum BlockSeparator {
+    /// A new block, e.g.: a paragraph, a heading or a code block.
+    #[default]
+    Block,
+    /// A new line of the same block, e.g.: the next list item or table row.
+    Line,
+    /// The next cell of the table row.
+    Cell,
+}
+
+impl BlockSeparator {
+    fn text(&self) -> &'static str {
+        match self {
+            Self::Block | Self::Line => "\n",
+            Self::Cell => "\t",
+        }
+    }
+
+    fn markdown(&self) -> &'static str {
+        match self {
+            Self::Block => "\n\n",
+            Self::Line => "\n",
+            Self::Cell => " | ",
+        }
+    }
+}
+
+/// The Markdown around the text of a block, used to copy the selection as Markdown.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct BlockMarkdown {
+    /// The prefix before the text, e.g.: `## ` of the heading, `- ` of the list item.
+    pub(crate) prefix: String,
+    /// The suffix after the text, e.g.: the closing fence of the code block.
+    pub(crate) suffix: String,
+    pub(crate) separator: BlockSeparator,
+}
+
+/// A text block rendered by the [`TextView`](super::TextView), e.g.: a paragraph, a table cell or a code block.
+#[derive(Debug, Clone)]
+struct TextBlock {
+    /// The rendered text.
+    text: SharedString,
+    /// The inline styles of the text, the plain text is used as Markdown if `None`, e.g.: the code block.
+    paragraph: Option<Paragraph>,
+    markdown: BlockMarkdown,
+}
+
+impl TextBlock {
+    /// Return the text of the `range` of the block, as plain text or Markdown.
+    fn slice(&self, range: Range<usize>, markdown: bool) -> String {
+        if !markdown {
+            return self.text[range].to_string();
+        }
+
+        let mut text = match &self.paragraph {
+            Some(paragraph) => {
+                let text = paragraph.slice(range.clone()).to_markdown();
+                text.strip_suffix("\n\n").unwrap_or(&text).to_string()
+            }
+            None => self.text[range.clone()].to_string(),
+        };
+        // The prefix and suffix are only kept when the start and end of the block are selected.
+        if range.start == 0 {
+            text.insert_str(0, &self.markdown.prefix);
+        }
+        if range.end == self.text.len() {
+            text.push_str(&self.markdown.suffix);
+        }
+        text
+    }
+}
+
+/// A position in the text blocks, the `offset` is the byte offset in the text of the block.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct TextPoint {
+    block: usize,
+    offset: usize,
+}
+
+#[derive(Default)]
+struct SelectionState {
+    selectable: bool,
+    blocks: Vec<TextBlock>,
+    /// The layouts of the `blocks`, they are available after prepaint.
+    layouts: Vec<TextLayout>,
+    anchor: TextPoint,
+    head: TextPoint,
+    selecting: bool,
+}
+
+impl SelectionState {
+    fn range(&self) -> Range<TextPoint> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// Return the point of the text at the `position`.
+    ///
+    /// If the position is not on any text, the closest block before it (in reading order) is used.
+    fn point_for_position(&self, position: Point<Pixels>) -> Option<TextPoint> {
+        let mut closest = None;
+        for (ix, layout) in self.layouts.iter().enumerate() {
+            let bounds = layout.bounds();
+            if bounds.contains(&position) {
+                let offset = layout.index_for_position(position).unwrap_or_else(|ix| ix);
+                return Some(TextPoint { block: ix, offset });
+            }
+
+            let is_before = position.y > bounds.bottom()
+                || (position.y >= bounds.top() && position.x >= bounds.left());
+            if is_before {
+                closest = Some(ix);
+            }
+        }
+
+        match closest {
+            Some(ix) => {
+                let layout = &self.layouts[ix];
+                let offset = if position.y > layout.bounds().bottom() {
+                    self.blocks[ix].text.len()
+                } else {
+                    layout.index_for_position(position).unwrap_or_else(|ix| ix)
+                };
+                Some(TextPoint { block: ix, offset })
+            }
+            None => (!self.blocks.is_empty()).then(TextPoint::default),
+        }
+    }
+
+    /// Start to select at the `point`, double click to select a word, triple click to select a paragraph.
+    fn select(&mut self, point: TextPoint, click_count: usize, extend: bool) {
+        let Some(block) = self.blocks.get(point.block) else {
+            return;
+        };
+
+        self.selecting = true;
+        if extend {
+            self.head = point;
+            return;
+        }
+
+        let range = match click_count {
+            2 => word_range(&block.text, point.offset),
+            3.. => paragraph_range(&block.text, point.offset),
+            _ => point.offset..point.offset,
+        };
+        self.anchor = TextPoint {
+            block: point.block,
+            offset: range.start,
+        };
+        self.head = TextPoint {
+            block: point.block,
+            offset: range.end,
+        };
+    }
+
+    fn selected_text(&self, markdown: bool) -> String {
+        selected_text(&self.blocks, self.range(), markdown)
+    }
+
+    fn paint(&self, window: &mut Window, cx: &mut App) {
+        if self.is_empty() {
+            return;
+        }
+
+        let range = self.range();
+        let color = cx.theme().selection.opacity(0.5);
+        for ix in range.start.block..=range.end.block {
+            let (Some(block), Some(layout)) = (self.blocks.get(ix), self.layouts.get(ix)) else {
+                break;
+            };
+
+            let len = block.text.len();
+            let start = if ix == range.start.block {
+                range.start.offset.min(len)
+            } else {
+                0
+            };
+            let end = if ix == range.end.block {
+                range.end.offset.min(len)
+            } else {
+                len
+            };
+            if start >= end {
+                continue;
+            }
+
+            for bounds in selection_bounds(layout, start..end) {
+                window.paint_quad(gpui::fill(bounds, color));
+            }
+        }
+    }
+}
+
+/// Return the bounds of the lines of the `range` in the text layout.
+fn selection_bounds(layout: &TextLayout, range: Range<usize>) -> Vec<Bounds<Pixels>> {
+    let bounds = layout.bounds();
+    let line_height = layout.line_height();
+    let Some(start) = layout.position_for_index(range.start) else {
+        return vec![];
+    };
+    let end = layout
+        .position_for_index(range.end)
+        .unwrap_or(point(bounds.right(), bounds.bottom() - line_height));
+
+    if start.y == end.y {
+        return vec![Bounds::from_corners(
+            start,
+            point(end.x, end.y + line_height),
+        )];
+    }
+
+    let mut lines = vec![Bounds::from_corners(
+        start,
+        point(bounds.right(), start.y + line_height),
+    )];
+    if end.y > start.y + line_height {
+        lines.push(Bounds::from_corners(
+            point(bounds.left(), start.y + line_height),
+            point(bounds.right(), end.y),
+        ));
+    }
+    lines.push(Bounds::from_corners(
+        point(bounds.left(), end.y),
+        point(end.x, end.y + line_height),
+    ));
+    lines
+}
+
+/// Return the text of the `range` in the `blocks`, as plain text or Markdown.
+fn selected_text(blocks: &[TextBlock], range: Range<TextPoint>, markdown: bool) -> String {
+    let mut output = String::new();
+    for (ix, block) in blocks
+        .iter()
+        .enumerate()
+        .take(range.end.block + 1)
+        .skip(range.start.block)
+    {
+        let len = block.text.len();
+        let start = if ix == range.start.block {
+            range.start.offset.min(len)
+        } else {
+            0
+        };
+        let end = if ix == range.end.block {
+            range.end.offset.min(len)
+        } else {
+            len
+        };
+        // The offsets may be inside a character after the blocks are changed.
+        let (start, end) = (
+            floor_char_boundary(&block.text, start),
+            ceil_char_boundary(&block.text, end),
+        );
+        if start >= end {
+            continue;
+        }
+
+        if !output.is_empty() {
+            output.push_str(if markdown {
+                block.markdown.separator.markdown()
+            } else {
+                block.markdown.separator.text()
+            });
+        }
+        output.push_str(&block.slice(start..end, markdown));
+    }
+    output
+}
+
+/// Return the nearest char boundary at or before the `offset`.
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Return the nearest char boundary at or after the `offset`.
+fn ceil_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset += 1;
+    }
+    offset
+}
+
+#[inline]
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_')
+}
+
+/// Return the range of the word at the `offset`, or the character if it is not a word.
+fn word_range(text: &str, offset: usize) -> Range<usize> {
+    let offset = offset.min(text.len());
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(offset, |(ix, _)| ix);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(text.len(), |(ix, _)| offset + ix);
+
+    if start < end {
+        return start..end;
+    }
+
+    // Select the character after the offset, e.g.: a space or a punctuation.
+    let end = text[offset..]
+        .chars()
+        .next()
+        .map_or(offset, |c| offset + c.len_utf8());
+    offset..end
+}
+
+/// Return the range of the paragraph at the `offset`, the paragraphs are separated by line breaks.
+fn paragraph_range(text: &str, offset: usize) -> Range<usize> {
+    let offset = offset.min(text.len());
+    let start = text[..offset].rfind('\n').map_or(0, |ix| ix + 1);
+    let end = text[offset..]
+        .find('\n')
+        .map_or(text.len(), |ix| offset + ix);
+    start..end
+}
+
+/// The text selection of a [`TextView`](super::TextView), it is kept in the element state,
+/// and the text blocks are registered when the nodes are rendered.
+#[derive(Clone)]
+pub(crate) struct TextSelection {
+    state: Rc<RefCell<SelectionState>>,
+    focus_handle: FocusHandle,
+}
+
+impl TextSelection {
+    pub(crate) fn new(cx: &mut App) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(SelectionState::default())),
+            focus_handle: cx.focus_handle(),
+        }
+    }
+
+    /// Clear the text blocks before the nodes are rendered again.
+    pub(crate) fn reset(&self, selectable: bool) {
+        let mut state = self.state.borrow_mut();
+        state.selectable = selectable;
+        state.blocks.clear();
+        state.layouts.clear();
+        if !selectable {
+            state.anchor = state.head;
+        }
+    }
+
+    pub(crate) fn is_selectable(&self) -> bool {
+        self.state.borrow().selectable
+    }
+
+    /// Register a text block in the document order, the `paragraph` is used to copy as Markdown.
+    pub(crate) fn push_block(
+        &self,
+        text: SharedString,
+        paragraph: Option<Paragraph>,
+        markdown: BlockMarkdown,
+        layout: TextLayout,
+    ) {
+        let mut state = self.state.borrow_mut();
+        if !state.selectable {
+            return;
+        }
+
+        state.blocks.push(TextBlock {
+            text,
+            paragraph,
+            markdown,
+        });
+        state.layouts.push(layout);
+    }
+
+    /// Wrap the rendered nodes to handle the copy actions.
+    pub(crate) fn wrap(&self, child: impl IntoElement) -> AnyElement {
+        if !self.is_selectable() {
+            return child.into_any_element();
+        }
+
+        div()
+            .key_context(CONTEXT)
+            .track_focus(&self.focus_handle)
+            .on_action({
+                let state = self.state.clone();
+                move |_: &Copy, _, cx| {
+                    let text = state.borrow().selected_text(false);
+                    if !text.is_empty() {
+                        cx.write_to_clipboard(ClipboardItem::new_string(text));
+                    }
+                }
+            })
+            .on_action({
+                let state = self.state.clone();
+                move |_: &CopyAsMarkdown, _, cx| {
+                    let text = state.borrow().selected_text(true);
+                    if !text.is_empty() {
+                        cx.write_to_clipboard(ClipboardItem::new_string(text));
+                    }
+                }
+            })
+            .child(child)
+            .into_any_element()
+    }
+
+    /// Paint the selection over the text, and handle the mouse events to select the text.
+    pub(crate) fn paint(&self, hitbox: &Hitbox, window: &mut Window, cx: &mut App) {
+        if !self.is_selectable() {
+            return;
+        }
+
+        self.state.borrow().paint(window, cx);
+        window.set_cursor_style(CursorStyle::IBeam, hitbox);
+
+        window.on_mouse_event({
+            let state = self.state.clone();
+            let focus_handle = self.focus_handle.clone();
+            let hitbox = hitbox.clone();
+            move |event: &MouseDownEvent, phase, window, _| {
+                if phase != DispatchPhase::Bubble || event.button != MouseButton::Left {
+                    return;
+                }
+
+                let mut state = state.borrow_mut();
+                if !hitbox.is_hovered(window) {
+                    // Click outside to clear the selection.
+                    if !state.is_empty() {
+                        state.anchor = state.head;
+                        window.refresh();
+                    }
+                    return;
+                }
+
+                if let Some(point) = state.point_for_position(event.position) {
+                    state.select(point, event.click_count, event.modifiers.shift);
+                    focus_handle.focus(window);
+                    window.refresh();
+                }
+            }
+        });
+
+        window.on_mouse_event({
+            let state = self.state.clone();
+            move |event: &MouseMoveEvent, phase, window, _| {
+                if phase != DispatchPhase::Bubble {
+                    return;
+                }
+
+                let mut state = state.borrow_mut();
+                if !state.selecting || event.pressed_button != Some(MouseButton::Left) {
+                    return;
+                }
+
+                if let Some(point) = state.point_for_position(event.position) {
+                    if state.head != point {
+                        state.head = point;
+                        window.refresh();
+                    }
+                }
+            }
+        });
+
+        window.on_mouse_event({
+            let state = self.state.clone();
+            move |_: &MouseUpEvent, phase, _, _| {
+                if phase == DispatchPhase::Bubble {
+                    state.borrow_mut().selecting = false;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::element::{InlineTextStyle, TextNode};
+
+    fn block(text: &str, markdown: BlockMarkdown) -> TextBlock {
+        TextBlock {
+            text: text.to_string().into(),
+            paragraph: None,
+            markdown,
+        }
+    }
+
+    fn point(block: usize, offset: usize) -> TextPoint {
+        TextPoint { block, offset }
+    }
+
+    #[test]
+    fn test_word_range() {
+        let text = "Hello, 世界 foo_bar!";
+        assert_eq!(word_range(text, 0), 0..5);
+        assert_eq!(word_range(text, 3), 0..5);
+        assert_eq!(word_range(text, 5), 0..5);
+        assert_eq!(word_range(text, 6), 6..7);
+        assert_eq!(word_range(text, 10), 7..13);
+        assert_eq!(word_range(text, 16), 14..21);
+        assert_eq!(word_range(text, 21), 14..21);
+        assert_eq!(word_range(text, 22), 22..22);
+        assert_eq!(word_range("", 0), 0..0);
+    }
+
+    #[test]
+    fn test_paragraph_range() {
+        let text = "fn main() {\n    println!();\n}";
+        assert_eq!(paragraph_range(text, 3), 0..11);
+        assert_eq!(paragraph_range(text, 11), 0..11);
+        assert_eq!(paragraph_range(text, 12), 12..27);
+        assert_eq!(paragraph_range(text, 29), 28..29);
+    }
+
+    #[test]
+    fn test_selected_text() {
+        let code = BlockMarkdown {
+            prefix: "```rust\n".into(),
+            suffix: "\n```".into(),
+            ..Default::default()
+        };
+        let cell = BlockMarkdown {
+            separator: BlockSeparator::Cell,
+            ..Default::default()
+        };
+        let row = BlockMarkdown {
+            separator: BlockSeparator::Line,
+            ..Default::default()
+        };
+        let blocks = vec![
+            block("Hello world", BlockMarkdown::default()),
+            block("fn main() {}", code),
+            block("A", BlockMarkdown::default()),
+            block("B", cell.clone()),
+            block("1", row),
+            block("2", cell),
+        ];
+
+        assert_eq!(
+            selected_text(&blocks, point(0, 6)..point(1, 12), false),
+            "world\nfn main() {}"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(0, 6)..point(1, 12), true),
+            "world\n\n```rust\nfn main() {}\n```"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(0, 11)..point(1, 2), true),
+            "```rust\nfn"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(2, 0)..point(5, 1), false),
+            "A\tB\n1\t2"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(2, 0)..point(5, 1), true),
+            "A | B\n1 | 2"
+        );
+        assert_eq!(selected_text(&blocks, point(0, 3)..point(0, 3), false), "");
+        // The blocks are changed after the selection.
+        assert_eq!(selected_text(&blocks, point(5, 0)..point(9, 3), false), "2");
+    }
+
+    #[test]
+    fn test_selected_text_in_char() {
+        let blocks = vec![
+            block("你好世界", BlockMarkdown::default()),
+            block("Hi 👋 there", BlockMarkdown::default()),
+        ];
+
+        // The offsets are inside the characters after the blocks are changed.
+        assert_eq!(
+            selected_text(&blocks, point(0, 4)..point(0, 8), false),
+            "好世"
+        );
+        assert_eq!(selected_text(&blocks, point(0, 1)..point(0, 2), true), "你");
+        assert_eq!(
+            selected_text(&blocks, point(0, 10)..point(1, 5), false),
+            "界\nHi 👋"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(1, 4)..point(1, 6), false),
+            "👋"
+        );
+    }
+
+    #[test]
+    fn test_selected_markdown() {
+        let paragraph = Paragraph::Texts {
+            span: None,
+            children: vec![
+                TextNode {
+                    text: "  Hello ".into(),
+                    marks: vec![(0..8, InlineTextStyle::default())],
+                },
+                TextNode {
+                    text: "bold".into(),
+                    marks: vec![(
+                        0..4,
+                        InlineTextStyle {
+                            bold: true,
+                            ..Default::default()
+                        },
+                    )],
+                },
+                TextNode {
+                    text: " text".into(),
+                    marks: vec![(0..5, InlineTextStyle::default())],
+                },
+            ],
+        };
+        let blocks = vec![TextBlock {
+            text: "Hello bold text".into(),
+            paragraph: Some(paragraph),
+            markdown: BlockMarkdown {
+                prefix: "## ".into(),
+                ..Default::default()
+            },
+        }];
+
+        assert_eq!(
+            selected_text(&blocks, point(0, 0)..point(0, 15), true),
+            "## Hello **bold** text"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(0, 6)..point(0, 8), true),
+            "**bo**"
+        );
+        assert_eq!(
+            selected_text(&blocks, point(0, 8)..point(0, 12), false),
+            "ld t"
+        );
+    }
+}
@@ -78,6 +78,8 @@ pub struct TextViewStyle {
     /// Highlight theme for code blocks. Default: [`HighlightTheme::default_light()`]
     pub highlight_theme: Rc<HighlightTheme>,
     pub is_dark: bool,
+    /// Detect the language of the code blocks without language tag, default is false.
+    pub detect_language: bool,
 }
 
 impl PartialEq for TextViewStyle {
@@ -85,6 +87,7 @@ impl PartialEq for TextViewStyle {
         self.paragraph_gap == other.paragraph_gap
             && self.heading_base_font_size == other.heading_base_font_size
             && self.highlight_theme == other.highlight_theme
+            && self.detect_language == other.detect_language
     }
 }
 
@@ -95,6 +98,7 @@ impl Default for TextViewStyle {
             heading_base_font_size: px(14.),
             highlight_theme: Rc::new(HighlightTheme::default_light().clone()),
             is_dark: false,
+            detect_language: false,
         }
     }
 }
@@ -105,6 +109,14 @@ impl TextViewStyle {
         self.paragraph_gap = gap;
         self
     }
+
+    /// Set to detect the language of the code blocks without language tag, default is false.
+    ///
+    /// See [`Language::detect`](crate::highlighter::Language::detect).
+    pub fn detect_language(mut self, detect_language: bool) -> Self {
+        self.detect_language = detect_language;
+        self
+    }
 }
 
 impl TextView {
@@ -133,6 +145,31 @@ impl TextView {
             Self::Html(el) => Self::Html(el.style(style)),
         }
     }
+
+    /// Set the text to be selectable by mouse, default is false.
+    ///
+    /// Drag to select the text, double click to select a word, triple click to select a paragraph,
+    /// then use [`Copy`](super::Copy) to copy the plain text, or [`CopyAsMarkdown`](super::CopyAsMarkdown)
+    /// to copy the selection as Markdown.
+    pub fn selectable(self, selectable: bool) -> Self {
+        match self {
+            Self::Markdown(el) => Self::Markdown(el.selectable(selectable)),
+            Self::Html(el) => Self::Html(el.selectable(selectable)),
+        }
+    }
+
+    /// Set to render the Markdown that is streaming in, e.g.: the output of the LLM, default is false.
+    ///
+    /// When the text is appended, the completed blocks are kept and only the last block is parsed again,
+    /// and the unfinished code fence, table and emphasis at the end are completed to render gracefully.
+    ///
+    /// This is only for Markdown, the HTML is not changed.
+    pub fn streaming(self, streaming: bool) -> Self {
+        match self {
+            Self::Markdown(el) => Self::Markdown(el.streaming(streaming)),
+            Self::Html(el) => Self::Html(el),
+        }
+    }
 }
 
 impl RenderOnce for TextView {